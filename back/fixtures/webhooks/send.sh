@@ -0,0 +1,35 @@
+#!/bin/sh
+# Signs a recorded webhook payload like its provider would and posts it to the API.
+set -o errexit
+set -o nounset
+
+if [ $# -lt 2 ]; then
+  echo "Usage: ${0##*/} {stripe|kofi|paypal} <payload.json> [api url]"
+  echo "Reads the signing secret from WEBHOOK_SECRET_<PROVIDER>, e.g. WEBHOOK_SECRET_STRIPE."
+  exit 1
+fi
+
+provider=$1
+payload=$2
+url=${3:-http://localhost:${PORT:-8080}}
+secret=$(printenv "WEBHOOK_SECRET_$(echo "$provider" | tr '[:lower:]' '[:upper:]')")
+
+hmac() {
+  openssl dgst -sha256 -hmac "$secret" -hex | sed 's/^.* //'
+}
+
+if [ "$provider" = "stripe" ]; then
+  timestamp=$(date +%s)
+  signature=$({ printf '%s.' "$timestamp"; cat "$payload"; } | hmac)
+  header="Stripe-Signature: t=$timestamp,v1=$signature"
+else
+  signature=$(hmac <"$payload")
+  header="X-Webhook-Signature: sha256=$signature"
+fi
+
+curl --silent --show-error \
+  -H "Content-Type: application/json" \
+  -H "$header" \
+  --data-binary "@$payload" \
+  "$url/webhooks/$provider"
+echo
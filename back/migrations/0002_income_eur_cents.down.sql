@@ -0,0 +1,4 @@
+ALTER TABLE donations ADD COLUMN income_eur DOUBLE NOT NULL DEFAULT 0 AFTER income_eur_cents;
+UPDATE donations SET income_eur = income_eur_cents / 100;
+ALTER TABLE donations ALTER COLUMN income_eur DROP DEFAULT;
+ALTER TABLE donations DROP COLUMN income_eur_cents;
@@ -0,0 +1,4 @@
+ALTER TABLE donations ADD COLUMN income_eur_cents BIGINT NOT NULL DEFAULT 0 AFTER income_eur;
+UPDATE donations SET income_eur_cents = ROUND(income_eur * 100);
+ALTER TABLE donations ALTER COLUMN income_eur_cents DROP DEFAULT;
+ALTER TABLE donations DROP COLUMN income_eur;
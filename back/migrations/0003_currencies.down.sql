@@ -0,0 +1,2 @@
+DROP TABLE exchange_rates;
+ALTER TABLE donations DROP COLUMN amount_cents, DROP COLUMN currency;
@@ -0,0 +1,12 @@
+ALTER TABLE donations
+    ADD COLUMN currency CHAR(3) NOT NULL DEFAULT 'EUR' AFTER income_eur_cents,
+    ADD COLUMN amount_cents BIGINT NOT NULL DEFAULT 0 AFTER currency;
+UPDATE donations SET amount_cents = income_eur_cents;
+ALTER TABLE donations ALTER COLUMN amount_cents DROP DEFAULT;
+CREATE TABLE IF NOT EXISTS exchange_rates (
+    currency CHAR(3) NOT NULL,
+    valid_from TIMESTAMP NOT NULL,
+    -- units of `currency` per one EUR, scaled by 10^6
+    rate_micros BIGINT NOT NULL,
+    PRIMARY KEY (currency, valid_from)
+);
@@ -0,0 +1,2 @@
+DROP INDEX donations_co_op_donated_at ON donations;
+DROP INDEX donations_donated_at ON donations;
@@ -0,0 +1,2 @@
+CREATE INDEX donations_donated_at ON donations (donated_at);
+CREATE INDEX donations_co_op_donated_at ON donations (co_op, donated_at);
@@ -0,0 +1 @@
+ALTER TABLE donations ADD COLUMN external_ref VARCHAR(255) NULL UNIQUE;
@@ -0,0 +1 @@
+ALTER TABLE supporters DROP COLUMN public;
@@ -0,0 +1 @@
+ALTER TABLE supporters ADD COLUMN public BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,24 @@
+-- Supporters are split back into one row per donation. Supporters without
+-- donations and donations without supporters are lost or left unlinked.
+DROP INDEX supporters_name ON supporters;
+
+ALTER TABLE supporters ADD COLUMN donation_id BIGINT UNSIGNED NULL;
+
+INSERT INTO supporters (name, public, donation_id)
+    SELECT supporters.name, supporters.public, donations.id
+    FROM donations
+    JOIN supporters ON supporters.id = donations.supporter_id;
+
+ALTER TABLE donations DROP FOREIGN KEY donations_supporter_id;
+DROP INDEX donations_supporter_id_donated_at ON donations;
+ALTER TABLE donations DROP COLUMN supporter_id;
+
+DELETE FROM supporters WHERE donation_id IS NULL;
+
+ALTER TABLE supporters
+    MODIFY donation_id BIGINT UNSIGNED NOT NULL UNIQUE,
+    ADD FOREIGN KEY (donation_id) REFERENCES donations(id) ON DELETE CASCADE ON UPDATE CASCADE,
+    DROP COLUMN email,
+    DROP COLUMN phone,
+    DROP COLUMN notes,
+    DROP COLUMN created_at;
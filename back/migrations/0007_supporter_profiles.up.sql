@@ -0,0 +1,40 @@
+ALTER TABLE supporters
+    ADD COLUMN email VARCHAR(255) NULL,
+    ADD COLUMN phone VARCHAR(64) NULL,
+    ADD COLUMN notes TEXT NULL,
+    ADD COLUMN created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP;
+
+ALTER TABLE donations
+    ADD COLUMN supporter_id BIGINT UNSIGNED NULL,
+    ADD CONSTRAINT donations_supporter_id FOREIGN KEY (supporter_id) REFERENCES supporters (id) ON DELETE SET NULL ON UPDATE CASCADE;
+
+-- Repeat donors were stored as one supporter row per donation. Rows sharing a
+-- name are merged into the oldest one; "Anonymous" rows stay separate since the
+-- name does not identify a person. A merged supporter is only public if every
+-- merged row had consented.
+CREATE TEMPORARY TABLE supporter_merges AS
+    SELECT name, MIN(id) AS id, MIN(public) AS public
+    FROM supporters
+    WHERE name <> 'Anonymous'
+    GROUP BY name;
+
+UPDATE donations
+    JOIN supporters ON supporters.donation_id = donations.id
+    LEFT JOIN supporter_merges ON supporter_merges.name = supporters.name
+    SET donations.supporter_id = COALESCE(supporter_merges.id, supporters.id);
+
+UPDATE supporters
+    JOIN supporter_merges ON supporter_merges.id = supporters.id
+    SET supporters.public = supporter_merges.public;
+
+DELETE supporters FROM supporters
+    JOIN supporter_merges ON supporter_merges.name = supporters.name
+    WHERE supporters.id <> supporter_merges.id;
+
+DROP TEMPORARY TABLE supporter_merges;
+
+ALTER TABLE supporters DROP FOREIGN KEY supporters_ibfk_1;
+ALTER TABLE supporters DROP COLUMN donation_id;
+
+CREATE INDEX supporters_name ON supporters (name);
+CREATE INDEX donations_supporter_id_donated_at ON donations (supporter_id, donated_at);
@@ -0,0 +1 @@
+DROP TABLE IF EXISTS supporter_merge_log;
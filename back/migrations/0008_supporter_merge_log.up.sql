@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS supporter_merge_log (
+    id BIGINT UNSIGNED NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    canonical_id BIGINT UNSIGNED NULL,
+    merged_id BIGINT UNSIGNED NOT NULL,
+    merged_name VARCHAR(255) NOT NULL,
+    merged_email VARCHAR(255) NULL,
+    -- JSON array of the ids of the donations re-pointed to the canonical supporter
+    donation_ids TEXT NOT NULL,
+    account_id BIGINT UNSIGNED NULL,
+    merged_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (canonical_id) REFERENCES supporters (id) ON DELETE SET NULL ON UPDATE CASCADE,
+    FOREIGN KEY (account_id) REFERENCES accounts (id) ON DELETE SET NULL ON UPDATE CASCADE,
+    INDEX supporter_merge_log_canonical_id (canonical_id)
+);
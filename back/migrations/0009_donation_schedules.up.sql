@@ -0,0 +1,29 @@
+CREATE TABLE IF NOT EXISTS donation_schedules (
+    id BIGINT UNSIGNED NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    supporter_id BIGINT UNSIGNED NOT NULL,
+    co_op ENUM('S4L','STUDIO-MATIC') NOT NULL,
+    currency CHAR(3) NOT NULL DEFAULT 'EUR',
+    amount_cents BIGINT NOT NULL,
+    interval_unit ENUM('week','month','year') NOT NULL,
+    interval_count INT UNSIGNED NOT NULL DEFAULT 1,
+    starts_on DATE NOT NULL,
+    ends_on DATE NULL,
+    -- days a donation may arrive before or after its due date and still count
+    grace_days INT UNSIGNED NOT NULL DEFAULT 7,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    -- deleting a supporter must not silently drop their schedules, merges move them first
+    FOREIGN KEY (supporter_id) REFERENCES supporters (id) ON DELETE RESTRICT ON UPDATE CASCADE
+);
+
+-- Outcome of each due date of a schedule once its grace period has passed
+CREATE TABLE IF NOT EXISTS schedule_occurrences (
+    schedule_id BIGINT UNSIGNED NOT NULL,
+    due_on DATE NOT NULL,
+    status ENUM('received','missed') NOT NULL,
+    donation_id BIGINT UNSIGNED NULL UNIQUE,
+    checked_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (schedule_id, due_on),
+    FOREIGN KEY (schedule_id) REFERENCES donation_schedules (id) ON DELETE CASCADE ON UPDATE CASCADE,
+    FOREIGN KEY (donation_id) REFERENCES donations (id) ON DELETE SET NULL ON UPDATE CASCADE,
+    INDEX schedule_occurrences_status_due_on (status, due_on)
+);
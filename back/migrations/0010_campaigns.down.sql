@@ -0,0 +1,4 @@
+ALTER TABLE donations DROP FOREIGN KEY donations_campaign_id;
+DROP INDEX donations_campaign_id_donated_at ON donations;
+ALTER TABLE donations DROP COLUMN campaign_id;
+DROP TABLE IF EXISTS campaigns;
@@ -0,0 +1,16 @@
+CREATE TABLE IF NOT EXISTS campaigns (
+    id BIGINT UNSIGNED NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    name VARCHAR(255) NOT NULL,
+    description TEXT NULL,
+    co_op ENUM('S4L','STUDIO-MATIC') NOT NULL,
+    goal_eur_cents BIGINT NOT NULL,
+    starts_at TIMESTAMP NOT NULL,
+    ends_at TIMESTAMP NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+ALTER TABLE donations
+    ADD COLUMN campaign_id BIGINT UNSIGNED NULL,
+    ADD CONSTRAINT donations_campaign_id FOREIGN KEY (campaign_id) REFERENCES campaigns (id) ON DELETE SET NULL ON UPDATE CASCADE;
+
+CREATE INDEX donations_campaign_id_donated_at ON donations (campaign_id, donated_at);
@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS webhook_events (
+    id BIGINT UNSIGNED NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    provider VARCHAR(32) NOT NULL,
+    event_id VARCHAR(255) NOT NULL,
+    event_type VARCHAR(255) NOT NULL,
+    payload MEDIUMBLOB NOT NULL,
+    status ENUM('processed','ignored','failed') NOT NULL,
+    error TEXT NULL,
+    donation_id BIGINT UNSIGNED NULL,
+    received_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    processed_at TIMESTAMP NULL,
+    CONSTRAINT webhook_events_provider_event_id UNIQUE (provider, event_id),
+    CONSTRAINT webhook_events_donation_id FOREIGN KEY (donation_id) REFERENCES donations (id) ON DELETE SET NULL ON UPDATE CASCADE
+);
@@ -0,0 +1,2 @@
+ALTER TABLE supporters DROP COLUMN version;
+ALTER TABLE donations DROP COLUMN version;
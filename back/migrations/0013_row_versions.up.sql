@@ -0,0 +1,2 @@
+ALTER TABLE donations ADD COLUMN version INT UNSIGNED NOT NULL DEFAULT 1;
+ALTER TABLE supporters ADD COLUMN version INT UNSIGNED NOT NULL DEFAULT 1;
@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS job_runs (
+    id BIGINT UNSIGNED NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    job VARCHAR(64) NOT NULL,
+    scheduled_at TIMESTAMP NOT NULL,
+    started_at TIMESTAMP NOT NULL,
+    finished_at TIMESTAMP NULL,
+    status ENUM('running','succeeded','failed') NOT NULL,
+    detail TEXT NULL,
+    instance VARCHAR(64) NOT NULL,
+    INDEX job_runs_job_scheduled_at (job, scheduled_at)
+);
@@ -0,0 +1,12 @@
+DROP TABLE IF EXISTS job_runs;
+DROP TABLE IF EXISTS idempotency_keys;
+DROP TABLE IF EXISTS webhook_events;
+DROP TABLE IF EXISTS schedule_occurrences;
+DROP TABLE IF EXISTS donation_schedules;
+DROP TABLE IF EXISTS supporter_merge_log;
+DROP TABLE IF EXISTS exchange_rates;
+DROP TABLE IF EXISTS donations;
+DROP TABLE IF EXISTS campaigns;
+DROP TABLE IF EXISTS supporters;
+DROP TABLE IF EXISTS sessions;
+DROP TABLE IF EXISTS accounts;
@@ -0,0 +1,150 @@
+-- SQLite schema matching the MySQL migrations up to 0014. Timestamps are stored
+-- as UTC RFC 3339 text without fractional seconds, so that they compare in
+-- chronological order, and dates as `YYYY-MM-DD` text.
+CREATE TABLE accounts (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    email TEXT NOT NULL UNIQUE,
+    password TEXT NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
+);
+
+CREATE TABLE sessions (
+    token TEXT NOT NULL PRIMARY KEY,
+    account_id INTEGER NOT NULL REFERENCES accounts (id) ON DELETE CASCADE ON UPDATE CASCADE,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
+    expires_at TEXT NOT NULL
+);
+
+CREATE TABLE supporters (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL DEFAULT 'Anonymous',
+    public INTEGER NOT NULL DEFAULT 0 CHECK (public IN (0, 1)),
+    email TEXT NULL,
+    phone TEXT NULL,
+    notes TEXT NULL,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
+    version INTEGER NOT NULL DEFAULT 1
+);
+
+CREATE INDEX supporters_name ON supporters (name);
+
+CREATE TABLE campaigns (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    description TEXT NULL,
+    co_op TEXT NOT NULL CHECK (co_op IN ('S4L', 'STUDIO-MATIC')),
+    goal_eur_cents INTEGER NOT NULL,
+    starts_at TEXT NOT NULL,
+    ends_at TEXT NULL,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
+);
+
+CREATE TABLE donations (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    coins INTEGER NOT NULL CHECK (coins >= 0),
+    donated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
+    income_eur_cents INTEGER NOT NULL,
+    currency TEXT NOT NULL DEFAULT 'EUR',
+    amount_cents INTEGER NOT NULL,
+    co_op TEXT NOT NULL CHECK (co_op IN ('S4L', 'STUDIO-MATIC')),
+    external_ref TEXT NULL UNIQUE,
+    supporter_id INTEGER NULL REFERENCES supporters (id) ON DELETE SET NULL ON UPDATE CASCADE,
+    campaign_id INTEGER NULL REFERENCES campaigns (id) ON DELETE SET NULL ON UPDATE CASCADE,
+    version INTEGER NOT NULL DEFAULT 1
+);
+
+CREATE INDEX donations_donated_at ON donations (donated_at);
+CREATE INDEX donations_co_op_donated_at ON donations (co_op, donated_at);
+CREATE INDEX donations_supporter_id_donated_at ON donations (supporter_id, donated_at);
+CREATE INDEX donations_campaign_id_donated_at ON donations (campaign_id, donated_at);
+
+CREATE TABLE exchange_rates (
+    currency TEXT NOT NULL,
+    valid_from TEXT NOT NULL,
+    -- units of `currency` per one EUR, scaled by 10^6
+    rate_micros INTEGER NOT NULL,
+    PRIMARY KEY (currency, valid_from)
+);
+
+CREATE TABLE supporter_merge_log (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    canonical_id INTEGER NULL REFERENCES supporters (id) ON DELETE SET NULL ON UPDATE CASCADE,
+    merged_id INTEGER NOT NULL,
+    merged_name TEXT NOT NULL,
+    merged_email TEXT NULL,
+    -- JSON array of the ids of the donations re-pointed to the canonical supporter
+    donation_ids TEXT NOT NULL,
+    account_id INTEGER NULL REFERENCES accounts (id) ON DELETE SET NULL ON UPDATE CASCADE,
+    merged_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
+);
+
+CREATE INDEX supporter_merge_log_canonical_id ON supporter_merge_log (canonical_id);
+
+CREATE TABLE donation_schedules (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    -- deleting a supporter must not silently drop their schedules, merges move them first
+    supporter_id INTEGER NOT NULL REFERENCES supporters (id) ON DELETE RESTRICT ON UPDATE CASCADE,
+    co_op TEXT NOT NULL CHECK (co_op IN ('S4L', 'STUDIO-MATIC')),
+    currency TEXT NOT NULL DEFAULT 'EUR',
+    amount_cents INTEGER NOT NULL,
+    interval_unit TEXT NOT NULL CHECK (interval_unit IN ('week', 'month', 'year')),
+    interval_count INTEGER NOT NULL DEFAULT 1 CHECK (interval_count >= 0),
+    starts_on TEXT NOT NULL,
+    ends_on TEXT NULL,
+    -- days a donation may arrive before or after its due date and still count
+    grace_days INTEGER NOT NULL DEFAULT 7 CHECK (grace_days >= 0),
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now'))
+);
+
+-- Outcome of each due date of a schedule once its grace period has passed
+CREATE TABLE schedule_occurrences (
+    schedule_id INTEGER NOT NULL REFERENCES donation_schedules (id) ON DELETE CASCADE ON UPDATE CASCADE,
+    due_on TEXT NOT NULL,
+    status TEXT NOT NULL CHECK (status IN ('received', 'missed')),
+    donation_id INTEGER NULL UNIQUE REFERENCES donations (id) ON DELETE SET NULL ON UPDATE CASCADE,
+    checked_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
+    PRIMARY KEY (schedule_id, due_on)
+);
+
+CREATE INDEX schedule_occurrences_status_due_on ON schedule_occurrences (status, due_on);
+
+CREATE TABLE webhook_events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    provider TEXT NOT NULL,
+    event_id TEXT NOT NULL,
+    event_type TEXT NOT NULL,
+    payload BLOB NOT NULL,
+    status TEXT NOT NULL CHECK (status IN ('processed', 'ignored', 'failed')),
+    error TEXT NULL,
+    donation_id INTEGER NULL REFERENCES donations (id) ON DELETE SET NULL ON UPDATE CASCADE,
+    received_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
+    processed_at TEXT NULL,
+    UNIQUE (provider, event_id)
+);
+
+CREATE TABLE idempotency_keys (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    account_id INTEGER NOT NULL REFERENCES accounts (id) ON DELETE CASCADE ON UPDATE CASCADE,
+    idempotency_key TEXT NOT NULL,
+    request_hash TEXT NOT NULL,
+    status_code INTEGER NULL,
+    content_type TEXT NULL,
+    response_body BLOB NULL,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%SZ', 'now')),
+    UNIQUE (account_id, idempotency_key)
+);
+
+CREATE INDEX idempotency_keys_created_at ON idempotency_keys (created_at);
+
+CREATE TABLE job_runs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    job TEXT NOT NULL,
+    scheduled_at TEXT NOT NULL,
+    started_at TEXT NOT NULL,
+    finished_at TEXT NULL,
+    status TEXT NOT NULL CHECK (status IN ('running', 'succeeded', 'failed')),
+    detail TEXT NULL,
+    instance TEXT NOT NULL
+);
+
+CREATE INDEX job_runs_job_scheduled_at ON job_runs (job, scheduled_at);
@@ -0,0 +1,520 @@
+use crate::{
+    ApiResult,
+    error::{FieldError, JsonBody, PathParams, Problem, ProblemDetails},
+    exchange_rates,
+    idempotency::IdempotencyKeyHeader,
+    money::Money,
+    repo::{Campaign, CampaignFields, CampaignTotals, Campaigns, Sessions},
+    users::auth::validate,
+};
+use axum::{
+    Json,
+    extract::State,
+    http::{HeaderMap, StatusCode, header},
+    response::IntoResponse,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use thiserror::Error;
+use time::{
+    Date, Duration, OffsetDateTime,
+    format_description::{FormatItem, well_known::Rfc3339},
+    macros::format_description,
+};
+
+#[derive(utoipa::OpenApi)]
+#[openapi(paths(
+    get_campaigns,
+    get_campaign,
+    post_campaign,
+    put_campaign,
+    delete_campaign,
+    get_campaign_progress
+))]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    ApiDoc::openapi()
+}
+
+const DATE: &[FormatItem] = format_description!("[year]-[month]-[day]");
+const PROGRESS_CACHE_CONTROL: &str = "public, max-age=60";
+
+#[derive(Error, Debug)]
+pub enum CampaignError {
+    #[error("Campaign not found")]
+    NotFound,
+    #[error("Invalid {0}: {1}")]
+    Invalid(&'static str, &'static str),
+    #[error("Could not format")]
+    FormatError(#[from] time::error::Format),
+    #[error("Could not query database")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl ProblemDetails for CampaignError {
+    fn status(&self) -> StatusCode {
+        match self {
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::Invalid(..) => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::FormatError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::DatabaseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn code(&self) -> &'static str {
+        match self {
+            Self::NotFound => "campaign_not_found",
+            Self::Invalid(..) => "invalid_campaign",
+            Self::FormatError(_) => "format_error",
+            Self::DatabaseError(_) => "database_error",
+        }
+    }
+
+    fn errors(&self) -> Vec<FieldError> {
+        match self {
+            Self::Invalid(field, message) => vec![FieldError {
+                field: field.to_string(),
+                message: message.to_string(),
+                line: None,
+            }],
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+struct CampaignResponse {
+    id: u64,
+    name: String,
+    description: Option<String>,
+    co_op: String,
+    goal_eur: Money,
+    starts_at: String,
+    ends_at: Option<String>,
+}
+
+impl TryFrom<Campaign> for CampaignResponse {
+    type Error = CampaignError;
+
+    fn try_from(campaign: Campaign) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: campaign.id,
+            name: campaign.name,
+            description: campaign.description,
+            co_op: campaign.co_op,
+            goal_eur: campaign.goal_eur,
+            starts_at: campaign.starts_at.to_utc().format(&Rfc3339)?,
+            ends_at: campaign
+                .ends_at
+                .map(|t| t.to_utc().format(&Rfc3339))
+                .transpose()?,
+        })
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+struct CampaignIdResponse {
+    id: u64,
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct CampaignRequest {
+    name: String,
+    description: Option<String>,
+    co_op: String,
+    goal_eur: Money,
+    /// RFC 3339 timestamp or `YYYY-MM-DD` date
+    starts_at: String,
+    /// RFC 3339 timestamp or `YYYY-MM-DD` date, open-ended if missing
+    ends_at: Option<String>,
+}
+
+impl CampaignRequest {
+    fn validate(self) -> Result<CampaignFields, CampaignError> {
+        let parse = |field, value: &str| {
+            exchange_rates::parse_timestamp(value).ok_or(CampaignError::Invalid(
+                field,
+                "expected an RFC 3339 timestamp or YYYY-MM-DD date",
+            ))
+        };
+        let starts_at = parse("starts_at", &self.starts_at)?;
+        let ends_at = self
+            .ends_at
+            .as_deref()
+            .map(|v| parse("ends_at", v))
+            .transpose()?;
+
+        if self.goal_eur.cents() <= 0 {
+            return Err(CampaignError::Invalid(
+                "goal_eur",
+                "must be greater than zero",
+            ));
+        }
+        if ends_at.is_some_and(|ends_at| ends_at <= starts_at) {
+            return Err(CampaignError::Invalid("ends_at", "must be after starts_at"));
+        }
+
+        Ok(CampaignFields {
+            name: self.name,
+            description: self.description,
+            co_op: self.co_op,
+            goal_eur: self.goal_eur,
+            starts_at,
+            ends_at,
+        })
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+struct DailyProgress {
+    /// `YYYY-MM-DD` in UTC
+    date: String,
+    raised_eur: Money,
+    /// Total raised up to and including `date`
+    cumulative_eur: Money,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+struct CampaignProgressResponse {
+    campaign_id: u64,
+    name: String,
+    co_op: String,
+    goal_eur: Money,
+    raised_eur: Money,
+    /// `raised_eur` relative to `goal_eur` in percent, may exceed 100
+    percent: f64,
+    donations: i64,
+    /// Distinct supporters, counting every donation without a supporter as its own donor
+    donors: i64,
+    starts_at: String,
+    ends_at: Option<String>,
+    /// One entry per day from the campaign start (or its first donation) until
+    /// its end (or today), including days without donations
+    daily: Vec<DailyProgress>,
+}
+
+async fn fetch_campaign(campaigns: &Campaigns, id: u64) -> Result<Campaign, CampaignError> {
+    campaigns.get(id).await?.ok_or(CampaignError::NotFound)
+}
+
+#[utoipa::path(
+    get,
+    path = "/campaigns",
+    responses(
+        (
+            status = StatusCode::OK,
+            body = Vec<CampaignResponse>,
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = Problem,
+            content_type = "application/problem+json",
+        )
+    ),
+)]
+pub async fn get_campaigns(
+    sessions: State<Sessions>,
+    State(campaigns): State<Campaigns>,
+    headers: HeaderMap,
+) -> ApiResult<impl IntoResponse> {
+    let _ = validate(sessions, headers).await?;
+
+    let campaigns = campaigns
+        .list()
+        .await?
+        .into_iter()
+        .map(CampaignResponse::try_from)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok((StatusCode::OK, Json(campaigns)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/campaigns/{id}",
+    params(("id" = u64, Path)),
+    responses(
+        (
+            status = StatusCode::OK,
+            body = CampaignResponse,
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            description = "Campaign not found",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = Problem,
+            content_type = "application/problem+json",
+        )
+    ),
+)]
+pub async fn get_campaign(
+    sessions: State<Sessions>,
+    State(campaigns): State<Campaigns>,
+    headers: HeaderMap,
+    PathParams(id): PathParams<u64>,
+) -> ApiResult<impl IntoResponse> {
+    let _ = validate(sessions, headers).await?;
+
+    let campaign = fetch_campaign(&campaigns, id).await?;
+
+    Ok((StatusCode::OK, Json(CampaignResponse::try_from(campaign)?)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/campaigns",
+    params(IdempotencyKeyHeader),
+    request_body = CampaignRequest,
+    responses(
+        (
+            status = StatusCode::CREATED,
+            body = CampaignIdResponse,
+            description = "Successfully added campaign",
+        ),
+        (
+            status = StatusCode::UNPROCESSABLE_ENTITY,
+            description = "Invalid request body",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::CONFLICT,
+            description = "A request with the same `Idempotency-Key` is still being processed",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = Problem,
+            content_type = "application/problem+json",
+        )
+    )
+)]
+pub async fn post_campaign(
+    sessions: State<Sessions>,
+    State(campaigns): State<Campaigns>,
+    headers: HeaderMap,
+    JsonBody(req): JsonBody<CampaignRequest>,
+) -> ApiResult<impl IntoResponse> {
+    let _ = validate(sessions, headers).await?;
+    let id = campaigns.create(req.validate()?).await?;
+
+    Ok((StatusCode::CREATED, Json(CampaignIdResponse { id })))
+}
+
+#[utoipa::path(
+    put,
+    path = "/campaigns/{id}",
+    params(("id" = u64, Path)),
+    request_body = CampaignRequest,
+    responses(
+        (
+            status = StatusCode::OK,
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            description = "Campaign not found",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::UNPROCESSABLE_ENTITY,
+            description = "Invalid request body",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = Problem,
+            content_type = "application/problem+json",
+        )
+    )
+)]
+pub async fn put_campaign(
+    sessions: State<Sessions>,
+    State(campaigns): State<Campaigns>,
+    headers: HeaderMap,
+    PathParams(id): PathParams<u64>,
+    JsonBody(req): JsonBody<CampaignRequest>,
+) -> ApiResult<impl IntoResponse> {
+    let _ = validate(sessions, headers).await?;
+    campaigns.update(id, req.validate()?).await?;
+
+    Ok(StatusCode::OK)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/campaigns/{id}",
+    params(("id" = u64, Path)),
+    responses(
+        (
+            status = StatusCode::NO_CONTENT,
+            description = "Campaign deleted, its donations are kept without a campaign",
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            description = "Campaign not found",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = Problem,
+            content_type = "application/problem+json",
+        )
+    )
+)]
+pub async fn delete_campaign(
+    sessions: State<Sessions>,
+    State(campaigns): State<Campaigns>,
+    headers: HeaderMap,
+    PathParams(id): PathParams<u64>,
+) -> ApiResult<impl IntoResponse> {
+    let _ = validate(sessions, headers).await?;
+
+    campaigns.delete(id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    get,
+    path = "/campaigns/{id}/progress",
+    params(("id" = u64, Path)),
+    responses(
+        (
+            status = StatusCode::OK,
+            body = CampaignProgressResponse,
+            description = "Public progress of the campaign towards its goal",
+            headers(
+                ("Cache-Control" = String),
+            ),
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            description = "Campaign not found",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::TOO_MANY_REQUESTS,
+            description = "Rate limit exceeded",
+            body = String,
+            content_type = "text/plain",
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = Problem,
+            content_type = "application/problem+json",
+        )
+    ),
+)]
+pub async fn get_campaign_progress(
+    State(campaigns): State<Campaigns>,
+    PathParams(id): PathParams<u64>,
+) -> ApiResult<impl IntoResponse> {
+    let campaign = fetch_campaign(&campaigns, id).await?;
+    let CampaignTotals {
+        donations,
+        donors,
+        raised_eur,
+    } = campaigns.totals(id).await?;
+    let by_day: HashMap<Date, Money> = campaigns.income_by_day(id).await?.into_iter().collect();
+
+    let today = OffsetDateTime::now_utc().date();
+    let start = by_day
+        .keys()
+        .copied()
+        .chain([campaign.starts_at.to_utc().date()])
+        .min()
+        .unwrap_or(today);
+    let end = by_day
+        .keys()
+        .copied()
+        .chain([campaign
+            .ends_at
+            .map_or(today, |ends_at| ends_at.to_utc().date().min(today))])
+        .max()
+        .unwrap_or(today);
+
+    let mut daily = Vec::new();
+    let mut cumulative = 0;
+    let mut day = start;
+    while day <= end {
+        let raised = by_day.get(&day).copied().unwrap_or_default();
+        cumulative += raised.cents();
+        daily.push(DailyProgress {
+            date: day.format(DATE).map_err(CampaignError::FormatError)?,
+            raised_eur: raised,
+            cumulative_eur: Money::from_cents(cumulative),
+        });
+        day += Duration::DAY;
+    }
+
+    let percent =
+        (raised_eur.cents() as f64 / campaign.goal_eur.cents() as f64 * 10_000.0).round() / 100.0;
+
+    let progress = CampaignProgressResponse {
+        campaign_id: campaign.id,
+        name: campaign.name,
+        co_op: campaign.co_op,
+        goal_eur: campaign.goal_eur,
+        raised_eur,
+        percent,
+        donations,
+        donors,
+        starts_at: campaign
+            .starts_at
+            .to_utc()
+            .format(&Rfc3339)
+            .map_err(CampaignError::FormatError)?,
+        ends_at: campaign
+            .ends_at
+            .map(|t| t.to_utc().format(&Rfc3339))
+            .transpose()
+            .map_err(CampaignError::FormatError)?,
+        daily,
+    };
+
+    Ok((
+        StatusCode::OK,
+        [(header::CACHE_CONTROL, PROGRESS_CACHE_CONTROL)],
+        Json(progress),
+    ))
+}
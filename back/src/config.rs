@@ -0,0 +1,513 @@
+//! Application configuration, read from an optional TOML file and overridden by
+//! environment variables. The file is `$CONFIG_FILE`, or `config.toml` in the
+//! working directory if that exists.
+//!
+//! | Environment variable           | TOML key                       | Default                    |
+//! | ------------------------------ | ------------------------------ | -------------------------- |
+//! | `DATABASE_URL`                 | `database_url`                 | required                   |
+//! | `PORT`                         | `port`                         | required                   |
+//! | `LOG_FORMAT`                   | `log_format`                   | `json`                     |
+//! | `INSTANCE_ID`                  | `instance_id`                  | `FLY_MACHINE_ID` or random |
+//! | `CORS_ALLOWED_ORIGINS`         | `cors.allowed_origins`         | none                       |
+//! | `CORS_ALLOW_ANY_ORIGIN`        | `cors.allow_any_origin`        | `false`                    |
+//! | `SESSION_MAX_AGE_SECS`         | `session.max_age_secs`         | `3600`                     |
+//! | `SESSION_CLEANUP_SCHEDULE`     | `session.cleanup_schedule`     | `*/5 * * * *`              |
+//! | `COOKIE_SECURE`                | `cookie.secure`                | `true`                     |
+//! | `COOKIE_SAME_SITE`             | `cookie.same_site`             | `none`                     |
+//! | `EMAIL_CHECK_DELIVERABILITY`   | `email.check_deliverability`   | `true`                     |
+//! | `RATE_LIMIT_PERIOD_MS`         | `rate_limit.api.period_ms`     | `500`                      |
+//! | `RATE_LIMIT_BURST_SIZE`        | `rate_limit.api.burst_size`    | `8`                        |
+//! | `PUBLIC_RATE_LIMIT_PERIOD_MS`  | `rate_limit.public.period_ms`  | `2000`                     |
+//! | `PUBLIC_RATE_LIMIT_BURST_SIZE` | `rate_limit.public.burst_size` | `20`                       |
+//! | `METRICS_PORT`                 | `metrics.port`                 | none                       |
+//! | `METRICS_TOKEN`                | `metrics.token`                | none                       |
+//! | `SHUTDOWN_DRAIN_TIMEOUT_SECS`  | `shutdown.drain_timeout_secs`  | `20`                       |
+//! | `WEBHOOK_SECRET_<PROVIDER>`    | `webhooks.secrets.<provider>`  | none                       |
+//! | `WEBHOOK_CO_OP_<PROVIDER>`     | `webhooks.co_ops.<provider>`   | none                       |
+//!
+//! `<PROVIDER>` is `STRIPE`, `KOFI` or `PAYPAL`, and webhooks of a provider
+//! without a secret are rejected. `CORS_ALLOWED_ORIGINS` is a whitespace separated list. Without `METRICS_PORT`,
+//! `/metrics` is served on `PORT`. Log levels are set with `RUST_LOG`, e.g.
+//! `info,api=debug`.
+
+use crate::{jobs::cron::Schedule, webhooks::providers::Provider};
+use axum::http::HeaderValue;
+use rand::Rng;
+use serde::Deserialize;
+use std::{collections::HashMap, env, fmt, fs, path::PathBuf, str::FromStr, time::Duration};
+
+#[derive(Debug)]
+pub struct Config {
+    pub database_url: String,
+    pub port: u16,
+    pub log_format: LogFormat,
+    /// Name of this machine in job runs
+    pub instance_id: String,
+    pub cors: CorsConfig,
+    pub session: SessionConfig,
+    pub cookie: CookieConfig,
+    pub email: EmailConfig,
+    pub rate_limit: RateLimitConfig,
+    pub metrics: MetricsConfig,
+    /// How long in-flight requests may take to finish after SIGTERM or SIGINT
+    pub drain_timeout: Duration,
+    pub webhooks: WebhookConfig,
+}
+
+#[derive(Debug)]
+pub struct CorsConfig {
+    pub allowed_origins: Vec<HeaderValue>,
+    /// Accept credentialed requests from every origin, only meant for local development
+    pub allow_any_origin: bool,
+}
+
+#[derive(Debug)]
+pub struct SessionConfig {
+    pub max_age: Duration,
+    /// Cron schedule of the expired session cleanup
+    pub cleanup_schedule: Schedule,
+}
+
+#[derive(Debug)]
+pub struct CookieConfig {
+    pub secure: bool,
+    pub same_site: SameSite,
+}
+
+#[derive(Debug)]
+pub struct EmailConfig {
+    /// Look up the MX, A or AAAA records of the domain of emails that sign up or in
+    pub check_deliverability: bool,
+}
+
+#[derive(Debug)]
+pub struct RateLimitConfig {
+    /// Authenticated API
+    pub api: RateLimit,
+    /// Public wall and campaign progress
+    pub public: RateLimit,
+}
+
+#[derive(Debug)]
+pub struct MetricsConfig {
+    /// Separate port to serve `/metrics` on, so that it is not publicly reachable
+    pub port: Option<u16>,
+    /// Bearer token required to scrape `/metrics`
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct WebhookConfig {
+    /// Signing secret of each provider whose webhooks are accepted
+    pub secrets: HashMap<Provider, String>,
+    /// Co-op of donations whose payload names none
+    pub co_ops: HashMap<Provider, String>,
+}
+
+/// Token bucket per client IP: `burst_size` requests, replenished one per `period`.
+#[derive(Debug)]
+pub struct RateLimit {
+    pub period: Duration,
+    pub burst_size: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// One JSON object per line, for log aggregation in production
+    Json,
+    /// Human readable, for development
+    Pretty,
+}
+
+impl FromStr for LogFormat {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" => Ok(Self::Json),
+            "pretty" => Ok(Self::Pretty),
+            _ => Err("expected json or pretty"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SameSite {
+    Strict,
+    Lax,
+    None,
+}
+
+impl FromStr for SameSite {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "strict" => Ok(Self::Strict),
+            "lax" => Ok(Self::Lax),
+            "none" => Ok(Self::None),
+            _ => Err("expected strict, lax or none"),
+        }
+    }
+}
+
+impl fmt::Display for SameSite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Strict => "Strict",
+            Self::Lax => "Lax",
+            Self::None => "None",
+        })
+    }
+}
+
+impl CookieConfig {
+    /// `Set-Cookie` value of the session cookie, an empty `token` with no
+    /// `max_age` removes it.
+    pub fn session_cookie(&self, token: &str, max_age: Duration) -> String {
+        let mut cookie = format!(
+            "session_token={token}; Max-Age={}; Path=/; HttpOnly; SameSite={}",
+            max_age.as_secs(),
+            self.same_site
+        );
+        if self.secure {
+            cookie.push_str("; Secure");
+        }
+        cookie
+    }
+}
+
+/// Every problem found while loading the configuration.
+#[derive(Debug)]
+pub struct ConfigError(Vec<String>);
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Invalid configuration:")?;
+        for error in &self.0 {
+            writeln!(f, "  - {error}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileConfig {
+    database_url: Option<String>,
+    port: Option<u16>,
+    log_format: Option<LogFormat>,
+    instance_id: Option<String>,
+    cors: FileCors,
+    session: FileSession,
+    cookie: FileCookie,
+    email: FileEmail,
+    rate_limit: FileRateLimits,
+    metrics: FileMetrics,
+    shutdown: FileShutdown,
+    webhooks: FileWebhooks,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileCors {
+    allowed_origins: Option<Vec<String>>,
+    allow_any_origin: Option<bool>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileSession {
+    max_age_secs: Option<u64>,
+    cleanup_schedule: Option<Schedule>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileCookie {
+    secure: Option<bool>,
+    same_site: Option<SameSite>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileEmail {
+    check_deliverability: Option<bool>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileRateLimits {
+    api: FileRateLimit,
+    public: FileRateLimit,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileRateLimit {
+    period_ms: Option<u64>,
+    burst_size: Option<u32>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileMetrics {
+    port: Option<u16>,
+    token: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileShutdown {
+    drain_timeout_secs: Option<u64>,
+}
+
+/// Values per provider name, e.g. `stripe`.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileWebhooks {
+    secrets: HashMap<String, String>,
+    co_ops: HashMap<String, String>,
+}
+
+/// Collects errors so that they can be reported together.
+#[derive(Default)]
+struct Loader(Vec<String>);
+
+impl Loader {
+    /// `var` from the environment if set, else the file's value.
+    fn value<T: FromStr>(&mut self, var: &str, file: Option<T>) -> Option<T>
+    where
+        T::Err: fmt::Display,
+    {
+        match env::var(var) {
+            Ok(v) => v
+                .trim()
+                .parse()
+                .map_err(|e| self.0.push(format!("{var}: {e}")))
+                .ok(),
+            Err(env::VarError::NotUnicode(_)) => {
+                self.0.push(format!("{var}: not valid unicode"));
+                None
+            }
+            Err(env::VarError::NotPresent) => file,
+        }
+    }
+
+    fn required<T: FromStr>(&mut self, var: &str, key: &str, file: Option<T>) -> Option<T>
+    where
+        T::Err: fmt::Display,
+    {
+        if env::var_os(var).is_none() && file.is_none() {
+            self.0.push(format!("{var} (or `{key}`) must be set"));
+            return None;
+        }
+        self.value(var, file)
+    }
+
+    fn positive<T: Default + PartialEq>(&mut self, var: &str, value: T) -> T {
+        if value == T::default() {
+            self.0.push(format!("{var} must be greater than 0"));
+        }
+        value
+    }
+
+    /// `<prefix>_<PROVIDER>` of every provider, else the file's value under `key`.
+    fn per_provider(
+        &mut self,
+        prefix: &str,
+        key: &str,
+        file: HashMap<String, String>,
+    ) -> HashMap<Provider, String> {
+        for name in file.keys() {
+            if name.parse::<Provider>().is_err() {
+                self.0.push(format!("{key}.{name}: unknown provider"));
+            }
+        }
+        Provider::ALL
+            .into_iter()
+            .filter_map(|provider| {
+                let var = format!("{prefix}_{}", provider.to_string().to_ascii_uppercase());
+                let value: String = self.value(&var, file.get(&provider.to_string()).cloned())?;
+                (!value.is_empty()).then_some((provider, value))
+            })
+            .collect()
+    }
+
+    fn rate_limit(
+        &mut self,
+        prefix: &str,
+        file: FileRateLimit,
+        default_period_ms: u64,
+        default_burst_size: u32,
+    ) -> RateLimit {
+        let period_var = format!("{prefix}RATE_LIMIT_PERIOD_MS");
+        let burst_var = format!("{prefix}RATE_LIMIT_BURST_SIZE");
+        let period_ms = self
+            .value(&period_var, file.period_ms)
+            .unwrap_or(default_period_ms);
+        let burst_size = self
+            .value(&burst_var, file.burst_size)
+            .unwrap_or(default_burst_size);
+        RateLimit {
+            period: Duration::from_millis(self.positive(&period_var, period_ms)),
+            burst_size: self.positive(&burst_var, burst_size),
+        }
+    }
+}
+
+impl Config {
+    pub fn load() -> Result<Self, ConfigError> {
+        let mut loader = Loader::default();
+        let file = read_file().unwrap_or_else(|e| {
+            loader.0.push(e);
+            FileConfig::default()
+        });
+
+        let database_url = loader.required("DATABASE_URL", "database_url", file.database_url);
+        let port = loader.required("PORT", "port", file.port);
+        let log_format = loader
+            .value("LOG_FORMAT", file.log_format)
+            .unwrap_or(LogFormat::Json);
+        let instance_id = loader
+            .value("INSTANCE_ID", file.instance_id)
+            .or_else(|| env::var("FLY_MACHINE_ID").ok())
+            .unwrap_or_else(|| {
+                rand::rng()
+                    .sample_iter(&rand::distr::Alphanumeric)
+                    .take(12)
+                    .map(char::from)
+                    .collect()
+            });
+
+        let allowed_origins = match env::var("CORS_ALLOWED_ORIGINS") {
+            Ok(v) => v.split_whitespace().map(str::to_owned).collect(),
+            Err(_) => file.cors.allowed_origins.unwrap_or_default(),
+        }
+        .into_iter()
+        .filter_map(|origin| {
+            HeaderValue::from_str(&origin)
+                .map_err(|_| {
+                    loader
+                        .0
+                        .push(format!("CORS_ALLOWED_ORIGINS: invalid origin {origin:?}"))
+                })
+                .ok()
+        })
+        .collect::<Vec<_>>();
+        let allow_any_origin = loader
+            .value("CORS_ALLOW_ANY_ORIGIN", file.cors.allow_any_origin)
+            .unwrap_or(false);
+        if allowed_origins.is_empty() && !allow_any_origin {
+            loader.0.push(
+                "CORS_ALLOWED_ORIGINS must be set, or CORS_ALLOW_ANY_ORIGIN for development"
+                    .to_owned(),
+            );
+        }
+
+        let max_age = loader
+            .value("SESSION_MAX_AGE_SECS", file.session.max_age_secs)
+            .unwrap_or(3600);
+        let max_age = loader.positive("SESSION_MAX_AGE_SECS", max_age);
+        let cleanup_schedule = loader
+            .value("SESSION_CLEANUP_SCHEDULE", file.session.cleanup_schedule)
+            .unwrap_or_else(|| "*/5 * * * *".parse().expect("valid default schedule"));
+
+        let secure = loader
+            .value("COOKIE_SECURE", file.cookie.secure)
+            .unwrap_or(true);
+        let same_site = loader
+            .value("COOKIE_SAME_SITE", file.cookie.same_site)
+            .unwrap_or(SameSite::None);
+        // browsers drop `SameSite=None` cookies without `Secure`
+        if same_site == SameSite::None && !secure {
+            loader
+                .0
+                .push("COOKIE_SAME_SITE=none requires COOKIE_SECURE=true".to_owned());
+        }
+
+        let check_deliverability = loader
+            .value(
+                "EMAIL_CHECK_DELIVERABILITY",
+                file.email.check_deliverability,
+            )
+            .unwrap_or(true);
+
+        let api = loader.rate_limit("", file.rate_limit.api, 500, 8);
+        let public = loader.rate_limit("PUBLIC_", file.rate_limit.public, 2000, 20);
+
+        let metrics_port = loader.value("METRICS_PORT", file.metrics.port);
+        if metrics_port.is_some() && metrics_port == port {
+            loader
+                .0
+                .push("METRICS_PORT must differ from PORT".to_owned());
+        }
+        let metrics_token = loader
+            .value("METRICS_TOKEN", file.metrics.token)
+            .filter(|t: &String| !t.is_empty());
+
+        let drain_timeout = loader
+            .value(
+                "SHUTDOWN_DRAIN_TIMEOUT_SECS",
+                file.shutdown.drain_timeout_secs,
+            )
+            .unwrap_or(20);
+
+        let webhooks = WebhookConfig {
+            secrets: loader.per_provider(
+                "WEBHOOK_SECRET",
+                "webhooks.secrets",
+                file.webhooks.secrets,
+            ),
+            co_ops: loader.per_provider("WEBHOOK_CO_OP", "webhooks.co_ops", file.webhooks.co_ops),
+        };
+
+        match (database_url, port) {
+            (Some(database_url), Some(port)) if loader.0.is_empty() => Ok(Self {
+                database_url,
+                port,
+                log_format,
+                instance_id,
+                cors: CorsConfig {
+                    allowed_origins,
+                    allow_any_origin,
+                },
+                session: SessionConfig {
+                    max_age: Duration::from_secs(max_age),
+                    cleanup_schedule,
+                },
+                cookie: CookieConfig { secure, same_site },
+                email: EmailConfig {
+                    check_deliverability,
+                },
+                rate_limit: RateLimitConfig { api, public },
+                metrics: MetricsConfig {
+                    port: metrics_port,
+                    token: metrics_token,
+                },
+                drain_timeout: Duration::from_secs(drain_timeout),
+                webhooks,
+            }),
+            _ => Err(ConfigError(loader.0)),
+        }
+    }
+}
+
+fn read_file() -> Result<FileConfig, String> {
+    let path = match env::var_os("CONFIG_FILE") {
+        Some(path) => PathBuf::from(path),
+        None => {
+            let path = PathBuf::from("config.toml");
+            if !path.exists() {
+                return Ok(FileConfig::default());
+            }
+            path
+        }
+    };
+    let content = fs::read_to_string(&path).map_err(|e| format!("{}: {e}", path.display()))?;
+    toml::from_str(&content).map_err(|e| format!("{}: {}", path.display(), e.message()))
+}
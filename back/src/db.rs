@@ -0,0 +1,165 @@
+//! Connection to the database selected by the `DATABASE_URL` scheme.
+//!
+//! Handlers store everything through the repositories of [`crate::repo`], so all
+//! endpoints work on both MySQL and SQLite.
+
+use crate::repo::{
+    self, Accounts, Campaigns, Donations, ExchangeRates, IdempotencyKeys, JobRuns, Merges,
+    Payments, Schedules, Sessions, Supporters,
+};
+use sqlx::{
+    MySqlPool, SqlitePool,
+    migrate::{MigrateError, Migrator},
+    sqlite::{SqliteConnectOptions, SqlitePoolOptions},
+};
+use std::{str::FromStr, sync::Arc};
+
+/// MySQL migrations embedded at build time.
+pub static MYSQL_MIGRATOR: Migrator = sqlx::migrate!();
+/// SQLite migrations embedded at build time.
+pub static SQLITE_MIGRATOR: Migrator = sqlx::migrate!("./migrations/sqlite");
+
+#[derive(Clone)]
+pub enum Database {
+    MySql(MySqlPool),
+    Sqlite(SqlitePool),
+}
+
+/// Repositories backed by one [`Database`].
+pub struct Repos {
+    pub donations: Donations,
+    pub supporters: Supporters,
+    pub accounts: Accounts,
+    pub sessions: Sessions,
+    pub exchange_rates: ExchangeRates,
+    pub campaigns: Campaigns,
+    pub merges: Merges,
+    pub schedules: Schedules,
+    pub payments: Payments,
+    pub idempotency_keys: IdempotencyKeys,
+    pub job_runs: JobRuns,
+}
+
+pub struct PoolStats {
+    pub size: u32,
+    pub idle: usize,
+    pub max: u32,
+}
+
+impl Database {
+    /// Connects to SQLite for `sqlite:` URLs and to MySQL otherwise.
+    pub async fn connect(url: &str) -> Result<Self, sqlx::Error> {
+        if url.starts_with("sqlite:") {
+            let options = SqliteConnectOptions::from_str(url)?
+                .create_if_missing(true)
+                .foreign_keys(true);
+            // a single connection that is never closed, so `sqlite::memory:`
+            // keeps its data for the lifetime of the pool
+            let pool = SqlitePoolOptions::new()
+                .max_connections(1)
+                .idle_timeout(None)
+                .max_lifetime(None)
+                .connect_with(options)
+                .await?;
+            Ok(Self::Sqlite(pool))
+        } else {
+            Ok(Self::MySql(MySqlPool::connect(url).await?))
+        }
+    }
+
+    pub fn migrator(&self) -> &'static Migrator {
+        match self {
+            Self::MySql(_) => &MYSQL_MIGRATOR,
+            Self::Sqlite(_) => &SQLITE_MIGRATOR,
+        }
+    }
+
+    pub async fn migrate(&self) -> Result<(), MigrateError> {
+        match self {
+            Self::MySql(pool) => MYSQL_MIGRATOR.run(pool).await,
+            Self::Sqlite(pool) => SQLITE_MIGRATOR.run(pool).await,
+        }
+    }
+
+    pub fn repos(&self) -> Repos {
+        match self {
+            Self::MySql(pool) => {
+                let repo = Arc::new(repo::mysql::MySqlRepo::new(pool.clone()));
+                Repos {
+                    donations: repo.clone(),
+                    supporters: repo.clone(),
+                    accounts: repo.clone(),
+                    sessions: repo.clone(),
+                    exchange_rates: repo.clone(),
+                    campaigns: repo.clone(),
+                    merges: repo.clone(),
+                    schedules: repo.clone(),
+                    payments: repo.clone(),
+                    idempotency_keys: repo.clone(),
+                    job_runs: repo,
+                }
+            }
+            Self::Sqlite(pool) => {
+                let repo = Arc::new(repo::sqlite::SqliteRepo::new(pool.clone()));
+                Repos {
+                    donations: repo.clone(),
+                    supporters: repo.clone(),
+                    accounts: repo.clone(),
+                    sessions: repo.clone(),
+                    exchange_rates: repo.clone(),
+                    campaigns: repo.clone(),
+                    merges: repo.clone(),
+                    schedules: repo.clone(),
+                    payments: repo.clone(),
+                    idempotency_keys: repo.clone(),
+                    job_runs: repo,
+                }
+            }
+        }
+    }
+
+    pub fn mysql(&self) -> Option<&MySqlPool> {
+        match self {
+            Self::MySql(pool) => Some(pool),
+            Self::Sqlite(_) => None,
+        }
+    }
+
+    pub async fn ping(&self) -> Result<(), sqlx::Error> {
+        match self {
+            Self::MySql(pool) => sqlx::query("SELECT 1").execute(pool).await.map(drop),
+            Self::Sqlite(pool) => sqlx::query("SELECT 1").execute(pool).await.map(drop),
+        }
+    }
+
+    /// Versions of the migrations that were applied successfully.
+    pub async fn applied_migrations(&self) -> Result<Vec<i64>, sqlx::Error> {
+        const SQL: &str = "SELECT version FROM _sqlx_migrations WHERE success";
+        match self {
+            Self::MySql(pool) => sqlx::query_scalar(SQL).fetch_all(pool).await,
+            Self::Sqlite(pool) => sqlx::query_scalar(SQL).fetch_all(pool).await,
+        }
+    }
+
+    pub fn pool_stats(&self) -> PoolStats {
+        match self {
+            Self::MySql(pool) => PoolStats {
+                size: pool.size(),
+                idle: pool.num_idle(),
+                max: pool.options().get_max_connections(),
+            },
+            Self::Sqlite(pool) => PoolStats {
+                size: pool.size(),
+                idle: pool.num_idle(),
+                max: pool.options().get_max_connections(),
+            },
+        }
+    }
+
+    pub async fn close(&self) {
+        match self {
+            Self::MySql(pool) => pool.close().await,
+            Self::Sqlite(pool) => pool.close().await,
+        }
+    }
+}
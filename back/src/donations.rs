@@ -1,12 +1,27 @@
-use crate::{ApiResult, users::auth::validate};
+pub mod export;
+pub mod import;
+pub mod stats;
+use crate::{
+    ApiResult,
+    error::{FieldError, JsonBody, PathParams, Problem, ProblemDetails, QueryParams},
+    etag::{self, IfMatch, IfMatchHeader},
+    exchange_rates,
+    idempotency::IdempotencyKeyHeader,
+    merge_patch::Patch,
+    money::{Currency, Money},
+    repo::{
+        Donation, DonationChanges, DonationRepo, Donations, NewDonation, ParsedDonationFilter,
+        Sessions,
+    },
+    users::auth::validate,
+};
 use axum::{
     Json,
-    extract::{Path, State},
-    http::{HeaderMap, StatusCode},
-    response::{IntoResponse, Response},
+    extract::State,
+    http::{HeaderMap, StatusCode, header},
+    response::IntoResponse,
 };
 use serde::{Deserialize, Serialize};
-use sqlx::MySqlPool;
 use thiserror::Error;
 use time::OffsetDateTime;
 
@@ -16,6 +31,7 @@ use time::OffsetDateTime;
     get_donation,
     post_donation,
     put_donation,
+    patch_donation,
     delete_donation
 ))]
 struct ApiDoc;
@@ -28,33 +44,191 @@ pub fn openapi() -> utoipa::openapi::OpenApi {
 pub enum DonationError {
     #[error("Donation not found")]
     NotFound,
+    #[error("Supporter not found")]
+    SupporterNotFound,
+    #[error("Campaign not found")]
+    CampaignNotFound,
+    #[error("Donation was modified since it was read")]
+    PreconditionFailed,
+    #[error("{0} must not be null")]
+    InvalidPatch(&'static str),
+    #[error("Invalid {0} timestamp: {1:?}")]
+    InvalidTimestamp(&'static str, String),
+    #[error("from must not be after to")]
+    InvalidRange,
     #[error("Could not format")]
     FormatError(#[from] time::error::Format),
     #[error("Could not query database")]
     DatabaseError(#[from] sqlx::Error),
 }
 
-impl IntoResponse for DonationError {
-    fn into_response(self) -> Response {
-        let status = match self {
+impl ProblemDetails for DonationError {
+    fn status(&self) -> StatusCode {
+        match self {
             Self::NotFound => StatusCode::NOT_FOUND,
+            Self::SupporterNotFound => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::CampaignNotFound => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::PreconditionFailed => StatusCode::PRECONDITION_FAILED,
+            Self::InvalidPatch(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::InvalidTimestamp(..) => StatusCode::BAD_REQUEST,
+            Self::InvalidRange => StatusCode::UNPROCESSABLE_ENTITY,
             Self::FormatError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             Self::DatabaseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
-        };
+        }
+    }
+
+    fn code(&self) -> &'static str {
+        match self {
+            Self::NotFound => "donation_not_found",
+            Self::SupporterNotFound => "supporter_not_found",
+            Self::CampaignNotFound => "campaign_not_found",
+            Self::PreconditionFailed => "precondition_failed",
+            Self::InvalidPatch(_) => "invalid_patch",
+            Self::InvalidTimestamp(..) => "invalid_timestamp",
+            Self::InvalidRange => "invalid_range",
+            Self::FormatError(_) => "format_error",
+            Self::DatabaseError(_) => "database_error",
+        }
+    }
+
+    fn errors(&self) -> Vec<FieldError> {
+        match self {
+            Self::InvalidTimestamp(field, _) => vec![FieldError {
+                field: field.to_string(),
+                message: self.to_string(),
+                line: None,
+            }],
+            Self::InvalidRange => vec![FieldError {
+                field: "from".to_owned(),
+                message: self.to_string(),
+                line: None,
+            }],
+            Self::SupporterNotFound => vec![FieldError {
+                field: "supporter_id".to_owned(),
+                message: self.to_string(),
+                line: None,
+            }],
+            Self::CampaignNotFound => vec![FieldError {
+                field: "campaign_id".to_owned(),
+                message: self.to_string(),
+                line: None,
+            }],
+            Self::InvalidPatch(field) => vec![FieldError {
+                field: field.to_string(),
+                message: self.to_string(),
+                line: None,
+            }],
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Parses an optional RFC 3339 timestamp or `YYYY-MM-DD` date query parameter.
+pub fn parse_time_param(
+    field: &'static str,
+    value: Option<String>,
+) -> Result<Option<OffsetDateTime>, DonationError> {
+    value
+        .map(|v| {
+            exchange_rates::parse_timestamp(&v).ok_or(DonationError::InvalidTimestamp(field, v))
+        })
+        .transpose()
+}
 
-        let msg = self.to_string();
+/// Filters shared by listing and exporting donations.
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct DonationFilter {
+    /// Only donations made at or after this RFC 3339 timestamp or `YYYY-MM-DD` date
+    from: Option<String>,
+    /// Only donations made before this RFC 3339 timestamp or `YYYY-MM-DD` date
+    to: Option<String>,
+    /// Only donations to this co-op
+    co_op: Option<String>,
+    /// Only donations made in this currency
+    currency: Option<Currency>,
+    /// Only donations made by this supporter
+    supporter_id: Option<u64>,
+    /// Only donations made to this campaign
+    campaign_id: Option<u64>,
+}
 
-        (status, Json(msg)).into_response()
+impl DonationFilter {
+    pub fn parse(self) -> Result<ParsedDonationFilter, DonationError> {
+        Ok(ParsedDonationFilter {
+            from: parse_time_param("from", self.from)?,
+            to: parse_time_param("to", self.to)?,
+            co_op: self.co_op,
+            currency: self.currency,
+            supporter_id: self.supporter_id,
+            campaign_id: self.campaign_id,
+        })
     }
 }
 
 #[derive(Serialize, utoipa::ToSchema)]
-struct DonationResponse {
+pub struct DonationResponse {
     id: u64,
     coins: u64,
     donated_at: String,
-    income_eur: f64,
+    /// Income converted to EUR at the exchange rate in effect at `donated_at`
+    income_eur: Money,
+    currency: Currency,
+    /// Income in `currency`
+    amount: Money,
     co_op: String,
+    supporter_id: Option<u64>,
+    campaign_id: Option<u64>,
+    /// Incremented on every change, sent as `ETag` by `GET /donations/{id}`
+    version: u32,
+}
+
+impl TryFrom<Donation> for DonationResponse {
+    type Error = DonationError;
+
+    fn try_from(donation: Donation) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: donation.id,
+            coins: donation.coins,
+            donated_at: donation
+                .donated_at
+                .to_utc()
+                .format(&time::format_description::well_known::Rfc3339)?,
+            income_eur: donation.income_eur,
+            currency: donation.currency,
+            amount: donation.amount,
+            co_op: donation.co_op,
+            supporter_id: donation.supporter_id,
+            campaign_id: donation.campaign_id,
+            version: donation.version,
+        })
+    }
+}
+
+/// Donations matching `filter`, oldest first.
+pub async fn list_donations(
+    donations: &dyn DonationRepo,
+    filter: &ParsedDonationFilter,
+) -> Result<Vec<DonationResponse>, DonationError> {
+    donations
+        .list(filter)
+        .await?
+        .into_iter()
+        .map(DonationResponse::try_from)
+        .collect()
+}
+
+/// The donation to change, if its version matches `If-Match`.
+async fn current_donation(
+    donations: &dyn DonationRepo,
+    id: u64,
+    if_match: &IfMatch,
+) -> Result<Donation, DonationError> {
+    let donation = donations.get(id).await?.ok_or(DonationError::NotFound)?;
+    if if_match.matches(donation.version) {
+        Ok(donation)
+    } else {
+        Err(DonationError::PreconditionFailed)
+    }
 }
 
 #[derive(Serialize, utoipa::ToSchema)]
@@ -65,51 +239,82 @@ struct DonationIdResponse {
 #[derive(Deserialize, utoipa::ToSchema)]
 pub struct DonationRequest {
     coins: u64,
-    income_eur: f64,
+    /// Income in `currency`; `income_eur` is accepted as an alias for EUR donations
+    #[serde(alias = "income_eur")]
+    amount: Money,
+    #[serde(default)]
+    currency: Currency,
     co_op: String, // TODO: validate to be either "S4L" or "STUDIO-MATIC"
+    #[serde(default)]
+    supporter_id: Option<u64>,
+    #[serde(default)]
+    campaign_id: Option<u64>,
+}
+
+/// JSON Merge Patch of a donation, members that are left out keep their value.
+#[derive(Deserialize, utoipa::ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct DonationPatch {
+    #[serde(default)]
+    #[schema(value_type = Option<u64>)]
+    coins: Patch<u64>,
+    /// Income in `currency`; `income_eur` is accepted as an alias
+    #[serde(default, alias = "income_eur")]
+    #[schema(value_type = Option<Money>)]
+    amount: Patch<Money>,
+    #[serde(default)]
+    #[schema(value_type = Option<Currency>)]
+    currency: Patch<Currency>,
+    #[serde(default)]
+    #[schema(value_type = Option<String>)]
+    co_op: Patch<String>,
+    /// `null` unlinks the supporter
+    #[serde(default)]
+    #[schema(value_type = Option<u64>)]
+    supporter_id: Patch<u64>,
+    /// `null` removes the donation from its campaign
+    #[serde(default)]
+    #[schema(value_type = Option<u64>)]
+    campaign_id: Patch<u64>,
 }
 
 #[utoipa::path(
     get,
     path = "/donations",
+    params(DonationFilter),
     responses(
         (
             status = StatusCode::OK,
             body = Vec<DonationResponse>,
         ),
+        (
+            status = StatusCode::BAD_REQUEST,
+            description = "Invalid filter",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
         (
             status = StatusCode::UNAUTHORIZED,
             description = "Not logged in",
+            body = Problem,
+            content_type = "application/problem+json",
         ),
-        (status = StatusCode::INTERNAL_SERVER_ERROR)
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = Problem,
+            content_type = "application/problem+json",
+        )
     ),
 )]
 pub async fn get_donations(
-    state_pool: State<MySqlPool>,
+    sessions: State<Sessions>,
+    State(donations): State<Donations>,
     headers: HeaderMap,
+    QueryParams(filter): QueryParams<DonationFilter>,
 ) -> ApiResult<impl IntoResponse> {
-    let _ = validate(state_pool.clone(), headers).await?;
-    let donations: Vec<(u64, u64, OffsetDateTime, f64, String)> =
-        sqlx::query_as("SELECT id, coins, donated_at, income_eur, co_op FROM donations")
-            .fetch_all(&state_pool.0)
-            .await
-            .map_err(DonationError::DatabaseError)?;
-
-    let donations = donations
-        .into_iter()
-        .map(|(a, b, c, d, e)| {
-            Ok(DonationResponse {
-                id: a,
-                coins: b,
-                donated_at: c
-                    .to_utc()
-                    .format(&time::format_description::well_known::Rfc3339)
-                    .map_err(DonationError::FormatError)?,
-                income_eur: d,
-                co_op: e,
-            })
-        })
-        .collect::<ApiResult<Vec<_>>>()?;
+    let _ = validate(sessions, headers).await?;
+    let filter = filter.parse()?;
+    let donations = list_donations(&*donations, &filter).await?;
 
     Ok((StatusCode::OK, Json(donations)))
 }
@@ -117,87 +322,109 @@ pub async fn get_donations(
 #[utoipa::path(
     get,
     path = "/donations/{id}",
+    params(("id" = u64, Path)),
     responses(
         (
             status = StatusCode::OK,
             body = DonationResponse,
+            headers(
+                ("ETag" = String, description = "Current version, for `If-Match`"),
+            ),
         ),
         (
             status = StatusCode::NOT_FOUND,
             description = "Donation not found",
+            body = Problem,
+            content_type = "application/problem+json",
         ),
         (
             status = StatusCode::UNAUTHORIZED,
             description = "Not logged in",
+            body = Problem,
+            content_type = "application/problem+json",
         ),
-        (status = StatusCode::INTERNAL_SERVER_ERROR)
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = Problem,
+            content_type = "application/problem+json",
+        )
     ),
 )]
 pub async fn get_donation(
-    state_pool: State<MySqlPool>,
+    sessions: State<Sessions>,
+    State(donations): State<Donations>,
     headers: HeaderMap,
-    Path(id): Path<u64>,
+    PathParams(id): PathParams<u64>,
 ) -> ApiResult<impl IntoResponse> {
-    let _ = validate(state_pool.clone(), headers).await?;
-    let donation: (u64, u64, OffsetDateTime, f64, String) = sqlx::query_as(
-        "SELECT id, coins, donated_at, income_eur, co_op FROM donations WHERE id = ? LIMIT 1",
-    )
-    .bind(id)
-    .fetch_optional(&state_pool.0)
-    .await
-    .map_err(DonationError::DatabaseError)?
-    .ok_or(DonationError::NotFound)?;
-
-    let (id, coins, donated_at, income_eur, co_op) = donation;
+    let _ = validate(sessions, headers).await?;
+    let donation = donations.get(id).await?.ok_or(DonationError::NotFound)?;
+    let donation = DonationResponse::try_from(donation)?;
 
-    let donations = DonationResponse {
-        id,
-        coins,
-        donated_at: donated_at
-            .to_utc()
-            .format(&time::format_description::well_known::Rfc3339)
-            .map_err(DonationError::FormatError)?,
-        income_eur,
-        co_op,
-    };
-
-    Ok((StatusCode::OK, Json(donations)))
+    Ok((
+        StatusCode::OK,
+        [(header::ETAG, etag::etag(donation.version))],
+        Json(donation),
+    ))
 }
 
 #[utoipa::path(
     post,
     path = "/donations",
+    params(IdempotencyKeyHeader),
+    request_body = DonationRequest,
     responses(
         (
             status = StatusCode::CREATED,
             body = DonationIdResponse,
             description = "Successfully added donation",
         ),
+        (
+            status = StatusCode::UNPROCESSABLE_ENTITY,
+            description = "Invalid request body or unknown supporter or campaign",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::CONFLICT,
+            description = "A request with the same `Idempotency-Key` is still being processed",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
         (
             status = StatusCode::UNAUTHORIZED,
             description = "Not logged in",
+            body = Problem,
+            content_type = "application/problem+json",
         ),
-        (status = StatusCode::INTERNAL_SERVER_ERROR)
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = Problem,
+            content_type = "application/problem+json",
+        )
     )
 )]
 pub async fn post_donation(
-    state_pool: State<MySqlPool>,
+    sessions: State<Sessions>,
+    State(donations): State<Donations>,
     headers: HeaderMap,
-    Json(req): Json<DonationRequest>,
+    JsonBody(req): JsonBody<DonationRequest>,
 ) -> ApiResult<impl IntoResponse> {
-    let _ = validate(state_pool.clone(), headers).await?;
+    let _ = validate(sessions, headers).await?;
 
-    let id = sqlx::query(
-        "INSERT INTO donations (coins, income_eur, co_op)
-        VALUES (?, ?, ?)",
-    )
-    .bind(req.coins)
-    .bind(req.income_eur)
-    .bind(req.co_op)
-    .execute(&state_pool.0)
-    .await
-    .map_err(DonationError::DatabaseError)?
-    .last_insert_id();
+    let rate = donations
+        .rate_at(&req.currency, OffsetDateTime::now_utc())
+        .await?;
+    let id = donations
+        .create(NewDonation {
+            coins: req.coins,
+            amount: req.amount,
+            income_eur: rate.to_eur(req.amount)?,
+            currency: req.currency,
+            co_op: req.co_op,
+            supporter_id: req.supporter_id,
+            campaign_id: req.campaign_id,
+        })
+        .await?;
 
     Ok((StatusCode::CREATED, Json(DonationIdResponse { id })))
 }
@@ -205,86 +432,230 @@ pub async fn post_donation(
 #[utoipa::path(
     put,
     path = "/donations/{id}",
+    params(("id" = u64, Path), IfMatchHeader),
+    request_body = DonationRequest,
     responses(
         (
             status = StatusCode::OK,
+            headers(
+                ("ETag" = String, description = "New version"),
+            ),
+        ),
+        (
+            status = StatusCode::PRECONDITION_FAILED,
+            description = "`If-Match` does not match the current version",
+            body = Problem,
+            content_type = "application/problem+json",
         ),
         (
             status = StatusCode::NOT_FOUND,
             description = "Donation not found",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::UNPROCESSABLE_ENTITY,
+            description = "Invalid request body or unknown supporter or campaign",
+            body = Problem,
+            content_type = "application/problem+json",
         ),
         (
             status = StatusCode::UNAUTHORIZED,
             description = "Not logged in",
+            body = Problem,
+            content_type = "application/problem+json",
         ),
-        (status = StatusCode::INTERNAL_SERVER_ERROR)
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = Problem,
+            content_type = "application/problem+json",
+        )
     )
 )]
 pub async fn put_donation(
-    state_pool: State<MySqlPool>,
+    sessions: State<Sessions>,
+    State(donations): State<Donations>,
     headers: HeaderMap,
-    Path(id): Path<u64>,
-    Json(req): Json<DonationRequest>,
+    PathParams(id): PathParams<u64>,
+    JsonBody(req): JsonBody<DonationRequest>,
 ) -> ApiResult<impl IntoResponse> {
-    let _ = validate(state_pool.clone(), headers).await?;
-
-    let res = sqlx::query(
-        "UPDATE donations 
-            SET 
-                coins = ?,
-                income_eur = ?,
-                co_op =?
-        WHERE id = ?",
+    let if_match = IfMatch::from_headers(&headers);
+    let _ = validate(sessions, headers).await?;
+
+    let donation = current_donation(&*donations, id, &if_match).await?;
+    let rate = donations
+        .rate_at(&req.currency, donation.donated_at)
+        .await?;
+    donations
+        .update(
+            id,
+            donation.version,
+            DonationChanges {
+                coins: Some(req.coins),
+                amount: Some(req.amount),
+                income_eur: Some(rate.to_eur(req.amount)?),
+                currency: Some(req.currency),
+                co_op: Some(req.co_op),
+                supporter_id: Some(req.supporter_id),
+                campaign_id: Some(req.campaign_id),
+            },
+        )
+        .await?;
+
+    Ok((
+        StatusCode::OK,
+        [(header::ETAG, etag::etag(donation.version + 1))],
+    ))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/donations/{id}",
+    params(("id" = u64, Path), IfMatchHeader),
+    request_body(
+        content = DonationPatch,
+        content_type = "application/merge-patch+json",
+    ),
+    responses(
+        (
+            status = StatusCode::OK,
+            body = DonationResponse,
+            description = "The updated donation",
+            headers(
+                ("ETag" = String, description = "New version"),
+            ),
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            description = "Donation not found",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::PRECONDITION_FAILED,
+            description = "`If-Match` does not match the current version",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::UNPROCESSABLE_ENTITY,
+            description = "Invalid patch, unknown supporter or campaign, or no exchange rate for \
+                the new currency",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = Problem,
+            content_type = "application/problem+json",
+        )
     )
-    .bind(req.coins)
-    .bind(req.income_eur)
-    .bind(req.co_op)
-    .bind(id)
-    .execute(&state_pool.0)
-    .await
-    .map_err(DonationError::DatabaseError)?;
-
-    if res.rows_affected() == 0 {
-        Err(DonationError::NotFound.into())
-    } else {
-        Ok(StatusCode::OK)
+)]
+pub async fn patch_donation(
+    sessions: State<Sessions>,
+    State(donations): State<Donations>,
+    headers: HeaderMap,
+    PathParams(id): PathParams<u64>,
+    JsonBody(patch): JsonBody<DonationPatch>,
+) -> ApiResult<impl IntoResponse> {
+    let if_match = IfMatch::from_headers(&headers);
+    let _ = validate(sessions, headers).await?;
+
+    let donation = current_donation(&*donations, id, &if_match).await?;
+
+    let coins = patch
+        .coins
+        .required("coins")
+        .map_err(DonationError::InvalidPatch)?;
+    let new_amount = patch
+        .amount
+        .required("amount")
+        .map_err(DonationError::InvalidPatch)?;
+    let new_currency = patch
+        .currency
+        .required("currency")
+        .map_err(DonationError::InvalidPatch)?;
+    let co_op = patch
+        .co_op
+        .required("co_op")
+        .map_err(DonationError::InvalidPatch)?;
+    let mut changes = DonationChanges {
+        coins,
+        co_op,
+        supporter_id: patch.supporter_id.nullable(),
+        campaign_id: patch.campaign_id.nullable(),
+        ..Default::default()
+    };
+    if new_amount.is_some() || new_currency.is_some() {
+        let amount = new_amount.unwrap_or(donation.amount);
+        let currency = new_currency.unwrap_or(donation.currency);
+        let rate = donations.rate_at(&currency, donation.donated_at).await?;
+        changes.amount = Some(amount);
+        changes.income_eur = Some(rate.to_eur(amount)?);
+        changes.currency = Some(currency);
     }
+    donations.update(id, donation.version, changes).await?;
+
+    let donation = donations.get(id).await?.ok_or(DonationError::NotFound)?;
+    let donation = DonationResponse::try_from(donation)?;
+
+    Ok((
+        StatusCode::OK,
+        [(header::ETAG, etag::etag(donation.version))],
+        Json(donation),
+    ))
 }
 
 #[utoipa::path(
     delete,
     path = "/donations/{id}",
+    params(("id" = u64, Path), IfMatchHeader),
     responses(
         (
             status = StatusCode::NO_CONTENT,
         ),
+        (
+            status = StatusCode::PRECONDITION_FAILED,
+            description = "`If-Match` does not match the current version",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
         (
             status = StatusCode::NOT_FOUND,
             description = "Donation not found",
+            body = Problem,
+            content_type = "application/problem+json",
         ),
         (
             status = StatusCode::UNAUTHORIZED,
             description = "Not logged in",
+            body = Problem,
+            content_type = "application/problem+json",
         ),
-        (status = StatusCode::INTERNAL_SERVER_ERROR)
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = Problem,
+            content_type = "application/problem+json",
+        )
     )
 )]
 pub async fn delete_donation(
-    state_pool: State<MySqlPool>,
+    sessions: State<Sessions>,
+    State(donations): State<Donations>,
     headers: HeaderMap,
-    Path(id): Path<u64>,
+    PathParams(id): PathParams<u64>,
 ) -> ApiResult<impl IntoResponse> {
-    let _ = validate(state_pool.clone(), headers).await?;
+    let if_match = IfMatch::from_headers(&headers);
+    let _ = validate(sessions, headers).await?;
 
-    let res = sqlx::query("DELETE FROM donations WHERE id = ?")
-        .bind(id)
-        .execute(&state_pool.0)
-        .await
-        .map_err(DonationError::DatabaseError)?;
+    let donation = current_donation(&*donations, id, &if_match).await?;
+    donations.delete(id, donation.version).await?;
 
-    if res.rows_affected() == 0 {
-        Err(DonationError::NotFound.into())
-    } else {
-        Ok(StatusCode::NO_CONTENT)
-    }
+    Ok(StatusCode::NO_CONTENT)
 }
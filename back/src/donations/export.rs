@@ -0,0 +1,423 @@
+mod ods;
+
+use super::DonationFilter;
+use crate::{
+    ApiResult,
+    error::{Problem, QueryParams},
+    money::{Currency, Money},
+    repo::{Donations, ExportedDonation, Sessions},
+    users::auth::validate,
+};
+use async_stream::try_stream;
+use axum::{
+    BoxError,
+    body::{Body, Bytes},
+    extract::State,
+    http::{HeaderMap, StatusCode, header},
+    response::IntoResponse,
+};
+use futures::{Stream, TryStreamExt, stream::BoxStream};
+use ods::{Cell, OdsWriter};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use time::{
+    OffsetDateTime, format_description::FormatItem, format_description::well_known::Rfc3339,
+    macros::format_description,
+};
+
+#[derive(utoipa::OpenApi)]
+#[openapi(paths(export_donations))]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    ApiDoc::openapi()
+}
+
+const COLUMNS: [&str; 9] = [
+    "id",
+    "donated_at",
+    "coins",
+    "co_op",
+    "currency",
+    "amount",
+    "income_eur",
+    "external_ref",
+    "supporter",
+];
+
+#[derive(Clone, Copy, Default, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Jsonl,
+    Ods,
+}
+
+impl ExportFormat {
+    fn content_type(self) -> &'static str {
+        match self {
+            Self::Csv => "text/csv; charset=utf-8",
+            Self::Jsonl => "application/x-ndjson",
+            Self::Ods => "application/vnd.oasis.opendocument.spreadsheet",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Jsonl => "jsonl",
+            Self::Ods => "ods",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DateFormat {
+    /// `2025-01-31T18:30:00Z`
+    #[default]
+    Rfc3339,
+    /// `2025-01-31 18:30:00`
+    Iso,
+    /// `31.01.2025 18:30:00`
+    Dmy,
+    /// `01/31/2025 18:30:00`
+    Mdy,
+}
+
+#[derive(Clone, Copy, Default, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DecimalSeparator {
+    #[default]
+    Point,
+    Comma,
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct ExportOptions {
+    /// File format, defaults to `csv`
+    #[serde(default)]
+    format: ExportFormat,
+    /// How timestamps are written in CSV and ODS exports, defaults to `rfc3339`
+    #[serde(default)]
+    date_format: DateFormat,
+    /// Decimal separator of amounts in CSV and ODS exports, defaults to `point`
+    #[serde(default)]
+    decimal_separator: DecimalSeparator,
+    /// CSV field delimiter, defaults to `;` with a comma decimal separator and `,` otherwise
+    #[serde(default, deserialize_with = "ascii_delimiter")]
+    #[param(value_type = Option<String>)]
+    delimiter: Option<u8>,
+}
+
+fn ascii_delimiter<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<u8>, D::Error> {
+    match char::deserialize(deserializer)? {
+        c if c.is_ascii() && !matches!(c, '"' | '\r' | '\n') => Ok(Some(c as u8)),
+        c => Err(serde::de::Error::custom(format!("invalid delimiter {c:?}"))),
+    }
+}
+
+/// Presentation of values in CSV and ODS exports. JSON Lines always use the
+/// API's own representation.
+struct Locale {
+    date_format: DateFormat,
+    decimal_separator: DecimalSeparator,
+}
+
+impl Locale {
+    fn date(&self, value: OffsetDateTime) -> Result<String, time::error::Format> {
+        const ISO: &[FormatItem] =
+            format_description!("[year]-[month]-[day] [hour]:[minute]:[second]");
+        const DMY: &[FormatItem] =
+            format_description!("[day].[month].[year] [hour]:[minute]:[second]");
+        const MDY: &[FormatItem] =
+            format_description!("[month]/[day]/[year] [hour]:[minute]:[second]");
+
+        let value = value.to_utc();
+        match self.date_format {
+            DateFormat::Rfc3339 => value.format(&Rfc3339),
+            DateFormat::Iso => value.format(ISO),
+            DateFormat::Dmy => value.format(DMY),
+            DateFormat::Mdy => value.format(MDY),
+        }
+    }
+
+    fn money(&self, value: Money) -> String {
+        match self.decimal_separator {
+            DecimalSeparator::Point => value.to_string(),
+            DecimalSeparator::Comma => value.to_string().replace('.', ","),
+        }
+    }
+}
+
+/// Free text as written to CSV and ODS cells. Text that spreadsheet
+/// applications would evaluate as a formula is prefixed with `'`.
+fn text(value: &str) -> Cow<'_, str> {
+    if value.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        Cow::Owned(format!("'{value}"))
+    } else {
+        Cow::Borrowed(value)
+    }
+}
+
+#[derive(Serialize)]
+struct JsonRow<'a> {
+    id: u64,
+    donated_at: String,
+    coins: u64,
+    co_op: &'a str,
+    currency: &'a Currency,
+    amount: Money,
+    income_eur: Money,
+    external_ref: Option<&'a str>,
+    supporter: Option<&'a str>,
+}
+
+enum Encoder {
+    Csv { delimiter: u8 },
+    Jsonl,
+    Ods(Box<OdsWriter>),
+}
+
+impl Encoder {
+    fn csv_record<I, T>(delimiter: u8, record: I) -> Result<Bytes, BoxError>
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<[u8]>,
+    {
+        let mut writer = csv::WriterBuilder::new()
+            .delimiter(delimiter)
+            .from_writer(Vec::new());
+        writer.write_record(record)?;
+        Ok(writer.into_inner().map_err(|e| e.into_error())?.into())
+    }
+
+    fn start(&mut self) -> Result<Bytes, BoxError> {
+        match self {
+            Self::Csv { delimiter } => Self::csv_record(*delimiter, COLUMNS),
+            Self::Jsonl => Ok(Bytes::new()),
+            Self::Ods(writer) => Ok(writer.start(&COLUMNS)?),
+        }
+    }
+
+    fn row(&mut self, row: &ExportedDonation, locale: &Locale) -> Result<Bytes, BoxError> {
+        let ExportedDonation {
+            id,
+            donated_at,
+            coins,
+            co_op,
+            currency,
+            amount,
+            income_eur,
+            external_ref,
+            supporter,
+        } = row;
+
+        match self {
+            Self::Csv { delimiter } => Self::csv_record(
+                *delimiter,
+                [
+                    id.to_string(),
+                    locale.date(*donated_at)?,
+                    coins.to_string(),
+                    text(co_op).into_owned(),
+                    currency.to_string(),
+                    locale.money(*amount),
+                    locale.money(*income_eur),
+                    external_ref
+                        .as_deref()
+                        .map(text)
+                        .unwrap_or_default()
+                        .into_owned(),
+                    supporter
+                        .as_deref()
+                        .map(text)
+                        .unwrap_or_default()
+                        .into_owned(),
+                ],
+            ),
+            Self::Jsonl => {
+                let mut line = serde_json::to_vec(&JsonRow {
+                    id: *id,
+                    donated_at: donated_at.to_utc().format(&Rfc3339)?,
+                    coins: *coins,
+                    co_op,
+                    currency,
+                    amount: *amount,
+                    income_eur: *income_eur,
+                    external_ref: external_ref.as_deref(),
+                    supporter: supporter.as_deref(),
+                })?;
+                line.push(b'\n');
+                Ok(line.into())
+            }
+            Self::Ods(writer) => {
+                const DATE_VALUE: &[FormatItem] =
+                    format_description!("[year]-[month]-[day]T[hour]:[minute]:[second]");
+
+                let id = id.to_string();
+                let coins = coins.to_string();
+                let currency = currency.to_string();
+                let (amount, amount_text) = (amount.to_string(), locale.money(*amount));
+                let (income, income_text) = (income_eur.to_string(), locale.money(*income_eur));
+                let date_value = donated_at.to_utc().format(DATE_VALUE)?;
+                let date_text = locale.date(*donated_at)?;
+
+                let external_ref = external_ref.as_deref().map(text);
+                let supporter = supporter.as_deref().map(text);
+
+                Ok(writer.row(&[
+                    Cell::Float(&id, &id),
+                    Cell::Date(&date_value, &date_text),
+                    Cell::Float(&coins, &coins),
+                    Cell::String(&text(co_op)),
+                    Cell::String(&currency),
+                    Cell::Float(&amount, &amount_text),
+                    Cell::Float(&income, &income_text),
+                    Cell::optional(external_ref.as_deref()),
+                    Cell::optional(supporter.as_deref()),
+                ])?)
+            }
+        }
+    }
+
+    fn finish(&mut self) -> Result<Bytes, BoxError> {
+        match self {
+            Self::Csv { .. } | Self::Jsonl => Ok(Bytes::new()),
+            Self::Ods(writer) => Ok(writer.finish()?),
+        }
+    }
+}
+
+/// Encodes donations as they are read from the database, so that exports are
+/// never held in memory as a whole.
+fn export_stream(
+    mut rows: BoxStream<'static, Result<ExportedDonation, super::DonationError>>,
+    mut encoder: Encoder,
+    locale: Locale,
+) -> impl Stream<Item = Result<Bytes, BoxError>> {
+    try_stream! {
+        yield encoder.start()?;
+        while let Some(row) = rows.try_next().await? {
+            yield encoder.row(&row, &locale)?;
+        }
+        yield encoder.finish()?;
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/donations/export",
+    params(DonationFilter, ExportOptions),
+    responses(
+        (
+            status = StatusCode::OK,
+            description = "Donations ordered by `donated_at`, with the columns `id`, `donated_at`, \
+                `coins`, `co_op`, `currency`, `amount`, `income_eur`, `external_ref` and `supporter`",
+            content(
+                (String = "text/csv"),
+                (String = "application/x-ndjson"),
+                (Vec<u8> = "application/vnd.oasis.opendocument.spreadsheet"),
+            ),
+        ),
+        (
+            status = StatusCode::BAD_REQUEST,
+            description = "Invalid filter or export options",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = Problem,
+            content_type = "application/problem+json",
+        )
+    ),
+)]
+pub async fn export_donations(
+    sessions: State<Sessions>,
+    State(donations): State<Donations>,
+    headers: HeaderMap,
+    QueryParams(filter): QueryParams<DonationFilter>,
+    QueryParams(options): QueryParams<ExportOptions>,
+) -> ApiResult<impl IntoResponse> {
+    let _ = validate(sessions, headers).await?;
+    let filter = filter.parse()?;
+
+    let now = OffsetDateTime::now_utc();
+    let encoder = match options.format {
+        ExportFormat::Csv => Encoder::Csv {
+            delimiter: match (options.delimiter, options.decimal_separator) {
+                (Some(delimiter), _) => delimiter,
+                (None, DecimalSeparator::Comma) => b';',
+                (None, DecimalSeparator::Point) => b',',
+            },
+        },
+        ExportFormat::Jsonl => Encoder::Jsonl,
+        ExportFormat::Ods => Encoder::Ods(Box::new(OdsWriter::new(now))),
+    };
+    let locale = Locale {
+        date_format: options.date_format,
+        decimal_separator: options.decimal_separator,
+    };
+
+    let filename = format!(
+        "donations-{}.{}",
+        now.format(format_description!(
+            "[year][month][day]-[hour][minute][second]"
+        ))
+        .map_err(super::DonationError::FormatError)?,
+        options.format.extension()
+    );
+    let body = Body::from_stream(export_stream(donations.export(filter), encoder, locale));
+
+    Ok((
+        StatusCode::OK,
+        [
+            (
+                header::CONTENT_TYPE,
+                options.format.content_type().to_owned(),
+            ),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{filename}\""),
+            ),
+        ],
+        body,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formulas_are_neutralised() {
+        for formula in ["=1+1", "+1", "-1", "@SUM(A1)", "\t=1", "\r=1"] {
+            assert_eq!(text(formula), format!("'{formula}"));
+        }
+        assert_eq!(text("Ada = 1"), "Ada = 1");
+        assert!(matches!(text("Ada"), Cow::Borrowed("Ada")));
+    }
+
+    #[test]
+    fn line_breaks_are_no_delimiters() {
+        let delimiter = |query: &str| {
+            let uri = format!("/donations/export?{query}").parse().unwrap();
+            axum::extract::Query::<ExportOptions>::try_from_uri(&uri).map(|q| q.0.delimiter)
+        };
+        assert_eq!(delimiter("delimiter=%3B").unwrap(), Some(b';'));
+        assert_eq!(delimiter("delimiter=%09").unwrap(), Some(b'\t'));
+        for query in ["delimiter=%0A", "delimiter=%0D", "delimiter=%22"] {
+            assert!(delimiter(query).is_err(), "{query}");
+        }
+    }
+}
@@ -0,0 +1,315 @@
+//! Streaming writer for OpenDocument spreadsheets.
+//!
+//! An `.ods` file is a ZIP archive. Entries are stored uncompressed so the
+//! archive can be emitted chunk by chunk: `content.xml` is written with a
+//! trailing data descriptor, and the central directory follows at the end.
+//! Zip64 is not written, so an archive fails with [`TooLarge`] once an entry or
+//! offset would no longer fit into 32 bits.
+
+use axum::body::Bytes;
+use crc32fast::Hasher;
+use thiserror::Error;
+use time::OffsetDateTime;
+
+const MIMETYPE: &[u8] = b"application/vnd.oasis.opendocument.spreadsheet";
+const MANIFEST: &[u8] = br#"<?xml version="1.0" encoding="UTF-8"?>
+<manifest:manifest xmlns:manifest="urn:oasis:names:tc:opendocument:xmlns:manifest:1.0" manifest:version="1.2">
+ <manifest:file-entry manifest:full-path="/" manifest:version="1.2" manifest:media-type="application/vnd.oasis.opendocument.spreadsheet"/>
+ <manifest:file-entry manifest:full-path="content.xml" manifest:media-type="text/xml"/>
+</manifest:manifest>
+"#;
+const CONTENT_START: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<office:document-content xmlns:office="urn:oasis:names:tc:opendocument:xmlns:office:1.0" xmlns:table="urn:oasis:names:tc:opendocument:xmlns:table:1.0" xmlns:text="urn:oasis:names:tc:opendocument:xmlns:text:1.0" office:version="1.2">
+<office:body><office:spreadsheet><table:table table:name="Donations">
+"#;
+const CONTENT_END: &str =
+    "</table:table></office:spreadsheet></office:body></office:document-content>\n";
+
+const LOCAL_HEADER: u32 = 0x0403_4b50;
+const DATA_DESCRIPTOR: u32 = 0x0807_4b50;
+const CENTRAL_HEADER: u32 = 0x0201_4b50;
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x0605_4b50;
+const FLAG_DATA_DESCRIPTOR: u16 = 0x0008;
+
+#[derive(Error, Debug)]
+#[error("Export exceeds the 4 GiB size limit of ODS files")]
+pub struct TooLarge;
+
+/// Advances a 32-bit size or offset of the archive by `len` bytes.
+fn advance(value: &mut u32, len: usize) -> Result<(), TooLarge> {
+    *value = u32::try_from(len)
+        .ok()
+        .and_then(|len| value.checked_add(len))
+        .ok_or(TooLarge)?;
+    Ok(())
+}
+
+pub enum Cell<'a> {
+    String(&'a str),
+    /// Numeric value with the text shown to the user
+    Float(&'a str, &'a str),
+    /// ISO 8601 date-time value with the text shown to the user
+    Date(&'a str, &'a str),
+    Empty,
+}
+
+impl<'a> Cell<'a> {
+    pub fn optional(value: Option<&'a str>) -> Self {
+        value.map_or(Self::Empty, Self::String)
+    }
+}
+
+struct Entry {
+    name: &'static str,
+    flags: u16,
+    crc: u32,
+    size: u32,
+    offset: u32,
+}
+
+pub struct OdsWriter {
+    dos_time: u16,
+    dos_date: u16,
+    offset: u32,
+    entries: Vec<Entry>,
+    content: Hasher,
+    content_size: u32,
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+impl OdsWriter {
+    pub fn new(now: OffsetDateTime) -> Self {
+        Self {
+            dos_time: (now.hour() as u16) << 11
+                | (now.minute() as u16) << 5
+                | (now.second() as u16 / 2),
+            dos_date: ((now.year().clamp(1980, 2107) - 1980) as u16) << 9
+                | (now.month() as u16) << 5
+                | now.day() as u16,
+            offset: 0,
+            entries: Vec::new(),
+            content: Hasher::new(),
+            content_size: 0,
+        }
+    }
+
+    fn local_header(
+        &mut self,
+        out: &mut Vec<u8>,
+        name: &'static str,
+        data: Option<&[u8]>,
+    ) -> Result<(), TooLarge> {
+        let (flags, crc, size) = match data {
+            Some(data) => (
+                0,
+                crc32fast::hash(data),
+                u32::try_from(data.len()).map_err(|_| TooLarge)?,
+            ),
+            None => (FLAG_DATA_DESCRIPTOR, 0, 0),
+        };
+        let start = out.len();
+
+        out.extend(LOCAL_HEADER.to_le_bytes());
+        out.extend(20_u16.to_le_bytes()); // version needed to extract
+        out.extend(flags.to_le_bytes());
+        out.extend(0_u16.to_le_bytes()); // stored
+        out.extend(self.dos_time.to_le_bytes());
+        out.extend(self.dos_date.to_le_bytes());
+        out.extend(crc.to_le_bytes());
+        out.extend(size.to_le_bytes());
+        out.extend(size.to_le_bytes());
+        out.extend((name.len() as u16).to_le_bytes());
+        out.extend(0_u16.to_le_bytes()); // extra field length
+        out.extend(name.as_bytes());
+        if let Some(data) = data {
+            out.extend(data);
+        }
+
+        self.entries.push(Entry {
+            name,
+            flags,
+            crc,
+            size,
+            offset: self.offset,
+        });
+        advance(&mut self.offset, out.len() - start)
+    }
+
+    fn content(&mut self, out: &mut Vec<u8>, xml: &[u8]) -> Result<(), TooLarge> {
+        advance(&mut self.content_size, xml.len())?;
+        advance(&mut self.offset, xml.len())?;
+        self.content.update(xml);
+        out.extend(xml);
+        Ok(())
+    }
+
+    /// Archive entries preceding the table rows, including the header row.
+    pub fn start(&mut self, columns: &[&str]) -> Result<Bytes, TooLarge> {
+        let mut out = Vec::new();
+        self.local_header(&mut out, "mimetype", Some(MIMETYPE))?;
+        self.local_header(&mut out, "META-INF/manifest.xml", Some(MANIFEST))?;
+        self.local_header(&mut out, "content.xml", None)?;
+        self.content(&mut out, CONTENT_START.as_bytes())?;
+        let header: Vec<_> = columns.iter().map(|c| Cell::String(c)).collect();
+        let row = Self::row_xml(&header);
+        self.content(&mut out, row.as_bytes())?;
+        Ok(out.into())
+    }
+
+    fn row_xml(cells: &[Cell]) -> String {
+        let mut xml = String::from("<table:table-row>");
+        for cell in cells {
+            match cell {
+                Cell::String(s) => xml.push_str(&format!(
+                    r#"<table:table-cell office:value-type="string"><text:p>{}</text:p></table:table-cell>"#,
+                    escape(s)
+                )),
+                Cell::Float(value, text) => xml.push_str(&format!(
+                    r#"<table:table-cell office:value-type="float" office:value="{}"><text:p>{}</text:p></table:table-cell>"#,
+                    escape(value),
+                    escape(text)
+                )),
+                Cell::Date(value, text) => xml.push_str(&format!(
+                    r#"<table:table-cell office:value-type="date" office:date-value="{}"><text:p>{}</text:p></table:table-cell>"#,
+                    escape(value),
+                    escape(text)
+                )),
+                Cell::Empty => xml.push_str("<table:table-cell/>"),
+            }
+        }
+        xml.push_str("</table:table-row>\n");
+        xml
+    }
+
+    pub fn row(&mut self, cells: &[Cell]) -> Result<Bytes, TooLarge> {
+        let mut out = Vec::new();
+        let row = Self::row_xml(cells);
+        self.content(&mut out, row.as_bytes())?;
+        Ok(out.into())
+    }
+
+    /// Closes `content.xml` and writes the central directory.
+    pub fn finish(&mut self) -> Result<Bytes, TooLarge> {
+        let mut out = Vec::new();
+        self.content(&mut out, CONTENT_END.as_bytes())?;
+
+        let crc = std::mem::take(&mut self.content).finalize();
+        let content = self.entries.last_mut().expect("content.xml entry");
+        content.crc = crc;
+        content.size = self.content_size;
+
+        out.extend(DATA_DESCRIPTOR.to_le_bytes());
+        out.extend(crc.to_le_bytes());
+        out.extend(self.content_size.to_le_bytes());
+        out.extend(self.content_size.to_le_bytes());
+        advance(&mut self.offset, 16)?;
+
+        let directory_offset = self.offset;
+        let directory_start = out.len();
+        for entry in &self.entries {
+            out.extend(CENTRAL_HEADER.to_le_bytes());
+            out.extend(20_u16.to_le_bytes()); // version made by
+            out.extend(20_u16.to_le_bytes()); // version needed to extract
+            out.extend(entry.flags.to_le_bytes());
+            out.extend(0_u16.to_le_bytes()); // stored
+            out.extend(self.dos_time.to_le_bytes());
+            out.extend(self.dos_date.to_le_bytes());
+            out.extend(entry.crc.to_le_bytes());
+            out.extend(entry.size.to_le_bytes());
+            out.extend(entry.size.to_le_bytes());
+            out.extend((entry.name.len() as u16).to_le_bytes());
+            out.extend(0_u16.to_le_bytes()); // extra field length
+            out.extend(0_u16.to_le_bytes()); // comment length
+            out.extend(0_u16.to_le_bytes()); // disk number
+            out.extend(0_u16.to_le_bytes()); // internal attributes
+            out.extend(0_u32.to_le_bytes()); // external attributes
+            out.extend(entry.offset.to_le_bytes());
+            out.extend(entry.name.as_bytes());
+        }
+        let mut directory_size = 0;
+        advance(&mut directory_size, out.len() - directory_start)?;
+
+        out.extend(END_OF_CENTRAL_DIRECTORY.to_le_bytes());
+        out.extend(0_u16.to_le_bytes()); // disk number
+        out.extend(0_u16.to_le_bytes()); // disk with central directory
+        out.extend((self.entries.len() as u16).to_le_bytes());
+        out.extend((self.entries.len() as u16).to_le_bytes());
+        out.extend(directory_size.to_le_bytes());
+        out.extend(directory_offset.to_le_bytes());
+        out.extend(0_u16.to_le_bytes()); // comment length
+        Ok(out.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Read};
+
+    fn archive(writer: &mut OdsWriter, rows: &[&[Cell]]) -> Result<Vec<u8>, TooLarge> {
+        let mut ods = writer.start(&["name", "amount"])?.to_vec();
+        for row in rows {
+            ods.extend(writer.row(row)?);
+        }
+        ods.extend(writer.finish()?);
+        Ok(ods)
+    }
+
+    #[test]
+    fn archives_can_be_read() {
+        let mut writer = OdsWriter::new(OffsetDateTime::UNIX_EPOCH);
+        let ods = archive(
+            &mut writer,
+            &[
+                &[Cell::String("Ada & <Bob>"), Cell::Float("1.5", "1,50")],
+                &[Cell::Empty, Cell::Date("2025-01-31T18:30:00", "31.01.2025")],
+            ],
+        )
+        .unwrap();
+
+        let mut zip = zip::ZipArchive::new(Cursor::new(ods)).unwrap();
+        assert_eq!(
+            zip.file_names().collect::<Vec<_>>(),
+            ["mimetype", "META-INF/manifest.xml", "content.xml"]
+        );
+        let mut mimetype = String::new();
+        zip.by_index(0)
+            .unwrap()
+            .read_to_string(&mut mimetype)
+            .unwrap();
+        assert_eq!(mimetype.as_bytes(), MIMETYPE);
+
+        // reading to the end checks the CRC
+        let mut content = String::new();
+        zip.by_name("content.xml")
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert!(content.starts_with(CONTENT_START));
+        assert!(content.ends_with(CONTENT_END));
+        assert!(content.contains(
+            r#"<table:table-cell office:value-type="string"><text:p>amount</text:p></table:table-cell>"#
+        ));
+        assert!(content.contains("<text:p>Ada &amp; &lt;Bob&gt;</text:p>"));
+        assert!(content.contains(r#"office:value="1.5"><text:p>1,50</text:p>"#));
+        assert!(content.contains(r#"office:date-value="2025-01-31T18:30:00""#));
+    }
+
+    #[test]
+    fn archives_beyond_32_bits_fail() {
+        let mut writer = OdsWriter::new(OffsetDateTime::UNIX_EPOCH);
+        writer.start(&["name"]).unwrap();
+        writer.content_size = u32::MAX - 10;
+        assert!(writer.row(&[Cell::String("Ada")]).is_err());
+
+        let mut writer = OdsWriter::new(OffsetDateTime::UNIX_EPOCH);
+        writer.offset = u32::MAX - 200;
+        assert!(archive(&mut writer, &[]).is_err());
+    }
+}
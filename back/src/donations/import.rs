@@ -0,0 +1,524 @@
+use crate::{
+    ApiResult,
+    error::{FieldError, Problem, ProblemDetails, QueryParams},
+    exchange_rates::{self, ExchangeRateError},
+    idempotency::IdempotencyKeyHeader,
+    money::{Currency, Money},
+    repo::{NewSupporter, PaidDonation, PaymentTx, Payments, Sessions},
+    users::auth::validate,
+};
+use axum::{
+    Json,
+    extract::State,
+    http::{HeaderMap, StatusCode, header},
+    response::IntoResponse,
+};
+use serde::{
+    Deserialize, Deserializer, Serialize,
+    de::{
+        self, IntoDeserializer, Visitor,
+        value::{self, MapDeserializer},
+    },
+};
+use std::collections::HashSet;
+use thiserror::Error;
+use time::OffsetDateTime;
+
+#[derive(utoipa::OpenApi)]
+#[openapi(paths(import_donations))]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    ApiDoc::openapi()
+}
+
+#[derive(Error, Debug)]
+pub enum ImportError {
+    #[error("Unsupported import format, send text/csv or application/x-ndjson")]
+    UnsupportedFormat,
+    #[error("Import failed, {} invalid rows", .0.len())]
+    InvalidRows(Vec<RowError>),
+    #[error("Could not query database")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl ProblemDetails for ImportError {
+    fn status(&self) -> StatusCode {
+        match self {
+            Self::UnsupportedFormat => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            Self::InvalidRows(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::DatabaseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn code(&self) -> &'static str {
+        match self {
+            Self::UnsupportedFormat => "unsupported_import_format",
+            Self::InvalidRows(_) => "invalid_import_rows",
+            Self::DatabaseError(_) => "database_error",
+        }
+    }
+
+    fn errors(&self) -> Vec<FieldError> {
+        match self {
+            Self::InvalidRows(rows) => rows
+                .iter()
+                .map(|e| FieldError {
+                    field: e.field.clone(),
+                    message: e.message.clone(),
+                    line: Some(e.line),
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Deserialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportFormat {
+    Csv,
+    Jsonl,
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct ImportQuery {
+    /// Overrides the format detected from `Content-Type`
+    format: Option<ImportFormat>,
+    /// Validate every row and report errors without saving anything
+    #[serde(default)]
+    dry_run: bool,
+}
+
+/// A row of an import file. CSV files need a header naming these columns.
+#[derive(Deserialize)]
+struct ImportRow {
+    coins: u64,
+    #[serde(alias = "income_eur")]
+    amount: Money,
+    currency: Option<Currency>,
+    co_op: String,
+    /// RFC 3339 timestamp or `YYYY-MM-DD` date, defaults to the time of import
+    donated_at: Option<String>,
+    /// Identifier in the source system; rows whose reference was already imported are skipped
+    external_ref: Option<String>,
+    /// Links the donation to the supporter with this name, creating one if none exists
+    name: Option<String>,
+}
+
+/// Field of a [`RowError`] that no single column is to blame for.
+const ROW: &str = "row";
+
+/// Co-ops a donation can be made to, as allowed by the `co_op` column.
+const CO_OPS: &[&str] = &["S4L", "STUDIO-MATIC"];
+
+#[derive(Debug, Serialize, utoipa::ToSchema)]
+pub struct RowError {
+    /// 1-based line number in the uploaded file
+    line: u64,
+    /// Column of the row that is invalid, `row` if the row as a whole is
+    #[schema(example = "donated_at")]
+    field: String,
+    message: String,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+struct ImportReport {
+    dry_run: bool,
+    rows: u64,
+    imported: u64,
+    supporters_created: u64,
+    skipped_duplicates: u64,
+    errors: Vec<RowError>,
+}
+
+fn detect_format(headers: &HeaderMap) -> Option<ImportFormat> {
+    let content_type = headers.get(header::CONTENT_TYPE)?.to_str().ok()?;
+    match content_type.split(';').next()?.trim() {
+        "text/csv" => Some(ImportFormat::Csv),
+        "application/x-ndjson" | "application/jsonl" | "application/json-lines" => {
+            Some(ImportFormat::Jsonl)
+        }
+        _ => None,
+    }
+}
+
+/// An invalid row, with the column to blame.
+struct Invalid {
+    field: String,
+    message: String,
+}
+
+impl Invalid {
+    fn new(field: impl Into<String>, message: impl ToString) -> Self {
+        Self {
+            field: field.into(),
+            message: message.to_string(),
+        }
+    }
+}
+
+/// Deserializes a row, blaming the column whose value is invalid.
+fn deserialize_row<'de, D: Deserializer<'de>>(de: D) -> Result<ImportRow, Invalid>
+where
+    D::Error: ToString,
+{
+    serde_path_to_error::deserialize(de).map_err(|e| {
+        let column = e
+            .path()
+            .iter()
+            .next()
+            .map_or(ROW.to_owned(), ToString::to_string);
+        Invalid::new(column, e.into_inner())
+    })
+}
+
+/// A CSV field, parsed into the type of its column, empty for `None`.
+struct Cell<'a>(&'a str);
+
+impl<'de> IntoDeserializer<'de, value::Error> for Cell<'de> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+impl<'de> Deserializer<'de> for Cell<'de> {
+    type Error = value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_borrowed_str(self.0)
+    }
+
+    fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_u64(self.0.parse().map_err(de::Error::custom)?)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        if self.0.is_empty() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u128 f32 f64 char str string bytes byte_buf unit
+        unit_struct newtype_struct seq tuple tuple_struct map struct enum identifier
+        ignored_any
+    }
+}
+
+fn parse_rows(format: ImportFormat, body: &str) -> Vec<(u64, Result<ImportRow, Invalid>)> {
+    match format {
+        ImportFormat::Csv => {
+            let mut reader = csv::ReaderBuilder::new()
+                .trim(csv::Trim::All)
+                .from_reader(body.as_bytes());
+            let header = match reader.headers() {
+                Ok(header) => header.clone(),
+                Err(e) => return vec![(1, Err(Invalid::new(ROW, e)))],
+            };
+            reader
+                .records()
+                .map(|record| match record {
+                    Ok(record) => (
+                        record.position().map(|p| p.line()).unwrap_or_default(),
+                        deserialize_row(MapDeserializer::new(
+                            header.iter().zip(record.iter().map(Cell)),
+                        )),
+                    ),
+                    Err(e) => (
+                        e.position().map(|p| p.line()).unwrap_or_default(),
+                        Err(Invalid::new(ROW, e)),
+                    ),
+                })
+                .collect()
+        }
+        ImportFormat::Jsonl => body
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                let mut de = serde_json::Deserializer::from_str(line);
+                let row = deserialize_row(&mut de)
+                    .and_then(|row| de.end().map(|()| row).map_err(|e| Invalid::new(ROW, e)));
+                (i as u64 + 1, row)
+            })
+            .collect(),
+    }
+}
+
+enum Imported {
+    Donation { supporter_created: bool },
+    Duplicate,
+}
+
+async fn import_row(
+    tx: &mut dyn PaymentTx,
+    row: ImportRow,
+    seen_refs: &mut HashSet<String>,
+) -> Result<Imported, ImportRowError> {
+    if let Some(external_ref) = &row.external_ref {
+        let exists = tx.external_ref_exists(external_ref).await?;
+        if exists || !seen_refs.insert(external_ref.clone()) {
+            return Ok(Imported::Duplicate);
+        }
+    }
+
+    if !CO_OPS.contains(&row.co_op.as_str()) {
+        return Err(ImportRowError::invalid(
+            "co_op",
+            format!("unknown co_op {:?}", row.co_op),
+        ));
+    }
+    let donated_at = match &row.donated_at {
+        Some(s) => exchange_rates::parse_timestamp(s).ok_or_else(|| {
+            ImportRowError::invalid("donated_at", format!("invalid donated_at {s:?}"))
+        })?,
+        None => OffsetDateTime::now_utc(),
+    };
+    let currency = row.currency.unwrap_or_default();
+    let rate = tx
+        .rate_at(&currency, donated_at)
+        .await
+        .map_err(|e| match e {
+            ExchangeRateError::DatabaseError(e) => ImportRowError::Database(e),
+            e => ImportRowError::invalid("currency", e),
+        })?;
+
+    let income_eur = rate
+        .to_eur(row.amount)
+        .map_err(|e| ImportRowError::invalid("amount", e))?;
+
+    let name = row
+        .name
+        .as_deref()
+        .map(str::trim)
+        .filter(|name| !name.is_empty());
+    let (supporter_id, supporter_created) = match name {
+        Some(name) => match tx.supporter_by_name(name).await? {
+            Some(id) => (Some(id), false),
+            None => {
+                let id = tx
+                    .create_supporter(NewSupporter {
+                        name: name.to_owned(),
+                        email: None,
+                        phone: None,
+                        notes: None,
+                        public: false,
+                    })
+                    .await?;
+                (Some(id), true)
+            }
+        },
+        None => (None, false),
+    };
+
+    tx.create_donation(PaidDonation {
+        coins: row.coins,
+        donated_at,
+        income_eur,
+        currency,
+        amount: row.amount,
+        co_op: row.co_op,
+        external_ref: row.external_ref,
+        supporter_id,
+    })
+    .await?;
+
+    Ok(Imported::Donation { supporter_created })
+}
+
+enum ImportRowError {
+    Invalid(Invalid),
+    Database(sqlx::Error),
+}
+
+impl ImportRowError {
+    fn invalid(field: &str, message: impl ToString) -> Self {
+        Self::Invalid(Invalid::new(field, message))
+    }
+}
+
+impl From<sqlx::Error> for ImportRowError {
+    fn from(e: sqlx::Error) -> Self {
+        match e {
+            // constraint violations only reject the row, anything else fails the import
+            // `external_ref` is the only unique column a row sets
+            sqlx::Error::Database(db) if db.is_unique_violation() => {
+                Self::invalid("external_ref", db.message())
+            }
+            sqlx::Error::Database(db)
+                if db.is_foreign_key_violation() || db.is_check_violation() =>
+            {
+                Self::invalid(ROW, db.message())
+            }
+            e => Self::Database(e),
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/donations/import",
+    params(ImportQuery, IdempotencyKeyHeader),
+    request_body(
+        content = String,
+        description = "CSV with a header row (`text/csv`) or one JSON object per line \
+            (`application/x-ndjson`) with the columns `coins`, `amount`, `currency`, `co_op`, \
+            `donated_at`, `external_ref` and `name`",
+        content_type = "text/csv",
+    ),
+    responses(
+        (
+            status = StatusCode::OK,
+            body = ImportReport,
+            description = "All rows imported, or the dry run report",
+        ),
+        (
+            status = StatusCode::UNPROCESSABLE_ENTITY,
+            description = "Some rows are invalid, nothing was imported",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            description = "Unknown import format",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::CONFLICT,
+            description = "A request with the same `Idempotency-Key` is still being processed",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = Problem,
+            content_type = "application/problem+json",
+        )
+    )
+)]
+pub async fn import_donations(
+    sessions: State<Sessions>,
+    State(payments): State<Payments>,
+    headers: HeaderMap,
+    QueryParams(query): QueryParams<ImportQuery>,
+    body: String,
+) -> ApiResult<impl IntoResponse> {
+    let format = query
+        .format
+        .or_else(|| detect_format(&headers))
+        .ok_or(ImportError::UnsupportedFormat)?;
+    let _ = validate(sessions, headers).await?;
+
+    let rows = parse_rows(format, &body);
+    let mut report = ImportReport {
+        dry_run: query.dry_run,
+        rows: rows.len() as u64,
+        imported: 0,
+        supporters_created: 0,
+        skipped_duplicates: 0,
+        errors: Vec::new(),
+    };
+
+    let mut tx = payments.begin().await.map_err(ImportError::DatabaseError)?;
+    let mut seen_refs = HashSet::new();
+    for (line, row) in rows {
+        let row = match row {
+            Ok(row) => row,
+            Err(Invalid { field, message }) => {
+                report.errors.push(RowError {
+                    line,
+                    field,
+                    message,
+                });
+                continue;
+            }
+        };
+
+        match import_row(&mut *tx, row, &mut seen_refs).await {
+            Ok(Imported::Donation { supporter_created }) => {
+                report.imported += 1;
+                report.supporters_created += u64::from(supporter_created);
+            }
+            Ok(Imported::Duplicate) => report.skipped_duplicates += 1,
+            Err(ImportRowError::Invalid(Invalid { field, message })) => {
+                report.errors.push(RowError {
+                    line,
+                    field,
+                    message,
+                })
+            }
+            Err(ImportRowError::Database(e)) => return Err(ImportError::DatabaseError(e).into()),
+        }
+    }
+
+    if query.dry_run {
+        tx.rollback().await.map_err(ImportError::DatabaseError)?;
+    } else if !report.errors.is_empty() {
+        tx.rollback().await.map_err(ImportError::DatabaseError)?;
+        return Err(ImportError::InvalidRows(report.errors).into());
+    } else {
+        tx.commit().await.map_err(ImportError::DatabaseError)?;
+    }
+
+    Ok((StatusCode::OK, Json(report)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn errors(format: ImportFormat, body: &str) -> Vec<(u64, String)> {
+        parse_rows(format, body)
+            .into_iter()
+            .filter_map(|(line, row)| row.err().map(|e| (line, e.field)))
+            .collect()
+    }
+
+    #[test]
+    fn csv_errors_name_the_column() {
+        let body = "coins,amount,co_op\n1,1.00,S4L\nmany,1.00,S4L\n1,lots,S4L\n1,1.00\n";
+        assert_eq!(
+            errors(ImportFormat::Csv, body),
+            [
+                (3, "coins".to_owned()),
+                (4, "amount".to_owned()),
+                (5, ROW.to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn jsonl_errors_name_the_column() {
+        let body = concat!(
+            r#"{"coins": 1, "amount": "1.00", "co_op": "S4L"}"#,
+            "\n\n",
+            r#"{"coins": -1, "amount": "1.00", "co_op": "S4L"}"#,
+            "\n",
+            r#"{"coins": 1, "amount": "1.00"}"#,
+            "\n",
+            "not json\n",
+            r#"{"coins": 1, "amount": "1.00", "co_op": "S4L"} trailing"#,
+        );
+        assert_eq!(
+            errors(ImportFormat::Jsonl, body),
+            [
+                (3, "coins".to_owned()),
+                (4, ROW.to_owned()),
+                (5, ROW.to_owned()),
+                (6, ROW.to_owned()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,225 @@
+use super::{DonationError, parse_time_param};
+use crate::{
+    ApiResult,
+    error::{Problem, QueryParams},
+    money::{Currency, Money, MoneyError, Rate},
+    repo::{Donations, Income, Interval, Sessions},
+    users::auth::validate,
+};
+use axum::{
+    Json,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+};
+use serde::{Deserialize, Serialize};
+use time::{Duration, OffsetDateTime, format_description::well_known::Rfc3339};
+
+#[derive(utoipa::OpenApi)]
+#[openapi(paths(get_donation_stats))]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    ApiDoc::openapi()
+}
+
+const DEFAULT_RANGE: Duration = Duration::days(30);
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct DonationStatsQuery {
+    /// Start of the range (inclusive), defaults to 30 days before `to`
+    from: Option<String>,
+    /// End of the range (exclusive), defaults to now
+    to: Option<String>,
+    /// Granularity of `by_period`, defaults to `day`
+    interval: Option<Interval>,
+    /// Number of top supporters to return, defaults to 10
+    top: Option<u32>,
+    /// Currency to present the `total`s in, defaults to EUR
+    currency: Option<Currency>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+struct Totals {
+    donations: i64,
+    coins: u64,
+    income_eur: Money,
+    /// `income_eur` converted to the requested currency at its current rate
+    total: Money,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+struct CoOpStats {
+    co_op: String,
+    #[serde(flatten)]
+    totals: Totals,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+struct PeriodStats {
+    /// First day of the period
+    period_start: String,
+    #[serde(flatten)]
+    totals: Totals,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+struct TopSupporter {
+    supporter_id: u64,
+    name: String,
+    donations: i64,
+    income_eur: Money,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+struct Comparison {
+    /// Totals of the preceding range of the same length
+    previous: Totals,
+    /// Change of `income_eur` relative to the previous range in percent, null if it was zero
+    income_change_percent: Option<f64>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+struct DonationStatsResponse {
+    from: String,
+    to: String,
+    interval: Interval,
+    /// Currency of the `total`s
+    currency: Currency,
+    /// Current rate used to convert the EUR income into `currency`
+    rate: Rate,
+    totals: Totals,
+    /// Average EUR income per coin as an exact decimal, null if no coins were donated
+    average_eur_per_coin: Option<String>,
+    by_co_op: Vec<CoOpStats>,
+    by_period: Vec<PeriodStats>,
+    top_supporters: Vec<TopSupporter>,
+    comparison: Comparison,
+}
+
+impl Totals {
+    fn new(income: Income, rate: Rate) -> Result<Self, MoneyError> {
+        Ok(Self {
+            donations: income.donations,
+            coins: income.coins,
+            income_eur: income.income_eur,
+            total: rate.convert_eur(income.income_eur)?,
+        })
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/donations/stats",
+    params(DonationStatsQuery),
+    responses(
+        (
+            status = StatusCode::OK,
+            body = DonationStatsResponse,
+        ),
+        (
+            status = StatusCode::BAD_REQUEST,
+            description = "Invalid timestamp or query",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::UNPROCESSABLE_ENTITY,
+            description = "`from` is after `to`, or no exchange rate for the requested currency",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = Problem,
+            content_type = "application/problem+json",
+        )
+    ),
+)]
+pub async fn get_donation_stats(
+    sessions: State<Sessions>,
+    State(donations): State<Donations>,
+    headers: HeaderMap,
+    QueryParams(query): QueryParams<DonationStatsQuery>,
+) -> ApiResult<impl IntoResponse> {
+    let _ = validate(sessions, headers).await?;
+
+    let to = parse_time_param("to", query.to)?.unwrap_or_else(OffsetDateTime::now_utc);
+    let from = parse_time_param("from", query.from)?.unwrap_or(to - DEFAULT_RANGE);
+    if from > to {
+        return Err(DonationError::InvalidRange.into());
+    }
+    let interval = query.interval.unwrap_or_default();
+    let currency = query.currency.unwrap_or_default();
+    let rate = donations
+        .rate_at(&currency, OffsetDateTime::now_utc())
+        .await?;
+
+    let current = donations.income(from, to).await?;
+    let previous = donations.income(from - (to - from), from).await?;
+    let average_eur_per_coin = current.income_eur.per(current.coins);
+    let income_change_percent = (previous.income_eur.cents() != 0).then(|| {
+        let change = (current.income_eur.cents() - previous.income_eur.cents()) as f64
+            / previous.income_eur.cents() as f64;
+        (change * 10_000.0).round() / 100.0
+    });
+
+    let by_co_op = donations
+        .income_by_co_op(from, to)
+        .await?
+        .into_iter()
+        .map(|income| {
+            Ok(CoOpStats {
+                co_op: income.co_op,
+                totals: Totals::new(income.income, rate)?,
+            })
+        })
+        .collect::<Result<_, MoneyError>>()?;
+    let by_period = donations
+        .income_by_period(from, to, interval)
+        .await?
+        .into_iter()
+        .map(|income| {
+            Ok(PeriodStats {
+                period_start: income.period_start.to_string(),
+                totals: Totals::new(income.income, rate)?,
+            })
+        })
+        .collect::<Result<_, MoneyError>>()?;
+    let top_supporters = donations
+        .top_supporters(from, to, query.top.unwrap_or(10))
+        .await?
+        .into_iter()
+        .map(|supporter| TopSupporter {
+            supporter_id: supporter.supporter_id,
+            name: supporter.name,
+            donations: supporter.donations,
+            income_eur: supporter.income_eur,
+        })
+        .collect();
+
+    let stats = DonationStatsResponse {
+        from: from.format(&Rfc3339).map_err(DonationError::FormatError)?,
+        to: to.format(&Rfc3339).map_err(DonationError::FormatError)?,
+        interval,
+        currency,
+        rate,
+        totals: Totals::new(current, rate)?,
+        average_eur_per_coin,
+        by_co_op,
+        by_period,
+        top_supporters,
+        comparison: Comparison {
+            previous: Totals::new(previous, rate)?,
+            income_change_percent,
+        },
+    };
+
+    Ok((StatusCode::OK, Json(stats)))
+}
@@ -0,0 +1,199 @@
+use crate::{
+    campaigns, donations, exchange_rates, idempotency, jobs, metrics, money, request_id, schedules,
+    supporters,
+    users::auth::{signin, signup, validate},
+    webhooks,
+};
+use axum::{
+    Json,
+    extract::{
+        FromRequest, FromRequestParts, Path, Query,
+        rejection::{JsonRejection, PathRejection, QueryRejection},
+    },
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use thiserror::Error;
+
+pub type ApiResult<T> = Result<T, ApiError>;
+
+#[derive(Error, Debug)]
+pub enum ApiError {
+    #[error("invalid request body: {0}")]
+    Body(#[from] JsonRejection),
+    #[error("invalid query string: {0}")]
+    Query(#[from] QueryRejection),
+    #[error("invalid path: {0}")]
+    Path(#[from] PathRejection),
+    #[error("could not validate session: {0}")]
+    Validation(#[from] validate::ValidationError),
+    #[error("could not sign in: {0}")]
+    Signin(#[from] signin::SigninError),
+    #[error("could not sign up: {0}")]
+    Signup(#[from] signup::SignupError),
+    #[error("could not get donations: {0}")]
+    Donation(#[from] donations::DonationError),
+    #[error("could not import donations: {0}")]
+    Import(#[from] donations::import::ImportError),
+    #[error("could not get supporters: {0}")]
+    Supporter(#[from] supporters::SupporterError),
+    #[error("could not convert amount: {0}")]
+    Money(#[from] money::MoneyError),
+    #[error("could not get exchange rates: {0}")]
+    ExchangeRate(#[from] exchange_rates::ExchangeRateError),
+    #[error("could not get donation schedules: {0}")]
+    Schedule(#[from] schedules::ScheduleError),
+    #[error("could not get campaigns: {0}")]
+    Campaign(#[from] campaigns::CampaignError),
+    #[error("could not process webhook: {0}")]
+    Webhook(#[from] webhooks::WebhookError),
+    #[error("could not handle idempotency key: {0}")]
+    Idempotency(#[from] idempotency::IdempotencyError),
+    #[error("could not get metrics: {0}")]
+    Metrics(#[from] metrics::MetricsError),
+    #[error("could not get jobs: {0}")]
+    Job(#[from] jobs::JobError),
+}
+
+/// Implemented by every error enum that can end up in an [`ApiError`].
+pub trait ProblemDetails: std::error::Error {
+    fn status(&self) -> StatusCode;
+    /// Stable, machine-readable identifier of the error.
+    fn code(&self) -> &'static str;
+    fn errors(&self) -> Vec<FieldError> {
+        Vec::new()
+    }
+}
+
+impl ApiError {
+    fn inner(&self) -> &dyn ProblemDetails {
+        match self {
+            ApiError::Body(e) => e,
+            ApiError::Query(e) => e,
+            ApiError::Path(e) => e,
+            ApiError::Validation(e) => e,
+            ApiError::Signin(e) => e,
+            ApiError::Signup(e) => e,
+            ApiError::Donation(e) => e,
+            ApiError::Import(e) => e,
+            ApiError::Supporter(e) => e,
+            ApiError::Money(e) => e,
+            ApiError::ExchangeRate(e) => e,
+            ApiError::Schedule(e) => e,
+            ApiError::Campaign(e) => e,
+            ApiError::Webhook(e) => e,
+            ApiError::Idempotency(e) => e,
+            ApiError::Metrics(e) => e,
+            ApiError::Job(e) => e,
+        }
+    }
+}
+
+/// [`Json`] extractor whose rejections are reported as [`Problem`]s.
+#[derive(FromRequest)]
+#[from_request(via(Json), rejection(ApiError))]
+pub struct JsonBody<T>(pub T);
+
+impl ProblemDetails for JsonRejection {
+    fn status(&self) -> StatusCode {
+        JsonRejection::status(self)
+    }
+
+    fn code(&self) -> &'static str {
+        "invalid_body"
+    }
+}
+
+/// [`Query`] extractor whose rejections are reported as [`Problem`]s.
+#[derive(FromRequestParts)]
+#[from_request(via(Query), rejection(ApiError))]
+pub struct QueryParams<T>(pub T);
+
+impl ProblemDetails for QueryRejection {
+    fn status(&self) -> StatusCode {
+        QueryRejection::status(self)
+    }
+
+    fn code(&self) -> &'static str {
+        "invalid_query"
+    }
+}
+
+/// [`Path`] extractor whose rejections are reported as [`Problem`]s.
+#[derive(FromRequestParts)]
+#[from_request(via(Path), rejection(ApiError))]
+pub struct PathParams<T>(pub T);
+
+impl ProblemDetails for PathRejection {
+    fn status(&self) -> StatusCode {
+        PathRejection::status(self)
+    }
+
+    fn code(&self) -> &'static str {
+        "invalid_path"
+    }
+}
+
+/// RFC 9457 problem details, returned as `application/problem+json` by every failing endpoint.
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct Problem {
+    #[serde(rename = "type")]
+    #[schema(example = "about:blank")]
+    r#type: &'static str,
+    #[schema(example = "Not Found")]
+    title: String,
+    #[schema(example = 404)]
+    status: u16,
+    #[schema(example = "donation_not_found")]
+    code: &'static str,
+    #[schema(example = "Donation not found")]
+    detail: String,
+    request_id: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    errors: Vec<FieldError>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct FieldError {
+    #[schema(example = "email")]
+    pub field: String,
+    pub message: String,
+    /// 1-based line of the uploaded file the error is about
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<u64>,
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let inner = self.inner();
+        let status = inner.status();
+        ::metrics::counter!(metrics::HTTP_PROBLEMS, "code" => inner.code()).increment(1);
+        // the problem's detail is kept generic, so the cause is only logged here
+        if status.is_server_error() {
+            tracing::error!(
+                code = inner.code(),
+                error = %inner,
+                cause = inner.source().map(ToString::to_string),
+                "{self}"
+            );
+        }
+
+        let problem = Problem {
+            r#type: "about:blank",
+            title: status.canonical_reason().unwrap_or_default().to_owned(),
+            status: status.as_u16(),
+            code: inner.code(),
+            detail: inner.to_string(),
+            request_id: request_id::current(),
+            errors: inner.errors(),
+        };
+
+        (
+            status,
+            [(header::CONTENT_TYPE, "application/problem+json")],
+            Json(problem),
+        )
+            .into_response()
+    }
+}
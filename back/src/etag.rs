@@ -0,0 +1,64 @@
+//! Optimistic concurrency for rows with a `version` column, which every write
+//! increments. The version is exposed as a strong `ETag` and checked against
+//! `If-Match` on updates and deletes.
+
+use axum::http::{HeaderMap, HeaderValue, header};
+
+pub fn etag(version: u32) -> HeaderValue {
+    HeaderValue::from_str(&format!("\"{version}\"")).expect("numeric etag")
+}
+
+/// Precondition of a request's `If-Match` header.
+pub enum IfMatch {
+    /// The header is missing or `*`
+    Any,
+    /// Versions of the listed entity tags. Weak and malformed tags are left out,
+    /// as `If-Match` uses strong comparison
+    Versions(Vec<u32>),
+}
+
+impl IfMatch {
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let values: Vec<_> = headers
+            .get_all(header::IF_MATCH)
+            .iter()
+            .map(|v| v.to_str().unwrap_or_default())
+            .collect();
+        if values.is_empty() || values.iter().any(|v| v.trim() == "*") {
+            return Self::Any;
+        }
+
+        Self::Versions(
+            values
+                .iter()
+                .flat_map(|v| v.split(','))
+                .filter_map(|tag| {
+                    tag.trim()
+                        .strip_prefix('"')?
+                        .strip_suffix('"')?
+                        .parse()
+                        .ok()
+                })
+                .collect(),
+        )
+    }
+
+    pub fn matches(&self, version: u32) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Versions(versions) => versions.contains(&version),
+        }
+    }
+}
+
+/// `If-Match` header accepted by updating and deleting endpoints, only used to
+/// document it since handlers read it through [`IfMatch`].
+#[allow(dead_code)]
+#[derive(utoipa::IntoParams)]
+#[into_params(parameter_in = Header)]
+pub struct IfMatchHeader {
+    /// `ETag` of the version the change is based on. The request fails with
+    /// `412 Precondition Failed` if the resource was modified since
+    #[param(rename = "If-Match")]
+    if_match: Option<String>,
+}
@@ -0,0 +1,390 @@
+use crate::{
+    ApiResult,
+    error::{FieldError, JsonBody, Problem, ProblemDetails, QueryParams},
+    idempotency::IdempotencyKeyHeader,
+    money::{Currency, Rate},
+    repo::{ExchangeRate, ExchangeRates, Sessions},
+    users::auth::validate,
+};
+use axum::{
+    Json,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use time::{Date, OffsetDateTime, format_description::well_known::Rfc3339};
+
+#[derive(utoipa::OpenApi)]
+#[openapi(paths(
+    get_exchange_rates,
+    get_currencies,
+    post_exchange_rate,
+    import_exchange_rates
+))]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    ApiDoc::openapi()
+}
+
+#[derive(Error, Debug)]
+pub enum ExchangeRateError {
+    #[error("No exchange rate for {0} at {1}")]
+    MissingRate(Currency, String),
+    #[error("Invalid timestamp: {0:?}")]
+    InvalidTimestamp(String),
+    #[error("EUR is the base currency, its rate is always 1")]
+    EurRate,
+    #[error("Invalid CSV on line {line}: {message}")]
+    InvalidCsv { line: u64, message: String },
+    #[error("Could not format")]
+    FormatError(#[from] time::error::Format),
+    #[error("Could not query database")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl ProblemDetails for ExchangeRateError {
+    fn status(&self) -> StatusCode {
+        match self {
+            Self::MissingRate(..) => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::InvalidTimestamp(_) => StatusCode::BAD_REQUEST,
+            Self::EurRate => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::InvalidCsv { .. } => StatusCode::BAD_REQUEST,
+            Self::FormatError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::DatabaseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn code(&self) -> &'static str {
+        match self {
+            Self::MissingRate(..) => "missing_exchange_rate",
+            Self::InvalidTimestamp(_) => "invalid_timestamp",
+            Self::EurRate => "eur_exchange_rate",
+            Self::InvalidCsv { .. } => "invalid_csv",
+            Self::FormatError(_) => "format_error",
+            Self::DatabaseError(_) => "database_error",
+        }
+    }
+
+    fn errors(&self) -> Vec<FieldError> {
+        match self {
+            Self::MissingRate(..) => vec![FieldError {
+                field: "currency".to_owned(),
+                message: self.to_string(),
+                line: None,
+            }],
+            Self::InvalidTimestamp(_) => vec![FieldError {
+                field: "valid_from".to_owned(),
+                message: self.to_string(),
+                line: None,
+            }],
+            Self::EurRate => vec![FieldError {
+                field: "currency".to_owned(),
+                message: self.to_string(),
+                line: None,
+            }],
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+struct ExchangeRateResponse {
+    currency: Currency,
+    valid_from: String,
+    rate: Rate,
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct ExchangeRateRequest {
+    currency: Currency,
+    /// RFC 3339 timestamp or `YYYY-MM-DD` date from which the rate applies
+    #[schema(example = "2025-01-01")]
+    valid_from: String,
+    rate: Rate,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+struct ImportResponse {
+    imported: u64,
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct ExchangeRateQuery {
+    /// Only list rates of this currency
+    currency: Option<Currency>,
+}
+
+/// Parses an RFC 3339 timestamp or a `YYYY-MM-DD` date (taken as midnight UTC).
+pub fn parse_timestamp(s: &str) -> Option<OffsetDateTime> {
+    let s = s.trim();
+    OffsetDateTime::parse(s, &Rfc3339).ok().or_else(|| {
+        Date::parse(s, time::macros::format_description!("[year]-[month]-[day]"))
+            .ok()
+            .map(|d| d.midnight().assume_utc())
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/exchange-rates",
+    params(ExchangeRateQuery),
+    responses(
+        (
+            status = StatusCode::OK,
+            body = Vec<ExchangeRateResponse>,
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = Problem,
+            content_type = "application/problem+json",
+        )
+    ),
+)]
+pub async fn get_exchange_rates(
+    sessions: State<Sessions>,
+    State(exchange_rates): State<ExchangeRates>,
+    headers: HeaderMap,
+    QueryParams(query): QueryParams<ExchangeRateQuery>,
+) -> ApiResult<impl IntoResponse> {
+    let _ = validate(sessions, headers).await?;
+    let rates = exchange_rates.list(query.currency.as_ref()).await?;
+
+    let rates = rates
+        .into_iter()
+        .map(
+            |ExchangeRate {
+                 currency,
+                 valid_from,
+                 rate,
+             }| {
+                Ok(ExchangeRateResponse {
+                    currency,
+                    valid_from: valid_from
+                        .to_utc()
+                        .format(&Rfc3339)
+                        .map_err(ExchangeRateError::FormatError)?,
+                    rate,
+                })
+            },
+        )
+        .collect::<ApiResult<Vec<_>>>()?;
+
+    Ok((StatusCode::OK, Json(rates)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/exchange-rates/currencies",
+    responses(
+        (
+            status = StatusCode::OK,
+            body = Vec<Currency>,
+            description = "EUR and every currency with at least one exchange rate",
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = Problem,
+            content_type = "application/problem+json",
+        )
+    ),
+)]
+pub async fn get_currencies(
+    sessions: State<Sessions>,
+    State(exchange_rates): State<ExchangeRates>,
+    headers: HeaderMap,
+) -> ApiResult<impl IntoResponse> {
+    let _ = validate(sessions, headers).await?;
+    let mut currencies = exchange_rates.currencies().await?;
+
+    if !currencies.iter().any(Currency::is_eur) {
+        currencies.insert(0, Currency::eur());
+    }
+
+    Ok((StatusCode::OK, Json(currencies)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/exchange-rates",
+    params(IdempotencyKeyHeader),
+    request_body = ExchangeRateRequest,
+    responses(
+        (
+            status = StatusCode::CREATED,
+            description = "Successfully added or replaced exchange rate",
+        ),
+        (
+            status = StatusCode::BAD_REQUEST,
+            description = "Invalid timestamp",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::UNPROCESSABLE_ENTITY,
+            description = "Invalid request body or a rate for EUR",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::CONFLICT,
+            description = "A request with the same `Idempotency-Key` is still being processed",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = Problem,
+            content_type = "application/problem+json",
+        )
+    )
+)]
+pub async fn post_exchange_rate(
+    sessions: State<Sessions>,
+    State(exchange_rates): State<ExchangeRates>,
+    headers: HeaderMap,
+    JsonBody(req): JsonBody<ExchangeRateRequest>,
+) -> ApiResult<impl IntoResponse> {
+    let _ = validate(sessions, headers).await?;
+
+    if req.currency.is_eur() {
+        return Err(ExchangeRateError::EurRate.into());
+    }
+    let valid_from = parse_timestamp(&req.valid_from)
+        .ok_or_else(|| ExchangeRateError::InvalidTimestamp(req.valid_from.clone()))?;
+
+    exchange_rates
+        .upsert(vec![ExchangeRate {
+            currency: req.currency,
+            valid_from,
+            rate: req.rate,
+        }])
+        .await?;
+
+    Ok(StatusCode::CREATED)
+}
+
+#[derive(Deserialize)]
+struct CsvRecord {
+    currency: String,
+    valid_from: String,
+    rate: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/exchange-rates/import",
+    params(IdempotencyKeyHeader),
+    request_body(
+        content = String,
+        content_type = "text/csv",
+        description = "CSV with a `currency,valid_from,rate` header; existing rates are replaced",
+    ),
+    responses(
+        (
+            status = StatusCode::OK,
+            body = ImportResponse,
+            description = "All rows imported",
+        ),
+        (
+            status = StatusCode::BAD_REQUEST,
+            description = "Invalid CSV, nothing was imported",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::UNPROCESSABLE_ENTITY,
+            description = "The `Idempotency-Key` was already used for a different request",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::CONFLICT,
+            description = "A request with the same `Idempotency-Key` is still being processed",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = Problem,
+            content_type = "application/problem+json",
+        )
+    )
+)]
+pub async fn import_exchange_rates(
+    sessions: State<Sessions>,
+    State(exchange_rates): State<ExchangeRates>,
+    headers: HeaderMap,
+    body: String,
+) -> ApiResult<impl IntoResponse> {
+    let _ = validate(sessions, headers).await?;
+
+    let invalid = |line, message: String| ExchangeRateError::InvalidCsv { line, message };
+    let csv_error = |e: csv::Error| {
+        invalid(
+            e.position().map(|p| p.line()).unwrap_or_default(),
+            e.to_string(),
+        )
+    };
+
+    let mut rows = Vec::new();
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(body.as_bytes());
+    let header = reader.headers().map_err(csv_error)?.clone();
+    for record in reader.records() {
+        let record = record.map_err(csv_error)?;
+        let line = record.position().map(|p| p.line()).unwrap_or_default();
+        let record: CsvRecord = record.deserialize(Some(&header)).map_err(csv_error)?;
+
+        let currency = record
+            .currency
+            .parse::<Currency>()
+            .map_err(|e| invalid(line, e.to_string()))?;
+        if currency.is_eur() {
+            return Err(invalid(line, ExchangeRateError::EurRate.to_string()).into());
+        }
+        let valid_from = parse_timestamp(&record.valid_from)
+            .ok_or_else(|| invalid(line, format!("invalid valid_from {:?}", record.valid_from)))?;
+        let rate = record
+            .rate
+            .parse::<Rate>()
+            .map_err(|e| invalid(line, e.to_string()))?;
+        rows.push(ExchangeRate {
+            currency,
+            valid_from,
+            rate,
+        });
+    }
+
+    let imported = rows.len() as u64;
+    exchange_rates.upsert(rows).await?;
+
+    Ok((StatusCode::OK, Json(ImportResponse { imported })))
+}
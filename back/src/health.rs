@@ -1,13 +1,119 @@
-use axum::http::StatusCode;
+use crate::db::Database;
+use axum::{Json, extract::State, http::StatusCode, response::IntoResponse};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashSet},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
 #[derive(utoipa::OpenApi)]
-#[openapi(paths(health))]
+#[openapi(paths(health, live, ready))]
 struct ApiDoc;
 pub fn openapi() -> utoipa::openapi::OpenApi {
     use utoipa::OpenApi;
     ApiDoc::openapi()
 }
 
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+const DATABASE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Last heartbeats of the background tasks, shared with the readiness check.
+#[derive(Clone, Default)]
+pub struct BackgroundTasks(Arc<Mutex<BTreeMap<&'static str, TaskBeat>>>);
+
+struct TaskBeat {
+    interval: Duration,
+    last: Instant,
+}
+
+impl BackgroundTasks {
+    /// Tracks a task that beats every `interval`.
+    pub fn register(&self, name: &'static str, interval: Duration) -> Heartbeat {
+        self.0.lock().unwrap().insert(
+            name,
+            TaskBeat {
+                interval,
+                last: Instant::now(),
+            },
+        );
+        Heartbeat {
+            tasks: self.clone(),
+            name,
+        }
+    }
+
+    /// A task is considered dead once it missed two beats.
+    fn statuses(&self) -> Vec<(&'static str, Option<Duration>)> {
+        self.0
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, beat)| {
+                let since = beat.last.elapsed();
+                (*name, (since <= beat.interval * 2).then_some(since))
+            })
+            .collect()
+    }
+}
+
+pub struct Heartbeat {
+    tasks: BackgroundTasks,
+    name: &'static str,
+}
+
+impl Heartbeat {
+    pub fn beat(&self) {
+        if let Some(beat) = self.tasks.0.lock().unwrap().get_mut(self.name) {
+            beat.last = Instant::now();
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Up,
+    Down,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct ComponentHealth {
+    status: Status,
+    #[schema(example = 1.5)]
+    latency_ms: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<String>,
+}
+
+impl ComponentHealth {
+    fn up(latency: Duration) -> Self {
+        Self {
+            status: Status::Up,
+            latency_ms: Some(latency.as_secs_f64() * 1000.0),
+            detail: None,
+        }
+    }
+
+    fn down(detail: impl Into<String>) -> Self {
+        Self {
+            status: Status::Down,
+            latency_ms: None,
+            detail: Some(detail.into()),
+        }
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct HealthResponse {
+    status: Status,
+    #[schema(example = "0.1.0")]
+    version: &'static str,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    components: BTreeMap<String, ComponentHealth>,
+}
+
+/// Kept for existing monitors, prefer `/health/live` or `/health/ready`.
 #[utoipa::path(
     get,
     path = "/health",
@@ -18,3 +124,130 @@ pub fn openapi() -> utoipa::openapi::OpenApi {
 pub async fn health() -> StatusCode {
     StatusCode::OK
 }
+
+#[utoipa::path(
+    get,
+    path = "/health/live",
+    responses(
+        (
+            status = StatusCode::OK,
+            description = "The process is up",
+            body = HealthResponse,
+        ),
+    ),
+)]
+pub async fn live() -> Json<HealthResponse> {
+    Json(HealthResponse {
+        status: Status::Up,
+        version: VERSION,
+        components: BTreeMap::new(),
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/health/ready",
+    responses(
+        (
+            status = StatusCode::OK,
+            description = "The database is reachable and migrated, and all background tasks are running",
+            body = HealthResponse,
+        ),
+        (
+            status = StatusCode::SERVICE_UNAVAILABLE,
+            description = "At least one component is down",
+            body = HealthResponse,
+        ),
+    ),
+)]
+pub async fn ready(
+    State(db): State<Database>,
+    State(tasks): State<BackgroundTasks>,
+) -> impl IntoResponse {
+    let mut components = BTreeMap::new();
+
+    let start = Instant::now();
+    let database = tokio::time::timeout(DATABASE_TIMEOUT, db.ping())
+        .await
+        .map_err(|_| "timed out".to_owned())
+        .and_then(|res| {
+            res.map_err(|e| {
+                // the cause may name hosts or users, so it is only logged
+                tracing::warn!(error = %e, "Database ping failed");
+                "unavailable".to_owned()
+            })
+        });
+    let database_up = database.is_ok();
+    components.insert(
+        "database".to_owned(),
+        match database {
+            Ok(_) => ComponentHealth::up(start.elapsed()),
+            Err(e) => ComponentHealth::down(e),
+        },
+    );
+
+    components.insert(
+        "migrations".to_owned(),
+        if database_up {
+            check_migrations(&db).await
+        } else {
+            ComponentHealth::down("database unavailable")
+        },
+    );
+
+    for (name, since) in tasks.statuses() {
+        components.insert(
+            format!("task:{name}"),
+            match since {
+                Some(since) => ComponentHealth {
+                    status: Status::Up,
+                    latency_ms: None,
+                    detail: Some(format!("last heartbeat {}s ago", since.as_secs())),
+                },
+                None => ComponentHealth::down("no heartbeat"),
+            },
+        );
+    }
+
+    let status = if components.values().all(|c| c.status == Status::Up) {
+        Status::Up
+    } else {
+        Status::Down
+    };
+    (
+        match status {
+            Status::Up => StatusCode::OK,
+            Status::Down => StatusCode::SERVICE_UNAVAILABLE,
+        },
+        Json(HealthResponse {
+            status,
+            version: VERSION,
+            components,
+        }),
+    )
+}
+
+/// Whether every migration embedded in the binary was applied successfully.
+async fn check_migrations(db: &Database) -> ComponentHealth {
+    let start = Instant::now();
+    let applied = match tokio::time::timeout(DATABASE_TIMEOUT, db.applied_migrations()).await {
+        Ok(Ok(applied)) => applied.into_iter().collect::<HashSet<_>>(),
+        Ok(Err(e)) => {
+            tracing::warn!(error = %e, "Could not read applied migrations");
+            return ComponentHealth::down("unavailable");
+        }
+        Err(_) => return ComponentHealth::down("timed out"),
+    };
+
+    let pending: Vec<_> = db
+        .migrator()
+        .iter()
+        .filter(|m| !m.migration_type.is_down_migration() && !applied.contains(&m.version))
+        .map(|m| m.version.to_string())
+        .collect();
+    if pending.is_empty() {
+        ComponentHealth::up(start.elapsed())
+    } else {
+        ComponentHealth::down(format!("pending: {}", pending.join(", ")))
+    }
+}
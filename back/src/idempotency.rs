@@ -0,0 +1,258 @@
+use crate::{
+    error::{ApiError, ProblemDetails},
+    jobs::JobResult,
+    repo::{IdempotencyKeys, SessionRepo, Sessions, StoredKey},
+    users::auth::validate::session_account,
+};
+use axum::{
+    body::{Body, to_bytes},
+    extract::{Request, State},
+    http::{HeaderName, HeaderValue, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+use thiserror::Error;
+
+pub const IDEMPOTENCY_KEY: HeaderName = HeaderName::from_static("idempotency-key");
+pub const IDEMPOTENT_REPLAYED: HeaderName = HeaderName::from_static("idempotent-replayed");
+
+/// How long a key and its response are kept for retries.
+const RETENTION: Duration = Duration::from_hours(24);
+pub const CLEANUP_SCHEDULE: &str = "30 * * * *";
+/// Largest request or response body that is hashed or stored, matching axum's default body limit.
+const MAX_BODY: usize = 2 * 1024 * 1024;
+/// How long a key may be claimed without a stored response before a retry takes
+/// it over, e.g. after the server was stopped while handling the request.
+const PENDING_TIMEOUT: Duration = Duration::from_mins(10);
+
+#[derive(Error, Debug)]
+pub enum IdempotencyError {
+    #[error("Idempotency-Key must be 1 to 255 visible ASCII characters")]
+    InvalidKey,
+    #[error("Idempotency-Key was already used for a different request")]
+    KeyReused,
+    #[error("A request with this Idempotency-Key is still being processed")]
+    InProgress,
+    #[error("Request body is too large")]
+    BodyTooLarge,
+    #[error("Could not query database")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl ProblemDetails for IdempotencyError {
+    fn status(&self) -> StatusCode {
+        match self {
+            Self::InvalidKey => StatusCode::BAD_REQUEST,
+            Self::KeyReused => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::InProgress => StatusCode::CONFLICT,
+            Self::BodyTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::DatabaseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn code(&self) -> &'static str {
+        match self {
+            Self::InvalidKey => "invalid_idempotency_key",
+            Self::KeyReused => "idempotency_key_reused",
+            Self::InProgress => "idempotency_key_in_use",
+            Self::BodyTooLarge => "body_too_large",
+            Self::DatabaseError(_) => "database_error",
+        }
+    }
+}
+
+/// Tells a retry of a request from a different request reusing its key.
+pub fn request_hash(req: &Request, body: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(req.method().as_str());
+    hasher.update(b" ");
+    hasher.update(req.uri().to_string());
+    hasher.update(b"\n");
+    hasher.update(body);
+    hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// A claimed key whose response is not stored yet. Dropping it releases the
+/// key, so that a request whose handler panicked or whose client went away
+/// can be retried.
+struct Claim {
+    keys: IdempotencyKeys,
+    account_id: u64,
+    key: String,
+    settled: bool,
+}
+
+impl Claim {
+    /// Releases the key for a request that did not take effect.
+    async fn release(mut self) -> Result<(), sqlx::Error> {
+        self.settled = true;
+        self.keys.release(self.account_id, &self.key).await
+    }
+
+    /// Stores the response to replay for retries.
+    async fn store(mut self, status: StatusCode, content_type: Option<&str>, body: &[u8]) {
+        self.settled = true;
+        // the request already took effect, so a failure to store its response must not hide it
+        if let Err(e) = self
+            .keys
+            .store(
+                self.account_id,
+                &self.key,
+                status.as_u16(),
+                content_type,
+                body,
+            )
+            .await
+        {
+            tracing::error!(error = %e, key = self.key, "Failed to store response for idempotency key");
+        }
+    }
+}
+
+impl Drop for Claim {
+    fn drop(&mut self) {
+        if self.settled {
+            return;
+        }
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let (keys, account_id, key) = (self.keys.clone(), self.account_id, self.key.clone());
+        runtime.spawn(async move {
+            if let Err(e) = keys.release(account_id, &key).await {
+                tracing::error!(error = %e, key, "Failed to release idempotency key");
+            }
+        });
+    }
+}
+
+fn replay(stored: StoredKey) -> Response {
+    let status = stored
+        .status_code
+        .and_then(|code| StatusCode::from_u16(code).ok())
+        .unwrap_or(StatusCode::OK);
+    let mut res = (status, stored.response_body.unwrap_or_default()).into_response();
+    if let Some(content_type) = stored
+        .content_type
+        .and_then(|v| HeaderValue::from_str(&v).ok())
+    {
+        res.headers_mut().insert(header::CONTENT_TYPE, content_type);
+    }
+    res.headers_mut()
+        .insert(IDEMPOTENT_REPLAYED, HeaderValue::from_static("true"));
+    res
+}
+
+/// Makes a creating endpoint safe to retry: requests carrying an `Idempotency-Key`
+/// header are run once per account and key, and retries get the original response.
+///
+/// Server errors are not stored, so the request can be retried with the same key,
+/// and neither are requests whose handler did not finish (see [`Claim`]).
+/// Requests without a valid session are passed through to be rejected by the handler.
+pub async fn idempotency(
+    State(keys): State<IdempotencyKeys>,
+    State(sessions): State<Sessions>,
+    req: Request,
+    next: Next,
+) -> Response {
+    match run(keys, &*sessions, req, next).await {
+        Ok(res) => res,
+        Err(e) => ApiError::from(e).into_response(),
+    }
+}
+
+async fn run(
+    keys: IdempotencyKeys,
+    sessions: &dyn SessionRepo,
+    req: Request,
+    next: Next,
+) -> Result<Response, IdempotencyError> {
+    let Some(key) = req.headers().get(&IDEMPOTENCY_KEY) else {
+        return Ok(next.run(req).await);
+    };
+    let key = key
+        .to_str()
+        .ok()
+        .filter(|k| !k.is_empty() && k.len() <= 255)
+        .ok_or(IdempotencyError::InvalidKey)?
+        .to_owned();
+    let Ok(account_id) = session_account(sessions, req.headers().clone()).await else {
+        return Ok(next.run(req).await);
+    };
+
+    let (parts, body) = req.into_parts();
+    let body = to_bytes(body, MAX_BODY)
+        .await
+        .map_err(|_| IdempotencyError::BodyTooLarge)?;
+    let req = Request::from_parts(parts, Body::from(body.clone()));
+    let hash = request_hash(&req, &body);
+
+    if !keys.claim(account_id, &key, &hash).await? {
+        let stored = keys
+            .get(account_id, &key)
+            .await?
+            // released again in the meantime
+            .ok_or(IdempotencyError::InProgress)?;
+
+        if stored.request_hash != hash {
+            return Err(IdempotencyError::KeyReused);
+        } else if stored.status_code.is_some() {
+            return Ok(replay(stored));
+        }
+
+        if !keys.take_over(account_id, &key, PENDING_TIMEOUT).await? {
+            return Err(IdempotencyError::InProgress);
+        }
+    }
+    let claim = Claim {
+        keys,
+        account_id,
+        key,
+        settled: false,
+    };
+
+    let res = next.run(req).await;
+    let status = res.status();
+
+    if status.is_server_error() {
+        claim.release().await?;
+        return Ok(res);
+    }
+
+    let (parts, body) = res.into_parts();
+    let Ok(body) = to_bytes(body, MAX_BODY).await else {
+        // the request took effect, so retries must not run it again
+        claim.store(status, None, &[]).await;
+        return Err(IdempotencyError::BodyTooLarge);
+    };
+    let content_type = parts
+        .headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok());
+    claim.store(status, content_type, &body).await;
+
+    Ok(Response::from_parts(parts, Body::from(body)))
+}
+
+pub async fn cleanup_expired_keys(keys: IdempotencyKeys) -> JobResult {
+    let deleted = keys.delete_expired(RETENTION).await?;
+    Ok(format!("Deleted {deleted} expired idempotency keys"))
+}
+
+/// `Idempotency-Key` header accepted by creating endpoints, only used to document
+/// it since [`idempotency`] reads the header itself.
+#[allow(dead_code)]
+#[derive(utoipa::IntoParams)]
+#[into_params(parameter_in = Header)]
+pub struct IdempotencyKeyHeader {
+    /// Unique key per logical request, e.g. a UUID. Retries with the same key and body
+    /// return the original response for 24 hours; reusing it with a different body is rejected
+    #[param(rename = "Idempotency-Key")]
+    idempotency_key: Option<String>,
+}
@@ -0,0 +1,410 @@
+//! Periodic jobs on cron schedules. Every run is recorded in `job_runs`, and a
+//! lock per job makes sure that only one instance runs it when several machines
+//! are up.
+
+pub mod cron;
+use crate::{
+    ApiResult,
+    error::{PathParams, Problem, ProblemDetails, QueryParams},
+    health::Heartbeat,
+    repo::{JobRun, JobRuns, Sessions},
+    users::auth::validate,
+};
+use axum::{
+    Json,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+};
+use cron::Schedule;
+use futures::{FutureExt, future::BoxFuture};
+use serde::{Deserialize, Serialize};
+use sqlx::{Database, Decode, Type, error::BoxDynError};
+use std::{panic::AssertUnwindSafe, str::FromStr, sync::Arc, time::Duration};
+use thiserror::Error;
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+use tokio_util::task::TaskTracker;
+
+#[derive(utoipa::OpenApi)]
+#[openapi(paths(get_jobs, get_job_runs))]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    ApiDoc::openapi()
+}
+
+/// The scheduler wakes up at least this often, which is also its heartbeat.
+pub const TICK: Duration = Duration::from_mins(1);
+const RETENTION: Duration = Duration::from_hours(30 * 24);
+pub const CLEANUP_SCHEDULE: &str = "15 3 * * *";
+pub const JOB_RUNS: &str = "job_runs_total";
+
+#[derive(Error, Debug)]
+pub enum JobError {
+    #[error("Job not found")]
+    NotFound,
+    #[error("Could not format")]
+    FormatError(#[from] time::error::Format),
+    #[error("Could not query database")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl ProblemDetails for JobError {
+    fn status(&self) -> StatusCode {
+        match self {
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::FormatError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::DatabaseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn code(&self) -> &'static str {
+        match self {
+            Self::NotFound => "job_not_found",
+            Self::FormatError(_) => "format_error",
+            Self::DatabaseError(_) => "database_error",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum RunStatus {
+    Running,
+    Succeeded,
+    Failed,
+}
+
+impl RunStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Running => "running",
+            Self::Succeeded => "succeeded",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+impl FromStr for RunStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "running" => Ok(Self::Running),
+            "succeeded" => Ok(Self::Succeeded),
+            "failed" => Ok(Self::Failed),
+            s => Err(format!("unknown job run status {s:?}")),
+        }
+    }
+}
+
+impl<DB: Database> Type<DB> for RunStatus
+where
+    str: Type<DB>,
+{
+    fn type_info() -> DB::TypeInfo {
+        <str as Type<DB>>::type_info()
+    }
+
+    fn compatible(ty: &DB::TypeInfo) -> bool {
+        <str as Type<DB>>::compatible(ty)
+    }
+}
+
+impl<'r, DB: Database> Decode<'r, DB> for RunStatus
+where
+    &'r str: Decode<'r, DB>,
+{
+    fn decode(value: DB::ValueRef<'r>) -> Result<Self, BoxDynError> {
+        Ok(<&str as Decode<DB>>::decode(value)?.parse()?)
+    }
+}
+
+/// Result of a job run, a short summary of what it did.
+pub type JobResult = Result<String, sqlx::Error>;
+type JobFn = Box<dyn Fn() -> BoxFuture<'static, JobResult> + Send + Sync>;
+
+struct Job {
+    name: &'static str,
+    schedule: Schedule,
+    run: JobFn,
+}
+
+pub struct Scheduler {
+    jobs: Vec<Arc<Job>>,
+    /// Recorded with each run, to tell machines apart
+    instance: String,
+}
+
+impl Scheduler {
+    pub fn new(instance: String) -> Self {
+        Self {
+            jobs: Vec::new(),
+            instance,
+        }
+    }
+
+    pub fn register<F, Fut>(&mut self, name: &'static str, schedule: Schedule, run: F)
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = JobResult> + Send + 'static,
+    {
+        self.jobs.push(Arc::new(Job {
+            name,
+            schedule,
+            run: Box::new(move || run().boxed()),
+        }));
+    }
+
+    /// Starts the jobs whenever they are due, forever. Runs are spawned on
+    /// `tracker`, so that shutdown waits for them to record their outcome and
+    /// release their lock.
+    pub async fn run(self: Arc<Self>, runs: JobRuns, tracker: TaskTracker, heartbeat: Heartbeat) {
+        let now = OffsetDateTime::now_utc();
+        let mut next: Vec<_> = self
+            .jobs
+            .iter()
+            .map(|job| job.schedule.next_after(now))
+            .collect();
+
+        loop {
+            heartbeat.beat();
+            let now = OffsetDateTime::now_utc();
+            for (job, next) in self.jobs.iter().zip(&mut next) {
+                if let Some(slot) = next.filter(|slot| *slot <= now) {
+                    tracker.spawn(run_job(
+                        runs.clone(),
+                        job.clone(),
+                        slot,
+                        self.instance.clone(),
+                    ));
+                    *next = job.schedule.next_after(now);
+                }
+            }
+
+            let until_next = next
+                .iter()
+                .flatten()
+                .min()
+                .map(|t| (*t - OffsetDateTime::now_utc()).unsigned_abs())
+                .unwrap_or(TICK);
+            tokio::time::sleep(until_next.min(TICK)).await;
+        }
+    }
+}
+
+/// Runs `job` for `slot`, unless another instance holds its lock or already ran it.
+async fn run_job(runs: JobRuns, job: Arc<Job>, slot: OffsetDateTime, instance: String) {
+    let result = async {
+        let Some(lock) = runs.lock(job.name).await? else {
+            tracing::debug!(job = job.name, "Job is running on another instance");
+            return Ok(());
+        };
+
+        let result = record_run(&runs, &job, slot, &instance).await;
+        lock.release().await?;
+        result
+    }
+    .await;
+
+    if let Err(e) = result {
+        tracing::error!(job = job.name, error = %e, "Failed to run job");
+    }
+}
+
+async fn record_run(
+    runs: &JobRuns,
+    job: &Job,
+    slot: OffsetDateTime,
+    instance: &str,
+) -> Result<(), sqlx::Error> {
+    if runs.ran(job.name, slot).await? {
+        return Ok(());
+    }
+
+    let id = runs.start(job.name, slot, instance).await?;
+
+    let (status, detail) = match AssertUnwindSafe((job.run)()).catch_unwind().await {
+        Ok(Ok(detail)) => {
+            tracing::info!(job = job.name, detail, "Job succeeded");
+            (RunStatus::Succeeded, detail)
+        }
+        Ok(Err(e)) => {
+            tracing::error!(job = job.name, error = %e, "Job failed");
+            (RunStatus::Failed, e.to_string())
+        }
+        Err(_) => {
+            tracing::error!(job = job.name, "Job panicked");
+            (RunStatus::Failed, "panicked".to_owned())
+        }
+    };
+    metrics::counter!(JOB_RUNS, "job" => job.name, "status" => status.as_str()).increment(1);
+
+    runs.finish(id, status, &detail).await
+}
+
+pub async fn cleanup_job_runs(runs: JobRuns) -> JobResult {
+    let deleted = runs.delete_older_than(RETENTION).await?;
+    Ok(format!("Deleted {deleted} old job runs"))
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+struct JobRunResponse {
+    id: u64,
+    job: String,
+    scheduled_at: String,
+    started_at: String,
+    finished_at: Option<String>,
+    status: RunStatus,
+    /// Summary of what the run did, or why it failed
+    detail: Option<String>,
+    /// Machine that ran the job
+    instance: String,
+}
+
+impl TryFrom<JobRun> for JobRunResponse {
+    type Error = JobError;
+
+    fn try_from(run: JobRun) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: run.id,
+            job: run.job,
+            scheduled_at: run.scheduled_at.to_utc().format(&Rfc3339)?,
+            started_at: run.started_at.to_utc().format(&Rfc3339)?,
+            finished_at: run
+                .finished_at
+                .map(|t| t.to_utc().format(&Rfc3339))
+                .transpose()?,
+            status: run.status,
+            detail: run.detail,
+            instance: run.instance,
+        })
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+struct JobResponse {
+    name: &'static str,
+    #[schema(example = "*/5 * * * *")]
+    schedule: String,
+    next_run: Option<String>,
+    last_run: Option<JobRunResponse>,
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct JobRunQuery {
+    /// Maximum number of runs, newest first (default 50, at most 500)
+    limit: Option<u32>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/jobs",
+    responses(
+        (
+            status = StatusCode::OK,
+            body = Vec<JobResponse>,
+            description = "Registered jobs with their latest run",
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = Problem,
+            content_type = "application/problem+json",
+        )
+    ),
+)]
+pub async fn get_jobs(
+    sessions: State<Sessions>,
+    State(runs): State<JobRuns>,
+    State(scheduler): State<Arc<Scheduler>>,
+    headers: HeaderMap,
+) -> ApiResult<impl IntoResponse> {
+    let _ = validate(sessions, headers).await?;
+
+    let mut last_runs = runs
+        .last_runs()
+        .await
+        .map_err(JobError::DatabaseError)?
+        .into_iter()
+        .map(|run| Ok((run.job.clone(), JobRunResponse::try_from(run)?)))
+        .collect::<Result<std::collections::HashMap<_, _>, JobError>>()?;
+
+    let now = OffsetDateTime::now_utc();
+    let jobs = scheduler
+        .jobs
+        .iter()
+        .map(|job| {
+            Ok(JobResponse {
+                name: job.name,
+                schedule: job.schedule.to_string(),
+                next_run: job
+                    .schedule
+                    .next_after(now)
+                    .map(|t| t.format(&Rfc3339))
+                    .transpose()?,
+                last_run: last_runs.remove(job.name),
+            })
+        })
+        .collect::<Result<Vec<_>, JobError>>()?;
+
+    Ok((StatusCode::OK, Json(jobs)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/jobs/{name}/runs",
+    params(("name" = String, Path), JobRunQuery),
+    responses(
+        (
+            status = StatusCode::OK,
+            body = Vec<JobRunResponse>,
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            description = "Job not found",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = Problem,
+            content_type = "application/problem+json",
+        )
+    ),
+)]
+pub async fn get_job_runs(
+    sessions: State<Sessions>,
+    State(runs): State<JobRuns>,
+    State(scheduler): State<Arc<Scheduler>>,
+    headers: HeaderMap,
+    PathParams(name): PathParams<String>,
+    QueryParams(query): QueryParams<JobRunQuery>,
+) -> ApiResult<impl IntoResponse> {
+    let _ = validate(sessions, headers).await?;
+
+    if !scheduler.jobs.iter().any(|job| job.name == name) {
+        return Err(JobError::NotFound.into());
+    }
+
+    let runs = runs
+        .runs(&name, query.limit.unwrap_or(50).min(500))
+        .await
+        .map_err(JobError::DatabaseError)?
+        .into_iter()
+        .map(JobRunResponse::try_from)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok((StatusCode::OK, Json(runs)))
+}
@@ -0,0 +1,303 @@
+use serde::{Deserialize, Deserializer};
+use std::{fmt, str::FromStr};
+use time::{Date, Duration, Month, OffsetDateTime, Time, UtcOffset};
+
+/// Cron expression with the fields minute, hour, day of month, month and day of
+/// week, evaluated in UTC. Fields accept `*`, values, ranges `1-5`, steps `*/15`
+/// or `0-30/10` and lists `1,15`. `@hourly`, `@daily`, `@weekly` and `@monthly`
+/// are shorthands.
+#[derive(Clone, Debug)]
+pub struct Schedule {
+    source: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Day of month or day of week is `*`, so only the other one restricts days
+    any_day: bool,
+    any_weekday: bool,
+}
+
+fn parse_field(field: &str, min: u8, max: u8) -> Result<u64, String> {
+    let mut bits = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (
+                range,
+                step.parse::<u8>()
+                    .ok()
+                    .filter(|s| *s > 0)
+                    .ok_or_else(|| format!("invalid step {step:?}"))?,
+            ),
+            None => (part, 1),
+        };
+        let value = |v: &str| {
+            v.parse::<u8>()
+                .ok()
+                .filter(|v| (min..=max).contains(v))
+                .ok_or_else(|| format!("{v:?} is not between {min} and {max}"))
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            range => match range.split_once('-') {
+                Some((start, end)) => (value(start)?, value(end)?),
+                // `5/15` means every 15 starting at 5
+                None if step > 1 => (value(range)?, max),
+                None => (value(range)?, value(range)?),
+            },
+        };
+        if start > end {
+            return Err(format!("invalid range {range:?}"));
+        }
+        for v in (start..=end).step_by(step.into()) {
+            bits |= 1 << v;
+        }
+    }
+    Ok(bits)
+}
+
+impl FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let expression = match s.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            s => s,
+        };
+        let fields: Vec<_> = expression.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(format!("expected 5 fields in {s:?}"));
+        };
+
+        let mut weekdays = parse_field(weekday, 0, 7)?;
+        // both 0 and 7 are Sunday
+        if weekdays & 1 << 7 != 0 {
+            weekdays = (weekdays | 1) & !(1 << 7);
+        }
+        let schedule = Self {
+            source: s.trim().to_owned(),
+            minutes: parse_field(minute, 0, 59)?,
+            hours: parse_field(hour, 0, 23)?,
+            days: parse_field(day, 1, 31)?,
+            months: parse_field(month, 1, 12)?,
+            weekdays,
+            any_day: day == "*",
+            any_weekday: weekday == "*",
+        };
+        schedule
+            .next_after(OffsetDateTime::UNIX_EPOCH)
+            .ok_or_else(|| format!("{s:?} never matches"))?;
+        Ok(schedule)
+    }
+}
+
+impl<'de> Deserialize<'de> for Schedule {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl Schedule {
+    fn matches_day(&self, date: Date) -> bool {
+        let day = self.days & 1 << date.day() != 0;
+        let weekday = self.weekdays & 1 << date.weekday().number_days_from_sunday() != 0;
+        // like cron, restricting both matches days that satisfy either
+        match (self.any_day, self.any_weekday) {
+            (true, true) => true,
+            (false, true) => day,
+            (true, false) => weekday,
+            (false, false) => day || weekday,
+        }
+    }
+
+    /// First minute after `after` that the schedule matches, `None` if there is
+    /// none within five years.
+    pub fn next_after(&self, after: OffsetDateTime) -> Option<OffsetDateTime> {
+        let after = after.to_offset(UtcOffset::UTC);
+        let mut t = after.replace_second(0).ok()?.replace_nanosecond(0).ok()? + Duration::MINUTE;
+        let limit = after + Duration::days(5 * 366);
+
+        while t < limit {
+            let date = t.date();
+            if self.months & 1 << u8::from(date.month()) == 0 {
+                let year = match date.month() {
+                    Month::December => date.year() + 1,
+                    _ => date.year(),
+                };
+                t = Date::from_calendar_date(year, date.month().next(), 1)
+                    .ok()?
+                    .with_time(Time::MIDNIGHT)
+                    .assume_utc();
+            } else if !self.matches_day(date) {
+                t = date.next_day()?.with_time(Time::MIDNIGHT).assume_utc();
+            } else if self.hours & 1 << t.hour() == 0 {
+                t = t.replace_minute(0).ok()? + Duration::HOUR;
+            } else if self.minutes & 1 << t.minute() == 0 {
+                t += Duration::MINUTE;
+            } else {
+                return Some(t);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::datetime;
+
+    fn next(expression: &str, after: OffsetDateTime) -> OffsetDateTime {
+        let schedule: Schedule = expression.parse().unwrap();
+        schedule.next_after(after).unwrap()
+    }
+
+    #[test]
+    fn steps() {
+        let every_quarter = "*/15 * * * *";
+        assert_eq!(
+            next(every_quarter, datetime!(2025-01-01 10:07 UTC)),
+            datetime!(2025-01-01 10:15 UTC)
+        );
+        assert_eq!(
+            next(every_quarter, datetime!(2025-01-01 10:45 UTC)),
+            datetime!(2025-01-01 11:00 UTC)
+        );
+        // seconds are dropped, the next match is strictly later
+        assert_eq!(
+            next(every_quarter, datetime!(2025-01-01 10:15:30 UTC)),
+            datetime!(2025-01-01 10:30 UTC)
+        );
+        assert_eq!(
+            next("5/20 * * * *", datetime!(2025-01-01 10:26 UTC)),
+            datetime!(2025-01-01 10:45 UTC)
+        );
+        assert_eq!(
+            next("0-30/10 * * * *", datetime!(2025-01-01 10:31 UTC)),
+            datetime!(2025-01-01 11:00 UTC)
+        );
+    }
+
+    #[test]
+    fn ranges_and_lists() {
+        // Friday evening to Monday morning
+        let office_hours = "0 9-17 * * 1-5";
+        assert_eq!(
+            next(office_hours, datetime!(2025-01-03 18:00 UTC)),
+            datetime!(2025-01-06 09:00 UTC)
+        );
+        assert_eq!(
+            next(office_hours, datetime!(2025-01-06 09:00 UTC)),
+            datetime!(2025-01-06 10:00 UTC)
+        );
+        assert_eq!(
+            next("0 0 1,15 * *", datetime!(2025-01-02 00:00 UTC)),
+            datetime!(2025-01-15 00:00 UTC)
+        );
+        assert_eq!(
+            next("0,30 6,18 * * *", datetime!(2025-01-01 06:30 UTC)),
+            datetime!(2025-01-01 18:00 UTC)
+        );
+    }
+
+    #[test]
+    fn sunday_is_0_and_7() {
+        // Wednesday 2025-05-28, the next Sunday is 2025-06-01
+        let wednesday = datetime!(2025-05-28 12:00 UTC);
+        let sunday = datetime!(2025-06-01 00:00 UTC);
+        assert_eq!(next("0 0 * * 0", wednesday), sunday);
+        assert_eq!(next("0 0 * * 7", wednesday), sunday);
+        assert_eq!(next("@weekly", wednesday), sunday);
+        assert_eq!(next("0 0 * * 6-7", datetime!(2025-05-31 00:00 UTC)), sunday);
+        assert_eq!(next("0 0 * * 7", sunday), datetime!(2025-06-08 00:00 UTC));
+    }
+
+    #[test]
+    fn day_of_month_or_day_of_week() {
+        // restricting both matches the 10th and every Friday
+        let either = "0 0 10 * 5";
+        assert_eq!(
+            next(either, datetime!(2025-06-01 00:00 UTC)),
+            datetime!(2025-06-06 00:00 UTC)
+        );
+        assert_eq!(
+            next(either, datetime!(2025-06-06 00:00 UTC)),
+            datetime!(2025-06-10 00:00 UTC)
+        );
+        assert_eq!(
+            next(either, datetime!(2025-06-10 00:00 UTC)),
+            datetime!(2025-06-13 00:00 UTC)
+        );
+        // a `*` leaves only the other field restricting days
+        assert_eq!(
+            next("0 0 10 * *", datetime!(2025-06-01 00:00 UTC)),
+            datetime!(2025-06-10 00:00 UTC)
+        );
+        assert_eq!(
+            next("0 0 * * 5", datetime!(2025-06-01 00:00 UTC)),
+            datetime!(2025-06-06 00:00 UTC)
+        );
+    }
+
+    #[test]
+    fn month_rollover() {
+        assert_eq!(
+            next("0 0 31 * *", datetime!(2025-01-31 00:00 UTC)),
+            datetime!(2025-03-31 00:00 UTC)
+        );
+        assert_eq!(
+            next("@monthly", datetime!(2025-12-15 08:00 UTC)),
+            datetime!(2026-01-01 00:00 UTC)
+        );
+        assert_eq!(
+            next("0 0 * 2 *", datetime!(2025-12-15 00:00 UTC)),
+            datetime!(2026-02-01 00:00 UTC)
+        );
+        assert_eq!(
+            next("30 23 31 12 *", datetime!(2025-12-31 23:30 UTC)),
+            datetime!(2026-12-31 23:30 UTC)
+        );
+        assert_eq!(
+            next("0 0 29 2 *", datetime!(2025-01-01 00:00 UTC)),
+            datetime!(2028-02-29 00:00 UTC)
+        );
+        assert_eq!(
+            next("59 23 * * *", datetime!(2025-02-28 23:59 UTC)),
+            datetime!(2025-03-01 23:59 UTC)
+        );
+    }
+
+    #[test]
+    fn invalid_expressions() {
+        for expression in [
+            "* * * *",
+            "* * * * * *",
+            "60 * * * *",
+            "* 24 * * *",
+            "* * 0 * *",
+            "* * * 13 *",
+            "* * * * 8",
+            "*/0 * * * *",
+            "5-1 * * * *",
+            "a * * * *",
+            "0 0 30 2 *",
+        ] {
+            assert!(
+                expression.parse::<Schedule>().is_err(),
+                "{expression:?} parsed"
+            );
+        }
+    }
+}
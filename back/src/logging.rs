@@ -0,0 +1,92 @@
+//! Structured logs through `tracing`, with one span per request carrying its
+//! [`request_id`](crate::request_id).
+
+use crate::{config::LogFormat, request_id::X_REQUEST_ID};
+use axum::{extract::Request, http::Response};
+use std::time::Duration;
+use tower_http::{
+    classify::{ServerErrorsAsFailures, ServerErrorsFailureClass, SharedClassifier},
+    trace::{
+        DefaultOnBodyChunk, DefaultOnEos, DefaultOnRequest, MakeSpan, OnFailure, OnResponse,
+        TraceLayer,
+    },
+};
+use tracing::{Level, Span};
+use tracing_subscriber::EnvFilter;
+
+/// Installs the global subscriber, filtered by `RUST_LOG` (default `info`).
+pub fn init(format: LogFormat) {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+    match format {
+        LogFormat::Json => builder
+            .json()
+            .flatten_event(true)
+            .with_current_span(true)
+            .with_span_list(false)
+            .init(),
+        LogFormat::Pretty => builder.pretty().init(),
+    }
+}
+
+#[derive(Clone)]
+pub struct RequestSpan;
+
+impl<B> MakeSpan<B> for RequestSpan {
+    fn make_span(&mut self, req: &Request<B>) -> Span {
+        let request_id = req
+            .headers()
+            .get(&X_REQUEST_ID)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default();
+        tracing::info_span!(
+            "request",
+            method = %req.method(),
+            path = %req.uri().path(),
+            request_id,
+        )
+    }
+}
+
+#[derive(Clone)]
+pub struct LogResponse;
+
+impl<B> OnResponse<B> for LogResponse {
+    fn on_response(self, res: &Response<B>, latency: Duration, _: &Span) {
+        let status = res.status().as_u16();
+        let latency_ms = latency.as_secs_f64() * 1000.0;
+        if res.status().is_server_error() {
+            tracing::error!(status, latency_ms, "request failed");
+        } else {
+            tracing::info!(status, latency_ms, "request finished");
+        }
+    }
+}
+
+/// Failures are already logged by [`LogResponse`] and, with their cause, by
+/// [`ApiError`](crate::error::ApiError).
+#[derive(Clone)]
+pub struct IgnoreFailure;
+
+impl OnFailure<ServerErrorsFailureClass> for IgnoreFailure {
+    fn on_failure(&mut self, _: ServerErrorsFailureClass, _: Duration, _: &Span) {}
+}
+
+pub type Trace = TraceLayer<
+    SharedClassifier<ServerErrorsAsFailures>,
+    RequestSpan,
+    DefaultOnRequest,
+    LogResponse,
+    DefaultOnBodyChunk,
+    DefaultOnEos,
+    IgnoreFailure,
+>;
+
+/// Must run inside [`request_id`](crate::request_id::request_id), which sets the header it reads.
+pub fn trace_layer() -> Trace {
+    TraceLayer::new_for_http()
+        .make_span_with(RequestSpan)
+        .on_request(DefaultOnRequest::new().level(Level::DEBUG))
+        .on_response(LogResponse)
+        .on_failure(IgnoreFailure)
+}
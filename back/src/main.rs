@@ -1,24 +1,47 @@
+mod campaigns;
+mod config;
+mod db;
 mod donations;
+mod error;
+mod etag;
+mod exchange_rates;
+mod merge_patch;
+mod metrics;
+mod money;
+mod repo;
+mod request_id;
+mod schedules;
+mod state;
 mod supporters;
+mod tasks;
+#[cfg(test)]
+mod tests;
 use axum::{
     Router,
-    http::{self, HeaderValue, Method, header, request::Parts},
-    response::{IntoResponse, Response},
-    routing,
+    body::Body,
+    http::{self, HeaderValue, Method, Response, header, request::Parts},
+    middleware,
+    routing::{self, MethodRouter},
 };
+use error::ApiResult;
 mod health;
+mod idempotency;
+mod jobs;
+mod logging;
 mod users;
-use sqlx::MySqlPool;
-use std::{env, net::SocketAddr};
-use thiserror::Error;
+mod webhooks;
+use state::AppState;
+use std::{net::SocketAddr, sync::Arc};
 use tokio::net::TcpListener;
-use tower_governor::{GovernorLayer, governor::GovernorConfig};
+use tokio_util::sync::CancellationToken;
+use tower_governor::{GovernorError, GovernorLayer, governor::GovernorConfigBuilder};
 use tower_http::cors::{AllowOrigin, CorsLayer};
 use users::auth;
 use users::me;
 use utoipa_swagger_ui::SwaggerUi;
 
 #[derive(utoipa::OpenApi)]
+#[openapi(components(schemas(error::Problem, error::FieldError)))]
 struct ApiDoc;
 fn openapi() -> utoipa::openapi::OpenApi {
     use utoipa::OpenApi;
@@ -29,107 +52,396 @@ fn openapi() -> utoipa::openapi::OpenApi {
     api.merge(auth::validate::openapi());
     api.merge(me::openapi());
     api.merge(health::openapi());
+    api.merge(metrics::openapi());
     api.merge(donations::openapi());
+    api.merge(donations::stats::openapi());
+    api.merge(donations::import::openapi());
+    api.merge(donations::export::openapi());
     api.merge(supporters::openapi());
+    api.merge(supporters::public::openapi());
+    api.merge(supporters::merge::openapi());
+    api.merge(exchange_rates::openapi());
+    api.merge(schedules::openapi());
+    api.merge(campaigns::openapi());
+    api.merge(webhooks::openapi());
+    api.merge(jobs::openapi());
     api
 }
 
-#[tokio::main]
-async fn main() {
-    let pool = MySqlPool::connect(&env::var("DATABASE_URL").expect("DATABASE_URL must be set"))
-        .await
-        .expect("Unable to connect to mysql database");
+/// Routes of the authenticated API. A path is listed once per method.
+fn api_routes(state: &AppState) -> Vec<(&'static str, MethodRouter<AppState>)> {
+    // POST endpoints replay the original response for retried `Idempotency-Key`s, except
+    // for sign-in and sign-up, which have no account yet, and provider webhooks, which are
+    // deduplicated by their event id. PUT, PATCH and DELETE are safe to retry with `If-Match`.
+    let idempotent = middleware::from_fn_with_state(state.clone(), idempotency::idempotency);
 
-    sqlx::migrate!()
-        .run(&pool)
-        .await
-        .expect("Unable to perform mysql database migrations");
-
-    tokio::spawn(auth::cleanup_expired_sessions(pool.clone()));
-
-    let app = Router::new()
-        .merge(SwaggerUi::new("/").url("/api-docs/openapi.json", openapi()))
-        .route("/health", routing::get(health::health))
-        .route("/auth/signup", routing::post(auth::signup))
-        .route("/users/auth/signin", routing::post(auth::signin))
-        .route("/users/auth/signout", routing::post(auth::signout))
-        .route("/users/auth/validate", routing::get(auth::validate))
-        .route("/users/me", routing::get(me::me))
-        .route("/donations", routing::get(donations::get_donations))
-        .route("/donations/{id}", routing::get(donations::get_donation))
-        .route("/donations", routing::post(donations::post_donation))
-        .route("/donations/{id}", routing::put(donations::put_donation))
-        .route(
+    vec![
+        ("/health", routing::get(health::health)),
+        ("/health/live", routing::get(health::live)),
+        ("/health/ready", routing::get(health::ready)),
+        ("/users/auth/signup", routing::post(auth::signup)),
+        ("/users/auth/signin", routing::post(auth::signin)),
+        ("/users/auth/signout", routing::post(auth::signout)),
+        ("/users/auth/validate", routing::get(auth::validate)),
+        ("/users/me", routing::get(me::me)),
+        ("/donations", routing::get(donations::get_donations)),
+        (
+            "/donations/stats",
+            routing::get(donations::stats::get_donation_stats),
+        ),
+        (
+            "/donations/export",
+            routing::get(donations::export::export_donations),
+        ),
+        ("/donations/{id}", routing::get(donations::get_donation)),
+        (
+            "/donations",
+            routing::post(donations::post_donation).layer(idempotent.clone()),
+        ),
+        (
+            "/donations/import",
+            routing::post(donations::import::import_donations).layer(idempotent.clone()),
+        ),
+        ("/donations/{id}", routing::put(donations::put_donation)),
+        ("/donations/{id}", routing::patch(donations::patch_donation)),
+        (
             "/donations/{id}",
             routing::delete(donations::delete_donation),
-        )
-        .route("/supporters", routing::get(supporters::get_supporters))
-        .route("/supporters/{id}", routing::get(supporters::get_supporter))
-        .route("/supporters", routing::post(supporters::post_supporter))
-        .route("/supporters/{id}", routing::put(supporters::put_supporter))
-        .route(
+        ),
+        ("/supporters", routing::get(supporters::get_supporters)),
+        ("/supporters/{id}", routing::get(supporters::get_supporter)),
+        (
+            "/supporters",
+            routing::post(supporters::post_supporter).layer(idempotent.clone()),
+        ),
+        ("/supporters/{id}", routing::put(supporters::put_supporter)),
+        (
+            "/supporters/{id}",
+            routing::patch(supporters::patch_supporter),
+        ),
+        (
             "/supporters/{id}",
             routing::delete(supporters::delete_supporter),
+        ),
+        (
+            "/supporters/duplicates",
+            routing::get(supporters::merge::get_duplicate_supporters),
+        ),
+        (
+            "/supporters/merge",
+            routing::post(supporters::merge::merge_supporters).layer(idempotent.clone()),
+        ),
+        (
+            "/supporters/merges",
+            routing::get(supporters::merge::get_supporter_merges),
+        ),
+        (
+            "/supporters/{id}/donations",
+            routing::get(supporters::get_supporter_donations),
+        ),
+        (
+            "/supporters/{id}/totals",
+            routing::get(supporters::get_supporter_totals),
+        ),
+        (
+            "/exchange-rates",
+            routing::get(exchange_rates::get_exchange_rates),
+        ),
+        (
+            "/exchange-rates",
+            routing::post(exchange_rates::post_exchange_rate).layer(idempotent.clone()),
+        ),
+        (
+            "/exchange-rates/currencies",
+            routing::get(exchange_rates::get_currencies),
+        ),
+        (
+            "/exchange-rates/import",
+            routing::post(exchange_rates::import_exchange_rates).layer(idempotent.clone()),
+        ),
+        ("/schedules", routing::get(schedules::get_schedules)),
+        (
+            "/schedules/upcoming",
+            routing::get(schedules::get_upcoming_donations),
+        ),
+        (
+            "/schedules/overdue",
+            routing::get(schedules::get_overdue_donations),
+        ),
+        ("/schedules/{id}", routing::get(schedules::get_schedule)),
+        (
+            "/schedules",
+            routing::post(schedules::post_schedule).layer(idempotent.clone()),
+        ),
+        ("/schedules/{id}", routing::put(schedules::put_schedule)),
+        (
+            "/schedules/{id}",
+            routing::delete(schedules::delete_schedule),
+        ),
+        ("/campaigns", routing::get(campaigns::get_campaigns)),
+        ("/campaigns/{id}", routing::get(campaigns::get_campaign)),
+        (
+            "/campaigns",
+            routing::post(campaigns::post_campaign).layer(idempotent.clone()),
+        ),
+        ("/campaigns/{id}", routing::put(campaigns::put_campaign)),
+        (
+            "/campaigns/{id}",
+            routing::delete(campaigns::delete_campaign),
+        ),
+        ("/jobs", routing::get(jobs::get_jobs)),
+        ("/jobs/{name}/runs", routing::get(jobs::get_job_runs)),
+        (
+            "/webhooks/{provider}",
+            routing::post(webhooks::receive_webhook),
+        ),
+        (
+            "/webhooks/events",
+            routing::get(webhooks::get_webhook_events),
+        ),
+        (
+            "/webhooks/events/{id}/replay",
+            routing::post(webhooks::replay_webhook_event).layer(idempotent.clone()),
+        ),
+    ]
+}
+
+/// Routes of the public wall and campaign progress.
+fn public_routes() -> Vec<(&'static str, MethodRouter<AppState>)> {
+    vec![
+        (
+            "/public/supporters",
+            routing::get(supporters::public::get_public_supporters),
+        ),
+        (
+            "/campaigns/{id}/progress",
+            routing::get(campaigns::get_campaign_progress),
+        ),
+    ]
+}
+
+/// `/metrics`, which is not rate limited since scrapers poll often.
+fn metrics_routes() -> Vec<(&'static str, MethodRouter<AppState>)> {
+    vec![("/metrics", routing::get(metrics::get_metrics))]
+}
+
+fn router(routes: Vec<(&'static str, MethodRouter<AppState>)>) -> Router<AppState> {
+    routes
+        .into_iter()
+        .fold(Router::new(), |router, (path, route)| {
+            router.route(path, route)
+        })
+}
+
+fn metrics_router(state: AppState) -> Router {
+    router(metrics_routes()).with_state(state)
+}
+
+/// Rate limit rejections, whose plain text message comes without a content type.
+fn rate_limited(e: GovernorError) -> Response<Body> {
+    let mut res = Response::<Body>::from(e);
+    res.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("text/plain; charset=utf-8"),
+    );
+    res
+}
+
+/// The API as served on `PORT`, including `/metrics` unless `METRICS_PORT` is set.
+fn app(state: AppState) -> Router {
+    let config = state.config.clone();
+
+    // The public wall and campaign progress are cached by clients, so they get
+    // a steadier but lower sustained rate than the authenticated API instead of
+    // sharing its budget.
+    let public = router(public_routes()).with_state(state.clone()).layer(
+        GovernorLayer::new(
+            GovernorConfigBuilder::default()
+                .period(config.rate_limit.public.period)
+                .burst_size(config.rate_limit.public.burst_size)
+                .methods(vec![Method::GET])
+                .finish()
+                .expect("valid public rate limit"),
         )
-        .with_state(pool)
-        .layer(GovernorLayer::new(GovernorConfig::default()))
+        .error_handler(rate_limited),
+    );
+
+    let api = router(api_routes(&state))
+        .merge(SwaggerUi::new("/").url("/api-docs/openapi.json", openapi()))
+        .with_state(state.clone())
+        .layer(
+            GovernorLayer::new(
+                GovernorConfigBuilder::default()
+                    .period(config.rate_limit.api.period)
+                    .burst_size(config.rate_limit.api.burst_size)
+                    .finish()
+                    .expect("valid rate limit"),
+            )
+            .error_handler(rate_limited),
+        )
+        .merge(public);
+    let api = match config.metrics.port {
+        Some(_) => api,
+        None => api.merge(metrics_router(state)),
+    };
+    api.layer(middleware::from_fn(metrics::track_requests))
         .layer(
             CorsLayer::new()
-                .allow_origin(if let Ok(v) = env::var("CORS_ALLOWED_ORIGINS") {
-                    v.split_whitespace()
-                        .map(|v| HeaderValue::from_str(v).expect("Invalid CORS_ALLOWED_ORIGINS"))
-                        .collect::<Vec<_>>()
-                        .into()
-                } else {
-                    #[cfg(not(debug_assertions))]
-                    panic!("CORS_ALLOWED_ORIGINS must be set");
-                    #[allow(unreachable_code)]
+                .allow_origin(if config.cors.allow_any_origin {
                     AllowOrigin::predicate(move |_: &http::HeaderValue, _: &Parts| true)
+                } else {
+                    config.cors.allowed_origins.clone().into()
                 })
-                .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE])
+                .allow_methods([
+                    Method::GET,
+                    Method::POST,
+                    Method::PUT,
+                    Method::PATCH,
+                    Method::DELETE,
+                ])
                 .allow_headers([
                     header::CONTENT_TYPE,
                     header::ACCEPT,
                     header::AUTHORIZATION,
                     header::ORIGIN,
                     header::USER_AGENT,
+                    request_id::X_REQUEST_ID,
+                    header::IF_MATCH,
+                    idempotency::IDEMPOTENCY_KEY,
+                ])
+                .expose_headers([
+                    header::ETAG,
+                    request_id::X_REQUEST_ID,
+                    idempotency::IDEMPOTENT_REPLAYED,
                 ])
                 .allow_credentials(true),
         )
-        .into_make_service_with_connect_info::<SocketAddr>();
+        .layer(logging::trace_layer())
+        .layer(middleware::from_fn(request_id::request_id))
+}
 
-    let port = env::var("PORT").expect("PORT must be set");
-    let listener = TcpListener::bind(format!("[::]:{port}"))
+#[tokio::main]
+async fn main() {
+    let config = Arc::new(config::Config::load().unwrap_or_else(|e| {
+        eprint!("{e}");
+        std::process::exit(1);
+    }));
+    logging::init(config.log_format);
+    let metrics_handle = metrics::install();
+
+    let db = db::Database::connect(&config.database_url)
         .await
-        .unwrap_or_else(|_| panic!("Unable to bind http://[::]:{port} and 0.0.0.0:{port}"));
-    println!("Listening on http://[::]:{port} and http://0.0.0.0:{port} ...");
-    axum::serve(listener, app).await.unwrap();
-}
+        .expect("Unable to connect to database");
 
-type ApiResult<T> = Result<T, ApiError>;
-
-#[derive(Error, Debug)]
-enum ApiError {
-    #[error("could not validate session: {0}")]
-    Validation(#[from] auth::validate::ValidationError),
-    #[error("could not sign in: {0}")]
-    Signin(#[from] auth::signin::SigninError),
-    #[error("could not sign up: {0}")]
-    Signup(#[from] auth::signup::SignupError),
-    #[error("could not get donations: {0}")]
-    Donation(#[from] donations::DonationError),
-    #[error("could not get supporters: {0}")]
-    Supporter(#[from] supporters::SupporterError),
-}
+    db.migrate()
+        .await
+        .expect("Unable to perform database migrations");
+
+    let repos = db.repos();
 
-impl IntoResponse for ApiError {
-    fn into_response(self) -> Response {
-        match self {
-            ApiError::Validation(e) => e.into_response(),
-            ApiError::Signin(e) => e.into_response(),
-            ApiError::Signup(e) => e.into_response(),
-            ApiError::Donation(e) => e.into_response(),
-            ApiError::Supporter(e) => e.into_response(),
+    let shutdown = CancellationToken::new();
+    tokio::spawn(tasks::watch_signals(shutdown.clone()));
+
+    let tasks = health::BackgroundTasks::default();
+    let supervisor = tasks::Supervisor::new(tasks.clone(), shutdown.child_token());
+    let mut scheduler = jobs::Scheduler::new(config.instance_id.clone());
+    scheduler.register(
+        "cleanup_expired_sessions",
+        config.session.cleanup_schedule.clone(),
+        {
+            let sessions = repos.sessions.clone();
+            move || auth::cleanup_expired_sessions(sessions.clone())
+        },
+    );
+    scheduler.register(
+        "flag_missed_donations",
+        schedules::CHECK_SCHEDULE.parse().expect("valid schedule"),
+        {
+            let schedules = repos.schedules.clone();
+            move || schedules::flag_missed_donations(schedules.clone())
+        },
+    );
+    scheduler.register(
+        "cleanup_expired_keys",
+        idempotency::CLEANUP_SCHEDULE
+            .parse()
+            .expect("valid schedule"),
+        {
+            let keys = repos.idempotency_keys.clone();
+            move || idempotency::cleanup_expired_keys(keys.clone())
+        },
+    );
+    scheduler.register(
+        "cleanup_job_runs",
+        jobs::CLEANUP_SCHEDULE.parse().expect("valid schedule"),
+        {
+            let runs = repos.job_runs.clone();
+            move || jobs::cleanup_job_runs(runs.clone())
+        },
+    );
+    let scheduler = Arc::new(scheduler);
+    supervisor.spawn("scheduler", jobs::TICK, {
+        let runs = repos.job_runs.clone();
+        let scheduler = scheduler.clone();
+        let tracker = supervisor.tracker();
+        move |heartbeat| {
+            scheduler
+                .clone()
+                .run(runs.clone(), tracker.clone(), heartbeat)
         }
+    });
+
+    let state = AppState {
+        db: db.clone(),
+        donations: repos.donations,
+        supporters: repos.supporters,
+        accounts: repos.accounts,
+        sessions: repos.sessions,
+        exchange_rates: repos.exchange_rates,
+        campaigns: repos.campaigns,
+        merges: repos.merges,
+        schedules: repos.schedules,
+        payments: repos.payments,
+        idempotency_keys: repos.idempotency_keys,
+        job_runs: repos.job_runs,
+        config: config.clone(),
+        metrics: metrics_handle,
+        tasks,
+        scheduler,
+    };
+
+    if let Some(metrics_port) = config.metrics.port {
+        let listener = TcpListener::bind(format!("[::]:{metrics_port}"))
+            .await
+            .unwrap_or_else(|_| panic!("Unable to bind metrics port {metrics_port}"));
+        tracing::info!("Serving metrics on http://[::]:{metrics_port}/metrics");
+        let metrics_router = metrics_router(state.clone());
+        let shutdown = shutdown.clone();
+        tokio::spawn(async move {
+            axum::serve(listener, metrics_router)
+                .with_graceful_shutdown(shutdown.cancelled_owned())
+                .await
+        });
     }
+    let app = app(state).into_make_service_with_connect_info::<SocketAddr>();
+
+    let port = config.port;
+    let listener = TcpListener::bind(format!("[::]:{port}"))
+        .await
+        .unwrap_or_else(|_| panic!("Unable to bind http://[::]:{port} and 0.0.0.0:{port}"));
+    tracing::info!("Listening on http://[::]:{port} and http://0.0.0.0:{port} ...");
+    let server =
+        axum::serve(listener, app).with_graceful_shutdown(shutdown.clone().cancelled_owned());
+    let drain_timeout = async {
+        shutdown.cancelled().await;
+        tokio::time::sleep(config.drain_timeout).await;
+    };
+    tokio::select! {
+        res = server => res.expect("Server failed"),
+        _ = drain_timeout => tracing::warn!("Dropping in-flight requests after the drain timeout"),
+    }
+
+    supervisor.shutdown().await;
+    db.close().await;
+    tracing::info!("Shut down");
 }
@@ -0,0 +1,40 @@
+//! Fields of JSON Merge Patch (RFC 7396) documents for flat resources, where a
+//! missing member keeps the current value and `null` removes it.
+
+use serde::{Deserialize, Deserializer};
+
+/// A member of a merge patch. Use with `#[serde(default)]` so that missing
+/// members deserialize to [`Patch::Missing`].
+#[derive(Default)]
+pub enum Patch<T> {
+    #[default]
+    Missing,
+    Null,
+    Value(T),
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Patch<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Option::deserialize(deserializer)?.map_or(Self::Null, Self::Value))
+    }
+}
+
+impl<T> Patch<T> {
+    /// New value of a field that cannot be removed, `Err(field)` if the patch sets it to `null`.
+    pub fn required(self, field: &'static str) -> Result<Option<T>, &'static str> {
+        match self {
+            Self::Missing => Ok(None),
+            Self::Null => Err(field),
+            Self::Value(value) => Ok(Some(value)),
+        }
+    }
+
+    /// New value of a nullable field, `None` if the patch leaves it unchanged.
+    pub fn nullable(self) -> Option<Option<T>> {
+        match self {
+            Self::Missing => None,
+            Self::Null => Some(None),
+            Self::Value(value) => Some(Some(value)),
+        }
+    }
+}
@@ -0,0 +1,160 @@
+//! Prometheus metrics, served at `/metrics` on the API port or on `METRICS_PORT`.
+
+use crate::{
+    ApiResult,
+    config::Config,
+    db::Database,
+    error::{Problem, ProblemDetails},
+    repo::Sessions,
+};
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::{HeaderMap, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use std::{sync::Arc, time::Instant};
+use subtle::ConstantTimeEq;
+use thiserror::Error;
+
+#[derive(utoipa::OpenApi)]
+#[openapi(paths(get_metrics))]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    ApiDoc::openapi()
+}
+
+pub const HTTP_REQUESTS: &str = "http_requests_total";
+pub const HTTP_REQUEST_DURATION: &str = "http_request_duration_seconds";
+/// Failed requests by problem `code`
+pub const HTTP_PROBLEMS: &str = "http_problems_total";
+pub const ARGON2_DURATION: &str = "argon2_duration_seconds";
+pub const SESSIONS_ACTIVE: &str = "sessions_active";
+pub const SESSIONS_EXPIRED_REMOVED: &str = "sessions_expired_removed_total";
+pub const DB_POOL_CONNECTIONS: &str = "db_pool_connections";
+pub const DB_POOL_IDLE: &str = "db_pool_idle_connections";
+pub const DB_POOL_MAX: &str = "db_pool_max_connections";
+
+#[derive(Error, Debug)]
+pub enum MetricsError {
+    #[error("Missing or invalid metrics token")]
+    Unauthorized,
+}
+
+impl ProblemDetails for MetricsError {
+    fn status(&self) -> StatusCode {
+        match self {
+            Self::Unauthorized => StatusCode::UNAUTHORIZED,
+        }
+    }
+
+    fn code(&self) -> &'static str {
+        match self {
+            Self::Unauthorized => "invalid_metrics_token",
+        }
+    }
+}
+
+/// Installs the global recorder, must be called once before any metric is recorded.
+pub fn install() -> PrometheusHandle {
+    PrometheusBuilder::new()
+        .set_buckets_for_metric(
+            Matcher::Full(HTTP_REQUEST_DURATION.to_owned()),
+            &[
+                0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+            ],
+        )
+        .and_then(|b| {
+            b.set_buckets_for_metric(
+                Matcher::Full(ARGON2_DURATION.to_owned()),
+                &[0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5],
+            )
+        })
+        .expect("non-empty buckets")
+        .install_recorder()
+        .expect("Unable to install metrics recorder")
+}
+
+/// Counts requests and their latency by method, matched route and status.
+pub async fn track_requests(req: Request, next: Next) -> Response {
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or("unmatched", MatchedPath::as_str)
+        .to_owned();
+    let method = req.method().to_string();
+    let start = Instant::now();
+
+    let res = next.run(req).await;
+
+    let labels = [
+        ("method", method),
+        ("route", route),
+        ("status", res.status().as_u16().to_string()),
+    ];
+    metrics::counter!(HTTP_REQUESTS, &labels).increment(1);
+    metrics::histogram!(HTTP_REQUEST_DURATION, &labels).record(start.elapsed());
+    res
+}
+
+/// Records how long the Argon2 `operation` in `f` took.
+pub fn time_argon2<T>(operation: &'static str, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let result = f();
+    metrics::histogram!(ARGON2_DURATION, "operation" => operation).record(start.elapsed());
+    result
+}
+
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    responses(
+        (
+            status = StatusCode::OK,
+            description = "Metrics in the Prometheus text format",
+            body = String,
+            content_type = "text/plain; version=0.0.4",
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "`METRICS_TOKEN` is set and not given as bearer token",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+    ),
+)]
+pub async fn get_metrics(
+    State(db): State<Database>,
+    State(sessions): State<Sessions>,
+    State(config): State<Arc<Config>>,
+    State(handle): State<PrometheusHandle>,
+    headers: HeaderMap,
+) -> ApiResult<impl IntoResponse> {
+    if let Some(token) = &config.metrics.token {
+        let bearer = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "));
+        // compared in constant time, so response times do not reveal the token
+        if !bearer.is_some_and(|bearer| bearer.as_bytes().ct_eq(token.as_bytes()).into()) {
+            return Err(MetricsError::Unauthorized.into());
+        }
+    }
+
+    let pool = db.pool_stats();
+    metrics::gauge!(DB_POOL_CONNECTIONS).set(pool.size);
+    metrics::gauge!(DB_POOL_IDLE).set(pool.idle as f64);
+    metrics::gauge!(DB_POOL_MAX).set(pool.max);
+    match sessions.count_active().await {
+        Ok(sessions) => metrics::gauge!(SESSIONS_ACTIVE).set(sessions as f64),
+        Err(e) => tracing::warn!(error = %e, "Failed to count active sessions"),
+    }
+
+    handle.run_upkeep();
+    Ok((
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        handle.render(),
+    ))
+}
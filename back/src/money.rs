@@ -0,0 +1,436 @@
+use crate::error::{FieldError, ProblemDetails};
+use axum::http::StatusCode;
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de};
+use std::{fmt, str::FromStr};
+use thiserror::Error;
+use utoipa::openapi::{ObjectBuilder, RefOr, Schema, Type};
+
+/// An exact amount of money, stored as an integer number of minor units (cents).
+///
+/// Serialized as a decimal string with exactly two fraction digits, e.g. `"12.50"`.
+/// Deserialized from a decimal string or JSON number; amounts with more than two
+/// fraction digits are rejected instead of being rounded.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, sqlx::Type)]
+#[sqlx(transparent)]
+pub struct Money(i64);
+
+/// Exchange rate in units of a currency per one EUR, with six decimal places.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, sqlx::Type)]
+#[sqlx(transparent)]
+pub struct Rate(i64);
+
+/// ISO 4217 currency code, e.g. `"EUR"`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, sqlx::Type)]
+#[sqlx(transparent)]
+pub struct Currency(String);
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum MoneyError {
+    #[error("amount must be a non-negative decimal number")]
+    Invalid,
+    #[error("amount must not have more than {0} decimal places")]
+    TooPrecise(usize),
+    #[error("amount is too large")]
+    Overflow,
+    #[error("exchange rate must be greater than zero")]
+    ZeroRate,
+    #[error("currency must be a three letter ISO 4217 code")]
+    InvalidCurrency,
+}
+
+impl ProblemDetails for MoneyError {
+    fn status(&self) -> StatusCode {
+        StatusCode::UNPROCESSABLE_ENTITY
+    }
+
+    fn code(&self) -> &'static str {
+        match self {
+            Self::Invalid | Self::TooPrecise(_) => "invalid_amount",
+            Self::Overflow => "amount_out_of_range",
+            Self::ZeroRate => "invalid_rate",
+            Self::InvalidCurrency => "invalid_currency",
+        }
+    }
+
+    fn errors(&self) -> Vec<FieldError> {
+        let field = match self {
+            Self::ZeroRate => "rate",
+            Self::InvalidCurrency => "currency",
+            _ => "amount",
+        };
+        vec![FieldError {
+            field: field.to_owned(),
+            message: self.to_string(),
+            line: None,
+        }]
+    }
+}
+
+/// Parses a non-negative decimal string into an integer scaled by `10^digits`.
+fn parse_fixed(s: &str, digits: usize) -> Result<i64, MoneyError> {
+    let s = s.trim();
+    let (units, fraction) = s.split_once('.').unwrap_or((s, ""));
+
+    if units.is_empty()
+        || !units.bytes().all(|b| b.is_ascii_digit())
+        || !fraction.bytes().all(|b| b.is_ascii_digit())
+    {
+        return Err(MoneyError::Invalid);
+    }
+
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.len() > digits {
+        return Err(MoneyError::TooPrecise(digits));
+    }
+
+    let units: i64 = units.parse().map_err(|_| MoneyError::Overflow)?;
+    let fraction: i64 = if digits == 0 {
+        0
+    } else {
+        format!("{fraction:0<digits$}")
+            .parse()
+            .expect("ascii digits")
+    };
+
+    units
+        .checked_mul(10_i64.pow(digits as u32))
+        .and_then(|v| v.checked_add(fraction))
+        .ok_or(MoneyError::Overflow)
+}
+
+fn fmt_fixed(f: &mut fmt::Formatter<'_>, value: i64, digits: usize) -> fmt::Result {
+    let sign = if value < 0 { "-" } else { "" };
+    let abs = value.unsigned_abs();
+    let scale = 10_u64.pow(digits as u32);
+    write!(f, "{sign}{}.{:0digits$}", abs / scale, abs % scale)
+}
+
+/// Divides rounding half away from zero.
+fn div_round(n: i128, d: i128) -> i128 {
+    let q = n / d;
+    let r = n % d;
+    if 2 * r.abs() >= d.abs() {
+        q + n.signum() * d.signum()
+    } else {
+        q
+    }
+}
+
+struct FixedVisitor(usize);
+
+impl de::Visitor<'_> for FixedVisitor {
+    type Value = i64;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a decimal number with at most {} decimal places", self.0)
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<i64, E> {
+        parse_fixed(v, self.0).map_err(E::custom)
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<i64, E> {
+        self.visit_str(&v.to_string())
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<i64, E> {
+        self.visit_str(&v.to_string())
+    }
+
+    // `f64`'s `Display` yields the shortest representation that round-trips,
+    // which is the literal the client sent for any amount within the allowed precision.
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<i64, E> {
+        self.visit_str(&v.to_string())
+    }
+}
+
+impl Money {
+    const DIGITS: usize = 2;
+
+    pub const fn from_cents(cents: i64) -> Self {
+        Self(cents)
+    }
+
+    pub const fn cents(self) -> i64 {
+        self.0
+    }
+
+    /// This amount divided by `n` as an exact decimal with six decimal places,
+    /// `None` if `n` is zero.
+    pub fn per(self, n: u64) -> Option<String> {
+        let micros = div_round(self.0 as i128 * 10_000, (n != 0).then_some(n)? as i128);
+        let sign = if micros < 0 { "-" } else { "" };
+        let abs = micros.unsigned_abs();
+        Some(format!("{sign}{}.{:06}", abs / 1_000_000, abs % 1_000_000))
+    }
+}
+
+impl FromStr for Money {
+    type Err = MoneyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_fixed(s, Self::DIGITS).map(Self)
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_fixed(f, self.0, Self::DIGITS)
+    }
+}
+
+impl Serialize for Money {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Money {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer
+            .deserialize_any(FixedVisitor(Self::DIGITS))
+            .map(Self)
+    }
+}
+
+impl utoipa::PartialSchema for Money {
+    fn schema() -> RefOr<Schema> {
+        ObjectBuilder::new()
+            .schema_type(Type::String)
+            .pattern(Some(r"^\d+(\.\d{1,2})?$"))
+            .description(Some("Exact decimal amount with at most two decimal places"))
+            .examples(["12.50"])
+            .into()
+    }
+}
+
+impl utoipa::ToSchema for Money {}
+
+impl Rate {
+    const DIGITS: usize = 6;
+    const SCALE: i128 = 1_000_000;
+
+    /// The rate of EUR itself.
+    pub const ONE: Self = Self(Self::SCALE as i64);
+
+    /// Converts an amount in this rate's currency to EUR.
+    pub fn to_eur(self, amount: Money) -> Result<Money, MoneyError> {
+        let cents = div_round(amount.0 as i128 * Self::SCALE, self.0 as i128);
+        i64::try_from(cents)
+            .map(Money)
+            .map_err(|_| MoneyError::Overflow)
+    }
+
+    /// Converts an amount in EUR to this rate's currency.
+    pub fn convert_eur(self, amount: Money) -> Result<Money, MoneyError> {
+        let cents = div_round(amount.0 as i128 * self.0 as i128, Self::SCALE);
+        i64::try_from(cents)
+            .map(Money)
+            .map_err(|_| MoneyError::Overflow)
+    }
+}
+
+impl FromStr for Rate {
+    type Err = MoneyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match parse_fixed(s, Self::DIGITS)? {
+            0 => Err(MoneyError::ZeroRate),
+            v => Ok(Self(v)),
+        }
+    }
+}
+
+impl fmt::Display for Rate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_fixed(f, self.0, Self::DIGITS)
+    }
+}
+
+impl Serialize for Rate {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Rate {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match deserializer.deserialize_any(FixedVisitor(Self::DIGITS))? {
+            0 => Err(de::Error::custom(MoneyError::ZeroRate)),
+            v => Ok(Self(v)),
+        }
+    }
+}
+
+impl utoipa::PartialSchema for Rate {
+    fn schema() -> RefOr<Schema> {
+        ObjectBuilder::new()
+            .schema_type(Type::String)
+            .pattern(Some(r"^\d+(\.\d{1,6})?$"))
+            .description(Some(
+                "Units of the currency per one EUR, with at most six decimal places",
+            ))
+            .examples(["1.082300"])
+            .into()
+    }
+}
+
+impl utoipa::ToSchema for Rate {}
+
+impl Currency {
+    pub fn eur() -> Self {
+        Self("EUR".to_owned())
+    }
+
+    pub fn is_eur(&self) -> bool {
+        self.0 == "EUR"
+    }
+}
+
+impl Default for Currency {
+    fn default() -> Self {
+        Self::eur()
+    }
+}
+
+impl FromStr for Currency {
+    type Err = MoneyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.len() == 3 && s.bytes().all(|b| b.is_ascii_alphabetic()) {
+            Ok(Self(s.to_ascii_uppercase()))
+        } else {
+            Err(MoneyError::InvalidCurrency)
+        }
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Currency {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+impl utoipa::PartialSchema for Currency {
+    fn schema() -> RefOr<Schema> {
+        ObjectBuilder::new()
+            .schema_type(Type::String)
+            .pattern(Some("^[A-Z]{3}$"))
+            .description(Some("ISO 4217 currency code"))
+            .examples(["EUR"])
+            .into()
+    }
+}
+
+impl utoipa::ToSchema for Currency {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_decimal_strings() {
+        assert_eq!(parse_fixed("12.5", 2), Ok(1250));
+        assert_eq!(parse_fixed(" 12.50 ", 2), Ok(1250));
+        assert_eq!(parse_fixed("12", 2), Ok(1200));
+        assert_eq!(parse_fixed("12.", 2), Ok(1200));
+        assert_eq!(parse_fixed("0.01", 2), Ok(1));
+        // trailing zeros do not count towards the precision
+        assert_eq!(parse_fixed("1.2300", 2), Ok(123));
+        assert_eq!(parse_fixed("1.0823", 6), Ok(1_082_300));
+        assert_eq!(parse_fixed("7.9", 0), Err(MoneyError::TooPrecise(0)));
+        assert_eq!(parse_fixed("7", 0), Ok(7));
+    }
+
+    #[test]
+    fn rejects_invalid_decimal_strings() {
+        for s in ["", ".5", "-1", "+1", "1,5", "1.2.3", "1e3", "abc", " "] {
+            assert_eq!(parse_fixed(s, 2), Err(MoneyError::Invalid), "{s:?}");
+        }
+        assert_eq!(parse_fixed("0.001", 2), Err(MoneyError::TooPrecise(2)));
+        assert_eq!(
+            parse_fixed("92233720368547758.08", 2),
+            Err(MoneyError::Overflow)
+        );
+        assert_eq!(
+            parse_fixed("99999999999999999999", 2),
+            Err(MoneyError::Overflow)
+        );
+    }
+
+    #[test]
+    fn formats_with_all_fraction_digits() {
+        assert_eq!(Money::from_cents(1250).to_string(), "12.50");
+        assert_eq!(Money::from_cents(5).to_string(), "0.05");
+        assert_eq!(Money::from_cents(0).to_string(), "0.00");
+        assert_eq!(Money::from_cents(-5).to_string(), "-0.05");
+        assert_eq!(
+            Money::from_cents(i64::MIN).to_string(),
+            "-92233720368547758.08"
+        );
+        assert_eq!(Rate::ONE.to_string(), "1.000000");
+        assert_eq!("1.0823".parse::<Rate>().unwrap().to_string(), "1.082300");
+    }
+
+    #[test]
+    fn rounds_half_away_from_zero() {
+        assert_eq!(div_round(5, 2), 3);
+        assert_eq!(div_round(-5, 2), -3);
+        assert_eq!(div_round(4, 3), 1);
+        assert_eq!(div_round(5, 3), 2);
+        assert_eq!(div_round(-4, 3), -1);
+        assert_eq!(div_round(5, -2), -3);
+        assert_eq!(div_round(6, 2), 3);
+    }
+
+    #[test]
+    fn money_round_trips_through_json() {
+        let money: Money = serde_json::from_str("\"12.50\"").unwrap();
+        assert_eq!(money, Money::from_cents(1250));
+        assert_eq!(serde_json::to_string(&money).unwrap(), "\"12.50\"");
+        let money: Money = serde_json::from_str("0.1").unwrap();
+        assert_eq!(money, Money::from_cents(10));
+        assert!(serde_json::from_str::<Money>("0.105").is_err());
+        assert!(serde_json::from_str::<Rate>("0").is_err());
+    }
+
+    #[test]
+    fn converts_between_currencies() {
+        let usd: Rate = "1.082300".parse().unwrap();
+        assert_eq!(
+            usd.to_eur(Money::from_cents(1000)),
+            Ok(Money::from_cents(924))
+        );
+        assert_eq!(
+            usd.convert_eur(Money::from_cents(924)),
+            Ok(Money::from_cents(1000))
+        );
+        assert_eq!(
+            Rate::ONE.to_eur(Money::from_cents(1234)),
+            Ok(Money::from_cents(1234))
+        );
+    }
+
+    #[test]
+    fn conversions_that_do_not_fit_are_errors() {
+        let tiny: Rate = "0.000001".parse().unwrap();
+        assert_eq!(
+            tiny.to_eur(Money::from_cents(i64::MAX)),
+            Err(MoneyError::Overflow)
+        );
+        let huge: Rate = "9000000".parse().unwrap();
+        assert_eq!(
+            huge.convert_eur(Money::from_cents(i64::MAX / 2)),
+            Err(MoneyError::Overflow)
+        );
+    }
+}
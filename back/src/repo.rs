@@ -0,0 +1,814 @@
+//! Storage behind traits, so handlers only deal with HTTP, work on MySQL and
+//! SQLite alike, and can be run against in-memory fakes.
+//!
+//! Writes take the `version` the handler read and checked against `If-Match`,
+//! and fail with a precondition error if the row was changed in the meantime.
+
+#[cfg(test)]
+pub mod memory;
+pub mod mysql;
+pub mod sqlite;
+use crate::{
+    campaigns::CampaignError,
+    donations::DonationError,
+    exchange_rates::ExchangeRateError,
+    jobs::RunStatus,
+    money::{Currency, Money, Rate},
+    schedules::{IntervalUnit, ScheduleError},
+    supporters::SupporterError,
+    webhooks::EventStatus,
+};
+use futures::{future::BoxFuture, stream::BoxStream};
+use serde::{Deserialize, Serialize};
+use std::{sync::Arc, time::Duration};
+use time::{Date, OffsetDateTime};
+
+pub type Donations = Arc<dyn DonationRepo>;
+pub type Supporters = Arc<dyn SupporterRepo>;
+pub type Accounts = Arc<dyn AccountRepo>;
+pub type Sessions = Arc<dyn SessionRepo>;
+pub type ExchangeRates = Arc<dyn ExchangeRateRepo>;
+pub type Campaigns = Arc<dyn CampaignRepo>;
+pub type Merges = Arc<dyn MergeRepo>;
+pub type Schedules = Arc<dyn ScheduleRepo>;
+pub type Payments = Arc<dyn PaymentRepo>;
+pub type IdempotencyKeys = Arc<dyn IdempotencyRepo>;
+pub type JobRuns = Arc<dyn JobRepo>;
+
+#[derive(Clone, Debug, sqlx::FromRow)]
+pub struct Donation {
+    pub id: u64,
+    pub coins: u64,
+    pub donated_at: OffsetDateTime,
+    #[sqlx(rename = "income_eur_cents")]
+    pub income_eur: Money,
+    pub currency: Currency,
+    #[sqlx(rename = "amount_cents")]
+    pub amount: Money,
+    pub co_op: String,
+    pub supporter_id: Option<u64>,
+    pub campaign_id: Option<u64>,
+    pub version: u32,
+}
+
+/// A donation as exported, with the name of its supporter.
+#[derive(sqlx::FromRow)]
+pub struct ExportedDonation {
+    pub id: u64,
+    pub donated_at: OffsetDateTime,
+    pub coins: u64,
+    pub co_op: String,
+    pub currency: Currency,
+    #[sqlx(rename = "amount_cents")]
+    pub amount: Money,
+    #[sqlx(rename = "income_eur_cents")]
+    pub income_eur: Money,
+    pub external_ref: Option<String>,
+    pub supporter: Option<String>,
+}
+
+/// Fields of a new donation, with its income already converted to EUR.
+pub struct NewDonation {
+    pub coins: u64,
+    pub amount: Money,
+    pub currency: Currency,
+    pub income_eur: Money,
+    pub co_op: String,
+    pub supporter_id: Option<u64>,
+    pub campaign_id: Option<u64>,
+}
+
+/// Changed fields of a donation, `None` keeps the stored value. `amount`,
+/// `currency` and `income_eur` are only ever changed together.
+#[derive(Default)]
+pub struct DonationChanges {
+    pub coins: Option<u64>,
+    pub amount: Option<Money>,
+    pub currency: Option<Currency>,
+    pub income_eur: Option<Money>,
+    pub co_op: Option<String>,
+    pub supporter_id: Option<Option<u64>>,
+    pub campaign_id: Option<Option<u64>>,
+}
+
+impl DonationChanges {
+    pub fn is_empty(&self) -> bool {
+        self.coins.is_none()
+            && self.amount.is_none()
+            && self.currency.is_none()
+            && self.income_eur.is_none()
+            && self.co_op.is_none()
+            && self.supporter_id.is_none()
+            && self.campaign_id.is_none()
+    }
+}
+
+/// Number, coins and EUR income of a set of donations.
+#[derive(sqlx::FromRow)]
+pub struct Income {
+    pub donations: i64,
+    pub coins: u64,
+    pub income_eur: Money,
+}
+
+#[derive(sqlx::FromRow)]
+pub struct CoOpIncome {
+    pub co_op: String,
+    #[sqlx(flatten)]
+    pub income: Income,
+}
+
+#[derive(sqlx::FromRow)]
+pub struct PeriodIncome {
+    /// First day of the period
+    pub period_start: Date,
+    #[sqlx(flatten)]
+    pub income: Income,
+}
+
+#[derive(sqlx::FromRow)]
+pub struct SupporterIncome {
+    pub supporter_id: u64,
+    pub name: String,
+    pub donations: i64,
+    pub income_eur: Money,
+}
+
+/// Length of the periods donations are grouped by.
+#[derive(Clone, Copy, Default, Deserialize, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Interval {
+    #[default]
+    Day,
+    /// Starting on Monday
+    Week,
+    Month,
+}
+
+/// Condition matching the donations selected by a [`ParsedDonationFilter`],
+/// taking each of its fields twice in declaration order, as bound by
+/// [`ParsedDonationFilter::bind`] on MySQL.
+pub const DONATION_FILTER_SQL: &str = "(? IS NULL OR donations.donated_at >= ?)
+    AND (? IS NULL OR donations.donated_at < ?)
+    AND (? IS NULL OR donations.co_op = ?)
+    AND (? IS NULL OR donations.currency = ?)
+    AND (? IS NULL OR donations.supporter_id = ?)
+    AND (? IS NULL OR donations.campaign_id = ?)";
+
+/// Donations to select, `None` matches any value.
+#[derive(Default)]
+pub struct ParsedDonationFilter {
+    /// Made at or after
+    pub from: Option<OffsetDateTime>,
+    /// Made before
+    pub to: Option<OffsetDateTime>,
+    pub co_op: Option<String>,
+    pub currency: Option<Currency>,
+    pub supporter_id: Option<u64>,
+    pub campaign_id: Option<u64>,
+}
+
+impl ParsedDonationFilter {
+    /// Restricts the filter to the donations of one supporter.
+    pub fn for_supporter(self, supporter_id: u64) -> Self {
+        Self {
+            supporter_id: Some(supporter_id),
+            ..self
+        }
+    }
+}
+
+pub trait DonationRepo: Send + Sync {
+    /// Donations matching `filter`, oldest first.
+    fn list<'a>(
+        &'a self,
+        filter: &'a ParsedDonationFilter,
+    ) -> BoxFuture<'a, Result<Vec<Donation>, DonationError>>;
+
+    fn get(&self, id: u64) -> BoxFuture<'_, Result<Option<Donation>, DonationError>>;
+
+    /// Donations matching `filter`, oldest first, read as the stream is polled
+    /// so that exports are never held in memory as a whole.
+    fn export(
+        &self,
+        filter: ParsedDonationFilter,
+    ) -> BoxStream<'static, Result<ExportedDonation, DonationError>>;
+
+    /// Rate that converts donations in `currency` made at `at` to EUR.
+    fn rate_at<'a>(
+        &'a self,
+        currency: &'a Currency,
+        at: OffsetDateTime,
+    ) -> BoxFuture<'a, Result<Rate, ExchangeRateError>>;
+
+    /// Stores a donation made now and returns its id.
+    fn create(&self, donation: NewDonation) -> BoxFuture<'_, Result<u64, DonationError>>;
+
+    /// Applies `changes` if the donation is still at `version`, which is then incremented.
+    fn update(
+        &self,
+        id: u64,
+        version: u32,
+        changes: DonationChanges,
+    ) -> BoxFuture<'_, Result<(), DonationError>>;
+
+    fn delete(&self, id: u64, version: u32) -> BoxFuture<'_, Result<(), DonationError>>;
+
+    /// Income of the donations made in `[from, to)`.
+    fn income(
+        &self,
+        from: OffsetDateTime,
+        to: OffsetDateTime,
+    ) -> BoxFuture<'_, Result<Income, DonationError>>;
+
+    /// Income per co-op of the donations made in `[from, to)`, ordered by co-op.
+    fn income_by_co_op(
+        &self,
+        from: OffsetDateTime,
+        to: OffsetDateTime,
+    ) -> BoxFuture<'_, Result<Vec<CoOpIncome>, DonationError>>;
+
+    /// Income per period of the donations made in `[from, to)`, in order,
+    /// leaving out periods without donations.
+    fn income_by_period(
+        &self,
+        from: OffsetDateTime,
+        to: OffsetDateTime,
+        interval: Interval,
+    ) -> BoxFuture<'_, Result<Vec<PeriodIncome>, DonationError>>;
+
+    /// The `limit` supporters with the most EUR income in `[from, to)`.
+    fn top_supporters(
+        &self,
+        from: OffsetDateTime,
+        to: OffsetDateTime,
+        limit: u32,
+    ) -> BoxFuture<'_, Result<Vec<SupporterIncome>, DonationError>>;
+}
+
+#[derive(Clone, Debug, sqlx::FromRow)]
+pub struct Supporter {
+    pub id: u64,
+    pub name: String,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub notes: Option<String>,
+    pub public: bool,
+    pub created_at: OffsetDateTime,
+    pub version: u32,
+}
+
+pub struct NewSupporter {
+    pub name: String,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub notes: Option<String>,
+    pub public: bool,
+}
+
+/// Changed fields of a supporter, `None` keeps the stored value.
+#[derive(Default)]
+pub struct SupporterChanges {
+    pub name: Option<String>,
+    pub email: Option<Option<String>>,
+    pub phone: Option<Option<String>>,
+    pub notes: Option<Option<String>>,
+    pub public: Option<bool>,
+}
+
+impl SupporterChanges {
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none()
+            && self.email.is_none()
+            && self.phone.is_none()
+            && self.notes.is_none()
+            && self.public.is_none()
+    }
+}
+
+/// Lifetime totals of a supporter's donations.
+#[derive(sqlx::FromRow)]
+pub struct SupporterTotals {
+    pub donations: i64,
+    pub coins: u64,
+    pub income_eur: Money,
+    pub first_donated_at: Option<OffsetDateTime>,
+    pub last_donated_at: Option<OffsetDateTime>,
+}
+
+pub trait SupporterRepo: Send + Sync {
+    /// All supporters, ordered by name.
+    fn list(&self) -> BoxFuture<'_, Result<Vec<Supporter>, SupporterError>>;
+
+    fn get(&self, id: u64) -> BoxFuture<'_, Result<Option<Supporter>, SupporterError>>;
+
+    fn create(&self, supporter: NewSupporter) -> BoxFuture<'_, Result<u64, SupporterError>>;
+
+    /// Applies `changes` if the supporter is still at `version`, which is then incremented.
+    fn update(
+        &self,
+        id: u64,
+        version: u32,
+        changes: SupporterChanges,
+    ) -> BoxFuture<'_, Result<(), SupporterError>>;
+
+    fn delete(&self, id: u64, version: u32) -> BoxFuture<'_, Result<(), SupporterError>>;
+
+    fn totals(&self, id: u64) -> BoxFuture<'_, Result<SupporterTotals, SupporterError>>;
+
+    /// Names and lifetime EUR income of the supporters who consented to being
+    /// listed and donated, ordered by name.
+    fn public_totals(&self) -> BoxFuture<'_, Result<Vec<(String, Money)>, SupporterError>>;
+}
+
+/// A supporter that may be a duplicate of another.
+#[derive(sqlx::FromRow)]
+pub struct MergeCandidate {
+    pub id: u64,
+    pub name: String,
+    pub email: Option<String>,
+}
+
+/// Contact details of a supporter, combined when duplicates are merged.
+#[derive(sqlx::FromRow)]
+pub struct Contact {
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub notes: Option<String>,
+}
+
+/// A duplicate merged into its canonical supporter.
+#[derive(sqlx::FromRow)]
+pub struct SupporterMerge {
+    pub id: u64,
+    /// `None` if the canonical supporter was deleted since
+    pub canonical_id: Option<u64>,
+    pub merged_id: u64,
+    pub merged_name: String,
+    pub merged_email: Option<String>,
+    /// JSON array of the ids of the donations moved to the canonical supporter
+    pub donation_ids: String,
+    /// `None` if the account was deleted since
+    pub account_id: Option<u64>,
+    pub merged_at: OffsetDateTime,
+}
+
+pub trait MergeRepo: Send + Sync {
+    /// Ids, names and emails of all supporters, to look for duplicates.
+    fn candidates(&self) -> BoxFuture<'_, Result<Vec<MergeCandidate>, SupporterError>>;
+
+    /// Moves the donations and schedules of `duplicate_ids` to `canonical_id`,
+    /// gives it the contact details folded with `combine` and deletes the
+    /// duplicates, all or nothing. Returns the number of donations moved.
+    fn merge<'a>(
+        &'a self,
+        canonical_id: u64,
+        duplicate_ids: &'a [u64],
+        account_id: u64,
+        combine: fn(Contact, Contact) -> Contact,
+    ) -> BoxFuture<'a, Result<u64, SupporterError>>;
+
+    /// Logged merges, newest first.
+    fn merges(&self) -> BoxFuture<'_, Result<Vec<SupporterMerge>, SupporterError>>;
+}
+
+#[derive(Clone, Debug, sqlx::FromRow)]
+pub struct Account {
+    pub id: u64,
+    pub email: String,
+    /// Argon2 hash in PHC string format
+    pub password: String,
+}
+
+pub trait AccountRepo: Send + Sync {
+    /// Stores a new account and returns its id, `None` if the email is taken.
+    fn create<'a>(
+        &'a self,
+        email: &'a str,
+        password: &'a str,
+    ) -> BoxFuture<'a, Result<Option<u64>, sqlx::Error>>;
+
+    fn get(&self, id: u64) -> BoxFuture<'_, Result<Option<Account>, sqlx::Error>>;
+
+    fn find_by_email<'a>(
+        &'a self,
+        email: &'a str,
+    ) -> BoxFuture<'a, Result<Option<Account>, sqlx::Error>>;
+}
+
+pub trait SessionRepo: Send + Sync {
+    fn create<'a>(
+        &'a self,
+        token: &'a str,
+        account_id: u64,
+        max_age: Duration,
+    ) -> BoxFuture<'a, Result<(), sqlx::Error>>;
+
+    /// Id of the account owning the unexpired session with `token`.
+    fn account_id<'a>(&'a self, token: &'a str) -> BoxFuture<'a, Result<Option<u64>, sqlx::Error>>;
+
+    fn delete<'a>(&'a self, token: &'a str) -> BoxFuture<'a, Result<(), sqlx::Error>>;
+
+    /// Number of sessions that have not expired yet.
+    fn count_active(&self) -> BoxFuture<'_, Result<i64, sqlx::Error>>;
+
+    /// Deletes expired sessions and returns how many there were.
+    fn delete_expired(&self) -> BoxFuture<'_, Result<u64, sqlx::Error>>;
+}
+
+/// Rate of a currency from `valid_from` until its next rate.
+#[derive(Clone, Debug, sqlx::FromRow)]
+pub struct ExchangeRate {
+    pub currency: Currency,
+    pub valid_from: OffsetDateTime,
+    #[sqlx(rename = "rate_micros")]
+    pub rate: Rate,
+}
+
+pub trait ExchangeRateRepo: Send + Sync {
+    /// Rates ordered by currency and start, only those of `currency` if given.
+    fn list<'a>(
+        &'a self,
+        currency: Option<&'a Currency>,
+    ) -> BoxFuture<'a, Result<Vec<ExchangeRate>, ExchangeRateError>>;
+
+    /// Currencies with at least one rate, in order.
+    fn currencies(&self) -> BoxFuture<'_, Result<Vec<Currency>, ExchangeRateError>>;
+
+    /// Stores all `rates` or none of them, replacing rates of the same currency
+    /// and start.
+    fn upsert(&self, rates: Vec<ExchangeRate>) -> BoxFuture<'_, Result<(), ExchangeRateError>>;
+}
+
+#[derive(Clone, Debug, sqlx::FromRow)]
+pub struct Campaign {
+    pub id: u64,
+    pub name: String,
+    pub description: Option<String>,
+    pub co_op: String,
+    #[sqlx(rename = "goal_eur_cents")]
+    pub goal_eur: Money,
+    pub starts_at: OffsetDateTime,
+    pub ends_at: Option<OffsetDateTime>,
+}
+
+/// Fields of a new campaign, or those replacing a stored one.
+pub struct CampaignFields {
+    pub name: String,
+    pub description: Option<String>,
+    pub co_op: String,
+    pub goal_eur: Money,
+    pub starts_at: OffsetDateTime,
+    pub ends_at: Option<OffsetDateTime>,
+}
+
+/// Totals of the donations to a campaign.
+#[derive(sqlx::FromRow)]
+pub struct CampaignTotals {
+    pub donations: i64,
+    /// Distinct supporters, counting every donation without a supporter as its own donor
+    pub donors: i64,
+    pub raised_eur: Money,
+}
+
+pub trait CampaignRepo: Send + Sync {
+    /// All campaigns, the latest start first.
+    fn list(&self) -> BoxFuture<'_, Result<Vec<Campaign>, CampaignError>>;
+
+    fn get(&self, id: u64) -> BoxFuture<'_, Result<Option<Campaign>, CampaignError>>;
+
+    fn create(&self, campaign: CampaignFields) -> BoxFuture<'_, Result<u64, CampaignError>>;
+
+    fn update(&self, id: u64, campaign: CampaignFields)
+    -> BoxFuture<'_, Result<(), CampaignError>>;
+
+    /// Deletes a campaign, keeping its donations without a campaign.
+    fn delete(&self, id: u64) -> BoxFuture<'_, Result<(), CampaignError>>;
+
+    fn totals(&self, id: u64) -> BoxFuture<'_, Result<CampaignTotals, CampaignError>>;
+
+    /// EUR income of the campaign per UTC day, leaving out days without donations.
+    fn income_by_day(&self, id: u64) -> BoxFuture<'_, Result<Vec<(Date, Money)>, CampaignError>>;
+}
+
+/// Donations a supporter promised to make regularly.
+#[derive(Clone, Debug, sqlx::FromRow)]
+pub struct Schedule {
+    pub id: u64,
+    pub supporter_id: u64,
+    pub supporter_name: String,
+    pub co_op: String,
+    pub currency: Currency,
+    #[sqlx(rename = "amount_cents")]
+    pub amount: Money,
+    pub interval_unit: IntervalUnit,
+    pub interval_count: u32,
+    pub starts_on: Date,
+    pub ends_on: Option<Date>,
+    pub grace_days: u32,
+}
+
+/// Fields of a new schedule, or those replacing a stored one.
+pub struct ScheduleFields {
+    pub supporter_id: u64,
+    pub co_op: String,
+    pub currency: Currency,
+    pub amount: Money,
+    pub interval_unit: IntervalUnit,
+    pub interval_count: u32,
+    pub starts_on: Date,
+    pub ends_on: Option<Date>,
+    pub grace_days: u32,
+}
+
+/// A due date of a schedule without a matching donation.
+#[derive(sqlx::FromRow)]
+pub struct MissedDonation {
+    pub schedule_id: u64,
+    pub supporter_id: u64,
+    pub supporter_name: String,
+    pub co_op: String,
+    pub currency: Currency,
+    #[sqlx(rename = "amount_cents")]
+    pub amount: Money,
+    pub due_on: Date,
+}
+
+pub trait ScheduleRepo: Send + Sync {
+    /// Schedules in order, only those not ended before `active_on` if given.
+    fn list(&self, active_on: Option<Date>) -> BoxFuture<'_, Result<Vec<Schedule>, sqlx::Error>>;
+
+    fn get(&self, id: u64) -> BoxFuture<'_, Result<Option<Schedule>, sqlx::Error>>;
+
+    /// Fails with [`ScheduleError::SupporterNotFound`] for unknown supporters.
+    fn create(&self, schedule: ScheduleFields) -> BoxFuture<'_, Result<u64, ScheduleError>>;
+
+    fn update(&self, id: u64, schedule: ScheduleFields)
+    -> BoxFuture<'_, Result<(), ScheduleError>>;
+
+    fn delete(&self, id: u64) -> BoxFuture<'_, Result<(), ScheduleError>>;
+
+    /// Due dates recorded as missed, the most recent first.
+    fn missed(&self) -> BoxFuture<'_, Result<Vec<MissedDonation>, sqlx::Error>>;
+
+    /// Due dates of the schedule whose outcome is recorded.
+    fn checked_due_dates(&self, schedule_id: u64) -> BoxFuture<'_, Result<Vec<Date>, sqlx::Error>>;
+
+    /// The donation by the schedule's supporter to its co-op made from `from`
+    /// until before `until` that is closest to `due_at` and not matched to
+    /// another due date yet.
+    fn unmatched_donation<'a>(
+        &'a self,
+        schedule: &'a Schedule,
+        from: OffsetDateTime,
+        until: OffsetDateTime,
+        due_at: OffsetDateTime,
+    ) -> BoxFuture<'a, Result<Option<u64>, sqlx::Error>>;
+
+    /// Records a due date as received with `donation_id` or as missed without,
+    /// unless its outcome is recorded already.
+    fn record_outcome(
+        &self,
+        schedule_id: u64,
+        due_on: Date,
+        donation_id: Option<u64>,
+    ) -> BoxFuture<'_, Result<(), sqlx::Error>>;
+}
+
+/// A donation recorded from an import or a payment provider.
+pub struct PaidDonation {
+    pub coins: u64,
+    pub donated_at: OffsetDateTime,
+    pub income_eur: Money,
+    pub currency: Currency,
+    pub amount: Money,
+    pub co_op: String,
+    pub external_ref: Option<String>,
+    pub supporter_id: Option<u64>,
+}
+
+/// An event as received from a payment provider and what became of it.
+#[derive(sqlx::FromRow)]
+pub struct WebhookEvent {
+    pub id: u64,
+    pub provider: String,
+    pub event_id: String,
+    pub event_type: String,
+    pub payload: Vec<u8>,
+    pub status: EventStatus,
+    pub error: Option<String>,
+    pub donation_id: Option<u64>,
+    pub received_at: OffsetDateTime,
+    pub processed_at: Option<OffsetDateTime>,
+}
+
+pub struct NewWebhookEvent<'a> {
+    pub provider: &'a str,
+    pub event_id: &'a str,
+    pub event_type: &'a str,
+    pub payload: &'a [u8],
+    pub status: EventStatus,
+    pub error: Option<&'a str>,
+    pub donation_id: Option<u64>,
+}
+
+pub trait PaymentRepo: Send + Sync {
+    /// Starts recording payments, which are rolled back unless committed.
+    fn begin(&self) -> BoxFuture<'_, Result<Box<dyn PaymentTx>, sqlx::Error>>;
+
+    /// Events the newest first, only those of `provider` and with `status` if given.
+    fn events<'a>(
+        &'a self,
+        provider: Option<&'a str>,
+        status: Option<EventStatus>,
+        limit: u32,
+    ) -> BoxFuture<'a, Result<Vec<WebhookEvent>, sqlx::Error>>;
+
+    fn event(&self, id: u64) -> BoxFuture<'_, Result<Option<WebhookEvent>, sqlx::Error>>;
+
+    /// The stored event with the provider's `event_id`.
+    fn event_by_provider_id<'a>(
+        &'a self,
+        provider: &'a str,
+        event_id: &'a str,
+    ) -> BoxFuture<'a, Result<Option<WebhookEvent>, sqlx::Error>>;
+}
+
+/// Writes of one import or webhook event. Constraint violations are returned
+/// as they are, so that they can reject single rows or events.
+pub trait PaymentTx: Send {
+    /// Whether a donation with `external_ref` was recorded.
+    fn external_ref_exists<'a>(
+        &'a mut self,
+        external_ref: &'a str,
+    ) -> BoxFuture<'a, Result<bool, sqlx::Error>>;
+
+    /// Rate of `currency` in effect at `at`.
+    fn rate_at<'a>(
+        &'a mut self,
+        currency: &'a Currency,
+        at: OffsetDateTime,
+    ) -> BoxFuture<'a, Result<Rate, ExchangeRateError>>;
+
+    /// The oldest supporter with `email`.
+    fn supporter_by_email<'a>(
+        &'a mut self,
+        email: &'a str,
+    ) -> BoxFuture<'a, Result<Option<u64>, sqlx::Error>>;
+
+    /// The oldest supporter named `name`.
+    fn supporter_by_name<'a>(
+        &'a mut self,
+        name: &'a str,
+    ) -> BoxFuture<'a, Result<Option<u64>, sqlx::Error>>;
+
+    fn create_supporter(
+        &mut self,
+        supporter: NewSupporter,
+    ) -> BoxFuture<'_, Result<u64, sqlx::Error>>;
+
+    fn create_donation(
+        &mut self,
+        donation: PaidDonation,
+    ) -> BoxFuture<'_, Result<u64, sqlx::Error>>;
+
+    /// Stores a processed event, failing with a unique violation if it was
+    /// stored before.
+    fn create_event<'a>(
+        &'a mut self,
+        event: NewWebhookEvent<'a>,
+    ) -> BoxFuture<'a, Result<u64, sqlx::Error>>;
+
+    /// Records the outcome of processing a stored event again.
+    fn update_event<'a>(
+        &'a mut self,
+        id: u64,
+        status: EventStatus,
+        error: Option<&'a str>,
+        donation_id: Option<u64>,
+    ) -> BoxFuture<'a, Result<(), sqlx::Error>>;
+
+    /// Marks the changes made so far, so that [`PaymentTx::rollback_to_savepoint`]
+    /// only undoes the later ones.
+    fn savepoint(&mut self) -> BoxFuture<'_, Result<(), sqlx::Error>>;
+
+    fn rollback_to_savepoint(&mut self) -> BoxFuture<'_, Result<(), sqlx::Error>>;
+
+    fn commit(self: Box<Self>) -> BoxFuture<'static, Result<(), sqlx::Error>>;
+
+    fn rollback(self: Box<Self>) -> BoxFuture<'static, Result<(), sqlx::Error>>;
+}
+
+/// A claimed `Idempotency-Key` and the response to replay once it is stored.
+#[derive(sqlx::FromRow)]
+pub struct StoredKey {
+    pub request_hash: String,
+    pub status_code: Option<u16>,
+    pub content_type: Option<String>,
+    pub response_body: Option<Vec<u8>>,
+}
+
+pub trait IdempotencyRepo: Send + Sync {
+    /// Claims `key` for a request hashing to `request_hash`, or returns false if
+    /// the account already claimed it.
+    fn claim<'a>(
+        &'a self,
+        account_id: u64,
+        key: &'a str,
+        request_hash: &'a str,
+    ) -> BoxFuture<'a, Result<bool, sqlx::Error>>;
+
+    fn get<'a>(
+        &'a self,
+        account_id: u64,
+        key: &'a str,
+    ) -> BoxFuture<'a, Result<Option<StoredKey>, sqlx::Error>>;
+
+    /// Claims `key` again if it was claimed longer than `pending` ago without
+    /// storing a response.
+    fn take_over<'a>(
+        &'a self,
+        account_id: u64,
+        key: &'a str,
+        pending: Duration,
+    ) -> BoxFuture<'a, Result<bool, sqlx::Error>>;
+
+    fn store<'a>(
+        &'a self,
+        account_id: u64,
+        key: &'a str,
+        status_code: u16,
+        content_type: Option<&'a str>,
+        body: &'a [u8],
+    ) -> BoxFuture<'a, Result<(), sqlx::Error>>;
+
+    /// Releases `key` unless its response was stored.
+    fn release<'a>(
+        &'a self,
+        account_id: u64,
+        key: &'a str,
+    ) -> BoxFuture<'a, Result<(), sqlx::Error>>;
+
+    /// Deletes keys claimed longer than `retention` ago and returns how many there were.
+    fn delete_expired(&self, retention: Duration) -> BoxFuture<'_, Result<u64, sqlx::Error>>;
+}
+
+#[derive(sqlx::FromRow)]
+pub struct JobRun {
+    pub id: u64,
+    pub job: String,
+    pub scheduled_at: OffsetDateTime,
+    pub started_at: OffsetDateTime,
+    pub finished_at: Option<OffsetDateTime>,
+    pub status: RunStatus,
+    pub detail: Option<String>,
+    pub instance: String,
+}
+
+/// Held while a job runs, so that no other instance starts it meanwhile.
+pub trait JobLock: Send {
+    fn release(self: Box<Self>) -> BoxFuture<'static, Result<(), sqlx::Error>>;
+}
+
+pub trait JobRepo: Send + Sync {
+    /// Locks `job`, or returns `None` if it is already running.
+    fn lock<'a>(
+        &'a self,
+        job: &'a str,
+    ) -> BoxFuture<'a, Result<Option<Box<dyn JobLock>>, sqlx::Error>>;
+
+    /// Whether `job` already ran for `slot` or a later one.
+    fn ran<'a>(
+        &'a self,
+        job: &'a str,
+        slot: OffsetDateTime,
+    ) -> BoxFuture<'a, Result<bool, sqlx::Error>>;
+
+    /// Records that `job` started running for `slot` and returns the run's id.
+    fn start<'a>(
+        &'a self,
+        job: &'a str,
+        slot: OffsetDateTime,
+        instance: &'a str,
+    ) -> BoxFuture<'a, Result<u64, sqlx::Error>>;
+
+    fn finish<'a>(
+        &'a self,
+        id: u64,
+        status: RunStatus,
+        detail: &'a str,
+    ) -> BoxFuture<'a, Result<(), sqlx::Error>>;
+
+    /// The latest run of every job that ran.
+    fn last_runs(&self) -> BoxFuture<'_, Result<Vec<JobRun>, sqlx::Error>>;
+
+    /// Runs of `job`, newest first.
+    fn runs<'a>(
+        &'a self,
+        job: &'a str,
+        limit: u32,
+    ) -> BoxFuture<'a, Result<Vec<JobRun>, sqlx::Error>>;
+
+    /// Deletes runs started longer than `retention` ago and returns how many there were.
+    fn delete_older_than(&self, retention: Duration) -> BoxFuture<'_, Result<u64, sqlx::Error>>;
+}
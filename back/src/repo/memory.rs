@@ -0,0 +1,506 @@
+//! In-memory [`DonationRepo`] and [`SupporterRepo`], to test handlers without a
+//! database. There are no campaigns, so donations referencing one are rejected,
+//! and exchange rates are added with [`MemoryRepo::set_rate`].
+
+use super::{
+    CoOpIncome, Donation, DonationChanges, DonationRepo, ExportedDonation, Income, Interval,
+    NewDonation, NewSupporter, ParsedDonationFilter, PeriodIncome, Supporter, SupporterChanges,
+    SupporterIncome, SupporterRepo, SupporterTotals,
+};
+use crate::{
+    donations::DonationError,
+    exchange_rates::ExchangeRateError,
+    money::{Currency, Money, Rate},
+    supporters::SupporterError,
+};
+use futures::{
+    FutureExt, StreamExt,
+    future::{BoxFuture, ready},
+    stream::{self, BoxStream},
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Mutex, MutexGuard},
+};
+use time::{Date, Duration, OffsetDateTime, format_description::well_known::Rfc3339};
+
+#[derive(Default)]
+pub struct MemoryRepo {
+    tables: Mutex<Tables>,
+}
+
+#[derive(Default)]
+struct Tables {
+    donations: BTreeMap<u64, Donation>,
+    last_donation_id: u64,
+    supporters: BTreeMap<u64, Supporter>,
+    last_supporter_id: u64,
+    /// Rates per currency and start of validity
+    rates: BTreeMap<(String, OffsetDateTime), Rate>,
+}
+
+/// Now, at the precision of stored timestamps.
+fn now() -> OffsetDateTime {
+    OffsetDateTime::now_utc()
+        .replace_nanosecond(0)
+        .expect("zero is a valid nanosecond")
+}
+
+fn matches(filter: &ParsedDonationFilter, donation: &Donation) -> bool {
+    filter.from.is_none_or(|from| donation.donated_at >= from)
+        && filter.to.is_none_or(|to| donation.donated_at < to)
+        && filter
+            .co_op
+            .as_ref()
+            .is_none_or(|co_op| donation.co_op == *co_op)
+        && filter
+            .currency
+            .as_ref()
+            .is_none_or(|currency| donation.currency == *currency)
+        && filter
+            .supporter_id
+            .is_none_or(|id| donation.supporter_id == Some(id))
+        && filter
+            .campaign_id
+            .is_none_or(|id| donation.campaign_id == Some(id))
+}
+
+fn income<'a>(donations: impl IntoIterator<Item = &'a Donation>) -> Income {
+    donations.into_iter().fold(
+        Income {
+            donations: 0,
+            coins: 0,
+            income_eur: Money::default(),
+        },
+        |income, donation| Income {
+            donations: income.donations + 1,
+            coins: income.coins + donation.coins,
+            income_eur: Money::from_cents(income.income_eur.cents() + donation.income_eur.cents()),
+        },
+    )
+}
+
+fn period_start(at: OffsetDateTime, interval: Interval) -> Date {
+    let date = at.date();
+    match interval {
+        Interval::Day => date,
+        Interval::Week => date - Duration::days(date.weekday().number_days_from_monday().into()),
+        Interval::Month => date.replace_day(1).expect("every month has a first day"),
+    }
+}
+
+impl MemoryRepo {
+    fn tables(&self) -> MutexGuard<'_, Tables> {
+        self.tables
+            .lock()
+            .expect("no test panicked holding the lock")
+    }
+
+    /// Makes `rate` the rate of `currency` from `valid_from` on.
+    pub fn set_rate(&self, currency: &Currency, valid_from: OffsetDateTime, rate: Rate) {
+        self.tables()
+            .rates
+            .insert((currency.to_string(), valid_from), rate);
+    }
+}
+
+impl Tables {
+    fn check_references(
+        &self,
+        supporter_id: Option<u64>,
+        campaign_id: Option<u64>,
+    ) -> Result<(), DonationError> {
+        if supporter_id.is_some_and(|id| !self.supporters.contains_key(&id)) {
+            return Err(DonationError::SupporterNotFound);
+        }
+        if campaign_id.is_some() {
+            return Err(DonationError::CampaignNotFound);
+        }
+        Ok(())
+    }
+
+    /// Donations made in `[from, to)`.
+    fn made_between(
+        &self,
+        from: OffsetDateTime,
+        to: OffsetDateTime,
+    ) -> impl Iterator<Item = &Donation> {
+        self.donations
+            .values()
+            .filter(move |donation| donation.donated_at >= from && donation.donated_at < to)
+    }
+}
+
+impl DonationRepo for MemoryRepo {
+    fn list<'a>(
+        &'a self,
+        filter: &'a ParsedDonationFilter,
+    ) -> BoxFuture<'a, Result<Vec<Donation>, DonationError>> {
+        let mut donations: Vec<_> = self
+            .tables()
+            .donations
+            .values()
+            .filter(|donation| matches(filter, donation))
+            .cloned()
+            .collect();
+        donations.sort_by_key(|donation| (donation.donated_at, donation.id));
+        ready(Ok(donations)).boxed()
+    }
+
+    fn get(&self, id: u64) -> BoxFuture<'_, Result<Option<Donation>, DonationError>> {
+        ready(Ok(self.tables().donations.get(&id).cloned())).boxed()
+    }
+
+    fn export(
+        &self,
+        filter: ParsedDonationFilter,
+    ) -> BoxStream<'static, Result<ExportedDonation, DonationError>> {
+        let tables = self.tables();
+        let mut donations: Vec<_> = tables
+            .donations
+            .values()
+            .filter(|donation| matches(&filter, donation))
+            .map(|donation| ExportedDonation {
+                id: donation.id,
+                donated_at: donation.donated_at,
+                coins: donation.coins,
+                co_op: donation.co_op.clone(),
+                currency: donation.currency.clone(),
+                amount: donation.amount,
+                income_eur: donation.income_eur,
+                external_ref: None,
+                supporter: donation
+                    .supporter_id
+                    .map(|id| tables.supporters[&id].name.clone()),
+            })
+            .collect();
+        donations.sort_by_key(|donation| (donation.donated_at, donation.id));
+        stream::iter(donations.into_iter().map(Ok)).boxed()
+    }
+
+    fn rate_at<'a>(
+        &'a self,
+        currency: &'a Currency,
+        at: OffsetDateTime,
+    ) -> BoxFuture<'a, Result<Rate, ExchangeRateError>> {
+        let rate = if currency.is_eur() {
+            Some(Rate::ONE)
+        } else {
+            let key = currency.to_string();
+            self.tables()
+                .rates
+                .range((key.clone(), OffsetDateTime::UNIX_EPOCH)..=(key, at))
+                .next_back()
+                .map(|(_, rate)| *rate)
+        };
+        ready(rate.ok_or_else(|| {
+            ExchangeRateError::MissingRate(
+                currency.clone(),
+                at.format(&Rfc3339).unwrap_or_default(),
+            )
+        }))
+        .boxed()
+    }
+
+    fn create(&self, donation: NewDonation) -> BoxFuture<'_, Result<u64, DonationError>> {
+        let mut tables = self.tables();
+        let res = tables
+            .check_references(donation.supporter_id, donation.campaign_id)
+            .map(|()| {
+                tables.last_donation_id += 1;
+                let id = tables.last_donation_id;
+                tables.donations.insert(
+                    id,
+                    Donation {
+                        id,
+                        coins: donation.coins,
+                        donated_at: now(),
+                        income_eur: donation.income_eur,
+                        currency: donation.currency,
+                        amount: donation.amount,
+                        co_op: donation.co_op,
+                        supporter_id: donation.supporter_id,
+                        campaign_id: donation.campaign_id,
+                        version: 1,
+                    },
+                );
+                id
+            });
+        ready(res).boxed()
+    }
+
+    fn update(
+        &self,
+        id: u64,
+        version: u32,
+        changes: DonationChanges,
+    ) -> BoxFuture<'_, Result<(), DonationError>> {
+        let mut tables = self.tables();
+        let res = (|| {
+            let current = tables.donations.get(&id).ok_or(DonationError::NotFound)?;
+            if current.version != version {
+                return Err(DonationError::PreconditionFailed);
+            }
+            if changes.is_empty() {
+                return Ok(());
+            }
+            tables.check_references(
+                changes.supporter_id.flatten(),
+                changes.campaign_id.flatten(),
+            )?;
+
+            let donation = tables.donations.get_mut(&id).expect("checked above");
+            donation.version += 1;
+            if let Some(coins) = changes.coins {
+                donation.coins = coins;
+            }
+            if let Some(amount) = changes.amount {
+                donation.amount = amount;
+            }
+            if let Some(currency) = changes.currency {
+                donation.currency = currency;
+            }
+            if let Some(income_eur) = changes.income_eur {
+                donation.income_eur = income_eur;
+            }
+            if let Some(co_op) = changes.co_op {
+                donation.co_op = co_op;
+            }
+            if let Some(supporter_id) = changes.supporter_id {
+                donation.supporter_id = supporter_id;
+            }
+            if let Some(campaign_id) = changes.campaign_id {
+                donation.campaign_id = campaign_id;
+            }
+            Ok(())
+        })();
+        ready(res).boxed()
+    }
+
+    fn delete(&self, id: u64, version: u32) -> BoxFuture<'_, Result<(), DonationError>> {
+        let mut tables = self.tables();
+        let res = match tables.donations.get(&id) {
+            None => Err(DonationError::NotFound),
+            Some(donation) if donation.version != version => Err(DonationError::PreconditionFailed),
+            Some(_) => {
+                tables.donations.remove(&id);
+                Ok(())
+            }
+        };
+        ready(res).boxed()
+    }
+
+    fn income(
+        &self,
+        from: OffsetDateTime,
+        to: OffsetDateTime,
+    ) -> BoxFuture<'_, Result<Income, DonationError>> {
+        ready(Ok(income(self.tables().made_between(from, to)))).boxed()
+    }
+
+    fn income_by_co_op(
+        &self,
+        from: OffsetDateTime,
+        to: OffsetDateTime,
+    ) -> BoxFuture<'_, Result<Vec<CoOpIncome>, DonationError>> {
+        let tables = self.tables();
+        let mut by_co_op = BTreeMap::<_, Vec<_>>::new();
+        for donation in tables.made_between(from, to) {
+            by_co_op.entry(&donation.co_op).or_default().push(donation);
+        }
+        let res = by_co_op
+            .into_iter()
+            .map(|(co_op, donations)| CoOpIncome {
+                co_op: co_op.clone(),
+                income: income(donations),
+            })
+            .collect();
+        ready(Ok(res)).boxed()
+    }
+
+    fn income_by_period(
+        &self,
+        from: OffsetDateTime,
+        to: OffsetDateTime,
+        interval: Interval,
+    ) -> BoxFuture<'_, Result<Vec<PeriodIncome>, DonationError>> {
+        let tables = self.tables();
+        let mut by_period = BTreeMap::<_, Vec<_>>::new();
+        for donation in tables.made_between(from, to) {
+            by_period
+                .entry(period_start(donation.donated_at, interval))
+                .or_default()
+                .push(donation);
+        }
+        let res = by_period
+            .into_iter()
+            .map(|(period_start, donations)| PeriodIncome {
+                period_start,
+                income: income(donations),
+            })
+            .collect();
+        ready(Ok(res)).boxed()
+    }
+
+    fn top_supporters(
+        &self,
+        from: OffsetDateTime,
+        to: OffsetDateTime,
+        limit: u32,
+    ) -> BoxFuture<'_, Result<Vec<SupporterIncome>, DonationError>> {
+        let tables = self.tables();
+        let mut by_supporter = HashMap::<_, Vec<_>>::new();
+        for donation in tables.made_between(from, to) {
+            if let Some(id) = donation.supporter_id {
+                by_supporter.entry(id).or_default().push(donation);
+            }
+        }
+        let mut top: Vec<_> = by_supporter
+            .into_iter()
+            .map(|(supporter_id, donations)| {
+                let income = income(donations);
+                SupporterIncome {
+                    supporter_id,
+                    name: tables.supporters[&supporter_id].name.clone(),
+                    donations: income.donations,
+                    income_eur: income.income_eur,
+                }
+            })
+            .collect();
+        top.sort_by_key(|supporter| {
+            (
+                std::cmp::Reverse(supporter.income_eur),
+                supporter.supporter_id,
+            )
+        });
+        top.truncate(limit.try_into().unwrap_or(usize::MAX));
+        ready(Ok(top)).boxed()
+    }
+}
+
+impl SupporterRepo for MemoryRepo {
+    fn list(&self) -> BoxFuture<'_, Result<Vec<Supporter>, SupporterError>> {
+        let mut supporters: Vec<_> = self.tables().supporters.values().cloned().collect();
+        supporters.sort_by(|a, b| a.name.cmp(&b.name).then(a.id.cmp(&b.id)));
+        ready(Ok(supporters)).boxed()
+    }
+
+    fn get(&self, id: u64) -> BoxFuture<'_, Result<Option<Supporter>, SupporterError>> {
+        ready(Ok(self.tables().supporters.get(&id).cloned())).boxed()
+    }
+
+    fn create(&self, supporter: NewSupporter) -> BoxFuture<'_, Result<u64, SupporterError>> {
+        let mut tables = self.tables();
+        tables.last_supporter_id += 1;
+        let id = tables.last_supporter_id;
+        tables.supporters.insert(
+            id,
+            Supporter {
+                id,
+                name: supporter.name,
+                email: supporter.email,
+                phone: supporter.phone,
+                notes: supporter.notes,
+                public: supporter.public,
+                created_at: now(),
+                version: 1,
+            },
+        );
+        ready(Ok(id)).boxed()
+    }
+
+    fn update(
+        &self,
+        id: u64,
+        version: u32,
+        changes: SupporterChanges,
+    ) -> BoxFuture<'_, Result<(), SupporterError>> {
+        let mut tables = self.tables();
+        let res = match tables.supporters.get_mut(&id) {
+            None => Err(SupporterError::NotFound),
+            Some(supporter) if supporter.version != version => {
+                Err(SupporterError::PreconditionFailed)
+            }
+            Some(_) if changes.is_empty() => Ok(()),
+            Some(supporter) => {
+                supporter.version += 1;
+                if let Some(name) = changes.name {
+                    supporter.name = name;
+                }
+                if let Some(email) = changes.email {
+                    supporter.email = email;
+                }
+                if let Some(phone) = changes.phone {
+                    supporter.phone = phone;
+                }
+                if let Some(notes) = changes.notes {
+                    supporter.notes = notes;
+                }
+                if let Some(public) = changes.public {
+                    supporter.public = public;
+                }
+                Ok(())
+            }
+        };
+        ready(res).boxed()
+    }
+
+    fn delete(&self, id: u64, version: u32) -> BoxFuture<'_, Result<(), SupporterError>> {
+        let mut tables = self.tables();
+        let res = match tables.supporters.get(&id) {
+            None => Err(SupporterError::NotFound),
+            Some(supporter) if supporter.version != version => {
+                Err(SupporterError::PreconditionFailed)
+            }
+            Some(_) => {
+                tables.supporters.remove(&id);
+                // like `ON DELETE SET NULL`
+                for donation in tables.donations.values_mut() {
+                    if donation.supporter_id == Some(id) {
+                        donation.supporter_id = None;
+                    }
+                }
+                Ok(())
+            }
+        };
+        ready(res).boxed()
+    }
+
+    fn totals(&self, id: u64) -> BoxFuture<'_, Result<SupporterTotals, SupporterError>> {
+        let tables = self.tables();
+        let donations: Vec<_> = tables
+            .donations
+            .values()
+            .filter(|donation| donation.supporter_id == Some(id))
+            .collect();
+        let first_donated_at = donations.iter().map(|d| d.donated_at).min();
+        let last_donated_at = donations.iter().map(|d| d.donated_at).max();
+        let income = income(donations);
+        ready(Ok(SupporterTotals {
+            donations: income.donations,
+            coins: income.coins,
+            income_eur: income.income_eur,
+            first_donated_at,
+            last_donated_at,
+        }))
+        .boxed()
+    }
+
+    fn public_totals(&self) -> BoxFuture<'_, Result<Vec<(String, Money)>, SupporterError>> {
+        let tables = self.tables();
+        let mut totals = BTreeMap::<_, i64>::new();
+        for donation in tables.donations.values() {
+            if let Some(supporter) = donation.supporter_id.map(|id| &tables.supporters[&id])
+                && supporter.public
+            {
+                *totals.entry((&supporter.name, supporter.id)).or_default() +=
+                    donation.income_eur.cents();
+            }
+        }
+        let res = totals
+            .into_iter()
+            .map(|((name, _), cents)| (name.clone(), Money::from_cents(cents)))
+            .collect();
+        ready(Ok(res)).boxed()
+    }
+}
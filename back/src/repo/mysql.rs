@@ -0,0 +1,712 @@
+// The repositories below share their queries with SQLite, written against
+// the dialect items of this module.
+#[path = "sql/campaigns.rs"]
+mod campaigns;
+#[path = "sql/exchange_rates.rs"]
+mod exchange_rates;
+#[path = "sql/idempotency.rs"]
+mod idempotency;
+#[path = "sql/jobs.rs"]
+mod jobs;
+#[path = "sql/merges.rs"]
+mod merges;
+#[path = "sql/payments.rs"]
+mod payments;
+#[path = "sql/schedules.rs"]
+mod schedules;
+
+use super::{
+    Account, AccountRepo, CoOpIncome, DONATION_FILTER_SQL, Donation, DonationChanges, DonationRepo,
+    ExportedDonation, Income, Interval, JobLock, NewDonation, NewSupporter, ParsedDonationFilter,
+    PeriodIncome, SessionRepo, Supporter, SupporterChanges, SupporterIncome, SupporterRepo,
+    SupporterTotals,
+};
+use crate::{
+    donations::DonationError,
+    exchange_rates::ExchangeRateError,
+    money::{Currency, Money, Rate},
+    supporters::SupporterError,
+};
+use async_stream::try_stream;
+use futures::{FutureExt, StreamExt, TryStreamExt, future::BoxFuture, stream::BoxStream};
+use sqlx::{
+    MySql, MySqlExecutor, MySqlPool, QueryBuilder,
+    mysql::{MySqlArguments, MySqlQueryResult},
+    pool::PoolConnection,
+    query::QueryAs,
+};
+use std::time::Duration;
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+
+const DONATION_COLUMNS: &str = "donations.id, donations.coins, donations.donated_at,
+    donations.income_eur_cents, donations.currency, donations.amount_cents, donations.co_op,
+    donations.supporter_id, donations.campaign_id, donations.version";
+
+/// Columns of an [`Income`] aggregated over the selected donations.
+const INCOME_COLUMNS: &str = "COUNT(*) AS donations,
+    CAST(COALESCE(SUM(coins), 0) AS UNSIGNED) AS coins,
+    CAST(COALESCE(SUM(income_eur_cents), 0) AS SIGNED) AS income_eur";
+
+const SUPPORTER_COLUMNS: &str = "id, name, email, phone, notes, public, created_at, version";
+
+type Repo = MySqlRepo;
+type Db = MySql;
+
+/// Locks the rows read for an update until the transaction ends.
+const FOR_UPDATE: &str = "FOR UPDATE";
+
+/// Inserts rows unless they would duplicate a unique key.
+const INSERT_IGNORE: &str = "INSERT IGNORE";
+
+/// Updates `column` of the row whose unique key an insert would duplicate.
+/// MySQL finds that row by any unique key, so `key` is not named.
+fn on_conflict_update(_key: &str, column: &str) -> String {
+    format!("ON DUPLICATE KEY UPDATE {column} = VALUES({column})")
+}
+
+/// Seconds from timestamp `from` to timestamp `to`.
+fn seconds_between(from: &str, to: &str) -> String {
+    format!("TIMESTAMPDIFF(SECOND, {from}, {to})")
+}
+
+/// MySQL columns are unsigned, so values are bound as they are.
+fn unsigned(v: u64) -> Result<u64, sqlx::Error> {
+    Ok(v)
+}
+
+/// MySQL binds timestamps natively.
+fn timestamp(t: OffsetDateTime) -> Result<OffsetDateTime, sqlx::Error> {
+    Ok(t)
+}
+
+/// Bound of a `>=` or `<` comparison with stored timestamps, which MySQL
+/// compares as they are.
+fn range_bound(t: OffsetDateTime) -> Result<OffsetDateTime, sqlx::Error> {
+    Ok(t)
+}
+
+/// Id of the row inserted by a query.
+fn inserted_id(result: MySqlQueryResult) -> u64 {
+    result.last_insert_id()
+}
+
+/// Returns the rate of `currency` in effect at `at`.
+async fn rate_at<'e>(
+    executor: impl MySqlExecutor<'e>,
+    currency: &Currency,
+    at: OffsetDateTime,
+) -> Result<Rate, ExchangeRateError> {
+    if currency.is_eur() {
+        return Ok(Rate::ONE);
+    }
+
+    sqlx::query_scalar(
+        "SELECT rate_micros FROM exchange_rates
+            WHERE currency = ? AND valid_from <= ?
+            ORDER BY valid_from DESC
+            LIMIT 1",
+    )
+    .bind(currency)
+    .bind(at)
+    .fetch_optional(executor)
+    .await?
+    .ok_or_else(|| {
+        ExchangeRateError::MissingRate(currency.clone(), at.format(&Rfc3339).unwrap_or_default())
+    })
+}
+
+impl ParsedDonationFilter {
+    /// Binds the parameters of [`DONATION_FILTER_SQL`].
+    pub fn bind<'q, O>(
+        &self,
+        query: QueryAs<'q, MySql, O, MySqlArguments>,
+    ) -> QueryAs<'q, MySql, O, MySqlArguments> {
+        query
+            .bind(self.from)
+            .bind(self.from)
+            .bind(self.to)
+            .bind(self.to)
+            .bind(self.co_op.clone())
+            .bind(self.co_op.clone())
+            .bind(self.currency.clone())
+            .bind(self.currency.clone())
+            .bind(self.supporter_id)
+            .bind(self.supporter_id)
+            .bind(self.campaign_id)
+            .bind(self.campaign_id)
+    }
+}
+
+/// A MySQL advisory lock, which belongs to a connection, so the same one has
+/// to release it.
+struct AdvisoryLock {
+    conn: PoolConnection<MySql>,
+    name: String,
+}
+
+impl JobLock for AdvisoryLock {
+    fn release(mut self: Box<Self>) -> BoxFuture<'static, Result<(), sqlx::Error>> {
+        async move {
+            sqlx::query("SELECT RELEASE_LOCK(?)")
+                .bind(&self.name)
+                .execute(&mut *self.conn)
+                .await?;
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+pub struct MySqlRepo {
+    pool: MySqlPool,
+}
+
+impl MySqlRepo {
+    pub fn new(pool: MySqlPool) -> Self {
+        Self { pool }
+    }
+
+    /// Whether row `id` of `table` exists, to tell why a write guarded by its
+    /// version matched no row.
+    async fn exists(&self, table: &str, id: u64) -> Result<bool, sqlx::Error> {
+        Ok(
+            sqlx::query(&format!("SELECT 1 FROM {table} WHERE id = ? LIMIT 1"))
+                .bind(id)
+                .fetch_optional(&self.pool)
+                .await?
+                .is_some(),
+        )
+    }
+
+    /// Takes a MySQL advisory lock, see [`JobRepo::lock`](super::JobRepo::lock).
+    fn lock_job<'a>(
+        &'a self,
+        job: &'a str,
+    ) -> BoxFuture<'a, Result<Option<Box<dyn JobLock>>, sqlx::Error>> {
+        async move {
+            let name = format!("job:{job}");
+            let mut conn = self.pool.acquire().await?;
+            let locked: Option<i64> = sqlx::query_scalar("SELECT GET_LOCK(?, 0)")
+                .bind(&name)
+                .fetch_one(&mut *conn)
+                .await?;
+            Ok((locked == Some(1))
+                .then(|| Box::new(AdvisoryLock { conn, name }) as Box<dyn JobLock>))
+        }
+        .boxed()
+    }
+}
+
+/// Maps foreign key violations on `supporter_id` and `campaign_id` to not found errors.
+fn unknown_reference(e: sqlx::Error) -> DonationError {
+    match &e {
+        sqlx::Error::Database(db) if db.is_foreign_key_violation() => {
+            if db.message().contains("donations_campaign_id") {
+                DonationError::CampaignNotFound
+            } else {
+                DonationError::SupporterNotFound
+            }
+        }
+        _ => DonationError::DatabaseError(e),
+    }
+}
+
+impl DonationRepo for MySqlRepo {
+    fn list<'a>(
+        &'a self,
+        filter: &'a ParsedDonationFilter,
+    ) -> BoxFuture<'a, Result<Vec<Donation>, DonationError>> {
+        async move {
+            let sql = format!(
+                "SELECT {DONATION_COLUMNS}
+                    FROM donations
+                    WHERE {DONATION_FILTER_SQL}
+                    ORDER BY donations.donated_at, donations.id"
+            );
+            Ok(filter
+                .bind(sqlx::query_as(&sql))
+                .fetch_all(&self.pool)
+                .await?)
+        }
+        .boxed()
+    }
+
+    fn get(&self, id: u64) -> BoxFuture<'_, Result<Option<Donation>, DonationError>> {
+        async move {
+            Ok(sqlx::query_as(&format!(
+                "SELECT {DONATION_COLUMNS} FROM donations WHERE id = ? LIMIT 1"
+            ))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?)
+        }
+        .boxed()
+    }
+
+    fn export(
+        &self,
+        filter: ParsedDonationFilter,
+    ) -> BoxStream<'static, Result<ExportedDonation, DonationError>> {
+        let pool = self.pool.clone();
+        try_stream! {
+            let sql = format!(
+                "SELECT donations.id, donations.donated_at, donations.coins, donations.co_op,
+                    donations.currency, donations.amount_cents, donations.income_eur_cents,
+                    donations.external_ref, supporters.name AS supporter
+                FROM donations
+                LEFT JOIN supporters ON supporters.id = donations.supporter_id
+                WHERE {DONATION_FILTER_SQL}
+                ORDER BY donations.donated_at, donations.id"
+            );
+            let mut rows = filter.bind(sqlx::query_as(&sql)).fetch(&pool);
+            while let Some(row) = rows.try_next().await? {
+                yield row;
+            }
+        }
+        .boxed()
+    }
+
+    fn rate_at<'a>(
+        &'a self,
+        currency: &'a Currency,
+        at: OffsetDateTime,
+    ) -> BoxFuture<'a, Result<Rate, ExchangeRateError>> {
+        rate_at(&self.pool, currency, at).boxed()
+    }
+
+    fn create(&self, donation: NewDonation) -> BoxFuture<'_, Result<u64, DonationError>> {
+        async move {
+            Ok(sqlx::query(
+                "INSERT INTO donations
+                    (coins, income_eur_cents, currency, amount_cents, co_op, supporter_id, campaign_id)
+                VALUES (?, ?, ?, ?, ?, ?, ?)",
+            )
+            .bind(donation.coins)
+            .bind(donation.income_eur)
+            .bind(donation.currency)
+            .bind(donation.amount)
+            .bind(donation.co_op)
+            .bind(donation.supporter_id)
+            .bind(donation.campaign_id)
+            .execute(&self.pool)
+            .await
+            .map_err(unknown_reference)?
+            .last_insert_id())
+        }
+        .boxed()
+    }
+
+    fn update(
+        &self,
+        id: u64,
+        version: u32,
+        changes: DonationChanges,
+    ) -> BoxFuture<'_, Result<(), DonationError>> {
+        async move {
+            // an empty change leaves the version alone, so it cannot fail concurrent edits
+            if changes.is_empty() {
+                return Ok(());
+            }
+            let mut update =
+                QueryBuilder::<MySql>::new("UPDATE donations SET version = version + 1");
+            if let Some(coins) = changes.coins {
+                update.push(", coins = ").push_bind(coins);
+            }
+            if let Some(amount) = changes.amount {
+                update.push(", amount_cents = ").push_bind(amount);
+            }
+            if let Some(currency) = changes.currency {
+                update.push(", currency = ").push_bind(currency);
+            }
+            if let Some(income_eur) = changes.income_eur {
+                update.push(", income_eur_cents = ").push_bind(income_eur);
+            }
+            if let Some(co_op) = changes.co_op {
+                update.push(", co_op = ").push_bind(co_op);
+            }
+            if let Some(supporter_id) = changes.supporter_id {
+                update.push(", supporter_id = ").push_bind(supporter_id);
+            }
+            if let Some(campaign_id) = changes.campaign_id {
+                update.push(", campaign_id = ").push_bind(campaign_id);
+            }
+            update
+                .push(" WHERE id = ")
+                .push_bind(id)
+                .push(" AND version = ")
+                .push_bind(version);
+
+            let res = update
+                .build()
+                .execute(&self.pool)
+                .await
+                .map_err(unknown_reference)?;
+            if res.rows_affected() > 0 {
+                Ok(())
+            } else if self.exists("donations", id).await? {
+                Err(DonationError::PreconditionFailed)
+            } else {
+                Err(DonationError::NotFound)
+            }
+        }
+        .boxed()
+    }
+
+    fn delete(&self, id: u64, version: u32) -> BoxFuture<'_, Result<(), DonationError>> {
+        async move {
+            let res = sqlx::query("DELETE FROM donations WHERE id = ? AND version = ?")
+                .bind(id)
+                .bind(version)
+                .execute(&self.pool)
+                .await?;
+            if res.rows_affected() > 0 {
+                Ok(())
+            } else if self.exists("donations", id).await? {
+                Err(DonationError::PreconditionFailed)
+            } else {
+                Err(DonationError::NotFound)
+            }
+        }
+        .boxed()
+    }
+
+    fn income(
+        &self,
+        from: OffsetDateTime,
+        to: OffsetDateTime,
+    ) -> BoxFuture<'_, Result<Income, DonationError>> {
+        async move {
+            Ok(sqlx::query_as(&format!(
+                "SELECT {INCOME_COLUMNS}
+                    FROM donations
+                    WHERE donated_at >= ? AND donated_at < ?"
+            ))
+            .bind(from)
+            .bind(to)
+            .fetch_one(&self.pool)
+            .await?)
+        }
+        .boxed()
+    }
+
+    fn income_by_co_op(
+        &self,
+        from: OffsetDateTime,
+        to: OffsetDateTime,
+    ) -> BoxFuture<'_, Result<Vec<CoOpIncome>, DonationError>> {
+        async move {
+            Ok(sqlx::query_as(&format!(
+                "SELECT co_op, {INCOME_COLUMNS}
+                    FROM donations
+                    WHERE donated_at >= ? AND donated_at < ?
+                    GROUP BY co_op
+                    ORDER BY co_op"
+            ))
+            .bind(from)
+            .bind(to)
+            .fetch_all(&self.pool)
+            .await?)
+        }
+        .boxed()
+    }
+
+    fn income_by_period(
+        &self,
+        from: OffsetDateTime,
+        to: OffsetDateTime,
+        interval: Interval,
+    ) -> BoxFuture<'_, Result<Vec<PeriodIncome>, DonationError>> {
+        let period_start = match interval {
+            Interval::Day => "DATE(donated_at)",
+            Interval::Week => "DATE_SUB(DATE(donated_at), INTERVAL WEEKDAY(donated_at) DAY)",
+            Interval::Month => "CAST(DATE_FORMAT(donated_at, '%Y-%m-01') AS DATE)",
+        };
+        async move {
+            Ok(sqlx::query_as(&format!(
+                "SELECT {period_start} AS period_start, {INCOME_COLUMNS}
+                    FROM donations
+                    WHERE donated_at >= ? AND donated_at < ?
+                    GROUP BY period_start
+                    ORDER BY period_start"
+            ))
+            .bind(from)
+            .bind(to)
+            .fetch_all(&self.pool)
+            .await?)
+        }
+        .boxed()
+    }
+
+    fn top_supporters(
+        &self,
+        from: OffsetDateTime,
+        to: OffsetDateTime,
+        limit: u32,
+    ) -> BoxFuture<'_, Result<Vec<SupporterIncome>, DonationError>> {
+        async move {
+            Ok(sqlx::query_as(
+                "SELECT supporters.id AS supporter_id, supporters.name,
+                        COUNT(*) AS donations,
+                        CAST(SUM(donations.income_eur_cents) AS SIGNED) AS income_eur
+                    FROM supporters
+                    JOIN donations ON donations.supporter_id = supporters.id
+                    WHERE donations.donated_at >= ? AND donations.donated_at < ?
+                    GROUP BY supporters.id, supporters.name
+                    ORDER BY income_eur DESC, supporters.id
+                    LIMIT ?",
+            )
+            .bind(from)
+            .bind(to)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?)
+        }
+        .boxed()
+    }
+}
+
+impl SupporterRepo for MySqlRepo {
+    fn list(&self) -> BoxFuture<'_, Result<Vec<Supporter>, SupporterError>> {
+        async move {
+            Ok(sqlx::query_as(&format!(
+                "SELECT {SUPPORTER_COLUMNS} FROM supporters ORDER BY name, id"
+            ))
+            .fetch_all(&self.pool)
+            .await?)
+        }
+        .boxed()
+    }
+
+    fn get(&self, id: u64) -> BoxFuture<'_, Result<Option<Supporter>, SupporterError>> {
+        async move {
+            Ok(sqlx::query_as(&format!(
+                "SELECT {SUPPORTER_COLUMNS} FROM supporters WHERE id = ? LIMIT 1"
+            ))
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?)
+        }
+        .boxed()
+    }
+
+    fn create(&self, supporter: NewSupporter) -> BoxFuture<'_, Result<u64, SupporterError>> {
+        async move {
+            Ok(sqlx::query(
+                "INSERT INTO supporters (name, email, phone, notes, public)
+                VALUES (?, ?, ?, ?, ?)",
+            )
+            .bind(supporter.name)
+            .bind(supporter.email)
+            .bind(supporter.phone)
+            .bind(supporter.notes)
+            .bind(supporter.public)
+            .execute(&self.pool)
+            .await?
+            .last_insert_id())
+        }
+        .boxed()
+    }
+
+    fn update(
+        &self,
+        id: u64,
+        version: u32,
+        changes: SupporterChanges,
+    ) -> BoxFuture<'_, Result<(), SupporterError>> {
+        async move {
+            // an empty change leaves the version alone, so it cannot fail concurrent edits
+            if changes.is_empty() {
+                return Ok(());
+            }
+            let mut update =
+                QueryBuilder::<MySql>::new("UPDATE supporters SET version = version + 1");
+            if let Some(name) = changes.name {
+                update.push(", name = ").push_bind(name);
+            }
+            if let Some(email) = changes.email {
+                update.push(", email = ").push_bind(email);
+            }
+            if let Some(phone) = changes.phone {
+                update.push(", phone = ").push_bind(phone);
+            }
+            if let Some(notes) = changes.notes {
+                update.push(", notes = ").push_bind(notes);
+            }
+            if let Some(public) = changes.public {
+                update.push(", public = ").push_bind(public);
+            }
+            update
+                .push(" WHERE id = ")
+                .push_bind(id)
+                .push(" AND version = ")
+                .push_bind(version);
+
+            let res = update.build().execute(&self.pool).await?;
+            if res.rows_affected() > 0 {
+                Ok(())
+            } else if self.exists("supporters", id).await? {
+                Err(SupporterError::PreconditionFailed)
+            } else {
+                Err(SupporterError::NotFound)
+            }
+        }
+        .boxed()
+    }
+
+    fn delete(&self, id: u64, version: u32) -> BoxFuture<'_, Result<(), SupporterError>> {
+        async move {
+            let res = sqlx::query("DELETE FROM supporters WHERE id = ? AND version = ?")
+                .bind(id)
+                .bind(version)
+                .execute(&self.pool)
+                .await
+                .map_err(|e| match e {
+                    // donation schedules restrict deleting their supporter
+                    sqlx::Error::Database(db) if db.is_foreign_key_violation() => {
+                        SupporterError::HasSchedules
+                    }
+                    e => e.into(),
+                })?;
+            if res.rows_affected() > 0 {
+                Ok(())
+            } else if self.exists("supporters", id).await? {
+                Err(SupporterError::PreconditionFailed)
+            } else {
+                Err(SupporterError::NotFound)
+            }
+        }
+        .boxed()
+    }
+
+    fn totals(&self, id: u64) -> BoxFuture<'_, Result<SupporterTotals, SupporterError>> {
+        async move {
+            Ok(sqlx::query_as(
+                "SELECT COUNT(*) AS donations,
+                        CAST(COALESCE(SUM(coins), 0) AS UNSIGNED) AS coins,
+                        CAST(COALESCE(SUM(income_eur_cents), 0) AS SIGNED) AS income_eur,
+                        MIN(donated_at) AS first_donated_at,
+                        MAX(donated_at) AS last_donated_at
+                    FROM donations
+                    WHERE supporter_id = ?",
+            )
+            .bind(id)
+            .fetch_one(&self.pool)
+            .await?)
+        }
+        .boxed()
+    }
+
+    fn public_totals(&self) -> BoxFuture<'_, Result<Vec<(String, Money)>, SupporterError>> {
+        async move {
+            Ok(sqlx::query_as(
+                "SELECT supporters.name, CAST(SUM(donations.income_eur_cents) AS SIGNED)
+                    FROM supporters
+                    JOIN donations ON donations.supporter_id = supporters.id
+                    WHERE supporters.public
+                    GROUP BY supporters.id, supporters.name
+                    ORDER BY supporters.name",
+            )
+            .fetch_all(&self.pool)
+            .await?)
+        }
+        .boxed()
+    }
+}
+
+impl AccountRepo for MySqlRepo {
+    fn create<'a>(
+        &'a self,
+        email: &'a str,
+        password: &'a str,
+    ) -> BoxFuture<'a, Result<Option<u64>, sqlx::Error>> {
+        async move {
+            match sqlx::query("INSERT INTO accounts (email, password) VALUES (?, ?)")
+                .bind(email)
+                .bind(password)
+                .execute(&self.pool)
+                .await
+            {
+                Ok(res) => Ok(Some(res.last_insert_id())),
+                Err(sqlx::Error::Database(e)) if e.is_unique_violation() => Ok(None),
+                Err(e) => Err(e),
+            }
+        }
+        .boxed()
+    }
+
+    fn get(&self, id: u64) -> BoxFuture<'_, Result<Option<Account>, sqlx::Error>> {
+        sqlx::query_as("SELECT id, email, password FROM accounts WHERE id = ? LIMIT 1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .boxed()
+    }
+
+    fn find_by_email<'a>(
+        &'a self,
+        email: &'a str,
+    ) -> BoxFuture<'a, Result<Option<Account>, sqlx::Error>> {
+        sqlx::query_as("SELECT id, email, password FROM accounts WHERE email = ? LIMIT 1")
+            .bind(email)
+            .fetch_optional(&self.pool)
+            .boxed()
+    }
+}
+
+impl SessionRepo for MySqlRepo {
+    fn create<'a>(
+        &'a self,
+        token: &'a str,
+        account_id: u64,
+        max_age: Duration,
+    ) -> BoxFuture<'a, Result<(), sqlx::Error>> {
+        async move {
+            sqlx::query("INSERT INTO sessions (token, account_id, expires_at) VALUES (?, ?, ?)")
+                .bind(token)
+                .bind(account_id)
+                .bind(OffsetDateTime::now_utc() + max_age)
+                .execute(&self.pool)
+                .await?;
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn account_id<'a>(&'a self, token: &'a str) -> BoxFuture<'a, Result<Option<u64>, sqlx::Error>> {
+        sqlx::query_scalar(
+            "SELECT account_id FROM sessions WHERE token = ? AND expires_at > ? LIMIT 1",
+        )
+        .bind(token)
+        .bind(OffsetDateTime::now_utc())
+        .fetch_optional(&self.pool)
+        .boxed()
+    }
+
+    fn delete<'a>(&'a self, token: &'a str) -> BoxFuture<'a, Result<(), sqlx::Error>> {
+        async move {
+            sqlx::query("DELETE FROM sessions WHERE token = ?")
+                .bind(token)
+                .execute(&self.pool)
+                .await?;
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn count_active(&self) -> BoxFuture<'_, Result<i64, sqlx::Error>> {
+        sqlx::query_scalar("SELECT COUNT(*) FROM sessions WHERE expires_at > ?")
+            .bind(OffsetDateTime::now_utc())
+            .fetch_one(&self.pool)
+            .boxed()
+    }
+
+    fn delete_expired(&self) -> BoxFuture<'_, Result<u64, sqlx::Error>> {
+        async move {
+            Ok(sqlx::query("DELETE FROM sessions WHERE expires_at < ?")
+                .bind(OffsetDateTime::now_utc())
+                .execute(&self.pool)
+                .await?
+                .rows_affected())
+        }
+        .boxed()
+    }
+}
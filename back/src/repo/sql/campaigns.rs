@@ -0,0 +1,140 @@
+//! Compiled into both the MySQL and the SQLite repository, against the dialect
+//! items of each.
+#![allow(clippy::duplicate_mod)]
+
+use super::{Repo, inserted_id, timestamp, unsigned};
+use crate::{
+    campaigns::CampaignError,
+    money::Money,
+    repo::{Campaign, CampaignFields, CampaignRepo, CampaignTotals},
+};
+use futures::{FutureExt, future::BoxFuture};
+use time::Date;
+
+const CAMPAIGN_COLUMNS: &str = "id, name, description, co_op, goal_eur_cents, starts_at, ends_at";
+
+impl CampaignRepo for Repo {
+    fn list(&self) -> BoxFuture<'_, Result<Vec<Campaign>, CampaignError>> {
+        async move {
+            Ok(sqlx::query_as(&format!(
+                "SELECT {CAMPAIGN_COLUMNS} FROM campaigns ORDER BY starts_at DESC, id DESC"
+            ))
+            .fetch_all(&self.pool)
+            .await?)
+        }
+        .boxed()
+    }
+
+    fn get(&self, id: u64) -> BoxFuture<'_, Result<Option<Campaign>, CampaignError>> {
+        async move {
+            Ok(sqlx::query_as(&format!(
+                "SELECT {CAMPAIGN_COLUMNS} FROM campaigns WHERE id = ? LIMIT 1"
+            ))
+            .bind(unsigned(id)?)
+            .fetch_optional(&self.pool)
+            .await?)
+        }
+        .boxed()
+    }
+
+    fn create(&self, campaign: CampaignFields) -> BoxFuture<'_, Result<u64, CampaignError>> {
+        async move {
+            Ok(inserted_id(sqlx::query(
+                "INSERT INTO campaigns (name, description, co_op, goal_eur_cents, starts_at, ends_at)
+                VALUES (?, ?, ?, ?, ?, ?)",
+            )
+            .bind(campaign.name)
+            .bind(campaign.description)
+            .bind(campaign.co_op)
+            .bind(campaign.goal_eur)
+            .bind(timestamp(campaign.starts_at)?)
+            .bind(campaign.ends_at.map(timestamp).transpose()?)
+            .execute(&self.pool)
+            .await?
+            ))
+        }
+        .boxed()
+    }
+
+    fn update(
+        &self,
+        id: u64,
+        campaign: CampaignFields,
+    ) -> BoxFuture<'_, Result<(), CampaignError>> {
+        async move {
+            let res = sqlx::query(
+                "UPDATE campaigns
+                    SET
+                        name = ?,
+                        description = ?,
+                        co_op = ?,
+                        goal_eur_cents = ?,
+                        starts_at = ?,
+                        ends_at = ?
+                WHERE id = ?",
+            )
+            .bind(campaign.name)
+            .bind(campaign.description)
+            .bind(campaign.co_op)
+            .bind(campaign.goal_eur)
+            .bind(timestamp(campaign.starts_at)?)
+            .bind(campaign.ends_at.map(timestamp).transpose()?)
+            .bind(unsigned(id)?)
+            .execute(&self.pool)
+            .await?;
+            if res.rows_affected() == 0 {
+                Err(CampaignError::NotFound)
+            } else {
+                Ok(())
+            }
+        }
+        .boxed()
+    }
+
+    fn delete(&self, id: u64) -> BoxFuture<'_, Result<(), CampaignError>> {
+        async move {
+            let res = sqlx::query("DELETE FROM campaigns WHERE id = ?")
+                .bind(unsigned(id)?)
+                .execute(&self.pool)
+                .await?;
+            if res.rows_affected() == 0 {
+                Err(CampaignError::NotFound)
+            } else {
+                Ok(())
+            }
+        }
+        .boxed()
+    }
+
+    fn totals(&self, id: u64) -> BoxFuture<'_, Result<CampaignTotals, CampaignError>> {
+        async move {
+            Ok(sqlx::query_as(
+                "SELECT COUNT(*) AS donations,
+                        CAST(COUNT(DISTINCT supporter_id) + COALESCE(SUM(supporter_id IS NULL), 0)
+                            AS SIGNED) AS donors,
+                        CAST(COALESCE(SUM(income_eur_cents), 0) AS SIGNED) AS raised_eur
+                    FROM donations
+                    WHERE campaign_id = ?",
+            )
+            .bind(unsigned(id)?)
+            .fetch_one(&self.pool)
+            .await?)
+        }
+        .boxed()
+    }
+
+    fn income_by_day(&self, id: u64) -> BoxFuture<'_, Result<Vec<(Date, Money)>, CampaignError>> {
+        async move {
+            Ok(sqlx::query_as(
+                "SELECT DATE(donated_at) AS day, CAST(SUM(income_eur_cents) AS SIGNED)
+                    FROM donations
+                    WHERE campaign_id = ?
+                    GROUP BY day",
+            )
+            .bind(unsigned(id)?)
+            .fetch_all(&self.pool)
+            .await?)
+        }
+        .boxed()
+    }
+}
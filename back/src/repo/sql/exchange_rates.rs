@@ -0,0 +1,66 @@
+//! Compiled into both the MySQL and the SQLite repository, against the dialect
+//! items of each.
+#![allow(clippy::duplicate_mod)]
+
+use super::{Repo, on_conflict_update, timestamp};
+use crate::{
+    exchange_rates::ExchangeRateError,
+    money::Currency,
+    repo::{ExchangeRate, ExchangeRateRepo},
+};
+use futures::{FutureExt, future::BoxFuture};
+
+impl ExchangeRateRepo for Repo {
+    fn list<'a>(
+        &'a self,
+        currency: Option<&'a Currency>,
+    ) -> BoxFuture<'a, Result<Vec<ExchangeRate>, ExchangeRateError>> {
+        async move {
+            Ok(sqlx::query_as(
+                "SELECT currency, valid_from, rate_micros FROM exchange_rates
+                    WHERE ? IS NULL OR currency = ?
+                    ORDER BY currency, valid_from",
+            )
+            .bind(currency)
+            .bind(currency)
+            .fetch_all(&self.pool)
+            .await?)
+        }
+        .boxed()
+    }
+
+    fn currencies(&self) -> BoxFuture<'_, Result<Vec<Currency>, ExchangeRateError>> {
+        async move {
+            Ok(
+                sqlx::query_scalar(
+                    "SELECT DISTINCT currency FROM exchange_rates ORDER BY currency",
+                )
+                .fetch_all(&self.pool)
+                .await?,
+            )
+        }
+        .boxed()
+    }
+
+    fn upsert(&self, rates: Vec<ExchangeRate>) -> BoxFuture<'_, Result<(), ExchangeRateError>> {
+        async move {
+            let mut tx = self.pool.begin().await?;
+            for rate in rates {
+                sqlx::query(&format!(
+                    "INSERT INTO exchange_rates (currency, valid_from, rate_micros)
+                    VALUES (?, ?, ?)
+                    {}",
+                    on_conflict_update("currency, valid_from", "rate_micros")
+                ))
+                .bind(rate.currency)
+                .bind(timestamp(rate.valid_from)?)
+                .bind(rate.rate)
+                .execute(&mut *tx)
+                .await?;
+            }
+            tx.commit().await?;
+            Ok(())
+        }
+        .boxed()
+    }
+}
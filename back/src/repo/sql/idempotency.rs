@@ -0,0 +1,139 @@
+//! Compiled into both the MySQL and the SQLite repository, against the dialect
+//! items of each.
+#![allow(clippy::duplicate_mod)]
+
+use super::{Repo, timestamp, unsigned};
+use crate::repo::{IdempotencyRepo, StoredKey};
+use futures::{FutureExt, future::BoxFuture};
+use std::time::Duration;
+use time::OffsetDateTime;
+
+impl IdempotencyRepo for Repo {
+    fn claim<'a>(
+        &'a self,
+        account_id: u64,
+        key: &'a str,
+        request_hash: &'a str,
+    ) -> BoxFuture<'a, Result<bool, sqlx::Error>> {
+        async move {
+            let res = sqlx::query(
+                "INSERT INTO idempotency_keys (account_id, idempotency_key, request_hash)
+                VALUES (?, ?, ?)",
+            )
+            .bind(unsigned(account_id)?)
+            .bind(key)
+            .bind(request_hash)
+            .execute(&self.pool)
+            .await;
+            match res {
+                Ok(_) => Ok(true),
+                Err(sqlx::Error::Database(e)) if e.is_unique_violation() => Ok(false),
+                Err(e) => Err(e),
+            }
+        }
+        .boxed()
+    }
+
+    fn get<'a>(
+        &'a self,
+        account_id: u64,
+        key: &'a str,
+    ) -> BoxFuture<'a, Result<Option<StoredKey>, sqlx::Error>> {
+        async move {
+            sqlx::query_as(
+                "SELECT request_hash, status_code, content_type, response_body
+                    FROM idempotency_keys
+                    WHERE account_id = ? AND idempotency_key = ?
+                    LIMIT 1",
+            )
+            .bind(unsigned(account_id)?)
+            .bind(key)
+            .fetch_optional(&self.pool)
+            .await
+        }
+        .boxed()
+    }
+
+    fn take_over<'a>(
+        &'a self,
+        account_id: u64,
+        key: &'a str,
+        pending: Duration,
+    ) -> BoxFuture<'a, Result<bool, sqlx::Error>> {
+        async move {
+            let now = OffsetDateTime::now_utc();
+            let res = sqlx::query(
+                "UPDATE idempotency_keys
+                    SET created_at = ?
+                WHERE account_id = ? AND idempotency_key = ? AND status_code IS NULL
+                    AND created_at < ?",
+            )
+            .bind(timestamp(now)?)
+            .bind(unsigned(account_id)?)
+            .bind(key)
+            .bind(timestamp(now - pending)?)
+            .execute(&self.pool)
+            .await?;
+            Ok(res.rows_affected() > 0)
+        }
+        .boxed()
+    }
+
+    fn store<'a>(
+        &'a self,
+        account_id: u64,
+        key: &'a str,
+        status_code: u16,
+        content_type: Option<&'a str>,
+        body: &'a [u8],
+    ) -> BoxFuture<'a, Result<(), sqlx::Error>> {
+        async move {
+            sqlx::query(
+                "UPDATE idempotency_keys
+                    SET status_code = ?, content_type = ?, response_body = ?
+                WHERE account_id = ? AND idempotency_key = ?",
+            )
+            .bind(status_code)
+            .bind(content_type)
+            .bind(body)
+            .bind(unsigned(account_id)?)
+            .bind(key)
+            .execute(&self.pool)
+            .await?;
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn release<'a>(
+        &'a self,
+        account_id: u64,
+        key: &'a str,
+    ) -> BoxFuture<'a, Result<(), sqlx::Error>> {
+        async move {
+            sqlx::query(
+                "DELETE FROM idempotency_keys
+                WHERE account_id = ? AND idempotency_key = ? AND status_code IS NULL",
+            )
+            .bind(unsigned(account_id)?)
+            .bind(key)
+            .execute(&self.pool)
+            .await?;
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn delete_expired(&self, retention: Duration) -> BoxFuture<'_, Result<u64, sqlx::Error>> {
+        async move {
+            Ok(
+                sqlx::query("DELETE FROM idempotency_keys WHERE created_at < ?")
+                    .bind(timestamp(OffsetDateTime::now_utc() - retention)?)
+                    .execute(&self.pool)
+                    .await?
+                    .rows_affected(),
+            )
+        }
+        .boxed()
+    }
+}
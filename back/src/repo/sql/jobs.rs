@@ -0,0 +1,127 @@
+//! Compiled into both the MySQL and the SQLite repository, against the dialect
+//! items of each.
+#![allow(clippy::duplicate_mod)]
+
+use super::{Repo, inserted_id, range_bound, timestamp, unsigned};
+use crate::{
+    jobs::RunStatus,
+    repo::{JobLock, JobRepo, JobRun},
+};
+use futures::{FutureExt, future::BoxFuture};
+use std::time::Duration;
+use time::OffsetDateTime;
+
+const RUN_COLUMNS: &str =
+    "id, job, scheduled_at, started_at, finished_at, status, detail, instance";
+
+impl JobRepo for Repo {
+    fn lock<'a>(
+        &'a self,
+        job: &'a str,
+    ) -> BoxFuture<'a, Result<Option<Box<dyn JobLock>>, sqlx::Error>> {
+        self.lock_job(job)
+    }
+
+    fn ran<'a>(
+        &'a self,
+        job: &'a str,
+        slot: OffsetDateTime,
+    ) -> BoxFuture<'a, Result<bool, sqlx::Error>> {
+        async move {
+            sqlx::query_scalar(
+                "SELECT EXISTS (SELECT 1 FROM job_runs WHERE job = ? AND scheduled_at >= ?)",
+            )
+            .bind(job)
+            .bind(range_bound(slot)?)
+            .fetch_one(&self.pool)
+            .await
+        }
+        .boxed()
+    }
+
+    fn start<'a>(
+        &'a self,
+        job: &'a str,
+        slot: OffsetDateTime,
+        instance: &'a str,
+    ) -> BoxFuture<'a, Result<u64, sqlx::Error>> {
+        async move {
+            Ok(inserted_id(
+                sqlx::query(
+                    "INSERT INTO job_runs (job, scheduled_at, started_at, status, instance)
+                    VALUES (?, ?, ?, ?, ?)",
+                )
+                .bind(job)
+                .bind(timestamp(slot)?)
+                .bind(timestamp(OffsetDateTime::now_utc())?)
+                .bind(RunStatus::Running.as_str())
+                .bind(instance)
+                .execute(&self.pool)
+                .await?,
+            ))
+        }
+        .boxed()
+    }
+
+    fn finish<'a>(
+        &'a self,
+        id: u64,
+        status: RunStatus,
+        detail: &'a str,
+    ) -> BoxFuture<'a, Result<(), sqlx::Error>> {
+        async move {
+            sqlx::query("UPDATE job_runs SET status = ?, detail = ?, finished_at = ? WHERE id = ?")
+                .bind(status.as_str())
+                .bind(detail)
+                .bind(timestamp(OffsetDateTime::now_utc())?)
+                .bind(unsigned(id)?)
+                .execute(&self.pool)
+                .await?;
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn last_runs(&self) -> BoxFuture<'_, Result<Vec<JobRun>, sqlx::Error>> {
+        async move {
+            sqlx::query_as(&format!(
+                "SELECT {RUN_COLUMNS} FROM job_runs
+                    WHERE id IN (SELECT MAX(id) FROM job_runs GROUP BY job)"
+            ))
+            .fetch_all(&self.pool)
+            .await
+        }
+        .boxed()
+    }
+
+    fn runs<'a>(
+        &'a self,
+        job: &'a str,
+        limit: u32,
+    ) -> BoxFuture<'a, Result<Vec<JobRun>, sqlx::Error>> {
+        async move {
+            sqlx::query_as(&format!(
+                "SELECT {RUN_COLUMNS} FROM job_runs
+                    WHERE job = ?
+                    ORDER BY id DESC
+                    LIMIT ?"
+            ))
+            .bind(job)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await
+        }
+        .boxed()
+    }
+
+    fn delete_older_than(&self, retention: Duration) -> BoxFuture<'_, Result<u64, sqlx::Error>> {
+        async move {
+            Ok(sqlx::query("DELETE FROM job_runs WHERE started_at < ?")
+                .bind(timestamp(OffsetDateTime::now_utc() - retention)?)
+                .execute(&self.pool)
+                .await?
+                .rows_affected())
+        }
+        .boxed()
+    }
+}
@@ -0,0 +1,141 @@
+//! Compiled into both the MySQL and the SQLite repository, against the dialect
+//! items of each.
+#![allow(clippy::duplicate_mod)]
+
+use super::{Db, FOR_UPDATE, Repo, unsigned};
+use crate::{
+    repo::{Contact, MergeCandidate, MergeRepo, SupporterMerge},
+    supporters::SupporterError,
+};
+use futures::{FutureExt, future::BoxFuture};
+use sqlx::QueryBuilder;
+
+impl MergeRepo for Repo {
+    fn candidates(&self) -> BoxFuture<'_, Result<Vec<MergeCandidate>, SupporterError>> {
+        async move {
+            Ok(
+                sqlx::query_as("SELECT id, name, email FROM supporters ORDER BY id")
+                    .fetch_all(&self.pool)
+                    .await?,
+            )
+        }
+        .boxed()
+    }
+
+    fn merge<'a>(
+        &'a self,
+        canonical_id: u64,
+        duplicate_ids: &'a [u64],
+        account_id: u64,
+        combine: fn(Contact, Contact) -> Contact,
+    ) -> BoxFuture<'a, Result<u64, SupporterError>> {
+        async move {
+            let mut tx = self.pool.begin().await?;
+
+            let mut contact: Contact = sqlx::query_as(
+                &format!("SELECT email, phone, notes FROM supporters WHERE id = ? {FOR_UPDATE}"),
+            )
+            .bind(unsigned(canonical_id)?)
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or(SupporterError::NotFound)?;
+
+            let mut donations_moved = 0;
+            for &duplicate_id in duplicate_ids {
+                let (name, email, phone, notes): (
+                    String,
+                    Option<String>,
+                    Option<String>,
+                    Option<String>,
+                ) = sqlx::query_as(
+                    &format!("SELECT name, email, phone, notes FROM supporters WHERE id = ? {FOR_UPDATE}"),
+                )
+                .bind(unsigned(duplicate_id)?)
+                .fetch_optional(&mut *tx)
+                .await?
+                .ok_or(SupporterError::NotFound)?;
+
+                let donation_ids: Vec<u64> = sqlx::query_scalar(
+                    "SELECT id FROM donations WHERE supporter_id = ? ORDER BY id",
+                )
+                .bind(unsigned(duplicate_id)?)
+                .fetch_all(&mut *tx)
+                .await?;
+                sqlx::query(
+                    "UPDATE donations SET supporter_id = ?, version = version + 1
+                    WHERE supporter_id = ?",
+                )
+                .bind(unsigned(canonical_id)?)
+                .bind(unsigned(duplicate_id)?)
+                .execute(&mut *tx)
+                .await?;
+                donations_moved += donation_ids.len() as u64;
+                sqlx::query("UPDATE donation_schedules SET supporter_id = ? WHERE supporter_id = ?")
+                    .bind(unsigned(canonical_id)?)
+                    .bind(unsigned(duplicate_id)?)
+                    .execute(&mut *tx)
+                    .await?;
+
+                sqlx::query(
+                    "INSERT INTO supporter_merge_log
+                        (canonical_id, merged_id, merged_name, merged_email, donation_ids, account_id)
+                    VALUES (?, ?, ?, ?, ?, ?)",
+                )
+                .bind(unsigned(canonical_id)?)
+                .bind(unsigned(duplicate_id)?)
+                .bind(name)
+                .bind(&email)
+                .bind(serde_json::to_string(&donation_ids).expect("serializable ids"))
+                .bind(unsigned(account_id)?)
+                .execute(&mut *tx)
+                .await?;
+
+                contact = combine(
+                    contact,
+                    Contact {
+                        email,
+                        phone,
+                        notes,
+                    },
+                );
+            }
+
+            sqlx::query(
+                "UPDATE supporters SET email = ?, phone = ?, notes = ?, version = version + 1
+                WHERE id = ?",
+            )
+            .bind(contact.email)
+            .bind(contact.phone)
+            .bind(contact.notes)
+            .bind(unsigned(canonical_id)?)
+            .execute(&mut *tx)
+            .await?;
+
+            let mut delete = QueryBuilder::<Db>::new("DELETE FROM supporters WHERE id IN (");
+            let mut ids = delete.separated(", ");
+            for id in duplicate_ids {
+                ids.push_bind(unsigned(*id)?);
+            }
+            delete.push(")");
+            delete.build().execute(&mut *tx).await?;
+
+            tx.commit().await?;
+            Ok(donations_moved)
+        }
+        .boxed()
+    }
+
+    fn merges(&self) -> BoxFuture<'_, Result<Vec<SupporterMerge>, SupporterError>> {
+        async move {
+            Ok(sqlx::query_as(
+                "SELECT id, canonical_id, merged_id, merged_name, merged_email, donation_ids,
+                        account_id, merged_at
+                    FROM supporter_merge_log
+                    ORDER BY merged_at DESC, id DESC",
+            )
+            .fetch_all(&self.pool)
+            .await?)
+        }
+        .boxed()
+    }
+}
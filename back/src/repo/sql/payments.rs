@@ -0,0 +1,254 @@
+//! Compiled into both the MySQL and the SQLite repository, against the dialect
+//! items of each.
+#![allow(clippy::duplicate_mod)]
+
+use super::{Db, Repo, inserted_id, rate_at, timestamp, unsigned};
+use crate::{
+    exchange_rates::ExchangeRateError,
+    money::{Currency, Rate},
+    repo::{NewSupporter, NewWebhookEvent, PaidDonation, PaymentRepo, PaymentTx, WebhookEvent},
+    webhooks::EventStatus,
+};
+use futures::{FutureExt, future::BoxFuture};
+use sqlx::Transaction;
+use time::OffsetDateTime;
+
+const EVENT_COLUMNS: &str = "id, provider, event_id, event_type, payload, status, error,
+    donation_id, received_at, processed_at";
+
+impl PaymentRepo for Repo {
+    fn begin(&self) -> BoxFuture<'_, Result<Box<dyn PaymentTx>, sqlx::Error>> {
+        async move { Ok(Box::new(self.pool.begin().await?) as Box<dyn PaymentTx>) }.boxed()
+    }
+
+    fn events<'a>(
+        &'a self,
+        provider: Option<&'a str>,
+        status: Option<EventStatus>,
+        limit: u32,
+    ) -> BoxFuture<'a, Result<Vec<WebhookEvent>, sqlx::Error>> {
+        async move {
+            let status = status.map(EventStatus::as_str);
+            sqlx::query_as(&format!(
+                "SELECT {EVENT_COLUMNS} FROM webhook_events
+                    WHERE (? IS NULL OR provider = ?)
+                    AND (? IS NULL OR status = ?)
+                    ORDER BY received_at DESC, id DESC
+                    LIMIT ?"
+            ))
+            .bind(provider)
+            .bind(provider)
+            .bind(status)
+            .bind(status)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await
+        }
+        .boxed()
+    }
+
+    fn event(&self, id: u64) -> BoxFuture<'_, Result<Option<WebhookEvent>, sqlx::Error>> {
+        async move {
+            sqlx::query_as(&format!(
+                "SELECT {EVENT_COLUMNS} FROM webhook_events WHERE id = ? LIMIT 1"
+            ))
+            .bind(unsigned(id)?)
+            .fetch_optional(&self.pool)
+            .await
+        }
+        .boxed()
+    }
+
+    fn event_by_provider_id<'a>(
+        &'a self,
+        provider: &'a str,
+        event_id: &'a str,
+    ) -> BoxFuture<'a, Result<Option<WebhookEvent>, sqlx::Error>> {
+        async move {
+            sqlx::query_as(&format!(
+                "SELECT {EVENT_COLUMNS} FROM webhook_events
+                    WHERE provider = ? AND event_id = ? LIMIT 1"
+            ))
+            .bind(provider)
+            .bind(event_id)
+            .fetch_optional(&self.pool)
+            .await
+        }
+        .boxed()
+    }
+}
+
+impl PaymentTx for Transaction<'static, Db> {
+    fn external_ref_exists<'a>(
+        &'a mut self,
+        external_ref: &'a str,
+    ) -> BoxFuture<'a, Result<bool, sqlx::Error>> {
+        async move {
+            Ok(
+                sqlx::query("SELECT 1 FROM donations WHERE external_ref = ? LIMIT 1")
+                    .bind(external_ref)
+                    .fetch_optional(&mut **self)
+                    .await?
+                    .is_some(),
+            )
+        }
+        .boxed()
+    }
+
+    fn rate_at<'a>(
+        &'a mut self,
+        currency: &'a Currency,
+        at: OffsetDateTime,
+    ) -> BoxFuture<'a, Result<Rate, ExchangeRateError>> {
+        rate_at(&mut **self, currency, at).boxed()
+    }
+
+    fn supporter_by_email<'a>(
+        &'a mut self,
+        email: &'a str,
+    ) -> BoxFuture<'a, Result<Option<u64>, sqlx::Error>> {
+        sqlx::query_scalar("SELECT id FROM supporters WHERE email = ? ORDER BY id LIMIT 1")
+            .bind(email)
+            .fetch_optional(&mut **self)
+            .boxed()
+    }
+
+    fn supporter_by_name<'a>(
+        &'a mut self,
+        name: &'a str,
+    ) -> BoxFuture<'a, Result<Option<u64>, sqlx::Error>> {
+        sqlx::query_scalar("SELECT id FROM supporters WHERE name = ? ORDER BY id LIMIT 1")
+            .bind(name)
+            .fetch_optional(&mut **self)
+            .boxed()
+    }
+
+    fn create_supporter(
+        &mut self,
+        supporter: NewSupporter,
+    ) -> BoxFuture<'_, Result<u64, sqlx::Error>> {
+        async move {
+            Ok(inserted_id(
+                sqlx::query(
+                    "INSERT INTO supporters (name, email, phone, notes, public)
+                VALUES (?, ?, ?, ?, ?)",
+                )
+                .bind(supporter.name)
+                .bind(supporter.email)
+                .bind(supporter.phone)
+                .bind(supporter.notes)
+                .bind(supporter.public)
+                .execute(&mut **self)
+                .await?,
+            ))
+        }
+        .boxed()
+    }
+
+    fn create_donation(
+        &mut self,
+        donation: PaidDonation,
+    ) -> BoxFuture<'_, Result<u64, sqlx::Error>> {
+        async move {
+            Ok(inserted_id(
+                sqlx::query(
+                    "INSERT INTO donations
+                    (coins, donated_at, income_eur_cents, currency, amount_cents, co_op,
+                        external_ref, supporter_id)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+                )
+                .bind(unsigned(donation.coins)?)
+                .bind(timestamp(donation.donated_at)?)
+                .bind(donation.income_eur)
+                .bind(donation.currency)
+                .bind(donation.amount)
+                .bind(donation.co_op)
+                .bind(donation.external_ref)
+                .bind(donation.supporter_id.map(unsigned).transpose()?)
+                .execute(&mut **self)
+                .await?,
+            ))
+        }
+        .boxed()
+    }
+
+    fn create_event<'a>(
+        &'a mut self,
+        event: NewWebhookEvent<'a>,
+    ) -> BoxFuture<'a, Result<u64, sqlx::Error>> {
+        async move {
+            Ok(inserted_id(
+                sqlx::query(
+                    "INSERT INTO webhook_events
+                    (provider, event_id, event_type, payload, status, error, donation_id,
+                        processed_at)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+                )
+                .bind(event.provider)
+                .bind(event.event_id)
+                .bind(event.event_type)
+                .bind(event.payload)
+                .bind(event.status.as_str())
+                .bind(event.error)
+                .bind(event.donation_id.map(unsigned).transpose()?)
+                .bind(timestamp(OffsetDateTime::now_utc())?)
+                .execute(&mut **self)
+                .await?,
+            ))
+        }
+        .boxed()
+    }
+
+    fn update_event<'a>(
+        &'a mut self,
+        id: u64,
+        status: EventStatus,
+        error: Option<&'a str>,
+        donation_id: Option<u64>,
+    ) -> BoxFuture<'a, Result<(), sqlx::Error>> {
+        async move {
+            sqlx::query(
+                "UPDATE webhook_events
+                    SET status = ?, error = ?, donation_id = ?, processed_at = ?
+                WHERE id = ?",
+            )
+            .bind(status.as_str())
+            .bind(error)
+            .bind(donation_id.map(unsigned).transpose()?)
+            .bind(timestamp(OffsetDateTime::now_utc())?)
+            .bind(unsigned(id)?)
+            .execute(&mut **self)
+            .await?;
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn savepoint(&mut self) -> BoxFuture<'_, Result<(), sqlx::Error>> {
+        async move {
+            sqlx::query("SAVEPOINT payment")
+                .execute(&mut **self)
+                .await?;
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn rollback_to_savepoint(&mut self) -> BoxFuture<'_, Result<(), sqlx::Error>> {
+        async move {
+            sqlx::query("ROLLBACK TO SAVEPOINT payment")
+                .execute(&mut **self)
+                .await?;
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn commit(self: Box<Self>) -> BoxFuture<'static, Result<(), sqlx::Error>> {
+        (*self).commit().boxed()
+    }
+
+    fn rollback(self: Box<Self>) -> BoxFuture<'static, Result<(), sqlx::Error>> {
+        (*self).rollback().boxed()
+    }
+}
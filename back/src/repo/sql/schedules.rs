@@ -0,0 +1,236 @@
+//! Compiled into both the MySQL and the SQLite repository, against the dialect
+//! items of each.
+#![allow(clippy::duplicate_mod)]
+
+use super::{INSERT_IGNORE, Repo, inserted_id, range_bound, seconds_between, timestamp, unsigned};
+use crate::{
+    repo::{MissedDonation, Schedule, ScheduleFields, ScheduleRepo},
+    schedules::ScheduleError,
+};
+use futures::{FutureExt, future::BoxFuture};
+use time::{Date, OffsetDateTime};
+
+const SCHEDULE_COLUMNS: &str = "donation_schedules.id, donation_schedules.supporter_id,
+    supporters.name AS supporter_name, donation_schedules.co_op, donation_schedules.currency,
+    donation_schedules.amount_cents, donation_schedules.interval_unit,
+    donation_schedules.interval_count, donation_schedules.starts_on, donation_schedules.ends_on,
+    donation_schedules.grace_days";
+
+/// Maps foreign key violations on `supporter_id` to [`ScheduleError::SupporterNotFound`].
+fn supporter_fk(e: sqlx::Error) -> ScheduleError {
+    match &e {
+        sqlx::Error::Database(db) if db.is_foreign_key_violation() => {
+            ScheduleError::SupporterNotFound
+        }
+        _ => ScheduleError::DatabaseError(e),
+    }
+}
+
+impl ScheduleRepo for Repo {
+    fn list(&self, active_on: Option<Date>) -> BoxFuture<'_, Result<Vec<Schedule>, sqlx::Error>> {
+        async move {
+            sqlx::query_as(&format!(
+                "SELECT {SCHEDULE_COLUMNS}
+                    FROM donation_schedules
+                    JOIN supporters ON supporters.id = donation_schedules.supporter_id
+                    WHERE ? IS NULL
+                        OR donation_schedules.ends_on IS NULL
+                        OR donation_schedules.ends_on >= ?
+                    ORDER BY donation_schedules.id"
+            ))
+            .bind(active_on)
+            .bind(active_on)
+            .fetch_all(&self.pool)
+            .await
+        }
+        .boxed()
+    }
+
+    fn get(&self, id: u64) -> BoxFuture<'_, Result<Option<Schedule>, sqlx::Error>> {
+        async move {
+            sqlx::query_as(&format!(
+                "SELECT {SCHEDULE_COLUMNS}
+                    FROM donation_schedules
+                    JOIN supporters ON supporters.id = donation_schedules.supporter_id
+                    WHERE donation_schedules.id = ?
+                    LIMIT 1"
+            ))
+            .bind(unsigned(id)?)
+            .fetch_optional(&self.pool)
+            .await
+        }
+        .boxed()
+    }
+
+    fn create(&self, schedule: ScheduleFields) -> BoxFuture<'_, Result<u64, ScheduleError>> {
+        async move {
+            Ok(inserted_id(
+                sqlx::query(
+                    "INSERT INTO donation_schedules
+                    (supporter_id, co_op, currency, amount_cents, interval_unit, interval_count,
+                        starts_on, ends_on, grace_days)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                )
+                .bind(unsigned(schedule.supporter_id)?)
+                .bind(schedule.co_op)
+                .bind(schedule.currency)
+                .bind(schedule.amount)
+                .bind(schedule.interval_unit.as_str())
+                .bind(schedule.interval_count)
+                .bind(schedule.starts_on)
+                .bind(schedule.ends_on)
+                .bind(schedule.grace_days)
+                .execute(&self.pool)
+                .await
+                .map_err(supporter_fk)?,
+            ))
+        }
+        .boxed()
+    }
+
+    fn update(
+        &self,
+        id: u64,
+        schedule: ScheduleFields,
+    ) -> BoxFuture<'_, Result<(), ScheduleError>> {
+        async move {
+            let res = sqlx::query(
+                "UPDATE donation_schedules
+                    SET
+                        supporter_id = ?,
+                        co_op = ?,
+                        currency = ?,
+                        amount_cents = ?,
+                        interval_unit = ?,
+                        interval_count = ?,
+                        starts_on = ?,
+                        ends_on = ?,
+                        grace_days = ?
+                WHERE id = ?",
+            )
+            .bind(unsigned(schedule.supporter_id)?)
+            .bind(schedule.co_op)
+            .bind(schedule.currency)
+            .bind(schedule.amount)
+            .bind(schedule.interval_unit.as_str())
+            .bind(schedule.interval_count)
+            .bind(schedule.starts_on)
+            .bind(schedule.ends_on)
+            .bind(schedule.grace_days)
+            .bind(unsigned(id)?)
+            .execute(&self.pool)
+            .await
+            .map_err(supporter_fk)?;
+            if res.rows_affected() == 0 {
+                Err(ScheduleError::NotFound)
+            } else {
+                Ok(())
+            }
+        }
+        .boxed()
+    }
+
+    fn delete(&self, id: u64) -> BoxFuture<'_, Result<(), ScheduleError>> {
+        async move {
+            let res = sqlx::query("DELETE FROM donation_schedules WHERE id = ?")
+                .bind(unsigned(id)?)
+                .execute(&self.pool)
+                .await?;
+            if res.rows_affected() == 0 {
+                Err(ScheduleError::NotFound)
+            } else {
+                Ok(())
+            }
+        }
+        .boxed()
+    }
+
+    fn missed(&self) -> BoxFuture<'_, Result<Vec<MissedDonation>, sqlx::Error>> {
+        async move {
+            sqlx::query_as(
+                "SELECT donation_schedules.id AS schedule_id, donation_schedules.supporter_id,
+                        supporters.name AS supporter_name, donation_schedules.co_op,
+                        donation_schedules.currency, donation_schedules.amount_cents,
+                        schedule_occurrences.due_on
+                    FROM schedule_occurrences
+                    JOIN donation_schedules
+                        ON donation_schedules.id = schedule_occurrences.schedule_id
+                    JOIN supporters ON supporters.id = donation_schedules.supporter_id
+                    WHERE schedule_occurrences.status = 'missed'
+                    ORDER BY schedule_occurrences.due_on DESC, donation_schedules.id",
+            )
+            .fetch_all(&self.pool)
+            .await
+        }
+        .boxed()
+    }
+
+    fn checked_due_dates(&self, schedule_id: u64) -> BoxFuture<'_, Result<Vec<Date>, sqlx::Error>> {
+        async move {
+            sqlx::query_scalar("SELECT due_on FROM schedule_occurrences WHERE schedule_id = ?")
+                .bind(unsigned(schedule_id)?)
+                .fetch_all(&self.pool)
+                .await
+        }
+        .boxed()
+    }
+
+    fn unmatched_donation<'a>(
+        &'a self,
+        schedule: &'a Schedule,
+        from: OffsetDateTime,
+        until: OffsetDateTime,
+        due_at: OffsetDateTime,
+    ) -> BoxFuture<'a, Result<Option<u64>, sqlx::Error>> {
+        async move {
+            sqlx::query_scalar(&format!(
+                "SELECT id
+                    FROM donations
+                    WHERE supporter_id = ?
+                        AND co_op = ?
+                        AND donated_at >= ?
+                        AND donated_at < ?
+                        AND id NOT IN (
+                            SELECT donation_id FROM schedule_occurrences WHERE donation_id IS NOT NULL
+                        )
+                    ORDER BY ABS({}), id
+                    LIMIT 1",
+                seconds_between("donated_at", "?")
+            ))
+            .bind(unsigned(schedule.supporter_id)?)
+            .bind(&schedule.co_op)
+            .bind(range_bound(from)?)
+            .bind(range_bound(until)?)
+            .bind(timestamp(due_at)?)
+            .fetch_optional(&self.pool)
+            .await
+        }
+        .boxed()
+    }
+
+    fn record_outcome(
+        &self,
+        schedule_id: u64,
+        due_on: Date,
+        donation_id: Option<u64>,
+    ) -> BoxFuture<'_, Result<(), sqlx::Error>> {
+        async move {
+            sqlx::query(&format!(
+                "{INSERT_IGNORE} INTO schedule_occurrences (schedule_id, due_on, status, donation_id)
+                VALUES (?, ?, ?, ?)"
+            ))
+            .bind(unsigned(schedule_id)?)
+            .bind(due_on)
+            .bind(if donation_id.is_some() {
+                "received"
+            } else {
+                "missed"
+            })
+            .bind(donation_id.map(unsigned).transpose()?)
+            .execute(&self.pool)
+            .await?;
+            Ok(())
+        }
+        .boxed()
+    }
+}
@@ -0,0 +1,770 @@
+// The repositories below share their queries with MySQL, written against
+// the dialect items of this module.
+#[path = "sql/campaigns.rs"]
+mod campaigns;
+#[path = "sql/exchange_rates.rs"]
+mod exchange_rates;
+#[path = "sql/idempotency.rs"]
+mod idempotency;
+#[path = "sql/jobs.rs"]
+mod jobs;
+#[path = "sql/merges.rs"]
+mod merges;
+#[path = "sql/payments.rs"]
+mod payments;
+#[path = "sql/schedules.rs"]
+mod schedules;
+
+use super::{
+    Account, AccountRepo, CoOpIncome, DONATION_FILTER_SQL, Donation, DonationChanges, DonationRepo,
+    ExportedDonation, Income, Interval, JobLock, NewDonation, NewSupporter, ParsedDonationFilter,
+    PeriodIncome, SessionRepo, Supporter, SupporterChanges, SupporterIncome, SupporterRepo,
+    SupporterTotals,
+};
+use crate::{
+    donations::DonationError,
+    exchange_rates::ExchangeRateError,
+    money::{Currency, Money, Rate},
+    supporters::SupporterError,
+};
+use async_stream::try_stream;
+use futures::{FutureExt, StreamExt, TryStreamExt, future::BoxFuture, stream::BoxStream};
+use sqlx::{
+    QueryBuilder, Sqlite, SqliteExecutor, SqlitePool,
+    query::QueryAs,
+    sqlite::{SqliteArguments, SqliteQueryResult},
+};
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use time::{
+    OffsetDateTime, UtcOffset,
+    format_description::{BorrowedFormatItem, well_known::Rfc3339},
+    macros::format_description,
+};
+
+const DONATION_COLUMNS: &str = "donations.id, donations.coins, donations.donated_at,
+    donations.income_eur_cents, donations.currency, donations.amount_cents, donations.co_op,
+    donations.supporter_id, donations.campaign_id, donations.version";
+
+/// Columns of an [`Income`] aggregated over the selected donations.
+const INCOME_COLUMNS: &str = "COUNT(*) AS donations,
+    COALESCE(SUM(coins), 0) AS coins,
+    COALESCE(SUM(income_eur_cents), 0) AS income_eur";
+
+const SUPPORTER_COLUMNS: &str = "id, name, email, phone, notes, public, created_at, version";
+
+type Repo = SqliteRepo;
+type Db = Sqlite;
+
+/// SQLite locks the whole database for a write transaction, so rows need no
+/// locks of their own.
+const FOR_UPDATE: &str = "";
+
+/// Inserts rows unless they would duplicate a unique key.
+const INSERT_IGNORE: &str = "INSERT OR IGNORE";
+
+/// Updates `column` of the row whose unique `key` an insert would duplicate.
+fn on_conflict_update(key: &str, column: &str) -> String {
+    format!("ON CONFLICT ({key}) DO UPDATE SET {column} = excluded.{column}")
+}
+
+/// Seconds from timestamp `from` to timestamp `to`.
+fn seconds_between(from: &str, to: &str) -> String {
+    format!("(julianday({to}) - julianday({from})) * 86400")
+}
+
+const TIMESTAMP: &[BorrowedFormatItem<'_>] =
+    format_description!("[year]-[month]-[day]T[hour]:[minute]:[second]Z");
+
+/// Timestamps are stored as UTC RFC 3339 text without fractional seconds, which
+/// sorts in chronological order.
+fn timestamp(t: OffsetDateTime) -> Result<String, sqlx::Error> {
+    t.to_offset(UtcOffset::UTC)
+        .format(TIMESTAMP)
+        .map_err(|e| sqlx::Error::Encode(e.into()))
+}
+
+/// Bound of a `>=` or `<` comparison with stored timestamps. Rounding up to the
+/// next whole second keeps such comparisons exact despite the stored precision.
+fn range_bound(t: OffsetDateTime) -> Result<String, sqlx::Error> {
+    let rounded = t.replace_nanosecond(0).expect("zero is a valid nanosecond");
+    if rounded == t {
+        timestamp(t)
+    } else {
+        timestamp(rounded + time::Duration::SECOND)
+    }
+}
+
+/// SQLite integers are signed, so unsigned values are bound as `i64`.
+fn unsigned(v: u64) -> Result<i64, sqlx::Error> {
+    i64::try_from(v).map_err(|e| sqlx::Error::Encode(e.into()))
+}
+
+/// Id of the row inserted by a query.
+fn inserted_id(result: SqliteQueryResult) -> u64 {
+    result.last_insert_rowid() as u64
+}
+
+/// Returns the rate of `currency` in effect at `at`.
+async fn rate_at<'e>(
+    executor: impl SqliteExecutor<'e>,
+    currency: &Currency,
+    at: OffsetDateTime,
+) -> Result<Rate, ExchangeRateError> {
+    if currency.is_eur() {
+        return Ok(Rate::ONE);
+    }
+
+    sqlx::query_scalar(
+        "SELECT rate_micros FROM exchange_rates
+            WHERE currency = ? AND valid_from <= ?
+            ORDER BY valid_from DESC
+            LIMIT 1",
+    )
+    .bind(currency)
+    .bind(timestamp(at)?)
+    .fetch_optional(executor)
+    .await?
+    .ok_or_else(|| {
+        ExchangeRateError::MissingRate(currency.clone(), at.format(&Rfc3339).unwrap_or_default())
+    })
+}
+
+/// Whether `e` violates a foreign key, including `ON DELETE RESTRICT` ones that
+/// SQLite reports as trigger constraints.
+fn is_foreign_key_violation(e: &sqlx::Error) -> bool {
+    matches!(e, sqlx::Error::Database(db)
+        if db.is_foreign_key_violation() || db.message() == "FOREIGN KEY constraint failed")
+}
+
+/// Binds the parameters of [`DONATION_FILTER_SQL`].
+fn bind_filter<'q, O>(
+    query: QueryAs<'q, Sqlite, O, SqliteArguments<'q>>,
+    filter: &ParsedDonationFilter,
+) -> Result<QueryAs<'q, Sqlite, O, SqliteArguments<'q>>, sqlx::Error> {
+    let from = filter.from.map(range_bound).transpose()?;
+    let to = filter.to.map(range_bound).transpose()?;
+    let supporter_id = filter.supporter_id.map(unsigned).transpose()?;
+    let campaign_id = filter.campaign_id.map(unsigned).transpose()?;
+    Ok(query
+        .bind(from.clone())
+        .bind(from)
+        .bind(to.clone())
+        .bind(to)
+        .bind(filter.co_op.clone())
+        .bind(filter.co_op.clone())
+        .bind(filter.currency.clone())
+        .bind(filter.currency.clone())
+        .bind(supporter_id)
+        .bind(supporter_id)
+        .bind(campaign_id)
+        .bind(campaign_id))
+}
+
+/// A SQLite database is not shared between machines, so jobs are locked
+/// within the process.
+struct ProcessLock {
+    held: Arc<Mutex<HashSet<String>>>,
+    job: String,
+}
+
+impl Drop for ProcessLock {
+    fn drop(&mut self) {
+        self.held
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.job);
+    }
+}
+
+impl JobLock for ProcessLock {
+    fn release(self: Box<Self>) -> BoxFuture<'static, Result<(), sqlx::Error>> {
+        drop(self);
+        futures::future::ok(()).boxed()
+    }
+}
+
+pub struct SqliteRepo {
+    pool: SqlitePool,
+    /// Jobs that are running, see [`JobRepo::lock`](super::JobRepo::lock)
+    job_locks: Arc<Mutex<HashSet<String>>>,
+}
+
+impl SqliteRepo {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self {
+            pool,
+            job_locks: Arc::default(),
+        }
+    }
+
+    /// Whether row `id` of `table` exists, to tell why a write guarded by its
+    /// version matched no row.
+    async fn exists(&self, table: &str, id: u64) -> Result<bool, sqlx::Error> {
+        Ok(
+            sqlx::query(&format!("SELECT 1 FROM {table} WHERE id = ? LIMIT 1"))
+                .bind(unsigned(id)?)
+                .fetch_optional(&self.pool)
+                .await?
+                .is_some(),
+        )
+    }
+
+    /// Takes a lock within the process, see [`JobRepo::lock`](super::JobRepo::lock).
+    fn lock_job<'a>(
+        &'a self,
+        job: &'a str,
+    ) -> BoxFuture<'a, Result<Option<Box<dyn JobLock>>, sqlx::Error>> {
+        let locked = self
+            .job_locks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(job.to_owned());
+        let lock = locked.then(|| {
+            Box::new(ProcessLock {
+                held: self.job_locks.clone(),
+                job: job.to_owned(),
+            }) as Box<dyn JobLock>
+        });
+        futures::future::ok(lock).boxed()
+    }
+
+    /// Maps foreign key violations to not found errors. SQLite does not name the
+    /// violated constraint, so the referenced campaign is looked up.
+    async fn unknown_reference(&self, e: sqlx::Error, campaign_id: Option<u64>) -> DonationError {
+        match &e {
+            sqlx::Error::Database(db) if db.is_foreign_key_violation() => match campaign_id {
+                Some(id) if !self.exists("campaigns", id).await.unwrap_or(true) => {
+                    DonationError::CampaignNotFound
+                }
+                _ => DonationError::SupporterNotFound,
+            },
+            _ => DonationError::DatabaseError(e),
+        }
+    }
+}
+
+impl DonationRepo for SqliteRepo {
+    fn list<'a>(
+        &'a self,
+        filter: &'a ParsedDonationFilter,
+    ) -> BoxFuture<'a, Result<Vec<Donation>, DonationError>> {
+        async move {
+            let sql = format!(
+                "SELECT {DONATION_COLUMNS}
+                    FROM donations
+                    WHERE {DONATION_FILTER_SQL}
+                    ORDER BY donations.donated_at, donations.id"
+            );
+            Ok(bind_filter(sqlx::query_as(&sql), filter)?
+                .fetch_all(&self.pool)
+                .await?)
+        }
+        .boxed()
+    }
+
+    fn get(&self, id: u64) -> BoxFuture<'_, Result<Option<Donation>, DonationError>> {
+        async move {
+            Ok(sqlx::query_as(&format!(
+                "SELECT {DONATION_COLUMNS} FROM donations WHERE id = ? LIMIT 1"
+            ))
+            .bind(unsigned(id)?)
+            .fetch_optional(&self.pool)
+            .await?)
+        }
+        .boxed()
+    }
+
+    fn export(
+        &self,
+        filter: ParsedDonationFilter,
+    ) -> BoxStream<'static, Result<ExportedDonation, DonationError>> {
+        let pool = self.pool.clone();
+        try_stream! {
+            let sql = format!(
+                "SELECT donations.id, donations.donated_at, donations.coins, donations.co_op,
+                    donations.currency, donations.amount_cents, donations.income_eur_cents,
+                    donations.external_ref, supporters.name AS supporter
+                FROM donations
+                LEFT JOIN supporters ON supporters.id = donations.supporter_id
+                WHERE {DONATION_FILTER_SQL}
+                ORDER BY donations.donated_at, donations.id"
+            );
+            let mut rows = bind_filter(sqlx::query_as(&sql), &filter)?.fetch(&pool);
+            while let Some(row) = rows.try_next().await? {
+                yield row;
+            }
+        }
+        .boxed()
+    }
+
+    fn rate_at<'a>(
+        &'a self,
+        currency: &'a Currency,
+        at: OffsetDateTime,
+    ) -> BoxFuture<'a, Result<Rate, ExchangeRateError>> {
+        rate_at(&self.pool, currency, at).boxed()
+    }
+
+    fn create(&self, donation: NewDonation) -> BoxFuture<'_, Result<u64, DonationError>> {
+        async move {
+            let res = sqlx::query(
+                "INSERT INTO donations
+                    (coins, income_eur_cents, currency, amount_cents, co_op, supporter_id, campaign_id)
+                VALUES (?, ?, ?, ?, ?, ?, ?)",
+            )
+            .bind(unsigned(donation.coins)?)
+            .bind(donation.income_eur)
+            .bind(donation.currency)
+            .bind(donation.amount)
+            .bind(donation.co_op)
+            .bind(donation.supporter_id.map(unsigned).transpose()?)
+            .bind(donation.campaign_id.map(unsigned).transpose()?)
+            .execute(&self.pool)
+            .await;
+            match res {
+                Ok(res) => Ok(res.last_insert_rowid() as u64),
+                Err(e) => Err(self.unknown_reference(e, donation.campaign_id).await),
+            }
+        }
+        .boxed()
+    }
+
+    fn update(
+        &self,
+        id: u64,
+        version: u32,
+        changes: DonationChanges,
+    ) -> BoxFuture<'_, Result<(), DonationError>> {
+        async move {
+            // an empty change leaves the version alone, so it cannot fail concurrent edits
+            if changes.is_empty() {
+                return Ok(());
+            }
+            let mut update =
+                QueryBuilder::<Sqlite>::new("UPDATE donations SET version = version + 1");
+            if let Some(coins) = changes.coins {
+                update.push(", coins = ").push_bind(unsigned(coins)?);
+            }
+            if let Some(amount) = changes.amount {
+                update.push(", amount_cents = ").push_bind(amount);
+            }
+            if let Some(currency) = changes.currency {
+                update.push(", currency = ").push_bind(currency);
+            }
+            if let Some(income_eur) = changes.income_eur {
+                update.push(", income_eur_cents = ").push_bind(income_eur);
+            }
+            if let Some(co_op) = changes.co_op {
+                update.push(", co_op = ").push_bind(co_op);
+            }
+            if let Some(supporter_id) = changes.supporter_id {
+                update
+                    .push(", supporter_id = ")
+                    .push_bind(supporter_id.map(unsigned).transpose()?);
+            }
+            if let Some(campaign_id) = changes.campaign_id {
+                update
+                    .push(", campaign_id = ")
+                    .push_bind(campaign_id.map(unsigned).transpose()?);
+            }
+            update
+                .push(" WHERE id = ")
+                .push_bind(unsigned(id)?)
+                .push(" AND version = ")
+                .push_bind(version);
+
+            let res = match update.build().execute(&self.pool).await {
+                Ok(res) => res,
+                Err(e) => {
+                    return Err(self
+                        .unknown_reference(e, changes.campaign_id.flatten())
+                        .await);
+                }
+            };
+            if res.rows_affected() > 0 {
+                Ok(())
+            } else if self.exists("donations", id).await? {
+                Err(DonationError::PreconditionFailed)
+            } else {
+                Err(DonationError::NotFound)
+            }
+        }
+        .boxed()
+    }
+
+    fn delete(&self, id: u64, version: u32) -> BoxFuture<'_, Result<(), DonationError>> {
+        async move {
+            let res = sqlx::query("DELETE FROM donations WHERE id = ? AND version = ?")
+                .bind(unsigned(id)?)
+                .bind(version)
+                .execute(&self.pool)
+                .await?;
+            if res.rows_affected() > 0 {
+                Ok(())
+            } else if self.exists("donations", id).await? {
+                Err(DonationError::PreconditionFailed)
+            } else {
+                Err(DonationError::NotFound)
+            }
+        }
+        .boxed()
+    }
+
+    fn income(
+        &self,
+        from: OffsetDateTime,
+        to: OffsetDateTime,
+    ) -> BoxFuture<'_, Result<Income, DonationError>> {
+        async move {
+            Ok(sqlx::query_as(&format!(
+                "SELECT {INCOME_COLUMNS}
+                    FROM donations
+                    WHERE donated_at >= ? AND donated_at < ?"
+            ))
+            .bind(range_bound(from)?)
+            .bind(range_bound(to)?)
+            .fetch_one(&self.pool)
+            .await?)
+        }
+        .boxed()
+    }
+
+    fn income_by_co_op(
+        &self,
+        from: OffsetDateTime,
+        to: OffsetDateTime,
+    ) -> BoxFuture<'_, Result<Vec<CoOpIncome>, DonationError>> {
+        async move {
+            Ok(sqlx::query_as(&format!(
+                "SELECT co_op, {INCOME_COLUMNS}
+                    FROM donations
+                    WHERE donated_at >= ? AND donated_at < ?
+                    GROUP BY co_op
+                    ORDER BY co_op"
+            ))
+            .bind(range_bound(from)?)
+            .bind(range_bound(to)?)
+            .fetch_all(&self.pool)
+            .await?)
+        }
+        .boxed()
+    }
+
+    fn income_by_period(
+        &self,
+        from: OffsetDateTime,
+        to: OffsetDateTime,
+        interval: Interval,
+    ) -> BoxFuture<'_, Result<Vec<PeriodIncome>, DonationError>> {
+        let period_start = match interval {
+            Interval::Day => "date(donated_at)",
+            // `%w` counts from Sunday
+            Interval::Week => {
+                "date(donated_at,
+                    '-' || ((CAST(strftime('%w', donated_at) AS INTEGER) + 6) % 7) || ' days')"
+            }
+            Interval::Month => "date(donated_at, 'start of month')",
+        };
+        async move {
+            Ok(sqlx::query_as(&format!(
+                "SELECT {period_start} AS period_start, {INCOME_COLUMNS}
+                    FROM donations
+                    WHERE donated_at >= ? AND donated_at < ?
+                    GROUP BY period_start
+                    ORDER BY period_start"
+            ))
+            .bind(range_bound(from)?)
+            .bind(range_bound(to)?)
+            .fetch_all(&self.pool)
+            .await?)
+        }
+        .boxed()
+    }
+
+    fn top_supporters(
+        &self,
+        from: OffsetDateTime,
+        to: OffsetDateTime,
+        limit: u32,
+    ) -> BoxFuture<'_, Result<Vec<SupporterIncome>, DonationError>> {
+        async move {
+            Ok(sqlx::query_as(
+                "SELECT supporters.id AS supporter_id, supporters.name,
+                        COUNT(*) AS donations,
+                        SUM(donations.income_eur_cents) AS income_eur
+                    FROM supporters
+                    JOIN donations ON donations.supporter_id = supporters.id
+                    WHERE donations.donated_at >= ? AND donations.donated_at < ?
+                    GROUP BY supporters.id, supporters.name
+                    ORDER BY income_eur DESC, supporters.id
+                    LIMIT ?",
+            )
+            .bind(range_bound(from)?)
+            .bind(range_bound(to)?)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?)
+        }
+        .boxed()
+    }
+}
+
+impl SupporterRepo for SqliteRepo {
+    fn list(&self) -> BoxFuture<'_, Result<Vec<Supporter>, SupporterError>> {
+        async move {
+            Ok(sqlx::query_as(&format!(
+                "SELECT {SUPPORTER_COLUMNS} FROM supporters ORDER BY name, id"
+            ))
+            .fetch_all(&self.pool)
+            .await?)
+        }
+        .boxed()
+    }
+
+    fn get(&self, id: u64) -> BoxFuture<'_, Result<Option<Supporter>, SupporterError>> {
+        async move {
+            Ok(sqlx::query_as(&format!(
+                "SELECT {SUPPORTER_COLUMNS} FROM supporters WHERE id = ? LIMIT 1"
+            ))
+            .bind(unsigned(id)?)
+            .fetch_optional(&self.pool)
+            .await?)
+        }
+        .boxed()
+    }
+
+    fn create(&self, supporter: NewSupporter) -> BoxFuture<'_, Result<u64, SupporterError>> {
+        async move {
+            Ok(sqlx::query(
+                "INSERT INTO supporters (name, email, phone, notes, public)
+                VALUES (?, ?, ?, ?, ?)",
+            )
+            .bind(supporter.name)
+            .bind(supporter.email)
+            .bind(supporter.phone)
+            .bind(supporter.notes)
+            .bind(supporter.public)
+            .execute(&self.pool)
+            .await?
+            .last_insert_rowid() as u64)
+        }
+        .boxed()
+    }
+
+    fn update(
+        &self,
+        id: u64,
+        version: u32,
+        changes: SupporterChanges,
+    ) -> BoxFuture<'_, Result<(), SupporterError>> {
+        async move {
+            // an empty change leaves the version alone, so it cannot fail concurrent edits
+            if changes.is_empty() {
+                return Ok(());
+            }
+            let mut update =
+                QueryBuilder::<Sqlite>::new("UPDATE supporters SET version = version + 1");
+            if let Some(name) = changes.name {
+                update.push(", name = ").push_bind(name);
+            }
+            if let Some(email) = changes.email {
+                update.push(", email = ").push_bind(email);
+            }
+            if let Some(phone) = changes.phone {
+                update.push(", phone = ").push_bind(phone);
+            }
+            if let Some(notes) = changes.notes {
+                update.push(", notes = ").push_bind(notes);
+            }
+            if let Some(public) = changes.public {
+                update.push(", public = ").push_bind(public);
+            }
+            update
+                .push(" WHERE id = ")
+                .push_bind(unsigned(id)?)
+                .push(" AND version = ")
+                .push_bind(version);
+
+            let res = update.build().execute(&self.pool).await?;
+            if res.rows_affected() > 0 {
+                Ok(())
+            } else if self.exists("supporters", id).await? {
+                Err(SupporterError::PreconditionFailed)
+            } else {
+                Err(SupporterError::NotFound)
+            }
+        }
+        .boxed()
+    }
+
+    fn delete(&self, id: u64, version: u32) -> BoxFuture<'_, Result<(), SupporterError>> {
+        async move {
+            let res = sqlx::query("DELETE FROM supporters WHERE id = ? AND version = ?")
+                .bind(unsigned(id)?)
+                .bind(version)
+                .execute(&self.pool)
+                .await
+                .map_err(|e| {
+                    // donation schedules restrict deleting their supporter
+                    if is_foreign_key_violation(&e) {
+                        SupporterError::HasSchedules
+                    } else {
+                        e.into()
+                    }
+                })?;
+            if res.rows_affected() > 0 {
+                Ok(())
+            } else if self.exists("supporters", id).await? {
+                Err(SupporterError::PreconditionFailed)
+            } else {
+                Err(SupporterError::NotFound)
+            }
+        }
+        .boxed()
+    }
+
+    fn totals(&self, id: u64) -> BoxFuture<'_, Result<SupporterTotals, SupporterError>> {
+        async move {
+            Ok(sqlx::query_as(
+                "SELECT COUNT(*) AS donations,
+                        COALESCE(SUM(coins), 0) AS coins,
+                        COALESCE(SUM(income_eur_cents), 0) AS income_eur,
+                        MIN(donated_at) AS first_donated_at,
+                        MAX(donated_at) AS last_donated_at
+                    FROM donations
+                    WHERE supporter_id = ?",
+            )
+            .bind(unsigned(id)?)
+            .fetch_one(&self.pool)
+            .await?)
+        }
+        .boxed()
+    }
+
+    fn public_totals(&self) -> BoxFuture<'_, Result<Vec<(String, Money)>, SupporterError>> {
+        async move {
+            Ok(sqlx::query_as(
+                "SELECT supporters.name, SUM(donations.income_eur_cents)
+                    FROM supporters
+                    JOIN donations ON donations.supporter_id = supporters.id
+                    WHERE supporters.public
+                    GROUP BY supporters.id, supporters.name
+                    ORDER BY supporters.name",
+            )
+            .fetch_all(&self.pool)
+            .await?)
+        }
+        .boxed()
+    }
+}
+
+impl AccountRepo for SqliteRepo {
+    fn create<'a>(
+        &'a self,
+        email: &'a str,
+        password: &'a str,
+    ) -> BoxFuture<'a, Result<Option<u64>, sqlx::Error>> {
+        async move {
+            match sqlx::query("INSERT INTO accounts (email, password) VALUES (?, ?)")
+                .bind(email)
+                .bind(password)
+                .execute(&self.pool)
+                .await
+            {
+                Ok(res) => Ok(Some(res.last_insert_rowid() as u64)),
+                Err(sqlx::Error::Database(e)) if e.is_unique_violation() => Ok(None),
+                Err(e) => Err(e),
+            }
+        }
+        .boxed()
+    }
+
+    fn get(&self, id: u64) -> BoxFuture<'_, Result<Option<Account>, sqlx::Error>> {
+        async move {
+            sqlx::query_as("SELECT id, email, password FROM accounts WHERE id = ? LIMIT 1")
+                .bind(unsigned(id)?)
+                .fetch_optional(&self.pool)
+                .await
+        }
+        .boxed()
+    }
+
+    fn find_by_email<'a>(
+        &'a self,
+        email: &'a str,
+    ) -> BoxFuture<'a, Result<Option<Account>, sqlx::Error>> {
+        sqlx::query_as("SELECT id, email, password FROM accounts WHERE email = ? LIMIT 1")
+            .bind(email)
+            .fetch_optional(&self.pool)
+            .boxed()
+    }
+}
+
+impl SessionRepo for SqliteRepo {
+    fn create<'a>(
+        &'a self,
+        token: &'a str,
+        account_id: u64,
+        max_age: Duration,
+    ) -> BoxFuture<'a, Result<(), sqlx::Error>> {
+        async move {
+            sqlx::query("INSERT INTO sessions (token, account_id, expires_at) VALUES (?, ?, ?)")
+                .bind(token)
+                .bind(unsigned(account_id)?)
+                .bind(timestamp(OffsetDateTime::now_utc() + max_age)?)
+                .execute(&self.pool)
+                .await?;
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn account_id<'a>(&'a self, token: &'a str) -> BoxFuture<'a, Result<Option<u64>, sqlx::Error>> {
+        async move {
+            sqlx::query_scalar(
+                "SELECT account_id FROM sessions WHERE token = ? AND expires_at > ? LIMIT 1",
+            )
+            .bind(token)
+            .bind(timestamp(OffsetDateTime::now_utc())?)
+            .fetch_optional(&self.pool)
+            .await
+        }
+        .boxed()
+    }
+
+    fn delete<'a>(&'a self, token: &'a str) -> BoxFuture<'a, Result<(), sqlx::Error>> {
+        async move {
+            sqlx::query("DELETE FROM sessions WHERE token = ?")
+                .bind(token)
+                .execute(&self.pool)
+                .await?;
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn count_active(&self) -> BoxFuture<'_, Result<i64, sqlx::Error>> {
+        async move {
+            sqlx::query_scalar("SELECT COUNT(*) FROM sessions WHERE expires_at > ?")
+                .bind(timestamp(OffsetDateTime::now_utc())?)
+                .fetch_one(&self.pool)
+                .await
+        }
+        .boxed()
+    }
+
+    fn delete_expired(&self) -> BoxFuture<'_, Result<u64, sqlx::Error>> {
+        async move {
+            Ok(sqlx::query("DELETE FROM sessions WHERE expires_at < ?")
+                .bind(timestamp(OffsetDateTime::now_utc())?)
+                .execute(&self.pool)
+                .await?
+                .rows_affected())
+        }
+        .boxed()
+    }
+}
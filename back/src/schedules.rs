@@ -0,0 +1,781 @@
+use crate::{
+    ApiResult,
+    error::{FieldError, JsonBody, PathParams, Problem, ProblemDetails, QueryParams},
+    idempotency::IdempotencyKeyHeader,
+    jobs::JobResult,
+    money::{Currency, Money},
+    repo::{Schedule, ScheduleFields, Schedules, Sessions},
+    users::auth::validate,
+};
+use axum::{
+    Json,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{Database, Decode, Type, error::BoxDynError};
+use std::{collections::HashSet, str::FromStr};
+use thiserror::Error;
+use time::{
+    Date, Duration, Month, OffsetDateTime, format_description::FormatItem,
+    macros::format_description,
+};
+
+#[derive(utoipa::OpenApi)]
+#[openapi(paths(
+    get_schedules,
+    get_schedule,
+    post_schedule,
+    put_schedule,
+    delete_schedule,
+    get_upcoming_donations,
+    get_overdue_donations
+))]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    ApiDoc::openapi()
+}
+
+const DATE: &[FormatItem] = format_description!("[year]-[month]-[day]");
+pub const CHECK_SCHEDULE: &str = "0 * * * *";
+
+#[derive(Error, Debug)]
+pub enum ScheduleError {
+    #[error("Schedule not found")]
+    NotFound,
+    #[error("Supporter not found")]
+    SupporterNotFound,
+    #[error("Invalid {0}: {1}")]
+    Invalid(&'static str, &'static str),
+    #[error("Could not format")]
+    FormatError(#[from] time::error::Format),
+    #[error("Could not query database")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl ProblemDetails for ScheduleError {
+    fn status(&self) -> StatusCode {
+        match self {
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::SupporterNotFound => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::Invalid(..) => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::FormatError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::DatabaseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn code(&self) -> &'static str {
+        match self {
+            Self::NotFound => "schedule_not_found",
+            Self::SupporterNotFound => "supporter_not_found",
+            Self::Invalid(..) => "invalid_schedule",
+            Self::FormatError(_) => "format_error",
+            Self::DatabaseError(_) => "database_error",
+        }
+    }
+
+    fn errors(&self) -> Vec<FieldError> {
+        match self {
+            Self::SupporterNotFound => vec![FieldError {
+                field: "supporter_id".to_owned(),
+                message: self.to_string(),
+                line: None,
+            }],
+            Self::Invalid(field, message) => vec![FieldError {
+                field: field.to_string(),
+                message: message.to_string(),
+                line: None,
+            }],
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum IntervalUnit {
+    Week,
+    Month,
+    Year,
+}
+
+impl IntervalUnit {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Week => "week",
+            Self::Month => "month",
+            Self::Year => "year",
+        }
+    }
+}
+
+impl FromStr for IntervalUnit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "week" => Ok(Self::Week),
+            "month" => Ok(Self::Month),
+            "year" => Ok(Self::Year),
+            s => Err(format!("unknown interval unit {s:?}")),
+        }
+    }
+}
+
+impl<DB: Database> Type<DB> for IntervalUnit
+where
+    str: Type<DB>,
+{
+    fn type_info() -> DB::TypeInfo {
+        <str as Type<DB>>::type_info()
+    }
+
+    fn compatible(ty: &DB::TypeInfo) -> bool {
+        <str as Type<DB>>::compatible(ty)
+    }
+}
+
+impl<'r, DB: Database> Decode<'r, DB> for IntervalUnit
+where
+    &'r str: Decode<'r, DB>,
+{
+    fn decode(value: DB::ValueRef<'r>) -> Result<Self, BoxDynError> {
+        Ok(<&str as Decode<DB>>::decode(value)?.parse()?)
+    }
+}
+
+fn add_months(date: Date, months: i64) -> Option<Date> {
+    let total = i64::from(date.year()) * 12 + i64::from(date.month() as u8 - 1) + months;
+    let year = i32::try_from(total.div_euclid(12)).ok()?;
+    let month = Month::try_from(total.rem_euclid(12) as u8 + 1).ok()?;
+    Date::from_calendar_date(year, month, date.day().min(month.length(year))).ok()
+}
+
+impl Schedule {
+    /// The `n`th due date, counted from `starts_on` so that month ends do not drift.
+    fn nth_due(&self, n: i64) -> Option<Date> {
+        let steps = n.checked_mul(i64::from(self.interval_count))?;
+        match self.interval_unit {
+            IntervalUnit::Week => self.starts_on.checked_add(Duration::weeks(steps)),
+            IntervalUnit::Month => add_months(self.starts_on, steps),
+            IntervalUnit::Year => add_months(self.starts_on, steps.checked_mul(12)?),
+        }
+    }
+
+    fn due_dates(&self) -> impl Iterator<Item = Date> + '_ {
+        (0..)
+            .map_while(|n| self.nth_due(n))
+            .take_while(|due| self.ends_on.is_none_or(|ends_on| *due <= ends_on))
+    }
+
+    fn grace(&self) -> Duration {
+        Duration::days(i64::from(self.grace_days))
+    }
+
+    /// Due dates whose grace period is over by `today`. Due dates whose grace
+    /// period would end beyond the calendar never are.
+    fn passed_due_dates(&self, today: Date) -> impl Iterator<Item = Date> + '_ {
+        self.due_dates().take_while(move |due_on| {
+            due_on
+                .checked_add(self.grace())
+                .is_some_and(|checked_on| checked_on < today)
+        })
+    }
+
+    fn expected(&self, due_on: Date) -> Result<ExpectedDonation, ScheduleError> {
+        Ok(ExpectedDonation {
+            schedule_id: self.id,
+            supporter_id: self.supporter_id,
+            supporter_name: self.supporter_name.clone(),
+            co_op: self.co_op.clone(),
+            currency: self.currency.clone(),
+            amount: self.amount,
+            due_on: due_on.format(DATE)?,
+        })
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+struct ScheduleResponse {
+    id: u64,
+    supporter_id: u64,
+    supporter_name: String,
+    co_op: String,
+    currency: Currency,
+    /// Expected amount in `currency` per donation
+    amount: Money,
+    interval_unit: IntervalUnit,
+    interval_count: u32,
+    /// First due date, `YYYY-MM-DD`
+    starts_on: String,
+    /// Last possible due date, `YYYY-MM-DD`
+    ends_on: Option<String>,
+    grace_days: u32,
+}
+
+impl TryFrom<Schedule> for ScheduleResponse {
+    type Error = ScheduleError;
+
+    fn try_from(schedule: Schedule) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: schedule.id,
+            supporter_id: schedule.supporter_id,
+            supporter_name: schedule.supporter_name,
+            co_op: schedule.co_op,
+            currency: schedule.currency,
+            amount: schedule.amount,
+            interval_unit: schedule.interval_unit,
+            interval_count: schedule.interval_count,
+            starts_on: schedule.starts_on.format(DATE)?,
+            ends_on: schedule.ends_on.map(|d| d.format(DATE)).transpose()?,
+            grace_days: schedule.grace_days,
+        })
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+struct ScheduleIdResponse {
+    id: u64,
+}
+
+fn default_interval_count() -> u32 {
+    1
+}
+
+fn default_grace_days() -> u32 {
+    7
+}
+
+const MAX_GRACE_DAYS: u32 = 365;
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct ScheduleRequest {
+    supporter_id: u64,
+    co_op: String,
+    #[serde(default)]
+    currency: Currency,
+    amount: Money,
+    interval_unit: IntervalUnit,
+    #[serde(default = "default_interval_count")]
+    interval_count: u32,
+    /// First due date, `YYYY-MM-DD`
+    starts_on: String,
+    /// Last possible due date, `YYYY-MM-DD`
+    ends_on: Option<String>,
+    /// Days a donation may arrive before or after its due date and still count, defaults to 7
+    /// and at most 365
+    #[serde(default = "default_grace_days")]
+    #[schema(maximum = 365)]
+    grace_days: u32,
+}
+
+impl ScheduleRequest {
+    fn validate(self) -> Result<ScheduleFields, ScheduleError> {
+        let parse = |field, value: &str| {
+            Date::parse(value, DATE)
+                .map_err(|_| ScheduleError::Invalid(field, "expected YYYY-MM-DD"))
+        };
+        let starts_on = parse("starts_on", &self.starts_on)?;
+        let ends_on = self
+            .ends_on
+            .as_deref()
+            .map(|v| parse("ends_on", v))
+            .transpose()?;
+
+        if self.interval_count == 0 {
+            return Err(ScheduleError::Invalid(
+                "interval_count",
+                "must be at least 1",
+            ));
+        }
+        if self.grace_days > MAX_GRACE_DAYS {
+            return Err(ScheduleError::Invalid("grace_days", "must be at most 365"));
+        }
+        if ends_on.is_some_and(|ends_on| ends_on < starts_on) {
+            return Err(ScheduleError::Invalid(
+                "ends_on",
+                "must not be before starts_on",
+            ));
+        }
+
+        Ok(ScheduleFields {
+            supporter_id: self.supporter_id,
+            co_op: self.co_op,
+            currency: self.currency,
+            amount: self.amount,
+            interval_unit: self.interval_unit,
+            interval_count: self.interval_count,
+            starts_on,
+            ends_on,
+            grace_days: self.grace_days,
+        })
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+struct ExpectedDonation {
+    schedule_id: u64,
+    supporter_id: u64,
+    supporter_name: String,
+    co_op: String,
+    currency: Currency,
+    amount: Money,
+    /// `YYYY-MM-DD`
+    due_on: String,
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct UpcomingQuery {
+    /// Number of days ahead to look, defaults to 30 and is capped at 366
+    days: Option<u16>,
+}
+
+/// Records the outcome of every due date whose grace period has passed,
+/// matching it to a donation by the same supporter to the same co-op within
+/// the grace period that is not matched to another due date yet.
+async fn check_schedules(schedules: &Schedules) -> Result<(u64, u64), sqlx::Error> {
+    let today = OffsetDateTime::now_utc().date();
+    let mut received = 0;
+    let mut missed = 0;
+
+    for schedule in schedules.list(None).await? {
+        let checked: HashSet<Date> = schedules
+            .checked_due_dates(schedule.id)
+            .await?
+            .into_iter()
+            .collect();
+
+        for due_on in schedule
+            .passed_due_dates(today)
+            .filter(|due_on| !checked.contains(due_on))
+        {
+            let due_at = due_on.midnight().assume_utc();
+            let (Some(from), Some(until)) = (
+                due_at.checked_sub(schedule.grace()),
+                due_at.checked_add(schedule.grace() + Duration::DAY),
+            ) else {
+                continue;
+            };
+            let donation_id = schedules
+                .unmatched_donation(&schedule, from, until, due_at)
+                .await?;
+            schedules
+                .record_outcome(schedule.id, due_on, donation_id)
+                .await?;
+
+            if donation_id.is_some() {
+                received += 1;
+            } else {
+                missed += 1;
+            }
+        }
+    }
+
+    Ok((received, missed))
+}
+
+pub async fn flag_missed_donations(schedules: Schedules) -> JobResult {
+    let (received, missed) = check_schedules(&schedules).await?;
+    Ok(format!(
+        "Checked scheduled donations: {received} received, {missed} missed"
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/schedules",
+    responses(
+        (
+            status = StatusCode::OK,
+            body = Vec<ScheduleResponse>,
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = Problem,
+            content_type = "application/problem+json",
+        )
+    ),
+)]
+pub async fn get_schedules(
+    sessions: State<Sessions>,
+    State(schedules): State<Schedules>,
+    headers: HeaderMap,
+) -> ApiResult<impl IntoResponse> {
+    let _ = validate(sessions, headers).await?;
+
+    let schedules = schedules
+        .list(None)
+        .await
+        .map_err(ScheduleError::DatabaseError)?
+        .into_iter()
+        .map(ScheduleResponse::try_from)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok((StatusCode::OK, Json(schedules)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/schedules/{id}",
+    params(("id" = u64, Path)),
+    responses(
+        (
+            status = StatusCode::OK,
+            body = ScheduleResponse,
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            description = "Schedule not found",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = Problem,
+            content_type = "application/problem+json",
+        )
+    ),
+)]
+pub async fn get_schedule(
+    sessions: State<Sessions>,
+    State(schedules): State<Schedules>,
+    headers: HeaderMap,
+    PathParams(id): PathParams<u64>,
+) -> ApiResult<impl IntoResponse> {
+    let _ = validate(sessions, headers).await?;
+
+    let schedule = schedules
+        .get(id)
+        .await
+        .map_err(ScheduleError::DatabaseError)?
+        .ok_or(ScheduleError::NotFound)?;
+
+    Ok((StatusCode::OK, Json(ScheduleResponse::try_from(schedule)?)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/schedules",
+    params(IdempotencyKeyHeader),
+    request_body = ScheduleRequest,
+    responses(
+        (
+            status = StatusCode::CREATED,
+            body = ScheduleIdResponse,
+            description = "Successfully added schedule",
+        ),
+        (
+            status = StatusCode::UNPROCESSABLE_ENTITY,
+            description = "Invalid request body or unknown supporter",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::CONFLICT,
+            description = "A request with the same `Idempotency-Key` is still being processed",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = Problem,
+            content_type = "application/problem+json",
+        )
+    )
+)]
+pub async fn post_schedule(
+    sessions: State<Sessions>,
+    State(schedules): State<Schedules>,
+    headers: HeaderMap,
+    JsonBody(req): JsonBody<ScheduleRequest>,
+) -> ApiResult<impl IntoResponse> {
+    let _ = validate(sessions, headers).await?;
+    let id = schedules.create(req.validate()?).await?;
+
+    Ok((StatusCode::CREATED, Json(ScheduleIdResponse { id })))
+}
+
+#[utoipa::path(
+    put,
+    path = "/schedules/{id}",
+    params(("id" = u64, Path)),
+    request_body = ScheduleRequest,
+    responses(
+        (
+            status = StatusCode::OK,
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            description = "Schedule not found",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::UNPROCESSABLE_ENTITY,
+            description = "Invalid request body or unknown supporter",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = Problem,
+            content_type = "application/problem+json",
+        )
+    )
+)]
+pub async fn put_schedule(
+    sessions: State<Sessions>,
+    State(schedules): State<Schedules>,
+    headers: HeaderMap,
+    PathParams(id): PathParams<u64>,
+    JsonBody(req): JsonBody<ScheduleRequest>,
+) -> ApiResult<impl IntoResponse> {
+    let _ = validate(sessions, headers).await?;
+    schedules.update(id, req.validate()?).await?;
+
+    Ok(StatusCode::OK)
+}
+
+#[utoipa::path(
+    delete,
+    path = "/schedules/{id}",
+    params(("id" = u64, Path)),
+    responses(
+        (
+            status = StatusCode::NO_CONTENT,
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            description = "Schedule not found",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = Problem,
+            content_type = "application/problem+json",
+        )
+    )
+)]
+pub async fn delete_schedule(
+    sessions: State<Sessions>,
+    State(schedules): State<Schedules>,
+    headers: HeaderMap,
+    PathParams(id): PathParams<u64>,
+) -> ApiResult<impl IntoResponse> {
+    let _ = validate(sessions, headers).await?;
+
+    schedules.delete(id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[utoipa::path(
+    get,
+    path = "/schedules/upcoming",
+    params(UpcomingQuery),
+    responses(
+        (
+            status = StatusCode::OK,
+            body = Vec<ExpectedDonation>,
+            description = "Scheduled donations due from today on, soonest first",
+        ),
+        (
+            status = StatusCode::BAD_REQUEST,
+            description = "Invalid query",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = Problem,
+            content_type = "application/problem+json",
+        )
+    ),
+)]
+pub async fn get_upcoming_donations(
+    sessions: State<Sessions>,
+    State(schedules): State<Schedules>,
+    headers: HeaderMap,
+    QueryParams(query): QueryParams<UpcomingQuery>,
+) -> ApiResult<impl IntoResponse> {
+    let _ = validate(sessions, headers).await?;
+
+    let today = OffsetDateTime::now_utc().date();
+    let until = today + Duration::days(i64::from(query.days.unwrap_or(30).min(366)));
+
+    let schedules = schedules
+        .list(Some(today))
+        .await
+        .map_err(ScheduleError::DatabaseError)?;
+
+    let mut upcoming: Vec<(Date, ExpectedDonation)> = Vec::new();
+    for schedule in &schedules {
+        for due_on in schedule
+            .due_dates()
+            .skip_while(|due_on| *due_on < today)
+            .take_while(|due_on| *due_on <= until)
+        {
+            upcoming.push((due_on, schedule.expected(due_on)?));
+        }
+    }
+    upcoming.sort_by_key(|(due_on, expected)| (*due_on, expected.schedule_id));
+
+    let upcoming: Vec<_> = upcoming.into_iter().map(|(_, expected)| expected).collect();
+
+    Ok((StatusCode::OK, Json(upcoming)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/schedules/overdue",
+    responses(
+        (
+            status = StatusCode::OK,
+            body = Vec<ExpectedDonation>,
+            description = "Scheduled donations that were not received within their grace period, \
+                most recent first",
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = Problem,
+            content_type = "application/problem+json",
+        )
+    ),
+)]
+pub async fn get_overdue_donations(
+    sessions: State<Sessions>,
+    State(schedules): State<Schedules>,
+    headers: HeaderMap,
+) -> ApiResult<impl IntoResponse> {
+    let _ = validate(sessions, headers).await?;
+
+    let missed = schedules
+        .missed()
+        .await
+        .map_err(ScheduleError::DatabaseError)?
+        .into_iter()
+        .map(|missed| {
+            Ok(ExpectedDonation {
+                schedule_id: missed.schedule_id,
+                supporter_id: missed.supporter_id,
+                supporter_name: missed.supporter_name,
+                co_op: missed.co_op,
+                currency: missed.currency,
+                amount: missed.amount,
+                due_on: missed
+                    .due_on
+                    .format(DATE)
+                    .map_err(ScheduleError::FormatError)?,
+            })
+        })
+        .collect::<ApiResult<Vec<_>>>()?;
+
+    Ok((StatusCode::OK, Json(missed)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::date;
+
+    fn monthly(starts_on: Date, grace_days: u32) -> Schedule {
+        Schedule {
+            id: 1,
+            supporter_id: 1,
+            supporter_name: "Ada".to_owned(),
+            co_op: "S4L".to_owned(),
+            currency: Currency::default(),
+            amount: Money::from_cents(500),
+            interval_unit: IntervalUnit::Month,
+            interval_count: 1,
+            starts_on,
+            ends_on: None,
+            grace_days,
+        }
+    }
+
+    fn request(grace_days: u32) -> ScheduleRequest {
+        ScheduleRequest {
+            supporter_id: 1,
+            co_op: "S4L".to_owned(),
+            currency: Currency::default(),
+            amount: Money::from_cents(500),
+            interval_unit: IntervalUnit::Month,
+            interval_count: 1,
+            starts_on: "2025-01-31".to_owned(),
+            ends_on: None,
+            grace_days,
+        }
+    }
+
+    #[test]
+    fn grace_days_are_bounded() {
+        assert!(request(MAX_GRACE_DAYS).validate().is_ok());
+        assert!(matches!(
+            request(MAX_GRACE_DAYS + 1).validate(),
+            Err(ScheduleError::Invalid("grace_days", _))
+        ));
+        assert!(request(u32::MAX).validate().is_err());
+    }
+
+    #[test]
+    fn due_dates_pass_after_their_grace_period() {
+        let schedule = monthly(date!(2025 - 01 - 31), 7);
+        let passed: Vec<_> = schedule.passed_due_dates(date!(2025 - 03 - 08)).collect();
+        assert_eq!(passed, [date!(2025 - 01 - 31), date!(2025 - 02 - 28)]);
+    }
+
+    #[test]
+    fn grace_periods_beyond_the_calendar_never_pass() {
+        let schedule = monthly(date!(9999 - 06 - 30), 365);
+        assert_eq!(schedule.passed_due_dates(Date::MAX).count(), 0);
+
+        let schedule = monthly(date!(9999 - 12 - 01), 7);
+        assert_eq!(schedule.passed_due_dates(Date::MAX).count(), 1);
+    }
+}
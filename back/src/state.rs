@@ -0,0 +1,35 @@
+use crate::{
+    config::Config,
+    db::Database,
+    health::BackgroundTasks,
+    jobs::Scheduler,
+    repo::{
+        Accounts, Campaigns, Donations, ExchangeRates, IdempotencyKeys, JobRuns, Merges, Payments,
+        Schedules, Sessions, Supporters,
+    },
+};
+use axum::extract::FromRef;
+use metrics_exporter_prometheus::PrometheusHandle;
+use std::sync::Arc;
+
+/// State shared by all handlers, which extract the parts they need with
+/// `State<Donations>`, `State<Database>` or `State<Arc<Config>>`.
+#[derive(Clone, FromRef)]
+pub struct AppState {
+    pub db: Database,
+    pub donations: Donations,
+    pub supporters: Supporters,
+    pub accounts: Accounts,
+    pub sessions: Sessions,
+    pub exchange_rates: ExchangeRates,
+    pub campaigns: Campaigns,
+    pub merges: Merges,
+    pub schedules: Schedules,
+    pub payments: Payments,
+    pub idempotency_keys: IdempotencyKeys,
+    pub job_runs: JobRuns,
+    pub config: Arc<Config>,
+    pub metrics: PrometheusHandle,
+    pub tasks: BackgroundTasks,
+    pub scheduler: Arc<Scheduler>,
+}
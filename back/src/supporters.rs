@@ -1,13 +1,27 @@
-use crate::{ApiResult, users::auth::validate};
+pub mod merge;
+pub mod public;
+use crate::{
+    ApiResult,
+    donations::{self, DonationFilter, DonationResponse},
+    error::{FieldError, JsonBody, PathParams, Problem, ProblemDetails, QueryParams},
+    etag::{self, IfMatch, IfMatchHeader},
+    idempotency::IdempotencyKeyHeader,
+    merge_patch::Patch,
+    money::Money,
+    repo::{
+        Donations, NewSupporter, Sessions, Supporter, SupporterChanges, SupporterRepo, Supporters,
+    },
+    users::auth::validate,
+};
 use axum::{
     Json,
-    extract::{Path, State},
-    http::{HeaderMap, StatusCode},
-    response::{IntoResponse, Response},
+    extract::State,
+    http::{HeaderMap, StatusCode, header},
+    response::IntoResponse,
 };
 use serde::{Deserialize, Serialize};
-use sqlx::MySqlPool;
 use thiserror::Error;
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
 
 #[derive(utoipa::OpenApi)]
 #[openapi(paths(
@@ -15,7 +29,10 @@ use thiserror::Error;
     get_supporter,
     post_supporter,
     put_supporter,
-    delete_supporter
+    patch_supporter,
+    delete_supporter,
+    get_supporter_donations,
+    get_supporter_totals
 ))]
 struct ApiDoc;
 pub fn openapi() -> utoipa::openapi::OpenApi {
@@ -27,23 +44,63 @@ pub fn openapi() -> utoipa::openapi::OpenApi {
 pub enum SupporterError {
     #[error("Supporter not found")]
     NotFound,
+    #[error("Invalid merge: {0}")]
+    InvalidMerge(&'static str),
+    #[error("Supporter was modified since it was read")]
+    PreconditionFailed,
+    #[error("Supporter has donation schedules, delete them or merge the supporter instead")]
+    HasSchedules,
+    #[error("{0} must not be null")]
+    InvalidPatch(&'static str),
+    #[error("min_score must be between 0 and 1")]
+    InvalidMinScore,
     #[error("Could not format")]
     FormatError(#[from] time::error::Format),
     #[error("Could not query database")]
     DatabaseError(#[from] sqlx::Error),
 }
 
-impl IntoResponse for SupporterError {
-    fn into_response(self) -> Response {
-        let status = match self {
+impl ProblemDetails for SupporterError {
+    fn status(&self) -> StatusCode {
+        match self {
             Self::NotFound => StatusCode::NOT_FOUND,
+            Self::InvalidMerge(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::PreconditionFailed => StatusCode::PRECONDITION_FAILED,
+            Self::HasSchedules => StatusCode::CONFLICT,
+            Self::InvalidPatch(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::InvalidMinScore => StatusCode::BAD_REQUEST,
             Self::FormatError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             Self::DatabaseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
-        };
+        }
+    }
 
-        let msg = self.to_string();
+    fn code(&self) -> &'static str {
+        match self {
+            Self::NotFound => "supporter_not_found",
+            Self::InvalidMerge(_) => "invalid_merge",
+            Self::PreconditionFailed => "precondition_failed",
+            Self::HasSchedules => "supporter_has_schedules",
+            Self::InvalidPatch(_) => "invalid_patch",
+            Self::InvalidMinScore => "invalid_min_score",
+            Self::FormatError(_) => "format_error",
+            Self::DatabaseError(_) => "database_error",
+        }
+    }
 
-        (status, Json(msg)).into_response()
+    fn errors(&self) -> Vec<FieldError> {
+        match self {
+            Self::InvalidPatch(field) => vec![FieldError {
+                field: field.to_string(),
+                message: self.to_string(),
+                line: None,
+            }],
+            Self::InvalidMinScore => vec![FieldError {
+                field: "min_score".to_owned(),
+                message: self.to_string(),
+                line: None,
+            }],
+            _ => Vec::new(),
+        }
     }
 }
 
@@ -51,7 +108,42 @@ impl IntoResponse for SupporterError {
 struct SupporterResponse {
     id: u64,
     name: String,
-    donation_id: u64,
+    email: Option<String>,
+    phone: Option<String>,
+    /// Internal notes, never shown publicly
+    notes: Option<String>,
+    /// Whether the supporter consented to being listed on the public supporters wall
+    public: bool,
+    created_at: String,
+    /// Incremented on every change, sent as `ETag` by `GET /supporters/{id}`
+    version: u32,
+}
+
+impl TryFrom<Supporter> for SupporterResponse {
+    type Error = SupporterError;
+
+    fn try_from(supporter: Supporter) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: supporter.id,
+            name: supporter.name,
+            email: supporter.email,
+            phone: supporter.phone,
+            notes: supporter.notes,
+            public: supporter.public,
+            created_at: supporter.created_at.to_utc().format(&Rfc3339)?,
+            version: supporter.version,
+        })
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+struct SupporterTotalsResponse {
+    supporter_id: u64,
+    donations: i64,
+    coins: u64,
+    income_eur: Money,
+    first_donated_at: Option<String>,
+    last_donated_at: Option<String>,
 }
 
 #[derive(Serialize, utoipa::ToSchema)]
@@ -62,7 +154,32 @@ struct SupporterIdResponse {
 #[derive(Deserialize, utoipa::ToSchema)]
 pub struct SupporterRequest {
     name: String,
-    donation_id: u64,
+    email: Option<String>,
+    phone: Option<String>,
+    notes: Option<String>,
+    #[serde(default)]
+    public: bool,
+}
+
+/// JSON Merge Patch of a supporter, members that are left out keep their value.
+#[derive(Deserialize, utoipa::ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct SupporterPatch {
+    #[serde(default)]
+    #[schema(value_type = Option<String>)]
+    name: Patch<String>,
+    #[serde(default)]
+    #[schema(value_type = Option<String>)]
+    email: Patch<String>,
+    #[serde(default)]
+    #[schema(value_type = Option<String>)]
+    phone: Patch<String>,
+    #[serde(default)]
+    #[schema(value_type = Option<String>)]
+    notes: Patch<String>,
+    #[serde(default)]
+    #[schema(value_type = Option<bool>)]
+    public: Patch<bool>,
 }
 
 #[utoipa::path(
@@ -76,31 +193,28 @@ pub struct SupporterRequest {
         (
             status = StatusCode::UNAUTHORIZED,
             description = "Not logged in",
+            body = Problem,
+            content_type = "application/problem+json",
         ),
-        (status = StatusCode::INTERNAL_SERVER_ERROR)
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = Problem,
+            content_type = "application/problem+json",
+        )
     ),
 )]
 pub async fn get_supporters(
-    state_pool: State<MySqlPool>,
+    sessions: State<Sessions>,
+    State(supporters): State<Supporters>,
     headers: HeaderMap,
 ) -> ApiResult<impl IntoResponse> {
-    let _ = validate(state_pool.clone(), headers).await?;
-    let supporters: Vec<(u64, String, u64)> =
-        sqlx::query_as("SELECT id, name, donation_id FROM supporters")
-            .fetch_all(&state_pool.0)
-            .await
-            .map_err(SupporterError::DatabaseError)?;
-
+    let _ = validate(sessions, headers).await?;
     let supporters = supporters
+        .list()
+        .await?
         .into_iter()
-        .map(|(a, b, c)| {
-            Ok(SupporterResponse {
-                id: a,
-                name: b,
-                donation_id: c,
-            })
-        })
-        .collect::<ApiResult<Vec<_>>>()?;
+        .map(SupporterResponse::try_from)
+        .collect::<Result<Vec<_>, _>>()?;
 
     Ok((StatusCode::OK, Json(supporters)))
 }
@@ -108,167 +222,450 @@ pub async fn get_supporters(
 #[utoipa::path(
     get,
     path = "/supporters/{id}",
+    params(("id" = u64, Path)),
     responses(
         (
             status = StatusCode::OK,
             body = SupporterResponse,
+            headers(
+                ("ETag" = String, description = "Current version, for `If-Match`"),
+            ),
         ),
         (
             status = StatusCode::NOT_FOUND,
             description = "Supporter not found",
+            body = Problem,
+            content_type = "application/problem+json",
         ),
         (
             status = StatusCode::UNAUTHORIZED,
             description = "Not logged in",
+            body = Problem,
+            content_type = "application/problem+json",
         ),
-        (status = StatusCode::INTERNAL_SERVER_ERROR)
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = Problem,
+            content_type = "application/problem+json",
+        )
     ),
 )]
 pub async fn get_supporter(
-    state_pool: State<MySqlPool>,
+    sessions: State<Sessions>,
+    State(supporters): State<Supporters>,
     headers: HeaderMap,
-    Path(id): Path<u64>,
+    PathParams(id): PathParams<u64>,
 ) -> ApiResult<impl IntoResponse> {
-    let _ = validate(state_pool.clone(), headers).await?;
-    let supporter: (u64, String, u64) = sqlx::query_as(
-        "SELECT id, name, donation_id FROM supporters WHERE supporters.id = ? LIMIT 1",
-    )
-    .bind(id)
-    .fetch_optional(&state_pool.0)
-    .await
-    .map_err(SupporterError::DatabaseError)?
-    .ok_or(SupporterError::NotFound)?;
-
-    let (id, name, donation_id) = supporter;
+    let _ = validate(sessions, headers).await?;
+    let supporter = supporters.get(id).await?.ok_or(SupporterError::NotFound)?;
+    let supporter = SupporterResponse::try_from(supporter)?;
 
-    let supporter = SupporterResponse {
-        id,
-        name,
-        donation_id,
-    };
-
-    Ok((StatusCode::OK, Json(supporter)))
+    Ok((
+        StatusCode::OK,
+        [(header::ETAG, etag::etag(supporter.version))],
+        Json(supporter),
+    ))
 }
 
 #[utoipa::path(
     post,
     path = "/supporters",
+    params(IdempotencyKeyHeader),
+    request_body = SupporterRequest,
     responses(
         (
             status = StatusCode::CREATED,
             body = SupporterIdResponse,
             description = "Successfully added supporter",
         ),
+        (
+            status = StatusCode::UNPROCESSABLE_ENTITY,
+            description = "Invalid request body",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::CONFLICT,
+            description = "A request with the same `Idempotency-Key` is still being processed",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
         (
             status = StatusCode::UNAUTHORIZED,
             description = "Not logged in",
+            body = Problem,
+            content_type = "application/problem+json",
         ),
-        (status = StatusCode::INTERNAL_SERVER_ERROR)
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = Problem,
+            content_type = "application/problem+json",
+        )
     )
 )]
 pub async fn post_supporter(
-    state_pool: State<MySqlPool>,
+    sessions: State<Sessions>,
+    State(supporters): State<Supporters>,
     headers: HeaderMap,
-    Json(req): Json<SupporterRequest>,
+    JsonBody(req): JsonBody<SupporterRequest>,
 ) -> ApiResult<impl IntoResponse> {
-    let _ = validate(state_pool.clone(), headers).await?;
+    let _ = validate(sessions, headers).await?;
 
-    let id = sqlx::query(
-        "INSERT INTO supporters (name, donation_id)
-        VALUES (?, ?)",
-    )
-    .bind(req.name)
-    .bind(req.donation_id)
-    .execute(&state_pool.0)
-    .await
-    .map_err(SupporterError::DatabaseError)?
-    .last_insert_id();
+    let id = supporters
+        .create(NewSupporter {
+            name: req.name,
+            email: req.email,
+            phone: req.phone,
+            notes: req.notes,
+            public: req.public,
+        })
+        .await?;
 
     Ok((StatusCode::CREATED, Json(SupporterIdResponse { id })))
 }
 
+/// The supporter to change, if its version matches `If-Match`.
+async fn current_supporter(
+    supporters: &dyn SupporterRepo,
+    id: u64,
+    if_match: &IfMatch,
+) -> Result<Supporter, SupporterError> {
+    let supporter = supporters.get(id).await?.ok_or(SupporterError::NotFound)?;
+    if if_match.matches(supporter.version) {
+        Ok(supporter)
+    } else {
+        Err(SupporterError::PreconditionFailed)
+    }
+}
+
 #[utoipa::path(
     put,
     path = "/supporters/{id}",
+    params(("id" = u64, Path), IfMatchHeader),
+    request_body = SupporterRequest,
     responses(
         (
             status = StatusCode::OK,
-            description = "Successfully added supporter",
+            description = "Successfully updated supporter",
+            headers(
+                ("ETag" = String, description = "New version"),
+            ),
+        ),
+        (
+            status = StatusCode::PRECONDITION_FAILED,
+            description = "`If-Match` does not match the current version",
+            body = Problem,
+            content_type = "application/problem+json",
         ),
         (
             status = StatusCode::NOT_FOUND,
             description = "Supporter not found",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::UNPROCESSABLE_ENTITY,
+            description = "Invalid request body",
+            body = Problem,
+            content_type = "application/problem+json",
         ),
         (
             status = StatusCode::UNAUTHORIZED,
             description = "Not logged in",
+            body = Problem,
+            content_type = "application/problem+json",
         ),
-        (status = StatusCode::INTERNAL_SERVER_ERROR)
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = Problem,
+            content_type = "application/problem+json",
+        )
     )
 )]
 pub async fn put_supporter(
-    state_pool: State<MySqlPool>,
+    sessions: State<Sessions>,
+    State(supporters): State<Supporters>,
     headers: HeaderMap,
-    Path(id): Path<u64>,
-    Json(req): Json<SupporterRequest>,
+    PathParams(id): PathParams<u64>,
+    JsonBody(req): JsonBody<SupporterRequest>,
 ) -> ApiResult<impl IntoResponse> {
-    let _ = validate(state_pool.clone(), headers).await?;
-
-    let res = sqlx::query(
-        "UPDATE supporters 
-            SET 
-                name = ?,
-                donation_id = ?
-        WHERE id = ?",
+    let if_match = IfMatch::from_headers(&headers);
+    let _ = validate(sessions, headers).await?;
+
+    let supporter = current_supporter(&*supporters, id, &if_match).await?;
+    supporters
+        .update(
+            id,
+            supporter.version,
+            SupporterChanges {
+                name: Some(req.name),
+                email: Some(req.email),
+                phone: Some(req.phone),
+                notes: Some(req.notes),
+                public: Some(req.public),
+            },
+        )
+        .await?;
+
+    Ok((
+        StatusCode::OK,
+        [(header::ETAG, etag::etag(supporter.version + 1))],
+    ))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/supporters/{id}",
+    params(("id" = u64, Path), IfMatchHeader),
+    request_body(
+        content = SupporterPatch,
+        content_type = "application/merge-patch+json",
+    ),
+    responses(
+        (
+            status = StatusCode::OK,
+            body = SupporterResponse,
+            description = "The updated supporter",
+            headers(
+                ("ETag" = String, description = "New version"),
+            ),
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            description = "Supporter not found",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::PRECONDITION_FAILED,
+            description = "`If-Match` does not match the current version",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::UNPROCESSABLE_ENTITY,
+            description = "Invalid patch",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = Problem,
+            content_type = "application/problem+json",
+        )
     )
-    .bind(req.name)
-    .bind(req.donation_id)
-    .bind(id)
-    .execute(&state_pool.0)
-    .await
-    .map_err(SupporterError::DatabaseError)?;
-
-    if res.rows_affected() == 0 {
-        Err(SupporterError::NotFound.into())
-    } else {
-        Ok(StatusCode::OK)
-    }
+)]
+pub async fn patch_supporter(
+    sessions: State<Sessions>,
+    State(supporters): State<Supporters>,
+    headers: HeaderMap,
+    PathParams(id): PathParams<u64>,
+    JsonBody(patch): JsonBody<SupporterPatch>,
+) -> ApiResult<impl IntoResponse> {
+    let if_match = IfMatch::from_headers(&headers);
+    let _ = validate(sessions, headers).await?;
+
+    let name = patch
+        .name
+        .required("name")
+        .map_err(SupporterError::InvalidPatch)?;
+    let public = patch
+        .public
+        .required("public")
+        .map_err(SupporterError::InvalidPatch)?;
+    let changes = SupporterChanges {
+        name,
+        email: patch.email.nullable(),
+        phone: patch.phone.nullable(),
+        notes: patch.notes.nullable(),
+        public,
+    };
+
+    let supporter = current_supporter(&*supporters, id, &if_match).await?;
+    supporters.update(id, supporter.version, changes).await?;
+
+    let supporter = supporters.get(id).await?.ok_or(SupporterError::NotFound)?;
+    let supporter = SupporterResponse::try_from(supporter)?;
+
+    Ok((
+        StatusCode::OK,
+        [(header::ETAG, etag::etag(supporter.version))],
+        Json(supporter),
+    ))
 }
 
 #[utoipa::path(
     delete,
     path = "/supporters/{id}",
+    params(("id" = u64, Path), IfMatchHeader),
     responses(
         (
             status = StatusCode::NO_CONTENT,
         ),
+        (
+            status = StatusCode::PRECONDITION_FAILED,
+            description = "`If-Match` does not match the current version",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::CONFLICT,
+            description = "The supporter still has donation schedules",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
         (
             status = StatusCode::NOT_FOUND,
             description = "Supporter not found",
+            body = Problem,
+            content_type = "application/problem+json",
         ),
         (
             status = StatusCode::UNAUTHORIZED,
             description = "Not logged in",
+            body = Problem,
+            content_type = "application/problem+json",
         ),
-        (status = StatusCode::INTERNAL_SERVER_ERROR)
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = Problem,
+            content_type = "application/problem+json",
+        )
     )
 )]
 pub async fn delete_supporter(
-    state_pool: State<MySqlPool>,
+    sessions: State<Sessions>,
+    State(supporters): State<Supporters>,
     headers: HeaderMap,
-    Path(id): Path<u64>,
+    PathParams(id): PathParams<u64>,
 ) -> ApiResult<impl IntoResponse> {
-    let _ = validate(state_pool.clone(), headers).await?;
+    let if_match = IfMatch::from_headers(&headers);
+    let _ = validate(sessions, headers).await?;
 
-    let res = sqlx::query("DELETE FROM supporters WHERE id = ?")
-        .bind(id)
-        .execute(&state_pool.0)
-        .await
-        .map_err(SupporterError::DatabaseError)?;
+    let supporter = current_supporter(&*supporters, id, &if_match).await?;
+    supporters.delete(id, supporter.version).await?;
 
-    if res.rows_affected() == 0 {
-        Err(SupporterError::NotFound.into())
-    } else {
-        Ok(StatusCode::NO_CONTENT)
-    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn ensure_exists(supporters: &dyn SupporterRepo, id: u64) -> Result<(), SupporterError> {
+    supporters
+        .get(id)
+        .await?
+        .ok_or(SupporterError::NotFound)
+        .map(|_| ())
+}
+
+#[utoipa::path(
+    get,
+    path = "/supporters/{id}/donations",
+    params(("id" = u64, Path), DonationFilter),
+    responses(
+        (
+            status = StatusCode::OK,
+            body = Vec<DonationResponse>,
+            description = "The supporter's donations, oldest first",
+        ),
+        (
+            status = StatusCode::BAD_REQUEST,
+            description = "Invalid filter",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            description = "Supporter not found",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = Problem,
+            content_type = "application/problem+json",
+        )
+    ),
+)]
+pub async fn get_supporter_donations(
+    sessions: State<Sessions>,
+    State(supporters): State<Supporters>,
+    State(donations): State<Donations>,
+    headers: HeaderMap,
+    PathParams(id): PathParams<u64>,
+    QueryParams(filter): QueryParams<DonationFilter>,
+) -> ApiResult<impl IntoResponse> {
+    let _ = validate(sessions, headers).await?;
+    let filter = filter.parse()?.for_supporter(id);
+    ensure_exists(&*supporters, id).await?;
+
+    let donations = donations::list_donations(&*donations, &filter).await?;
+
+    Ok((StatusCode::OK, Json(donations)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/supporters/{id}/totals",
+    params(("id" = u64, Path)),
+    responses(
+        (
+            status = StatusCode::OK,
+            body = SupporterTotalsResponse,
+            description = "Lifetime totals of the supporter's donations",
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            description = "Supporter not found",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = Problem,
+            content_type = "application/problem+json",
+        )
+    ),
+)]
+pub async fn get_supporter_totals(
+    sessions: State<Sessions>,
+    State(supporters): State<Supporters>,
+    headers: HeaderMap,
+    PathParams(id): PathParams<u64>,
+) -> ApiResult<impl IntoResponse> {
+    let _ = validate(sessions, headers).await?;
+    ensure_exists(&*supporters, id).await?;
+
+    let totals = supporters.totals(id).await?;
+
+    let format = |t: Option<OffsetDateTime>| t.map(|t| t.to_utc().format(&Rfc3339)).transpose();
+
+    Ok((
+        StatusCode::OK,
+        Json(SupporterTotalsResponse {
+            supporter_id: id,
+            donations: totals.donations,
+            coins: totals.coins,
+            income_eur: totals.income_eur,
+            first_donated_at: format(totals.first_donated_at)
+                .map_err(SupporterError::FormatError)?,
+            last_donated_at: format(totals.last_donated_at).map_err(SupporterError::FormatError)?,
+        }),
+    ))
 }
@@ -0,0 +1,549 @@
+use super::SupporterError;
+use crate::{
+    ApiResult,
+    error::{JsonBody, Problem, QueryParams},
+    idempotency::IdempotencyKeyHeader,
+    repo::{Contact, MergeCandidate, Merges, Sessions},
+    users::auth::validate::{self, validate},
+};
+use axum::{
+    Json,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use time::format_description::well_known::Rfc3339;
+
+#[derive(utoipa::OpenApi)]
+#[openapi(paths(get_duplicate_supporters, merge_supporters, get_supporter_merges))]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    ApiDoc::openapi()
+}
+
+/// Default name of supporters who did not give one, never suggested as a duplicate.
+const ANONYMOUS: &str = "anonymous";
+
+/// Number of following names in alphabetical order each name is compared with.
+/// Names further apart rarely share enough of a prefix to be similar.
+const NAME_WINDOW: usize = 50;
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct DuplicatesQuery {
+    /// Minimum similarity between 0 and 1 for a pair to be suggested, defaults to 0.85
+    min_score: Option<f64>,
+    /// Maximum number of suggestions, defaults to 100
+    limit: Option<usize>,
+}
+
+#[derive(Clone, Serialize, utoipa::ToSchema)]
+struct DuplicateCandidate {
+    id: u64,
+    name: String,
+    email: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "snake_case")]
+enum DuplicateReason {
+    SameEmail,
+    SameName,
+    /// One name is the other with further name parts or initials, e.g. "Bob" and "Bob S."
+    NamePrefix,
+    SimilarName,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+struct DuplicateSuggestion {
+    supporters: [DuplicateCandidate; 2],
+    /// Similarity between 0 and 1
+    score: f64,
+    reasons: Vec<DuplicateReason>,
+}
+
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct MergeRequest {
+    /// Supporter that is kept
+    canonical_id: u64,
+    /// Supporters whose donations and schedules are moved to the canonical supporter before they
+    /// are deleted
+    duplicate_ids: Vec<u64>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+struct MergeResponse {
+    canonical_id: u64,
+    merged_ids: Vec<u64>,
+    donations_moved: u64,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+struct MergeLogEntry {
+    id: u64,
+    /// Null if the canonical supporter was deleted since
+    canonical_id: Option<u64>,
+    merged_id: u64,
+    merged_name: String,
+    merged_email: Option<String>,
+    /// Donations moved from the merged supporter to the canonical one
+    donation_ids: Vec<u64>,
+    /// Account that performed the merge, null if it was deleted since
+    account_id: Option<u64>,
+    merged_at: String,
+}
+
+/// Contact details only fill gaps, notes of both are kept. Consent to being
+/// listed publicly is not carried over from the duplicate.
+fn combine(canonical: Contact, duplicate: Contact) -> Contact {
+    Contact {
+        email: canonical.email.or(duplicate.email),
+        phone: canonical.phone.or(duplicate.phone),
+        notes: match (canonical.notes, duplicate.notes) {
+            (Some(a), Some(b)) => Some(format!("{a}\n\n{b}")),
+            (a, b) => a.or(b),
+        },
+    }
+}
+
+/// Lowercases and strips punctuation so that "Bob S." and " bob  s" compare equal.
+fn normalize(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_alphanumeric() {
+                c.to_lowercase().next().unwrap_or(c)
+            } else {
+                ' '
+            }
+        })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Whether the shorter name's parts start the longer one, allowing initials
+/// to stand for whole parts.
+fn is_name_prefix(a: &str, b: &str) -> bool {
+    let (a, b): (Vec<_>, Vec<_>) = (a.split(' ').collect(), b.split(' ').collect());
+    let (short, long) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+    short.len() < long.len()
+        && short.iter().zip(&long).all(|(s, l)| {
+            s == l
+                || (s.chars().count() == 1 && l.starts_with(s))
+                || (l.chars().count() == 1 && s.starts_with(l))
+        })
+}
+
+/// Jaro-Winkler similarity of two strings between 0 and 1.
+fn jaro_winkler(a: &str, b: &str) -> f64 {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    if a.is_empty() || b.is_empty() {
+        return if a == b { 1.0 } else { 0.0 };
+    }
+
+    let window = (a.len().max(b.len()) / 2).saturating_sub(1);
+    let mut b_matched = vec![false; b.len()];
+    let mut a_matches = Vec::new();
+    for (i, &c) in a.iter().enumerate() {
+        let start = i.saturating_sub(window);
+        let end = (i + window + 1).min(b.len());
+        if let Some(j) = (start..end).find(|&j| !b_matched[j] && b[j] == c) {
+            b_matched[j] = true;
+            a_matches.push(c);
+        }
+    }
+    if a_matches.is_empty() {
+        return 0.0;
+    }
+
+    let b_matches = b
+        .iter()
+        .zip(&b_matched)
+        .filter(|(_, m)| **m)
+        .map(|(c, _)| *c);
+    let transpositions = a_matches
+        .iter()
+        .zip(b_matches)
+        .filter(|(x, y)| **x != *y)
+        .count();
+    let m = a_matches.len() as f64;
+    let jaro =
+        (m / a.len() as f64 + m / b.len() as f64 + (m - transpositions as f64 / 2.0) / m) / 3.0;
+
+    let prefix = a.iter().zip(&b).take(4).take_while(|(x, y)| x == y).count();
+    jaro + prefix as f64 * 0.1 * (1.0 - jaro)
+}
+
+/// Pairs of supporters worth comparing, as indices into `supporters` with the
+/// lower one first: supporters sharing an email, and each name with its
+/// [`NAME_WINDOW`] successors in alphabetical order, so that the number of
+/// pairs grows linearly with the number of supporters.
+fn candidate_pairs(supporters: &[(DuplicateCandidate, String)]) -> BTreeSet<(usize, usize)> {
+    let mut pairs = BTreeSet::new();
+
+    let mut by_email: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, (supporter, _)) in supporters.iter().enumerate() {
+        if let Some(email) = &supporter.email {
+            by_email
+                .entry(email.trim().to_lowercase())
+                .or_default()
+                .push(i);
+        }
+    }
+    for bucket in by_email.values() {
+        for (n, &i) in bucket.iter().enumerate() {
+            pairs.extend(bucket[n + 1..].iter().take(NAME_WINDOW).map(|&j| (i, j)));
+        }
+    }
+
+    let mut by_name: Vec<_> = (0..supporters.len())
+        .filter(|&i| {
+            let name = &supporters[i].1;
+            !name.is_empty() && name != ANONYMOUS
+        })
+        .collect();
+    by_name.sort_by(|&i, &j| supporters[i].1.cmp(&supporters[j].1));
+    for (n, &i) in by_name.iter().enumerate() {
+        pairs.extend(
+            by_name[n + 1..]
+                .iter()
+                .take(NAME_WINDOW)
+                .map(|&j| (i.min(j), i.max(j))),
+        );
+    }
+
+    pairs
+}
+
+/// Why two supporters are probably the same person, if they are at least
+/// `min_score` similar.
+fn compare(
+    (a, a_name): &(DuplicateCandidate, String),
+    (b, b_name): &(DuplicateCandidate, String),
+    min_score: f64,
+) -> Option<DuplicateSuggestion> {
+    let mut reasons = Vec::new();
+    let mut score: f64 = 0.0;
+
+    if let (Some(x), Some(y)) = (&a.email, &b.email)
+        && x.trim().eq_ignore_ascii_case(y.trim())
+    {
+        reasons.push(DuplicateReason::SameEmail);
+        score = 1.0;
+    }
+
+    let named =
+        !a_name.is_empty() && !b_name.is_empty() && a_name != ANONYMOUS && b_name != ANONYMOUS;
+    if named {
+        if a_name == b_name {
+            reasons.push(DuplicateReason::SameName);
+            score = 1.0;
+        } else if is_name_prefix(a_name, b_name) {
+            reasons.push(DuplicateReason::NamePrefix);
+            score = score.max(0.9);
+        } else {
+            let similarity = jaro_winkler(a_name, b_name);
+            if similarity >= min_score {
+                reasons.push(DuplicateReason::SimilarName);
+                score = score.max(similarity);
+            }
+        }
+    }
+
+    (!reasons.is_empty() && score >= min_score).then(|| DuplicateSuggestion {
+        supporters: [a.clone(), b.clone()],
+        score: (score * 1000.0).round() / 1000.0,
+        reasons,
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/supporters/duplicates",
+    params(DuplicatesQuery),
+    responses(
+        (
+            status = StatusCode::OK,
+            body = Vec<DuplicateSuggestion>,
+            description = "Pairs of supporters that are probably the same person, most likely \
+                first. Each name is only compared with the 50 names following it alphabetically.",
+        ),
+        (
+            status = StatusCode::BAD_REQUEST,
+            description = "Invalid query",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = Problem,
+            content_type = "application/problem+json",
+        )
+    ),
+)]
+pub async fn get_duplicate_supporters(
+    sessions: State<Sessions>,
+    State(merges): State<Merges>,
+    headers: HeaderMap,
+    QueryParams(query): QueryParams<DuplicatesQuery>,
+) -> ApiResult<impl IntoResponse> {
+    let _ = validate(sessions, headers).await?;
+    let min_score = query.min_score.unwrap_or(0.85);
+    if !(0.0..=1.0).contains(&min_score) {
+        return Err(SupporterError::InvalidMinScore.into());
+    }
+
+    let supporters: Vec<_> = merges
+        .candidates()
+        .await?
+        .into_iter()
+        .map(|MergeCandidate { id, name, email }| {
+            let normalized = normalize(&name);
+            let email = email.filter(|e| !e.trim().is_empty());
+            (DuplicateCandidate { id, name, email }, normalized)
+        })
+        .collect();
+
+    let mut suggestions: Vec<_> = candidate_pairs(&supporters)
+        .into_iter()
+        .filter_map(|(i, j)| compare(&supporters[i], &supporters[j], min_score))
+        .collect();
+
+    suggestions.sort_by(|x, y| y.score.total_cmp(&x.score));
+    suggestions.truncate(query.limit.unwrap_or(100));
+
+    Ok((StatusCode::OK, Json(suggestions)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/supporters/merge",
+    params(IdempotencyKeyHeader),
+    request_body = MergeRequest,
+    responses(
+        (
+            status = StatusCode::OK,
+            body = MergeResponse,
+            description = "Duplicates merged into the canonical supporter",
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            description = "One of the supporters does not exist",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::UNPROCESSABLE_ENTITY,
+            description = "Invalid request body",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::CONFLICT,
+            description = "A request with the same `Idempotency-Key` is still being processed",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = Problem,
+            content_type = "application/problem+json",
+        )
+    )
+)]
+pub async fn merge_supporters(
+    sessions: State<Sessions>,
+    State(merges): State<Merges>,
+    headers: HeaderMap,
+    JsonBody(req): JsonBody<MergeRequest>,
+) -> ApiResult<impl IntoResponse> {
+    let account_id = validate::session_account(&*sessions.0, headers).await?;
+
+    let duplicate_ids: BTreeSet<u64> = req.duplicate_ids.into_iter().collect();
+    if duplicate_ids.is_empty() {
+        return Err(SupporterError::InvalidMerge("duplicate_ids must not be empty").into());
+    }
+    if duplicate_ids.contains(&req.canonical_id) {
+        return Err(
+            SupporterError::InvalidMerge("duplicate_ids must not contain canonical_id").into(),
+        );
+    }
+
+    let duplicate_ids: Vec<u64> = duplicate_ids.into_iter().collect();
+    let donations_moved = merges
+        .merge(req.canonical_id, &duplicate_ids, account_id, combine)
+        .await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(MergeResponse {
+            canonical_id: req.canonical_id,
+            merged_ids: duplicate_ids,
+            donations_moved,
+        }),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/supporters/merges",
+    responses(
+        (
+            status = StatusCode::OK,
+            body = Vec<MergeLogEntry>,
+            description = "Audit trail of supporter merges, newest first",
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = Problem,
+            content_type = "application/problem+json",
+        )
+    ),
+)]
+pub async fn get_supporter_merges(
+    sessions: State<Sessions>,
+    State(merges): State<Merges>,
+    headers: HeaderMap,
+) -> ApiResult<impl IntoResponse> {
+    let _ = validate(sessions, headers).await?;
+
+    let merges = merges
+        .merges()
+        .await?
+        .into_iter()
+        .map(|merge| {
+            Ok(MergeLogEntry {
+                id: merge.id,
+                canonical_id: merge.canonical_id,
+                merged_id: merge.merged_id,
+                merged_name: merge.merged_name,
+                merged_email: merge.merged_email,
+                donation_ids: serde_json::from_str(&merge.donation_ids).unwrap_or_default(),
+                account_id: merge.account_id,
+                merged_at: merge
+                    .merged_at
+                    .to_utc()
+                    .format(&Rfc3339)
+                    .map_err(SupporterError::FormatError)?,
+            })
+        })
+        .collect::<ApiResult<Vec<_>>>()?;
+
+    Ok((StatusCode::OK, Json(merges)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn supporters(names: &[(&str, Option<&str>)]) -> Vec<(DuplicateCandidate, String)> {
+        names
+            .iter()
+            .zip(1..)
+            .map(|(&(name, email), id)| {
+                let candidate = DuplicateCandidate {
+                    id,
+                    name: name.to_owned(),
+                    email: email.map(str::to_owned),
+                };
+                (candidate, normalize(name))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn names_are_normalized() {
+        assert_eq!(normalize(" Bob  S."), "bob s");
+        assert_eq!(normalize("ÉMILE-Zola"), "émile zola");
+        assert_eq!(normalize("O'Brien, Pat"), "o brien pat");
+        assert_eq!(normalize("..."), "");
+    }
+
+    #[test]
+    fn name_prefixes_allow_initials() {
+        assert!(is_name_prefix("bob", "bob smith"));
+        assert!(is_name_prefix("bob s", "bob smith jr"));
+        assert!(is_name_prefix("b smith jr", "bob smith"));
+        assert!(!is_name_prefix("bob smith", "bob smith"));
+        assert!(!is_name_prefix("bob", "bobby smith"));
+        assert!(!is_name_prefix("ada s", "ada lovelace king"));
+    }
+
+    #[test]
+    fn jaro_winkler_matches_reference_values() {
+        let similarity = |a, b| (jaro_winkler(a, b) * 1000.0).round() / 1000.0;
+        assert_eq!(similarity("martha", "marhta"), 0.961);
+        assert_eq!(similarity("dwayne", "duane"), 0.84);
+        assert_eq!(similarity("dixon", "dicksonx"), 0.813);
+        assert_eq!(similarity("ada", "ada"), 1.0);
+        assert_eq!(similarity("abc", "xyz"), 0.0);
+        assert_eq!(similarity("", ""), 1.0);
+        assert_eq!(similarity("ada", ""), 0.0);
+        assert_eq!(
+            jaro_winkler("dwayne", "duane"),
+            jaro_winkler("duane", "dwayne")
+        );
+    }
+
+    #[test]
+    fn duplicates_are_found_by_email_and_name() {
+        let supporters = supporters(&[
+            ("Bob Smith", Some("bob@example.com")),
+            ("Anonymous", Some(" BOB@example.com")),
+            ("bob", None),
+            ("Robert", None),
+            ("Anonymous", None),
+        ]);
+        let found: Vec<_> = candidate_pairs(&supporters)
+            .into_iter()
+            .filter_map(|(i, j)| compare(&supporters[i], &supporters[j], 0.85))
+            .map(|suggestion| {
+                let [a, b] = suggestion.supporters;
+                (a.id, b.id, suggestion.reasons)
+            })
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (1, 2, vec![DuplicateReason::SameEmail]),
+                (1, 3, vec![DuplicateReason::NamePrefix]),
+            ]
+        );
+    }
+
+    #[test]
+    fn names_are_compared_with_their_alphabetical_neighbours() {
+        let names: Vec<_> = (0..1000).map(|n| format!("Supporter {n:04}")).collect();
+        let supporters = supporters(
+            &names
+                .iter()
+                .map(|name| (name.as_str(), None))
+                .collect::<Vec<_>>(),
+        );
+        let pairs = candidate_pairs(&supporters);
+        assert!(pairs.len() <= supporters.len() * NAME_WINDOW);
+        assert!(pairs.contains(&(0, NAME_WINDOW)));
+        assert!(!pairs.contains(&(0, NAME_WINDOW + 1)));
+    }
+}
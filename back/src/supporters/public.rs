@@ -0,0 +1,140 @@
+use crate::{
+    ApiResult,
+    error::{Problem, QueryParams},
+    money::Money,
+    repo::Supporters,
+};
+use axum::{
+    extract::State,
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+#[derive(utoipa::OpenApi)]
+#[openapi(paths(get_public_supporters))]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    ApiDoc::openapi()
+}
+
+const CACHE_CONTROL: &str = "public, max-age=300, stale-while-revalidate=3600";
+
+/// Lower bounds of the contribution tiers in EUR cents, highest first.
+const TIERS: [i64; 5] = [100_000, 50_000, 10_000, 5_000, 1_000];
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct PublicSupportersQuery {
+    /// Include each supporter's contribution tier
+    #[serde(default)]
+    tiers: bool,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+struct PublicSupporter {
+    name: String,
+    /// Lower bound of the tier of the supporter's total contributions in EUR,
+    /// one of 10, 50, 100, 500 and 1000. Omitted below 10 EUR or without `tiers=true`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tier_eur: Option<Money>,
+}
+
+fn tier(total: Money) -> Option<Money> {
+    TIERS
+        .into_iter()
+        .find(|&tier| total.cents() >= tier)
+        .map(Money::from_cents)
+}
+
+fn etag(body: &[u8]) -> String {
+    let digest = Sha256::digest(body);
+    let hex: String = digest[..16].iter().map(|b| format!("{b:02x}")).collect();
+    format!("\"{hex}\"")
+}
+
+fn not_modified(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(|v| v.trim())
+        .any(|v| v == "*" || v.trim_start_matches("W/") == etag)
+}
+
+#[utoipa::path(
+    get,
+    path = "/public/supporters",
+    params(PublicSupportersQuery),
+    responses(
+        (
+            status = StatusCode::OK,
+            body = Vec<PublicSupporter>,
+            description = "Supporters who consented to being listed, ordered by name",
+            headers(
+                ("ETag" = String),
+                ("Cache-Control" = String),
+            ),
+        ),
+        (
+            status = StatusCode::NOT_MODIFIED,
+            description = "The list still matches `If-None-Match`",
+        ),
+        (
+            status = StatusCode::TOO_MANY_REQUESTS,
+            description = "Rate limit exceeded",
+            body = String,
+            content_type = "text/plain",
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = Problem,
+            content_type = "application/problem+json",
+        )
+    ),
+)]
+pub async fn get_public_supporters(
+    State(supporters): State<Supporters>,
+    headers: HeaderMap,
+    QueryParams(query): QueryParams<PublicSupportersQuery>,
+) -> ApiResult<Response> {
+    let supporters: Vec<_> = supporters
+        .public_totals()
+        .await?
+        .into_iter()
+        .map(|(name, total)| PublicSupporter {
+            name,
+            tier_eur: if query.tiers { tier(total) } else { None },
+        })
+        .collect();
+
+    let body = serde_json::to_vec(&supporters).expect("serializable supporters");
+    let etag = etag(&body);
+    let headers_out = [
+        (
+            header::ETAG,
+            HeaderValue::from_str(&etag).expect("hex etag"),
+        ),
+        (
+            header::CACHE_CONTROL,
+            HeaderValue::from_static(CACHE_CONTROL),
+        ),
+    ];
+
+    if not_modified(&headers, &etag) {
+        return Ok((StatusCode::NOT_MODIFIED, headers_out).into_response());
+    }
+
+    Ok((
+        StatusCode::OK,
+        headers_out,
+        [(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static("application/json"),
+        )],
+        body,
+    )
+        .into_response())
+}
@@ -0,0 +1,111 @@
+//! Supervision of background jobs and graceful shutdown.
+
+use crate::health::{BackgroundTasks, Heartbeat};
+use std::{
+    future::Future,
+    time::{Duration, Instant},
+};
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
+
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_mins(5);
+pub const TASK_RESTARTS: &str = "background_task_restarts_total";
+
+/// Restarts background jobs that panic or return, and stops them on shutdown.
+pub struct Supervisor {
+    tasks: BackgroundTasks,
+    tracker: TaskTracker,
+    shutdown: CancellationToken,
+}
+
+impl Supervisor {
+    pub fn new(tasks: BackgroundTasks, shutdown: CancellationToken) -> Self {
+        Self {
+            tasks,
+            tracker: TaskTracker::new(),
+            shutdown,
+        }
+    }
+
+    /// Runs the job created by `job` until shutdown. It is expected to run
+    /// forever and to beat every `interval`, a failed job is logged and
+    /// restarted with exponential backoff.
+    pub fn spawn<F, Fut>(&self, name: &'static str, interval: Duration, job: F)
+    where
+        F: Fn(Heartbeat) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let tasks = self.tasks.clone();
+        let shutdown = self.shutdown.clone();
+        self.tracker.spawn(async move {
+            let mut backoff = MIN_BACKOFF;
+            loop {
+                let started = Instant::now();
+                let mut handle = tokio::spawn(job(tasks.register(name, interval)));
+                let result = tokio::select! {
+                    result = &mut handle => result,
+                    _ = shutdown.cancelled() => {
+                        handle.abort();
+                        return;
+                    }
+                };
+
+                match result {
+                    Ok(()) => tracing::error!(task = name, "Background task returned"),
+                    Err(e) if e.is_panic() => {
+                        tracing::error!(task = name, "Background task panicked")
+                    }
+                    Err(e) => tracing::error!(task = name, error = %e, "Background task failed"),
+                }
+                metrics::counter!(TASK_RESTARTS, "task" => name).increment(1);
+                if started.elapsed() > MAX_BACKOFF {
+                    backoff = MIN_BACKOFF;
+                }
+                tracing::info!(task = name, ?backoff, "Restarting background task");
+
+                tokio::select! {
+                    _ = tokio::time::sleep(backoff) => {}
+                    _ = shutdown.cancelled() => return,
+                }
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+        });
+    }
+
+    /// Tracks work that must finish rather than be aborted on shutdown, which
+    /// then waits for it.
+    pub fn tracker(&self) -> TaskTracker {
+        self.tracker.clone()
+    }
+
+    /// Stops all jobs, which is only meant to be called after the server has shut down.
+    pub async fn shutdown(self) {
+        self.shutdown.cancel();
+        self.tracker.close();
+        self.tracker.wait().await;
+    }
+}
+
+/// Cancels `shutdown` on SIGINT or SIGTERM.
+pub async fn watch_signals(shutdown: CancellationToken) {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Unable to listen for SIGINT");
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Unable to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => tracing::info!("Received SIGINT, shutting down"),
+        _ = terminate => tracing::info!("Received SIGTERM, shutting down"),
+    }
+    shutdown.cancel();
+}
@@ -0,0 +1,480 @@
+//! End-to-end tests of the router built by [`app`], each against a throwaway
+//! database: in-memory SQLite by default, or a new database on the MySQL server
+//! of `TEST_DATABASE_URL`, e.g. `mysql://root@localhost/db`. The MySQL databases
+//! are named `api_test_*` and dropped when their test finishes, unless
+//! `KEEP_TEST_DB` is set to leave them behind for inspection.
+
+mod auth;
+mod campaigns;
+mod donations;
+mod errors;
+mod exchange_rates;
+mod idempotency;
+mod jobs;
+mod memory;
+mod openapi;
+mod schedules;
+mod sessions;
+mod supporters;
+mod webhooks;
+
+use crate::{
+    app,
+    config::{
+        Config, CookieConfig, CorsConfig, EmailConfig, LogFormat, MetricsConfig, RateLimit,
+        RateLimitConfig, SameSite, SessionConfig, WebhookConfig,
+    },
+    db::{Database, MYSQL_MIGRATOR},
+    health::BackgroundTasks,
+    jobs::Scheduler,
+    repo::memory::MemoryRepo,
+    state::AppState,
+};
+use axum::{
+    Router,
+    body::{Body, Bytes, to_bytes},
+    extract::ConnectInfo,
+    http::{HeaderMap, Method, Request, StatusCode, header, request},
+};
+use hmac::{Hmac, Mac};
+use metrics_exporter_prometheus::PrometheusBuilder;
+use rand::Rng;
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::Value;
+use sha2::Sha256;
+use sqlx::{MySqlPool, migrate::Migrator};
+use std::{
+    borrow::Cow,
+    env,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tower::ServiceExt;
+
+pub const EMAIL: &str = "treasurer@studio-matic.org";
+pub const PASSWORD: &str = "correct horse battery staple";
+pub const WEBHOOK_SECRET: &str = "whsec_test";
+
+/// `X-Webhook-Signature` of `body` as added by the relay of Ko-fi and PayPal.
+pub fn webhook_signature(body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(WEBHOOK_SECRET.as_bytes()).expect("any key length");
+    mac.update(body);
+    let signature: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect();
+    format!("sha256={signature}")
+}
+
+/// Settings of a development setup, without rate limits getting in the way.
+pub fn test_config() -> Config {
+    let unlimited = || RateLimit {
+        period: Duration::from_millis(1),
+        burst_size: 10_000,
+    };
+    Config {
+        database_url: String::new(),
+        port: 0,
+        log_format: LogFormat::Pretty,
+        instance_id: "test".to_owned(),
+        cors: CorsConfig {
+            allowed_origins: Vec::new(),
+            allow_any_origin: true,
+        },
+        session: SessionConfig {
+            max_age: Duration::from_secs(3600),
+            cleanup_schedule: "*/5 * * * *".parse().expect("valid schedule"),
+        },
+        cookie: CookieConfig {
+            secure: true,
+            same_site: SameSite::Lax,
+        },
+        email: EmailConfig {
+            check_deliverability: false,
+        },
+        rate_limit: RateLimitConfig {
+            api: unlimited(),
+            public: unlimited(),
+        },
+        metrics: MetricsConfig {
+            port: None,
+            token: None,
+        },
+        drain_timeout: Duration::from_secs(1),
+        webhooks: WebhookConfig::default(),
+    }
+}
+
+/// The MySQL migrations without the `CREATE DATABASE db` and `USE db` of the
+/// first one, which would move the connection off the throwaway database.
+fn throwaway_migrator() -> Migrator {
+    let migrations = MYSQL_MIGRATOR
+        .iter()
+        .map(|migration| {
+            let mut migration = migration.clone();
+            if migration.version == 1 {
+                let sql: String = migration
+                    .sql
+                    .lines()
+                    .filter(|line| {
+                        !matches!(line.trim(), "CREATE DATABASE IF NOT EXISTS db;" | "USE db;")
+                    })
+                    .map(|line| format!("{line}\n"))
+                    .collect();
+                assert_ne!(sql, migration.sql, "first migration no longer selects `db`");
+                migration.sql = Cow::Owned(sql);
+            }
+            migration
+        })
+        .collect();
+    Migrator {
+        migrations: Cow::Owned(migrations),
+        ..Migrator::DEFAULT
+    }
+}
+
+/// Drops the MySQL database of a test once it finishes, unless `KEEP_TEST_DB` is set.
+struct DropDatabase {
+    url: String,
+    name: String,
+}
+
+impl Drop for DropDatabase {
+    fn drop(&mut self) {
+        if env::var_os("KEEP_TEST_DB").is_some() {
+            return;
+        }
+        let (url, name) = (self.url.clone(), self.name.clone());
+        // the runtime of the test cannot be blocked on, so another thread drops it
+        let dropped = std::thread::spawn(move || {
+            tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .expect("Unable to start a runtime")
+                .block_on(async {
+                    let server = MySqlPool::connect(&url).await?;
+                    sqlx::query(&format!("DROP DATABASE {name}"))
+                        .execute(&server)
+                        .await?;
+                    server.close().await;
+                    Ok::<_, sqlx::Error>(())
+                })
+        })
+        .join();
+        if let Ok(Err(e)) = dropped {
+            eprintln!("Unable to drop test database {}: {e}", self.name);
+        }
+    }
+}
+
+async fn throwaway_database() -> (Database, Option<DropDatabase>) {
+    match env::var("TEST_DATABASE_URL") {
+        Ok(url) => {
+            let name: String = rand::rng()
+                .sample_iter(&rand::distr::Alphanumeric)
+                .take(12)
+                .map(|c| char::from(c).to_ascii_lowercase())
+                .collect();
+            let name = format!("api_test_{name}");
+            let server = MySqlPool::connect(&url)
+                .await
+                .expect("Unable to connect to TEST_DATABASE_URL");
+            sqlx::query(&format!("CREATE DATABASE {name}"))
+                .execute(&server)
+                .await
+                .expect("Unable to create test database");
+            server.close().await;
+            let guard = DropDatabase {
+                url: url.clone(),
+                name: name.clone(),
+            };
+            let (server_url, _) = url
+                .rsplit_once('/')
+                .expect("TEST_DATABASE_URL names a database");
+            let db = Database::connect(&format!("{server_url}/{name}"))
+                .await
+                .expect("Unable to connect to test database");
+            let pool = db.mysql().expect("MySQL database");
+            throwaway_migrator()
+                .run(pool)
+                .await
+                .expect("Unable to migrate test database");
+            (db, Some(guard))
+        }
+        Err(_) => {
+            let db = Database::connect("sqlite::memory:")
+                .await
+                .expect("Unable to connect to test database");
+            db.migrate().await.expect("Unable to migrate test database");
+            (db, None)
+        }
+    }
+}
+
+/// The API on a fresh database, with a cookie jar for the session cookie.
+pub struct TestApp {
+    router: Router,
+    pub state: AppState,
+    session_cookie: Mutex<Option<String>>,
+    _database: Option<DropDatabase>,
+}
+
+impl TestApp {
+    pub async fn new() -> Self {
+        Self::with_config(|_| {}).await
+    }
+
+    pub async fn with_config(configure: impl FnOnce(&mut Config)) -> Self {
+        let mut config = test_config();
+        configure(&mut config);
+        let config = Arc::new(config);
+
+        let (db, database) = throwaway_database().await;
+        let repos = db.repos();
+        let state = AppState {
+            db,
+            donations: repos.donations,
+            supporters: repos.supporters,
+            accounts: repos.accounts,
+            sessions: repos.sessions,
+            exchange_rates: repos.exchange_rates,
+            campaigns: repos.campaigns,
+            merges: repos.merges,
+            schedules: repos.schedules,
+            payments: repos.payments,
+            idempotency_keys: repos.idempotency_keys,
+            job_runs: repos.job_runs,
+            metrics: PrometheusBuilder::new().build_recorder().handle(),
+            tasks: BackgroundTasks::default(),
+            scheduler: Arc::new(Scheduler::new(config.instance_id.clone())),
+            config,
+        };
+        Self {
+            router: app(state.clone()),
+            state,
+            session_cookie: Mutex::new(None),
+            _database: database,
+        }
+    }
+
+    /// An app with the account [`EMAIL`] signed in.
+    pub async fn signed_in() -> Self {
+        Self::new().await.sign_up_and_in().await
+    }
+
+    /// An app with the account [`EMAIL`] signed in that stores donations and
+    /// supporters in `repo` instead of the database.
+    pub async fn signed_in_with(repo: Arc<MemoryRepo>) -> Self {
+        let mut app = Self::new().await;
+        app.state.donations = repo.clone();
+        app.state.supporters = repo;
+        app.router = crate::app(app.state.clone());
+        app.sign_up_and_in().await
+    }
+
+    /// Runs the jobs of `scheduler` instead of none.
+    pub fn with_scheduler(mut self, scheduler: Scheduler) -> Self {
+        self.state.scheduler = Arc::new(scheduler);
+        self.router = crate::app(self.state.clone());
+        self
+    }
+
+    async fn sign_up_and_in(self) -> Self {
+        self.signup(EMAIL, PASSWORD)
+            .await
+            .assert_status(StatusCode::CREATED);
+        self.signin(EMAIL, PASSWORD)
+            .await
+            .assert_status(StatusCode::OK);
+        self
+    }
+
+    pub async fn signup(&self, email: &str, password: &str) -> TestResponse {
+        self.post("/users/auth/signup")
+            .json(&serde_json::json!({ "email": email, "password": password }))
+            .send()
+            .await
+    }
+
+    /// Signs in, and on success sends the session cookie with later requests.
+    pub async fn signin(&self, email: &str, password: &str) -> TestResponse {
+        self.post("/users/auth/signin")
+            .json(&serde_json::json!({ "email": email, "password": password }))
+            .send()
+            .await
+    }
+
+    pub fn request(&self, method: Method, uri: &str) -> TestRequest<'_> {
+        TestRequest {
+            app: self,
+            request: Request::builder().method(method).uri(uri),
+            body: Body::empty(),
+            with_session: true,
+        }
+    }
+
+    pub fn get(&self, uri: &str) -> TestRequest<'_> {
+        self.request(Method::GET, uri)
+    }
+
+    pub fn post(&self, uri: &str) -> TestRequest<'_> {
+        self.request(Method::POST, uri)
+    }
+
+    pub fn put(&self, uri: &str) -> TestRequest<'_> {
+        self.request(Method::PUT, uri)
+    }
+
+    pub fn patch(&self, uri: &str) -> TestRequest<'_> {
+        self.request(Method::PATCH, uri)
+    }
+
+    pub fn delete(&self, uri: &str) -> TestRequest<'_> {
+        self.request(Method::DELETE, uri)
+    }
+
+    /// Keeps the session cookie set by `res`, an empty one removes it.
+    fn store_session_cookie(&self, res: &TestResponse) {
+        for cookie in res.headers.get_all(header::SET_COOKIE) {
+            let Some(token) = cookie
+                .to_str()
+                .ok()
+                .and_then(|c| c.split(';').next())
+                .and_then(|c| c.strip_prefix("session_token="))
+            else {
+                continue;
+            };
+            *self.session_cookie.lock().unwrap() =
+                (!token.is_empty()).then(|| format!("session_token={token}"));
+        }
+    }
+}
+
+pub struct TestRequest<'a> {
+    app: &'a TestApp,
+    request: request::Builder,
+    body: Body,
+    with_session: bool,
+}
+
+impl TestRequest<'_> {
+    pub fn header(mut self, name: impl AsRef<str>, value: impl AsRef<str>) -> Self {
+        self.request = self.request.header(name.as_ref(), value.as_ref());
+        self
+    }
+
+    pub fn json(self, body: &impl Serialize) -> Self {
+        self.body(
+            "application/json",
+            serde_json::to_vec(body).expect("serializable body"),
+        )
+    }
+
+    pub fn body(mut self, content_type: &str, body: impl Into<Body>) -> Self {
+        self.request = self.request.header(header::CONTENT_TYPE, content_type);
+        self.body = body.into();
+        self
+    }
+
+    /// Leaves out the session cookie.
+    pub fn anonymous(mut self) -> Self {
+        self.with_session = false;
+        self
+    }
+
+    pub async fn send(self) -> TestResponse {
+        let mut request = self.request;
+        if self.with_session
+            && let Some(cookie) = self.app.session_cookie.lock().unwrap().clone()
+        {
+            request = request.header(header::COOKIE, cookie);
+        }
+        // the rate limiter is keyed by the peer address
+        let request = request
+            .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 40000))))
+            .body(self.body)
+            .expect("valid request");
+        let method = request.method().clone();
+        let uri = request.uri().clone();
+        let content_type = request
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_owned);
+
+        let res = self
+            .app
+            .router
+            .clone()
+            .oneshot(request)
+            .await
+            .expect("infallible router");
+        let (parts, body) = res.into_parts();
+        let res = TestResponse {
+            method,
+            uri: uri.to_string(),
+            status: parts.status,
+            headers: parts.headers,
+            body: to_bytes(body, usize::MAX).await.expect("readable body"),
+        };
+        openapi::check(content_type.as_deref(), &res);
+        self.app.store_session_cookie(&res);
+        res
+    }
+}
+
+pub struct TestResponse {
+    pub method: Method,
+    pub uri: String,
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+impl TestResponse {
+    #[track_caller]
+    pub fn assert_status(&self, status: StatusCode) -> &Self {
+        assert_eq!(
+            self.status,
+            status,
+            "{} {} responded {}",
+            self.method,
+            self.uri,
+            String::from_utf8_lossy(&self.body)
+        );
+        self
+    }
+
+    pub fn json<T: DeserializeOwned>(&self) -> T {
+        serde_json::from_slice(&self.body).unwrap_or_else(|e| {
+            panic!(
+                "{} {} responded with invalid JSON ({e}): {}",
+                self.method,
+                self.uri,
+                String::from_utf8_lossy(&self.body)
+            )
+        })
+    }
+
+    /// `code` of a problem details response.
+    pub fn code(&self) -> String {
+        self.json::<Value>()["code"]
+            .as_str()
+            .unwrap_or_default()
+            .to_owned()
+    }
+
+    pub fn header(&self, name: impl header::AsHeaderName) -> Option<&str> {
+        self.headers.get(name).and_then(|v| v.to_str().ok())
+    }
+
+    pub fn etag(&self) -> String {
+        self.header(header::ETAG)
+            .expect("response has an ETag")
+            .to_owned()
+    }
+}
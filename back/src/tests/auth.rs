@@ -0,0 +1,106 @@
+use super::{EMAIL, PASSWORD, TestApp};
+use axum::http::{StatusCode, header};
+use serde_json::Value;
+
+#[tokio::test]
+async fn signin_sets_a_session_cookie() {
+    let app = TestApp::new().await;
+    app.signup(EMAIL, PASSWORD)
+        .await
+        .assert_status(StatusCode::CREATED);
+
+    let res = app.signin(EMAIL, PASSWORD).await;
+    res.assert_status(StatusCode::OK);
+    let cookie = res.header(header::SET_COOKIE).unwrap();
+    assert!(cookie.starts_with("session_token="), "{cookie}");
+    assert!(cookie.contains("HttpOnly"), "{cookie}");
+    assert!(cookie.contains("Max-Age=3600"), "{cookie}");
+
+    app.get("/users/auth/validate")
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+    let me: Value = app
+        .get("/users/me")
+        .send()
+        .await
+        .assert_status(StatusCode::OK)
+        .json();
+    assert_eq!(me["email"], EMAIL);
+}
+
+#[tokio::test]
+async fn signup_rejects_taken_and_invalid_emails() {
+    let app = TestApp::new().await;
+    app.signup(EMAIL, PASSWORD)
+        .await
+        .assert_status(StatusCode::CREATED);
+
+    let res = app.signup(EMAIL, "another password").await;
+    res.assert_status(StatusCode::CONFLICT);
+    assert_eq!(res.code(), "account_exists");
+
+    let res = app.signup("not an email", PASSWORD).await;
+    res.assert_status(StatusCode::BAD_REQUEST);
+    assert_eq!(res.code(), "invalid_email");
+}
+
+#[tokio::test]
+async fn signin_rejects_wrong_credentials() {
+    let app = TestApp::new().await;
+    app.signup(EMAIL, PASSWORD)
+        .await
+        .assert_status(StatusCode::CREATED);
+
+    let res = app.signin(EMAIL, "wrong password").await;
+    res.assert_status(StatusCode::UNAUTHORIZED);
+    assert_eq!(res.code(), "incorrect_password");
+    assert!(res.header(header::SET_COOKIE).is_none());
+
+    let res = app.signin("someone@studio-matic.org", PASSWORD).await;
+    res.assert_status(StatusCode::NOT_FOUND);
+    assert_eq!(res.code(), "account_not_found");
+
+    app.get("/users/me")
+        .send()
+        .await
+        .assert_status(StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn signout_ends_the_session() {
+    let app = TestApp::signed_in().await;
+    let token = app.session_cookie.lock().unwrap().clone().unwrap();
+
+    let res = app.post("/users/auth/signout").send().await;
+    res.assert_status(StatusCode::OK);
+    assert!(
+        res.header(header::SET_COOKIE)
+            .unwrap()
+            .starts_with("session_token=; Max-Age=0")
+    );
+
+    // the old token is not valid anymore, even if a client keeps sending it
+    let res = app
+        .get("/users/me")
+        .anonymous()
+        .header(header::COOKIE, token)
+        .send()
+        .await;
+    res.assert_status(StatusCode::UNAUTHORIZED);
+    assert_eq!(res.code(), "invalid_session_token");
+}
+
+#[tokio::test]
+async fn sessions_belong_to_their_account() {
+    let app = TestApp::signed_in().await;
+    app.signup("bookkeeper@studio-matic.org", PASSWORD)
+        .await
+        .assert_status(StatusCode::CREATED);
+    app.signin("bookkeeper@studio-matic.org", PASSWORD)
+        .await
+        .assert_status(StatusCode::OK);
+
+    let me: Value = app.get("/users/me").send().await.json();
+    assert_eq!(me["email"], "bookkeeper@studio-matic.org");
+}
@@ -0,0 +1,101 @@
+use super::TestApp;
+use axum::http::{StatusCode, header};
+use serde_json::{Value, json};
+use time::OffsetDateTime;
+
+#[tokio::test]
+async fn campaigns_track_their_progress() {
+    let app = TestApp::signed_in().await;
+    let today = OffsetDateTime::now_utc().date();
+
+    let campaign: Value = app
+        .post("/campaigns")
+        .json(&json!({
+            "name": "Studio roof",
+            "co_op": "STUDIO-MATIC",
+            "goal_eur": "40.00",
+            "starts_at": today.to_string(),
+        }))
+        .send()
+        .await
+        .assert_status(StatusCode::CREATED)
+        .json();
+    let id = campaign["id"].as_u64().unwrap();
+
+    let supporter: Value = app
+        .post("/supporters")
+        .json(&json!({ "name": "Ada" }))
+        .send()
+        .await
+        .json();
+    for supporter_id in [
+        supporter["id"].clone(),
+        Value::Null,
+        supporter["id"].clone(),
+    ] {
+        app.post("/donations")
+            .json(&json!({
+                "coins": 500,
+                "amount": "5.00",
+                "co_op": "STUDIO-MATIC",
+                "supporter_id": supporter_id,
+                "campaign_id": id,
+            }))
+            .send()
+            .await
+            .assert_status(StatusCode::CREATED);
+    }
+
+    let res = app
+        .get(&format!("/campaigns/{id}/progress"))
+        .anonymous()
+        .send()
+        .await;
+    res.assert_status(StatusCode::OK);
+    assert_eq!(
+        res.header(header::CACHE_CONTROL),
+        Some("public, max-age=60")
+    );
+    let progress: Value = res.json();
+    assert_eq!(progress["raised_eur"], "15.00");
+    assert_eq!(progress["percent"], 37.5);
+    assert_eq!(progress["donations"], 3);
+    assert_eq!(progress["donors"], 2);
+    assert_eq!(
+        progress["daily"],
+        json!([{ "date": today.to_string(), "raised_eur": "15.00", "cumulative_eur": "15.00" }])
+    );
+
+    app.put(&format!("/campaigns/{id}"))
+        .json(&json!({
+            "name": "Studio roof and gutters",
+            "co_op": "STUDIO-MATIC",
+            "goal_eur": "60.00",
+            "starts_at": "2025-01-01T00:00:00Z",
+            "ends_at": "2025-02-01T00:00:00Z",
+        }))
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+    let campaigns: Value = app
+        .get("/campaigns")
+        .send()
+        .await
+        .assert_status(StatusCode::OK)
+        .json();
+    assert_eq!(campaigns[0]["name"], "Studio roof and gutters");
+    assert_eq!(campaigns[0]["ends_at"], "2025-02-01T00:00:00Z");
+
+    app.delete(&format!("/campaigns/{id}"))
+        .send()
+        .await
+        .assert_status(StatusCode::NO_CONTENT);
+    app.get(&format!("/campaigns/{id}"))
+        .send()
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+    // the donations are kept without a campaign
+    let donations: Value = app.get("/donations").send().await.json();
+    assert_eq!(donations.as_array().unwrap().len(), 3);
+    assert_eq!(donations[0]["campaign_id"], Value::Null);
+}
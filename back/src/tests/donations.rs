@@ -0,0 +1,330 @@
+use super::TestApp;
+use axum::http::{StatusCode, header};
+use serde_json::{Value, json};
+
+async fn create_supporter(app: &TestApp, name: &str) -> u64 {
+    let res: Value = app
+        .post("/supporters")
+        .json(&json!({ "name": name }))
+        .send()
+        .await
+        .assert_status(StatusCode::CREATED)
+        .json();
+    res["id"].as_u64().unwrap()
+}
+
+async fn create_donation(app: &TestApp, donation: Value) -> u64 {
+    let res: Value = app
+        .post("/donations")
+        .json(&donation)
+        .send()
+        .await
+        .assert_status(StatusCode::CREATED)
+        .json();
+    res["id"].as_u64().unwrap()
+}
+
+#[tokio::test]
+async fn donations_can_be_created_read_updated_and_deleted() {
+    let app = TestApp::signed_in().await;
+    let supporter_id = create_supporter(&app, "Ada").await;
+
+    let id = create_donation(
+        &app,
+        json!({ "coins": 500, "amount": "5.00", "co_op": "S4L", "supporter_id": supporter_id }),
+    )
+    .await;
+
+    let res = app.get(&format!("/donations/{id}")).send().await;
+    res.assert_status(StatusCode::OK);
+    assert_eq!(res.etag(), "\"1\"");
+    let donation: Value = res.json();
+    assert_eq!(donation["coins"], 500);
+    assert_eq!(donation["amount"], "5.00");
+    assert_eq!(donation["income_eur"], "5.00");
+    assert_eq!(donation["currency"], "EUR");
+    assert_eq!(donation["co_op"], "S4L");
+    assert_eq!(donation["supporter_id"], supporter_id);
+    assert_eq!(donation["campaign_id"], Value::Null);
+
+    let res = app
+        .put(&format!("/donations/{id}"))
+        .header(header::IF_MATCH, "\"1\"")
+        .json(&json!({ "coins": 700, "amount": "7.00", "co_op": "STUDIO-MATIC" }))
+        .send()
+        .await;
+    res.assert_status(StatusCode::OK);
+    assert_eq!(res.etag(), "\"2\"");
+
+    let res = app
+        .patch(&format!("/donations/{id}"))
+        .header(header::IF_MATCH, "\"2\"")
+        .body("application/merge-patch+json", r#"{"coins": 750}"#)
+        .send()
+        .await;
+    res.assert_status(StatusCode::OK);
+    assert_eq!(res.etag(), "\"3\"");
+
+    let donation: Value = app
+        .get(&format!("/donations/{id}"))
+        .send()
+        .await
+        .assert_status(StatusCode::OK)
+        .json();
+    assert_eq!(donation["coins"], 750);
+    assert_eq!(donation["amount"], "7.00");
+    assert_eq!(donation["co_op"], "STUDIO-MATIC");
+    assert_eq!(donation["supporter_id"], Value::Null);
+    assert_eq!(donation["version"], 3);
+
+    app.delete(&format!("/donations/{id}"))
+        .header(header::IF_MATCH, "\"3\"")
+        .send()
+        .await
+        .assert_status(StatusCode::NO_CONTENT);
+    app.get(&format!("/donations/{id}"))
+        .send()
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn stale_versions_are_rejected() {
+    let app = TestApp::signed_in().await;
+    let id = create_donation(
+        &app,
+        json!({ "coins": 1, "amount": "1.00", "co_op": "S4L" }),
+    )
+    .await;
+
+    app.patch(&format!("/donations/{id}"))
+        .header(header::IF_MATCH, "\"1\"")
+        .body("application/merge-patch+json", r#"{"coins": 2}"#)
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+
+    let res = app
+        .patch(&format!("/donations/{id}"))
+        .header(header::IF_MATCH, "\"1\"")
+        .body("application/merge-patch+json", r#"{"coins": 3}"#)
+        .send()
+        .await;
+    res.assert_status(StatusCode::PRECONDITION_FAILED);
+    assert_eq!(res.code(), "precondition_failed");
+
+    app.delete(&format!("/donations/{id}"))
+        .header(header::IF_MATCH, "\"1\"")
+        .send()
+        .await
+        .assert_status(StatusCode::PRECONDITION_FAILED);
+
+    // without `If-Match` the change applies to whatever version is current
+    app.patch(&format!("/donations/{id}"))
+        .body("application/merge-patch+json", r#"{"coins": 3}"#)
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+    let donation: Value = app.get(&format!("/donations/{id}")).send().await.json();
+    assert_eq!(donation["coins"], 3);
+}
+
+#[tokio::test]
+async fn donations_are_listed_by_filter_and_totalled() {
+    let app = TestApp::signed_in().await;
+    let ada = create_supporter(&app, "Ada").await;
+    create_donation(
+        &app,
+        json!({ "coins": 1, "amount": "1.50", "co_op": "S4L", "supporter_id": ada }),
+    )
+    .await;
+    create_donation(
+        &app,
+        json!({ "coins": 2, "amount": "2.25", "co_op": "S4L", "supporter_id": ada }),
+    )
+    .await;
+    create_donation(
+        &app,
+        json!({ "coins": 3, "amount": "4.00", "co_op": "STUDIO-MATIC" }),
+    )
+    .await;
+
+    let all: Vec<Value> = app
+        .get("/donations")
+        .send()
+        .await
+        .assert_status(StatusCode::OK)
+        .json();
+    assert_eq!(all.len(), 3);
+
+    let s4l: Vec<Value> = app.get("/donations?co_op=S4L").send().await.json();
+    assert_eq!(s4l.len(), 2);
+    let by_ada: Vec<Value> = app
+        .get(&format!("/donations?supporter_id={ada}"))
+        .send()
+        .await
+        .json();
+    assert_eq!(by_ada.len(), 2);
+    let before_2000: Vec<Value> = app.get("/donations?to=2000-01-01").send().await.json();
+    assert!(before_2000.is_empty());
+
+    let stats: Value = app
+        .get("/donations/stats")
+        .send()
+        .await
+        .assert_status(StatusCode::OK)
+        .json();
+    assert_eq!(stats["currency"], "EUR");
+    assert_eq!(
+        stats["totals"],
+        json!({ "donations": 3, "coins": 6, "income_eur": "7.75", "total": "7.75" })
+    );
+    assert_eq!(stats["average_eur_per_coin"], "1.291667");
+    assert_eq!(
+        stats["by_co_op"],
+        json!([
+            { "co_op": "S4L", "donations": 2, "coins": 3, "income_eur": "3.75", "total": "3.75" },
+            {
+                "co_op": "STUDIO-MATIC",
+                "donations": 1,
+                "coins": 3,
+                "income_eur": "4.00",
+                "total": "4.00",
+            },
+        ])
+    );
+    assert_eq!(stats["top_supporters"][0]["income_eur"], "3.75");
+    assert_eq!(stats["comparison"]["previous"]["donations"], 0);
+
+    let res = app
+        .get("/donations/stats?from=2024-02-01T00:00:00Z&to=2024-01-01T00:00:00Z")
+        .send()
+        .await;
+    res.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(res.code(), "invalid_range");
+}
+
+#[tokio::test]
+async fn references_must_exist() {
+    let app = TestApp::signed_in().await;
+
+    let res = app
+        .post("/donations")
+        .json(&json!({ "coins": 1, "amount": "1.00", "co_op": "S4L", "supporter_id": 404 }))
+        .send()
+        .await;
+    res.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(res.code(), "supporter_not_found");
+
+    let res = app
+        .post("/donations")
+        .json(&json!({ "coins": 1, "amount": "1.00", "co_op": "S4L", "campaign_id": 404 }))
+        .send()
+        .await;
+    res.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(res.code(), "campaign_not_found");
+
+    let res = app
+        .post("/donations")
+        .json(&json!({ "coins": 1, "amount": "1.00", "currency": "USD", "co_op": "S4L" }))
+        .send()
+        .await;
+    res.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(res.code(), "missing_exchange_rate");
+}
+
+#[tokio::test]
+async fn malformed_ids_are_reported_as_problems() {
+    let app = TestApp::signed_in().await;
+
+    let res = app.get("/donations/abc").send().await;
+    res.assert_status(StatusCode::BAD_REQUEST);
+    assert_eq!(
+        res.header(header::CONTENT_TYPE),
+        Some("application/problem+json")
+    );
+    assert_eq!(res.code(), "invalid_path");
+}
+
+#[tokio::test]
+async fn exports_name_the_supporters_of_filtered_donations() {
+    let app = TestApp::signed_in().await;
+    let supporter_id = create_supporter(&app, "=Ada").await;
+    create_donation(
+        &app,
+        json!({ "coins": 500, "amount": "5.00", "co_op": "S4L", "supporter_id": supporter_id }),
+    )
+    .await;
+    create_donation(
+        &app,
+        json!({ "coins": 700, "amount": "7.00", "co_op": "STUDIO-MATIC" }),
+    )
+    .await;
+
+    let res = app
+        .get("/donations/export?co_op=S4L&decimal_separator=comma")
+        .send()
+        .await;
+    res.assert_status(StatusCode::OK);
+    let csv = String::from_utf8(res.body.to_vec()).unwrap();
+    let lines: Vec<_> = csv.lines().collect();
+    assert_eq!(
+        lines[0],
+        "id;donated_at;coins;co_op;currency;amount;income_eur;external_ref;supporter"
+    );
+    assert_eq!(lines.len(), 2);
+    assert!(lines[1].ends_with(";500;S4L;EUR;5,00;5,00;;'=Ada"), "{csv}");
+
+    let res = app.get("/donations/export?format=jsonl").send().await;
+    res.assert_status(StatusCode::OK);
+    let rows: Vec<Value> = res
+        .body
+        .split(|b| *b == b'\n')
+        .filter(|line| !line.is_empty())
+        .map(|line| serde_json::from_slice(line).unwrap())
+        .collect();
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[0]["supporter"], "=Ada");
+    assert_eq!(rows[1]["supporter"], Value::Null);
+    assert_eq!(rows[1]["income_eur"], "7.00");
+}
+
+#[tokio::test]
+async fn imports_are_all_or_nothing() {
+    let app = TestApp::signed_in().await;
+    let import = |body: &'static str| app.post("/donations/import").body("text/csv", body).send();
+
+    let res = import(concat!(
+        "coins,amount,currency,co_op,donated_at,external_ref,name\n",
+        "100,1.00,,S4L,2025-01-10,ref-1,Ada\n",
+        "300,3.00,USD,S4L,2025-01-12,ref-2,Grace\n",
+    ))
+    .await;
+    res.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(res.json::<Value>()["errors"][0]["field"], "currency");
+    let all: Vec<Value> = app.get("/donations").send().await.json();
+    assert!(all.is_empty());
+
+    let body = concat!(
+        "coins,amount,currency,co_op,donated_at,external_ref,name\n",
+        "100,1.00,,S4L,2025-01-10,ref-1,Ada\n",
+        "200,2.00,,S4L,2025-01-11,ref-1,Ada\n",
+    );
+    let report: Value = import(body).await.assert_status(StatusCode::OK).json();
+    assert_eq!(report["imported"], 1);
+    assert_eq!(report["supporters_created"], 1);
+    assert_eq!(report["skipped_duplicates"], 1);
+    let report: Value = import(body).await.assert_status(StatusCode::OK).json();
+    assert_eq!(report["imported"], 0);
+    assert_eq!(report["skipped_duplicates"], 2);
+
+    let all: Vec<Value> = app.get("/donations").send().await.json();
+    assert_eq!(all.len(), 1);
+    assert_eq!(all[0]["donated_at"], "2025-01-10T00:00:00Z");
+    let supporter: Value = app
+        .get(&format!("/supporters/{}", all[0]["supporter_id"]))
+        .send()
+        .await
+        .json();
+    assert_eq!(supporter["name"], "Ada");
+}
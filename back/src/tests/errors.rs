@@ -0,0 +1,1176 @@
+//! Every status declared in the OpenAPI document is returned by some request,
+//! and no undeclared one is.
+
+use super::{EMAIL, PASSWORD, TestApp, TestResponse, WEBHOOK_SECRET, webhook_signature};
+use crate::{
+    config::RateLimit, idempotency::request_hash, jobs::Scheduler, webhooks::providers::Provider,
+};
+use axum::{
+    body::Body,
+    http::{Request, StatusCode, header},
+};
+use serde_json::{Value, json};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    time::Duration,
+};
+use utoipa::openapi::OpenApi;
+
+const KOFI: &[u8] = include_bytes!("../../fixtures/webhooks/kofi_donation.json");
+const PAYPAL: &[u8] = include_bytes!("../../fixtures/webhooks/paypal_capture_completed.json");
+
+/// Internal server errors are declared everywhere but cannot be provoked.
+const UNREACHABLE: &[u16] = &[500];
+
+type Statuses = BTreeMap<(String, String), BTreeSet<u16>>;
+
+fn declared(api: &OpenApi) -> Statuses {
+    let mut statuses = Statuses::new();
+    for (path, item) in &api.paths.paths {
+        let operations = [
+            ("get", &item.get),
+            ("post", &item.post),
+            ("put", &item.put),
+            ("patch", &item.patch),
+            ("delete", &item.delete),
+        ];
+        for (method, operation) in operations {
+            let Some(operation) = operation else {
+                continue;
+            };
+            statuses.insert(
+                (method.to_owned(), path.clone()),
+                operation
+                    .responses
+                    .responses
+                    .keys()
+                    .map(|status| status.parse().expect("numeric status"))
+                    .collect(),
+            );
+        }
+    }
+    statuses
+}
+
+/// Statuses returned per operation, keyed by the path as documented.
+#[derive(Default)]
+struct Observed(Statuses);
+
+impl Observed {
+    #[track_caller]
+    fn record(&mut self, path: &str, status: StatusCode, res: TestResponse) -> TestResponse {
+        res.assert_status(status);
+        self.0
+            .entry((res.method.as_str().to_ascii_lowercase(), path.to_owned()))
+            .or_default()
+            .insert(status.as_u16());
+        res
+    }
+}
+
+#[tokio::test]
+async fn declared_statuses_are_returned() {
+    let api = crate::openapi();
+
+    let mut scheduler = Scheduler::new("test".to_owned());
+    scheduler.register(
+        "noop",
+        "0 * * * *".parse().expect("valid schedule"),
+        || async { Ok("Nothing to do".to_owned()) },
+    );
+    let app = TestApp::with_config(|config| {
+        for provider in [Provider::Kofi, Provider::Paypal] {
+            config
+                .webhooks
+                .secrets
+                .insert(provider, WEBHOOK_SECRET.to_owned());
+        }
+    })
+    .await
+    .with_scheduler(scheduler);
+    let mut seen = Observed::default();
+    auth_statuses(&app, &mut seen).await;
+    donation_statuses(&app, &mut seen).await;
+    import_export_statuses(&app, &mut seen).await;
+    supporter_statuses(&app, &mut seen).await;
+    merge_statuses(&app, &mut seen).await;
+    schedule_statuses(&app, &mut seen).await;
+    campaign_statuses(&app, &mut seen).await;
+    exchange_rate_statuses(&app, &mut seen).await;
+    webhook_statuses(&app, &mut seen).await;
+    job_statuses(&app, &mut seen).await;
+    idempotency_statuses(&app, &mut seen).await;
+    public_statuses(&app, &mut seen).await;
+    operational_statuses(app, &mut seen).await;
+
+    let mut expected = declared(&api);
+    for statuses in expected.values_mut() {
+        statuses.retain(|status| !UNREACHABLE.contains(status));
+    }
+    assert_eq!(seen.0, expected);
+}
+
+async fn auth_statuses(app: &TestApp, seen: &mut Observed) {
+    let signup = "/users/auth/signup";
+    seen.record(
+        signup,
+        StatusCode::CREATED,
+        app.signup(EMAIL, PASSWORD).await,
+    );
+    seen.record(
+        signup,
+        StatusCode::CONFLICT,
+        app.signup(EMAIL, PASSWORD).await,
+    );
+    seen.record(
+        signup,
+        StatusCode::BAD_REQUEST,
+        app.signup("treasurer", PASSWORD).await,
+    );
+
+    for path in ["/users/auth/validate", "/users/me"] {
+        seen.record(path, StatusCode::UNAUTHORIZED, app.get(path).send().await);
+    }
+    seen.record(
+        "/users/auth/signout",
+        StatusCode::UNAUTHORIZED,
+        app.post("/users/auth/signout").send().await,
+    );
+
+    let signin = "/users/auth/signin";
+    seen.record(
+        signin,
+        StatusCode::BAD_REQUEST,
+        app.signin("treasurer", PASSWORD).await,
+    );
+    seen.record(
+        signin,
+        StatusCode::NOT_FOUND,
+        app.signin("someone@studio-matic.org", PASSWORD).await,
+    );
+    seen.record(
+        signin,
+        StatusCode::UNAUTHORIZED,
+        app.signin(EMAIL, "wrong password").await,
+    );
+    seen.record(signin, StatusCode::OK, app.signin(EMAIL, PASSWORD).await);
+
+    for path in ["/users/auth/validate", "/users/me"] {
+        seen.record(path, StatusCode::OK, app.get(path).send().await);
+    }
+    seen.record(
+        "/users/auth/signout",
+        StatusCode::OK,
+        app.post("/users/auth/signout").send().await,
+    );
+    app.signin(EMAIL, PASSWORD)
+        .await
+        .assert_status(StatusCode::OK);
+}
+
+/// Requests authenticated by the session of [`auth_statuses`].
+async fn donation_statuses(app: &TestApp, seen: &mut Observed) {
+    let list = "/donations";
+    seen.record(list, StatusCode::OK, app.get("/donations").send().await);
+    seen.record(
+        list,
+        StatusCode::BAD_REQUEST,
+        app.get("/donations?from=yesterday").send().await,
+    );
+    seen.record(
+        list,
+        StatusCode::UNAUTHORIZED,
+        app.get("/donations").anonymous().send().await,
+    );
+
+    let stats = "/donations/stats";
+    seen.record(stats, StatusCode::OK, app.get(stats).send().await);
+    seen.record(
+        stats,
+        StatusCode::BAD_REQUEST,
+        app.get("/donations/stats?to=tomorrow").send().await,
+    );
+    seen.record(
+        stats,
+        StatusCode::UNPROCESSABLE_ENTITY,
+        app.get("/donations/stats?currency=USD").send().await,
+    );
+    seen.record(
+        stats,
+        StatusCode::UNPROCESSABLE_ENTITY,
+        app.get("/donations/stats?from=2024-02-01T00:00:00Z&to=2024-01-01T00:00:00Z")
+            .send()
+            .await,
+    );
+    seen.record(
+        stats,
+        StatusCode::UNAUTHORIZED,
+        app.get(stats).anonymous().send().await,
+    );
+
+    let donation = json!({ "coins": 1, "amount": "1.00", "co_op": "S4L" });
+    seen.record(
+        list,
+        StatusCode::CREATED,
+        app.post("/donations").json(&donation).send().await,
+    );
+    seen.record(
+        list,
+        StatusCode::UNPROCESSABLE_ENTITY,
+        app.post("/donations")
+            .json(&json!({ "coins": 1, "amount": "1.00", "co_op": "S4L", "supporter_id": 404 }))
+            .send()
+            .await,
+    );
+    seen.record(
+        list,
+        StatusCode::UNAUTHORIZED,
+        app.post("/donations")
+            .anonymous()
+            .json(&donation)
+            .send()
+            .await,
+    );
+
+    let one = "/donations/{id}";
+    seen.record(one, StatusCode::OK, app.get("/donations/1").send().await);
+    seen.record(
+        one,
+        StatusCode::NOT_FOUND,
+        app.get("/donations/404").send().await,
+    );
+    seen.record(
+        one,
+        StatusCode::UNAUTHORIZED,
+        app.get("/donations/1").anonymous().send().await,
+    );
+
+    seen.record(
+        one,
+        StatusCode::OK,
+        app.put("/donations/1").json(&donation).send().await,
+    );
+    seen.record(
+        one,
+        StatusCode::PRECONDITION_FAILED,
+        app.put("/donations/1")
+            .header(header::IF_MATCH, "\"1\"")
+            .json(&donation)
+            .send()
+            .await,
+    );
+    seen.record(
+        one,
+        StatusCode::NOT_FOUND,
+        app.put("/donations/404").json(&donation).send().await,
+    );
+    seen.record(
+        one,
+        StatusCode::UNPROCESSABLE_ENTITY,
+        app.put("/donations/1")
+            .json(&json!({ "coins": 1, "amount": "1.00", "co_op": "S4L", "campaign_id": 404 }))
+            .send()
+            .await,
+    );
+    seen.record(
+        one,
+        StatusCode::UNAUTHORIZED,
+        app.put("/donations/1")
+            .anonymous()
+            .json(&donation)
+            .send()
+            .await,
+    );
+
+    let patch =
+        |uri: &str, body: &'static str| app.patch(uri).body("application/merge-patch+json", body);
+    seen.record(
+        one,
+        StatusCode::OK,
+        patch("/donations/1", r#"{"coins": 2}"#).send().await,
+    );
+    seen.record(
+        one,
+        StatusCode::PRECONDITION_FAILED,
+        patch("/donations/1", r#"{"coins": 3}"#)
+            .header(header::IF_MATCH, "\"1\"")
+            .send()
+            .await,
+    );
+    seen.record(
+        one,
+        StatusCode::NOT_FOUND,
+        patch("/donations/404", r#"{"coins": 3}"#).send().await,
+    );
+    seen.record(
+        one,
+        StatusCode::UNPROCESSABLE_ENTITY,
+        patch("/donations/1", r#"{"coins": null}"#).send().await,
+    );
+    seen.record(
+        one,
+        StatusCode::UNAUTHORIZED,
+        patch("/donations/1", r#"{"coins": 3}"#)
+            .anonymous()
+            .send()
+            .await,
+    );
+
+    seen.record(
+        one,
+        StatusCode::PRECONDITION_FAILED,
+        app.delete("/donations/1")
+            .header(header::IF_MATCH, "\"1\"")
+            .send()
+            .await,
+    );
+    seen.record(
+        one,
+        StatusCode::UNAUTHORIZED,
+        app.delete("/donations/1").anonymous().send().await,
+    );
+    seen.record(
+        one,
+        StatusCode::NO_CONTENT,
+        app.delete("/donations/1").send().await,
+    );
+    seen.record(
+        one,
+        StatusCode::NOT_FOUND,
+        app.delete("/donations/1").send().await,
+    );
+}
+
+async fn supporter_statuses(app: &TestApp, seen: &mut Observed) {
+    let list = "/supporters";
+    let supporter = json!({ "name": "Ada" });
+    seen.record(
+        list,
+        StatusCode::CREATED,
+        app.post(list).json(&supporter).send().await,
+    );
+    seen.record(
+        list,
+        StatusCode::UNPROCESSABLE_ENTITY,
+        app.post(list).json(&json!({ "public": true })).send().await,
+    );
+    seen.record(
+        list,
+        StatusCode::UNAUTHORIZED,
+        app.post(list).anonymous().json(&supporter).send().await,
+    );
+    seen.record(list, StatusCode::OK, app.get(list).send().await);
+    seen.record(
+        list,
+        StatusCode::UNAUTHORIZED,
+        app.get(list).anonymous().send().await,
+    );
+    let id = app
+        .get(list)
+        .send()
+        .await
+        .json::<Vec<Value>>()
+        .last()
+        .unwrap()["id"]
+        .as_u64()
+        .unwrap();
+
+    let one = "/supporters/{id}";
+    let uri = format!("/supporters/{id}");
+    seen.record(one, StatusCode::OK, app.get(&uri).send().await);
+    seen.record(
+        one,
+        StatusCode::NOT_FOUND,
+        app.get("/supporters/404").send().await,
+    );
+    seen.record(
+        one,
+        StatusCode::UNAUTHORIZED,
+        app.get(&uri).anonymous().send().await,
+    );
+
+    seen.record(
+        one,
+        StatusCode::OK,
+        app.put(&uri).json(&supporter).send().await,
+    );
+    seen.record(
+        one,
+        StatusCode::PRECONDITION_FAILED,
+        app.put(&uri)
+            .header(header::IF_MATCH, "\"1\"")
+            .json(&supporter)
+            .send()
+            .await,
+    );
+    seen.record(
+        one,
+        StatusCode::NOT_FOUND,
+        app.put("/supporters/404").json(&supporter).send().await,
+    );
+    seen.record(
+        one,
+        StatusCode::UNPROCESSABLE_ENTITY,
+        app.put(&uri).json(&json!({ "public": true })).send().await,
+    );
+    seen.record(
+        one,
+        StatusCode::UNAUTHORIZED,
+        app.put(&uri).anonymous().json(&supporter).send().await,
+    );
+
+    let patch =
+        |uri: &str, body: &'static str| app.patch(uri).body("application/merge-patch+json", body);
+    seen.record(
+        one,
+        StatusCode::OK,
+        patch(&uri, r#"{"public": true}"#).send().await,
+    );
+    seen.record(
+        one,
+        StatusCode::PRECONDITION_FAILED,
+        patch(&uri, r#"{"public": false}"#)
+            .header(header::IF_MATCH, "\"1\"")
+            .send()
+            .await,
+    );
+    seen.record(
+        one,
+        StatusCode::NOT_FOUND,
+        patch("/supporters/404", r#"{"public": false}"#)
+            .send()
+            .await,
+    );
+    seen.record(
+        one,
+        StatusCode::UNPROCESSABLE_ENTITY,
+        patch(&uri, r#"{"name": null}"#).send().await,
+    );
+    seen.record(
+        one,
+        StatusCode::UNAUTHORIZED,
+        patch(&uri, r#"{"public": false}"#).anonymous().send().await,
+    );
+
+    let donations = "/supporters/{id}/donations";
+    seen.record(
+        donations,
+        StatusCode::OK,
+        app.get(&format!("{uri}/donations")).send().await,
+    );
+    seen.record(
+        donations,
+        StatusCode::BAD_REQUEST,
+        app.get(&format!("{uri}/donations?from=yesterday"))
+            .send()
+            .await,
+    );
+    seen.record(
+        donations,
+        StatusCode::NOT_FOUND,
+        app.get("/supporters/404/donations").send().await,
+    );
+    seen.record(
+        donations,
+        StatusCode::UNAUTHORIZED,
+        app.get(&format!("{uri}/donations"))
+            .anonymous()
+            .send()
+            .await,
+    );
+
+    let totals = "/supporters/{id}/totals";
+    seen.record(
+        totals,
+        StatusCode::OK,
+        app.get(&format!("{uri}/totals")).send().await,
+    );
+    seen.record(
+        totals,
+        StatusCode::NOT_FOUND,
+        app.get("/supporters/404/totals").send().await,
+    );
+    seen.record(
+        totals,
+        StatusCode::UNAUTHORIZED,
+        app.get(&format!("{uri}/totals")).anonymous().send().await,
+    );
+
+    seen.record(
+        one,
+        StatusCode::PRECONDITION_FAILED,
+        app.delete(&uri)
+            .header(header::IF_MATCH, "\"1\"")
+            .send()
+            .await,
+    );
+    seen.record(
+        one,
+        StatusCode::UNAUTHORIZED,
+        app.delete(&uri).anonymous().send().await,
+    );
+    seen.record(one, StatusCode::NO_CONTENT, app.delete(&uri).send().await);
+    seen.record(one, StatusCode::NOT_FOUND, app.delete(&uri).send().await);
+}
+
+async fn import_export_statuses(app: &TestApp, seen: &mut Observed) {
+    let import = "/donations/import";
+    let csv = "coins,amount,currency,co_op,donated_at,external_ref,name\n\
+        100,1.00,,S4L,2025-01-10,ref-1,Ada\n";
+    seen.record(
+        import,
+        StatusCode::OK,
+        app.post(import).body("text/csv", csv).send().await,
+    );
+    seen.record(
+        import,
+        StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        app.post(import).send().await,
+    );
+    seen.record(
+        import,
+        StatusCode::UNAUTHORIZED,
+        app.post(import)
+            .anonymous()
+            .body("text/csv", csv)
+            .send()
+            .await,
+    );
+
+    let export = "/donations/export";
+    seen.record(export, StatusCode::OK, app.get(export).send().await);
+    seen.record(
+        export,
+        StatusCode::BAD_REQUEST,
+        app.get("/donations/export?format=xml").send().await,
+    );
+    seen.record(
+        export,
+        StatusCode::UNAUTHORIZED,
+        app.get(export).anonymous().send().await,
+    );
+}
+
+async fn merge_statuses(app: &TestApp, seen: &mut Observed) {
+    let mut ids = Vec::new();
+    for name in ["Grace Hopper", "Grace Hoper"] {
+        let supporter: Value = app
+            .post("/supporters")
+            .json(&json!({ "name": name }))
+            .send()
+            .await
+            .json();
+        ids.push(supporter["id"].clone());
+    }
+
+    let duplicates = "/supporters/duplicates";
+    seen.record(duplicates, StatusCode::OK, app.get(duplicates).send().await);
+    seen.record(
+        duplicates,
+        StatusCode::BAD_REQUEST,
+        app.get("/supporters/duplicates?min_score=high")
+            .send()
+            .await,
+    );
+    seen.record(
+        duplicates,
+        StatusCode::UNAUTHORIZED,
+        app.get(duplicates).anonymous().send().await,
+    );
+
+    let merge = "/supporters/merge";
+    let request = json!({ "canonical_id": ids[0], "duplicate_ids": [ids[1]] });
+    seen.record(
+        merge,
+        StatusCode::UNAUTHORIZED,
+        app.post(merge).anonymous().json(&request).send().await,
+    );
+    seen.record(
+        merge,
+        StatusCode::UNPROCESSABLE_ENTITY,
+        app.post(merge)
+            .json(&json!({ "canonical_id": ids[0], "duplicate_ids": [] }))
+            .send()
+            .await,
+    );
+    seen.record(
+        merge,
+        StatusCode::OK,
+        app.post(merge).json(&request).send().await,
+    );
+    seen.record(
+        merge,
+        StatusCode::NOT_FOUND,
+        app.post(merge).json(&request).send().await,
+    );
+
+    let merges = "/supporters/merges";
+    seen.record(merges, StatusCode::OK, app.get(merges).send().await);
+    seen.record(
+        merges,
+        StatusCode::UNAUTHORIZED,
+        app.get(merges).anonymous().send().await,
+    );
+}
+
+async fn schedule_statuses(app: &TestApp, seen: &mut Observed) {
+    let supporter: Value = app
+        .post("/supporters")
+        .json(&json!({ "name": "Ada" }))
+        .send()
+        .await
+        .json();
+    let schedule = json!({
+        "supporter_id": supporter["id"],
+        "co_op": "S4L",
+        "amount": "5.00",
+        "interval_unit": "month",
+        "starts_on": "2025-01-01",
+    });
+    let list = "/schedules";
+    let created: Value = seen
+        .record(
+            list,
+            StatusCode::CREATED,
+            app.post(list).json(&schedule).send().await,
+        )
+        .json();
+    seen.record(
+        list,
+        StatusCode::UNPROCESSABLE_ENTITY,
+        app.post(list)
+            .json(&json!({
+                "supporter_id": 404,
+                "co_op": "S4L",
+                "amount": "5.00",
+                "interval_unit": "month",
+                "starts_on": "2025-01-01",
+            }))
+            .send()
+            .await,
+    );
+    seen.record(
+        list,
+        StatusCode::UNAUTHORIZED,
+        app.post(list).anonymous().json(&schedule).send().await,
+    );
+    seen.record(list, StatusCode::OK, app.get(list).send().await);
+    seen.record(
+        list,
+        StatusCode::UNAUTHORIZED,
+        app.get(list).anonymous().send().await,
+    );
+
+    let upcoming = "/schedules/upcoming";
+    seen.record(upcoming, StatusCode::OK, app.get(upcoming).send().await);
+    seen.record(
+        upcoming,
+        StatusCode::BAD_REQUEST,
+        app.get("/schedules/upcoming?days=soon").send().await,
+    );
+    seen.record(
+        upcoming,
+        StatusCode::UNAUTHORIZED,
+        app.get(upcoming).anonymous().send().await,
+    );
+    let overdue = "/schedules/overdue";
+    seen.record(overdue, StatusCode::OK, app.get(overdue).send().await);
+    seen.record(
+        overdue,
+        StatusCode::UNAUTHORIZED,
+        app.get(overdue).anonymous().send().await,
+    );
+
+    let one = "/schedules/{id}";
+    let uri = format!("/schedules/{}", created["id"]);
+    seen.record(one, StatusCode::OK, app.get(&uri).send().await);
+    seen.record(
+        one,
+        StatusCode::NOT_FOUND,
+        app.get("/schedules/404").send().await,
+    );
+    seen.record(
+        one,
+        StatusCode::UNAUTHORIZED,
+        app.get(&uri).anonymous().send().await,
+    );
+    seen.record(
+        one,
+        StatusCode::OK,
+        app.put(&uri).json(&schedule).send().await,
+    );
+    seen.record(
+        one,
+        StatusCode::NOT_FOUND,
+        app.put("/schedules/404").json(&schedule).send().await,
+    );
+    seen.record(
+        one,
+        StatusCode::UNPROCESSABLE_ENTITY,
+        app.put(&uri)
+            .json(&json!({
+                "supporter_id": supporter["id"],
+                "co_op": "S4L",
+                "amount": "5.00",
+                "interval_unit": "month",
+                "starts_on": "first of the month",
+            }))
+            .send()
+            .await,
+    );
+    seen.record(
+        one,
+        StatusCode::UNAUTHORIZED,
+        app.put(&uri).anonymous().json(&schedule).send().await,
+    );
+
+    // supporters with schedules are kept
+    seen.record(
+        "/supporters/{id}",
+        StatusCode::CONFLICT,
+        app.delete(&format!("/supporters/{}", supporter["id"]))
+            .send()
+            .await,
+    );
+
+    seen.record(
+        one,
+        StatusCode::UNAUTHORIZED,
+        app.delete(&uri).anonymous().send().await,
+    );
+    seen.record(one, StatusCode::NO_CONTENT, app.delete(&uri).send().await);
+    seen.record(one, StatusCode::NOT_FOUND, app.delete(&uri).send().await);
+}
+
+async fn campaign_statuses(app: &TestApp, seen: &mut Observed) {
+    let campaign = json!({
+        "name": "Studio roof",
+        "co_op": "STUDIO-MATIC",
+        "goal_eur": "40.00",
+        "starts_at": "2025-01-01",
+    });
+    let invalid = json!({
+        "name": "Studio roof",
+        "co_op": "STUDIO-MATIC",
+        "goal_eur": "0.00",
+        "starts_at": "2025-01-01",
+    });
+    let list = "/campaigns";
+    let created: Value = seen
+        .record(
+            list,
+            StatusCode::CREATED,
+            app.post(list).json(&campaign).send().await,
+        )
+        .json();
+    seen.record(
+        list,
+        StatusCode::UNPROCESSABLE_ENTITY,
+        app.post(list).json(&invalid).send().await,
+    );
+    seen.record(
+        list,
+        StatusCode::UNAUTHORIZED,
+        app.post(list).anonymous().json(&campaign).send().await,
+    );
+    seen.record(list, StatusCode::OK, app.get(list).send().await);
+    seen.record(
+        list,
+        StatusCode::UNAUTHORIZED,
+        app.get(list).anonymous().send().await,
+    );
+
+    let one = "/campaigns/{id}";
+    let uri = format!("/campaigns/{}", created["id"]);
+    seen.record(one, StatusCode::OK, app.get(&uri).send().await);
+    seen.record(
+        one,
+        StatusCode::NOT_FOUND,
+        app.get("/campaigns/404").send().await,
+    );
+    seen.record(
+        one,
+        StatusCode::UNAUTHORIZED,
+        app.get(&uri).anonymous().send().await,
+    );
+    seen.record(
+        one,
+        StatusCode::OK,
+        app.put(&uri).json(&campaign).send().await,
+    );
+    seen.record(
+        one,
+        StatusCode::NOT_FOUND,
+        app.put("/campaigns/404").json(&campaign).send().await,
+    );
+    seen.record(
+        one,
+        StatusCode::UNPROCESSABLE_ENTITY,
+        app.put(&uri).json(&invalid).send().await,
+    );
+    seen.record(
+        one,
+        StatusCode::UNAUTHORIZED,
+        app.put(&uri).anonymous().json(&campaign).send().await,
+    );
+
+    let progress = "/campaigns/{id}/progress";
+    seen.record(
+        progress,
+        StatusCode::OK,
+        app.get(&format!("{uri}/progress")).anonymous().send().await,
+    );
+    seen.record(
+        progress,
+        StatusCode::NOT_FOUND,
+        app.get("/campaigns/404/progress").anonymous().send().await,
+    );
+
+    seen.record(
+        one,
+        StatusCode::UNAUTHORIZED,
+        app.delete(&uri).anonymous().send().await,
+    );
+    seen.record(one, StatusCode::NO_CONTENT, app.delete(&uri).send().await);
+    seen.record(one, StatusCode::NOT_FOUND, app.delete(&uri).send().await);
+}
+
+async fn exchange_rate_statuses(app: &TestApp, seen: &mut Observed) {
+    let list = "/exchange-rates";
+    let rate = json!({ "currency": "USD", "valid_from": "2025-01-01", "rate": "1.25" });
+    seen.record(
+        list,
+        StatusCode::CREATED,
+        app.post(list).json(&rate).send().await,
+    );
+    seen.record(
+        list,
+        StatusCode::BAD_REQUEST,
+        app.post(list)
+            .json(&json!({ "currency": "USD", "valid_from": "yesterday", "rate": "1.25" }))
+            .send()
+            .await,
+    );
+    seen.record(
+        list,
+        StatusCode::UNPROCESSABLE_ENTITY,
+        app.post(list)
+            .json(&json!({ "currency": "EUR", "valid_from": "2025-01-01", "rate": "1.00" }))
+            .send()
+            .await,
+    );
+    seen.record(
+        list,
+        StatusCode::UNAUTHORIZED,
+        app.post(list).anonymous().json(&rate).send().await,
+    );
+    for path in [list, "/exchange-rates/currencies"] {
+        seen.record(path, StatusCode::OK, app.get(path).send().await);
+        seen.record(
+            path,
+            StatusCode::UNAUTHORIZED,
+            app.get(path).anonymous().send().await,
+        );
+    }
+
+    let import = "/exchange-rates/import";
+    let csv = "currency,valid_from,rate\nGBP,2025-01-01,0.8\n";
+    seen.record(
+        import,
+        StatusCode::OK,
+        app.post(import).body("text/csv", csv).send().await,
+    );
+    seen.record(
+        import,
+        StatusCode::BAD_REQUEST,
+        app.post(import)
+            .body("text/csv", "currency,valid_from,rate\nGBP,someday,0.8\n")
+            .send()
+            .await,
+    );
+    seen.record(
+        import,
+        StatusCode::UNAUTHORIZED,
+        app.post(import)
+            .anonymous()
+            .body("text/csv", csv)
+            .send()
+            .await,
+    );
+}
+
+/// Webhooks of Ko-fi and PayPal, whose secrets are configured, while Stripe's is not.
+async fn webhook_statuses(app: &TestApp, seen: &mut Observed) {
+    let receive = "/webhooks/{provider}";
+    let deliver = |provider: &str, body: &'static [u8]| {
+        app.post(&format!("/webhooks/{provider}"))
+            .anonymous()
+            .header("x-webhook-signature", webhook_signature(body))
+            .body("application/json", body)
+    };
+    // PayPal captures name their co-op
+    let processed: Value = seen
+        .record(
+            receive,
+            StatusCode::OK,
+            deliver("paypal", PAYPAL).send().await,
+        )
+        .json();
+    // Ko-fi donations do not, and no default co-op is configured
+    let failed: Value = seen
+        .record(receive, StatusCode::OK, deliver("kofi", KOFI).send().await)
+        .json();
+    assert_eq!(failed["status"], "failed");
+    seen.record(
+        receive,
+        StatusCode::BAD_REQUEST,
+        deliver("kofi", b"{}").send().await,
+    );
+    seen.record(
+        receive,
+        StatusCode::UNAUTHORIZED,
+        app.post("/webhooks/kofi")
+            .anonymous()
+            .header("x-webhook-signature", "sha256=00")
+            .body("application/json", KOFI)
+            .send()
+            .await,
+    );
+    seen.record(
+        receive,
+        StatusCode::NOT_FOUND,
+        deliver("venmo", KOFI).send().await,
+    );
+    seen.record(
+        receive,
+        StatusCode::SERVICE_UNAVAILABLE,
+        deliver("stripe", KOFI).send().await,
+    );
+
+    let events = "/webhooks/events";
+    seen.record(events, StatusCode::OK, app.get(events).send().await);
+    seen.record(
+        events,
+        StatusCode::UNAUTHORIZED,
+        app.get(events).anonymous().send().await,
+    );
+
+    let replay = "/webhooks/events/{id}/replay";
+    let uri = |event: &Value| format!("/webhooks/events/{}/replay", event["id"]);
+    seen.record(replay, StatusCode::OK, app.post(&uri(&failed)).send().await);
+    seen.record(
+        replay,
+        StatusCode::CONFLICT,
+        app.post(&uri(&processed)).send().await,
+    );
+    seen.record(
+        replay,
+        StatusCode::NOT_FOUND,
+        app.post("/webhooks/events/404/replay").send().await,
+    );
+    seen.record(
+        replay,
+        StatusCode::UNAUTHORIZED,
+        app.post(&uri(&failed)).anonymous().send().await,
+    );
+}
+
+async fn job_statuses(app: &TestApp, seen: &mut Observed) {
+    let jobs = "/jobs";
+    seen.record(jobs, StatusCode::OK, app.get(jobs).send().await);
+    seen.record(
+        jobs,
+        StatusCode::UNAUTHORIZED,
+        app.get(jobs).anonymous().send().await,
+    );
+
+    let runs = "/jobs/{name}/runs";
+    seen.record(
+        runs,
+        StatusCode::OK,
+        app.get("/jobs/noop/runs").send().await,
+    );
+    seen.record(
+        runs,
+        StatusCode::NOT_FOUND,
+        app.get("/jobs/unknown/runs").send().await,
+    );
+    seen.record(
+        runs,
+        StatusCode::UNAUTHORIZED,
+        app.get("/jobs/noop/runs").anonymous().send().await,
+    );
+}
+
+/// An `Idempotency-Key` still claimed by a request conflicts, and one used for
+/// a different request is rejected, before the handler runs.
+async fn idempotency_statuses(app: &TestApp, seen: &mut Observed) {
+    let me: Value = app.get("/users/me").send().await.json();
+    let account_id = me["id"].as_u64().unwrap();
+    let claim = async |key: &str, hash: &str| {
+        assert!(
+            app.state
+                .idempotency_keys
+                .claim(account_id, key, hash)
+                .await
+                .unwrap()
+        );
+    };
+
+    let json = |body: Value| Some(("application/json", body.to_string()));
+    let csv = |body: &str| Some(("text/csv", body.to_owned()));
+    for (path, uri, body) in [
+        (
+            "/donations",
+            "/donations",
+            json(json!({ "coins": 1, "amount": "1.00", "co_op": "S4L" })),
+        ),
+        (
+            "/donations/import",
+            "/donations/import",
+            csv("coins,amount,co_op\n100,1.00,S4L\n"),
+        ),
+        ("/supporters", "/supporters", json(json!({ "name": "Ada" }))),
+        (
+            "/supporters/merge",
+            "/supporters/merge",
+            json(json!({ "canonical_id": 1, "duplicate_ids": [2] })),
+        ),
+        (
+            "/schedules",
+            "/schedules",
+            json(json!({
+                "supporter_id": 1,
+                "co_op": "S4L",
+                "amount": "5.00",
+                "interval_unit": "month",
+                "starts_on": "2025-01-01",
+            })),
+        ),
+        (
+            "/campaigns",
+            "/campaigns",
+            json(json!({
+                "name": "Studio roof",
+                "co_op": "STUDIO-MATIC",
+                "goal_eur": "40.00",
+                "starts_at": "2025-01-01",
+            })),
+        ),
+        (
+            "/exchange-rates",
+            "/exchange-rates",
+            json(json!({ "currency": "USD", "valid_from": "2025-01-01", "rate": "1.25" })),
+        ),
+        (
+            "/exchange-rates/import",
+            "/exchange-rates/import",
+            csv("currency,valid_from,rate\nUSD,2025-01-01,1.25\n"),
+        ),
+        (
+            "/webhooks/events/{id}/replay",
+            "/webhooks/events/1/replay",
+            None,
+        ),
+    ] {
+        let send = |key: String| {
+            let req = app.post(uri).header("idempotency-key", key);
+            match &body {
+                Some((content_type, body)) => req.body(content_type, body.clone()),
+                None => req,
+            }
+        };
+        let bytes = body
+            .as_ref()
+            .map(|(_, body)| body.as_bytes())
+            .unwrap_or_default();
+        let hash = request_hash(&Request::post(uri).body(Body::empty()).unwrap(), bytes);
+
+        let key = format!("pending {uri}");
+        claim(&key, &hash).await;
+        seen.record(path, StatusCode::CONFLICT, send(key).send().await);
+
+        let key = format!("used {uri}");
+        claim(&key, "a different request").await;
+        seen.record(
+            path,
+            StatusCode::UNPROCESSABLE_ENTITY,
+            send(key).send().await,
+        );
+    }
+}
+
+/// The public wall and campaign progress, which are cached and rate limited.
+async fn public_statuses(app: &TestApp, seen: &mut Observed) {
+    let wall = "/public/supporters";
+    let res = seen.record(wall, StatusCode::OK, app.get(wall).anonymous().send().await);
+    seen.record(
+        wall,
+        StatusCode::NOT_MODIFIED,
+        app.get(wall)
+            .anonymous()
+            .header(header::IF_NONE_MATCH, res.etag())
+            .send()
+            .await,
+    );
+
+    let limited = TestApp::with_config(|config| {
+        config.rate_limit.public = RateLimit {
+            period: Duration::from_secs(3600),
+            burst_size: 1,
+        }
+    })
+    .await;
+    limited
+        .get(wall)
+        .anonymous()
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+    seen.record(
+        wall,
+        StatusCode::TOO_MANY_REQUESTS,
+        limited.get(wall).anonymous().send().await,
+    );
+    seen.record(
+        "/campaigns/{id}/progress",
+        StatusCode::TOO_MANY_REQUESTS,
+        limited
+            .get("/campaigns/1/progress")
+            .anonymous()
+            .send()
+            .await,
+    );
+}
+
+/// Health checks and metrics, last as the database is closed.
+async fn operational_statuses(app: TestApp, seen: &mut Observed) {
+    for path in ["/health", "/health/live", "/health/ready", "/metrics"] {
+        seen.record(path, StatusCode::OK, app.get(path).anonymous().send().await);
+    }
+
+    let protected =
+        TestApp::with_config(|config| config.metrics.token = Some("scrape".to_owned())).await;
+    seen.record(
+        "/metrics",
+        StatusCode::UNAUTHORIZED,
+        protected.get("/metrics").send().await,
+    );
+    protected
+        .get("/metrics")
+        .header(header::AUTHORIZATION, "Bearer scrape")
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+
+    app.state.db.close().await;
+    let health: Value = seen
+        .record(
+            "/health/ready",
+            StatusCode::SERVICE_UNAVAILABLE,
+            app.get("/health/ready").send().await,
+        )
+        .json();
+    // the cause is logged, not shown
+    assert_eq!(health["components"]["database"]["detail"], "unavailable");
+}
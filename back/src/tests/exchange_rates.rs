@@ -0,0 +1,81 @@
+use super::TestApp;
+use axum::http::StatusCode;
+use serde_json::{Value, json};
+
+#[tokio::test]
+async fn imported_rates_convert_donations() {
+    let app = TestApp::signed_in().await;
+
+    let res: Value = app
+        .post("/exchange-rates/import")
+        .body(
+            "text/csv",
+            "currency,valid_from,rate\nUSD,2020-01-01,1.25\nGBP,2020-01-01T12:00:00+02:00,0.8\nUSD,2020-01-01,1.2\n",
+        )
+        .send()
+        .await
+        .assert_status(StatusCode::OK)
+        .json();
+    assert_eq!(res["imported"], 3);
+
+    // the second USD row replaced the first
+    let rates: Value = app
+        .get("/exchange-rates?currency=USD")
+        .send()
+        .await
+        .assert_status(StatusCode::OK)
+        .json();
+    assert_eq!(
+        rates,
+        json!([{ "currency": "USD", "valid_from": "2020-01-01T00:00:00Z", "rate": "1.200000" }])
+    );
+    let rates: Value = app.get("/exchange-rates").send().await.json();
+    assert_eq!(rates[0]["currency"], "GBP");
+    assert_eq!(rates[0]["valid_from"], "2020-01-01T10:00:00Z");
+
+    let currencies: Value = app
+        .get("/exchange-rates/currencies")
+        .send()
+        .await
+        .assert_status(StatusCode::OK)
+        .json();
+    assert_eq!(currencies, json!(["EUR", "GBP", "USD"]));
+
+    app.post("/exchange-rates")
+        .json(&json!({ "currency": "USD", "valid_from": "2021-01-01", "rate": "1.25" }))
+        .send()
+        .await
+        .assert_status(StatusCode::CREATED);
+    let created: Value = app
+        .post("/donations")
+        .json(&json!({ "coins": 500, "amount": "5.00", "currency": "USD", "co_op": "S4L" }))
+        .send()
+        .await
+        .assert_status(StatusCode::CREATED)
+        .json();
+    let donation: Value = app
+        .get(&format!("/donations/{}", created["id"]))
+        .send()
+        .await
+        .json();
+    assert_eq!(donation["income_eur"], "4.00");
+}
+
+#[tokio::test]
+async fn invalid_imports_store_nothing() {
+    let app = TestApp::signed_in().await;
+
+    let res = app
+        .post("/exchange-rates/import")
+        .body(
+            "text/csv",
+            "currency,valid_from,rate\nUSD,2020-01-01,1.25\nEUR,2020-01-01,1\n",
+        )
+        .send()
+        .await;
+    res.assert_status(StatusCode::BAD_REQUEST);
+    assert_eq!(res.code(), "invalid_csv");
+
+    let rates: Value = app.get("/exchange-rates").send().await.json();
+    assert_eq!(rates, json!([]));
+}
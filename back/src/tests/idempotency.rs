@@ -0,0 +1,39 @@
+use super::{PASSWORD, TestApp};
+use axum::http::StatusCode;
+use serde_json::{Value, json};
+
+#[tokio::test]
+async fn retries_replay_the_original_response() {
+    let app = TestApp::signed_in().await;
+    let create = |name: &'static str| {
+        app.post("/supporters")
+            .header("idempotency-key", "retried")
+            .json(&json!({ "name": name }))
+            .send()
+    };
+
+    let res = create("Ada").await;
+    res.assert_status(StatusCode::CREATED);
+    assert_eq!(res.header("idempotent-replayed"), None);
+    let created: Value = res.json();
+
+    let res = create("Ada").await;
+    res.assert_status(StatusCode::CREATED);
+    assert_eq!(res.header("idempotent-replayed"), Some("true"));
+    assert_eq!(res.json::<Value>(), created);
+    let all: Vec<Value> = app.get("/supporters").send().await.json();
+    assert_eq!(all.len(), 1);
+
+    let res = create("Grace").await;
+    res.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(res.code(), "idempotency_key_reused");
+
+    // keys belong to the account that used them
+    app.signup("grace@studio-matic.org", PASSWORD)
+        .await
+        .assert_status(StatusCode::CREATED);
+    app.signin("grace@studio-matic.org", PASSWORD)
+        .await
+        .assert_status(StatusCode::OK);
+    create("Grace").await.assert_status(StatusCode::CREATED);
+}
@@ -0,0 +1,61 @@
+use super::TestApp;
+use crate::jobs::{RunStatus, Scheduler, cleanup_job_runs};
+use axum::http::StatusCode;
+use serde_json::Value;
+use time::{OffsetDateTime, macros::datetime};
+
+#[tokio::test]
+async fn runs_are_locked_recorded_and_listed() {
+    let mut scheduler = Scheduler::new("test".to_owned());
+    scheduler.register(
+        "noop",
+        "0 * * * *".parse().expect("valid schedule"),
+        || async { Ok("Nothing to do".to_owned()) },
+    );
+    let app = TestApp::signed_in().await.with_scheduler(scheduler);
+    let runs = app.state.job_runs.clone();
+
+    let lock = runs.lock("noop").await.unwrap().expect("unlocked");
+    assert!(runs.lock("noop").await.unwrap().is_none());
+    assert!(runs.lock("other").await.unwrap().is_some());
+    lock.release().await.unwrap();
+    let lock = runs.lock("noop").await.unwrap().expect("released");
+
+    let slot = datetime!(2026-10-19 12:00 UTC);
+    assert!(!runs.ran("noop", slot).await.unwrap());
+    let id = runs.start("noop", slot, "test").await.unwrap();
+    assert!(runs.ran("noop", slot).await.unwrap());
+    assert!(
+        !runs
+            .ran("noop", datetime!(2026-10-19 13:00 UTC))
+            .await
+            .unwrap()
+    );
+    runs.finish(id, RunStatus::Succeeded, "Nothing to do")
+        .await
+        .unwrap();
+    lock.release().await.unwrap();
+
+    let res = app.get("/jobs").send().await;
+    res.assert_status(StatusCode::OK);
+    let jobs: Vec<Value> = res.json();
+    assert_eq!(jobs.len(), 1);
+    assert_eq!(jobs[0]["name"], "noop");
+    assert_eq!(jobs[0]["last_run"]["status"], "succeeded");
+    assert_eq!(jobs[0]["last_run"]["scheduled_at"], "2026-10-19T12:00:00Z");
+
+    let res = app.get("/jobs/noop/runs").send().await;
+    res.assert_status(StatusCode::OK);
+    let listed: Vec<Value> = res.json();
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0]["detail"], "Nothing to do");
+    assert_eq!(listed[0]["instance"], "test");
+
+    // runs are kept for a while
+    assert_eq!(
+        cleanup_job_runs(runs.clone()).await.unwrap(),
+        "Deleted 0 old job runs"
+    );
+    let started = runs.runs("noop", 1).await.unwrap()[0].started_at;
+    assert!(OffsetDateTime::now_utc() - started < time::Duration::MINUTE);
+}
@@ -0,0 +1,144 @@
+//! Handlers against [`MemoryRepo`], which leaves the database without donations
+//! or supporters.
+
+use super::TestApp;
+use crate::repo::{
+    DonationChanges, DonationRepo, NewDonation, ParsedDonationFilter, memory::MemoryRepo,
+};
+use axum::http::{StatusCode, header};
+use serde_json::{Value, json};
+use std::sync::Arc;
+use time::{Duration, OffsetDateTime};
+
+#[tokio::test]
+async fn handlers_use_the_repositories() {
+    let repo = Arc::new(MemoryRepo::default());
+    repo.set_rate(
+        &"USD".parse().unwrap(),
+        OffsetDateTime::now_utc() - Duration::DAY,
+        "1.25".parse().unwrap(),
+    );
+    let app = TestApp::signed_in_with(repo.clone()).await;
+
+    let supporter: Value = app
+        .post("/supporters")
+        .json(&json!({ "name": "Ada" }))
+        .send()
+        .await
+        .assert_status(StatusCode::CREATED)
+        .json();
+    let supporter_id = supporter["id"].as_u64().unwrap();
+    let donation: Value = app
+        .post("/donations")
+        .json(&json!({
+            "coins": 500,
+            "amount": "5.00",
+            "currency": "USD",
+            "co_op": "S4L",
+            "supporter_id": supporter_id,
+        }))
+        .send()
+        .await
+        .assert_status(StatusCode::CREATED)
+        .json();
+    let id = donation["id"].as_u64().unwrap();
+
+    let stored = repo.get(id).await.unwrap().expect("stored in memory");
+    assert_eq!(stored.income_eur.to_string(), "4.00");
+    assert_eq!(stored.supporter_id, Some(supporter_id));
+    let in_database = app
+        .state
+        .db
+        .repos()
+        .donations
+        .list(&ParsedDonationFilter::default())
+        .await
+        .unwrap();
+    assert!(in_database.is_empty());
+
+    let donations: Value = app
+        .get("/donations?currency=USD")
+        .send()
+        .await
+        .assert_status(StatusCode::OK)
+        .json();
+    assert_eq!(donations.as_array().unwrap().len(), 1);
+    assert_eq!(donations[0]["income_eur"], "4.00");
+
+    let totals: Value = app
+        .get(&format!("/supporters/{supporter_id}/totals"))
+        .send()
+        .await
+        .assert_status(StatusCode::OK)
+        .json();
+    assert_eq!(totals["donations"], 1);
+    assert_eq!(totals["coins"], 500);
+
+    let stats: Value = app
+        .get("/donations/stats")
+        .send()
+        .await
+        .assert_status(StatusCode::OK)
+        .json();
+    assert_eq!(stats["totals"]["income_eur"], "4.00");
+    assert_eq!(stats["top_supporters"][0]["name"], "Ada");
+
+    let res = app
+        .post("/donations")
+        .json(&json!({ "coins": 1, "amount": "1.00", "currency": "CHF", "co_op": "S4L" }))
+        .send()
+        .await;
+    res.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+async fn changes_since_reading_fail_the_precondition() {
+    let repo = Arc::new(MemoryRepo::default());
+    let app = TestApp::signed_in_with(repo.clone()).await;
+    let id = repo
+        .create(NewDonation {
+            coins: 100,
+            amount: "1.00".parse().unwrap(),
+            currency: Default::default(),
+            income_eur: "1.00".parse().unwrap(),
+            co_op: "S4L".to_owned(),
+            supporter_id: None,
+            campaign_id: None,
+        })
+        .await
+        .unwrap();
+
+    let res = app.get(&format!("/donations/{id}")).send().await;
+    res.assert_status(StatusCode::OK);
+    assert_eq!(res.etag(), "\"1\"");
+
+    // another client changes the donation in the meantime
+    repo.update(
+        id,
+        1,
+        DonationChanges {
+            coins: Some(200),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+
+    let res = app
+        .patch(&format!("/donations/{id}"))
+        .header(header::IF_MATCH, "\"1\"")
+        .body("application/merge-patch+json", r#"{"coins": 300}"#)
+        .send()
+        .await;
+    res.assert_status(StatusCode::PRECONDITION_FAILED);
+    assert_eq!(res.code(), "precondition_failed");
+    assert_eq!(repo.get(id).await.unwrap().unwrap().coins, 200);
+
+    let res = app
+        .delete(&format!("/donations/{id}"))
+        .header(header::IF_MATCH, "\"2\"")
+        .send()
+        .await;
+    res.assert_status(StatusCode::NO_CONTENT);
+    assert!(repo.get(id).await.unwrap().is_none());
+}
@@ -0,0 +1,246 @@
+//! The router and the OpenAPI document agree: every route is documented with
+//! the methods it accepts, and every response sent by the tests matches the
+//! schema declared for its status (see [`check`]).
+
+use super::{TestApp, TestResponse};
+use crate::{api_routes, metrics_routes, openapi, public_routes};
+use axum::http::{Method, StatusCode, header};
+use jsonschema::Validator;
+use serde_json::{Value, json};
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::LazyLock,
+};
+
+const METHODS: &[&str] = &["get", "post", "put", "patch", "delete"];
+
+/// Operations changing state that take no request body.
+const WITHOUT_BODY: &[(&str, &str)] = &[
+    ("post", "/users/auth/signout"),
+    ("post", "/webhooks/events/{id}/replay"),
+];
+
+struct Operation {
+    method: Method,
+    path: String,
+    request_content_types: Vec<String>,
+    /// Declared content types per status, none for a response without a body.
+    responses: HashMap<u16, Vec<(String, Validator)>>,
+}
+
+impl Operation {
+    /// Literal segments of the path if it matches `path`, so that
+    /// `/donations/stats` wins over `/donations/{id}`.
+    fn matches(&self, method: &Method, path: &str) -> Option<usize> {
+        if self.method != method {
+            return None;
+        }
+        let documented: Vec<_> = self.path.split('/').collect();
+        let actual: Vec<_> = path.split('/').collect();
+        if documented.len() != actual.len() {
+            return None;
+        }
+        let mut literal = 0;
+        for (documented, actual) in documented.into_iter().zip(actual) {
+            if documented.starts_with('{') {
+                continue;
+            }
+            if documented != actual {
+                return None;
+            }
+            literal += 1;
+        }
+        Some(literal)
+    }
+}
+
+/// The operations of [`openapi`], with a validator per JSON response schema.
+static OPERATIONS: LazyLock<Vec<Operation>> = LazyLock::new(|| {
+    let api = serde_json::to_value(openapi()).expect("serializable OpenAPI document");
+    let components = &api["components"];
+    let validator = |schema: &Value| {
+        // `$ref`s point into the components of the document root
+        jsonschema::draft202012::new(&json!({ "allOf": [schema], "components": components }))
+            .expect("valid schema")
+    };
+
+    let mut operations = Vec::new();
+    for (path, item) in api["paths"].as_object().expect("paths") {
+        for &method in METHODS {
+            let Some(operation) = item.get(method) else {
+                continue;
+            };
+            let request_content_types = operation["requestBody"]["content"]
+                .as_object()
+                .map(|content| content.keys().cloned().collect())
+                .unwrap_or_default();
+            let responses = operation["responses"]
+                .as_object()
+                .expect("responses")
+                .iter()
+                .map(|(status, response)| {
+                    let content = response["content"]
+                        .as_object()
+                        .into_iter()
+                        .flatten()
+                        .map(|(content_type, media)| {
+                            let schema = media.get("schema").unwrap_or(&Value::Bool(true));
+                            (content_type.clone(), validator(schema))
+                        })
+                        .collect();
+                    (status.parse().expect("numeric status"), content)
+                })
+                .collect();
+            operations.push(Operation {
+                method: method.to_ascii_uppercase().parse().expect("HTTP method"),
+                path: path.clone(),
+                request_content_types,
+                responses,
+            });
+        }
+    }
+    operations
+});
+
+/// Panics if the request or response of a test does not match the OpenAPI
+/// document. Undocumented routes and statuses are left to
+/// [`routes_are_documented`] and the `errors` tests.
+pub fn check(request_content_type: Option<&str>, res: &TestResponse) {
+    let path = res.uri.split('?').next().unwrap_or_default();
+    let Some(operation) = OPERATIONS
+        .iter()
+        .filter_map(|operation| Some((operation.matches(&res.method, path)?, operation)))
+        .max_by_key(|(literal, _)| *literal)
+        .map(|(_, operation)| operation)
+    else {
+        return;
+    };
+    let endpoint = format!("{} {}", operation.method, operation.path);
+
+    if let Some(content_type) = request_content_type {
+        assert!(
+            operation
+                .request_content_types
+                .iter()
+                .any(|declared| declared == content_type),
+            "{endpoint} declares no {content_type} request body, only {:?}",
+            operation.request_content_types
+        );
+    }
+
+    let Some(declared) = operation.responses.get(&res.status.as_u16()) else {
+        return;
+    };
+    let content_type = res.header(header::CONTENT_TYPE);
+    if declared.is_empty() {
+        assert!(
+            res.body.is_empty(),
+            "{endpoint} responded {} with an undeclared {content_type:?} body",
+            res.status
+        );
+        return;
+    }
+    let content_type = content_type.unwrap_or_default();
+    let Some((declared_content_type, schema)) = declared
+        .iter()
+        .find(|(declared, _)| content_type.starts_with(declared.as_str()))
+    else {
+        let declared: Vec<_> = declared.iter().map(|(declared, _)| declared).collect();
+        panic!(
+            "{endpoint} responded {} with {content_type}, declared are {declared:?}",
+            res.status
+        );
+    };
+    // JSON Lines are not one JSON document
+    if declared_content_type != "application/json" && !declared_content_type.ends_with("+json") {
+        return;
+    }
+    let body: Value = res.json();
+    let errors: Vec<_> = schema
+        .iter_errors(&body)
+        .map(|error| format!("{}: {error}", error.instance_path))
+        .collect();
+    assert!(
+        errors.is_empty(),
+        "{endpoint} responded {} not matching its schema:\n{}\n{body:#}",
+        res.status,
+        errors.join("\n")
+    );
+}
+
+/// Routed methods and paths, the methods as listed in `Allow` when a path is
+/// requested with a method it does not accept.
+async fn routed(app: &TestApp) -> BTreeSet<(String, String)> {
+    let paths: BTreeSet<_> = api_routes(&app.state)
+        .into_iter()
+        .chain(public_routes())
+        .chain(metrics_routes())
+        .map(|(path, _)| path)
+        .collect();
+
+    let mut routed = BTreeSet::new();
+    for path in paths {
+        let uri: String = path
+            .split('/')
+            .map(|segment| {
+                if segment.starts_with('{') {
+                    "1"
+                } else {
+                    segment
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("/");
+        let res = app.request(Method::TRACE, &uri).send().await;
+        res.assert_status(StatusCode::METHOD_NOT_ALLOWED);
+        let methods = res
+            .header(header::ALLOW)
+            .expect("405 lists the allowed methods")
+            .split(',')
+            .map(|method| method.trim().to_ascii_lowercase())
+            // axum answers HEAD wherever GET is routed
+            .filter(|method| method != "head");
+        routed.extend(methods.map(|method| (method, path.to_owned())));
+    }
+    routed
+}
+
+#[tokio::test]
+async fn routes_are_documented() {
+    let app = TestApp::new().await;
+
+    let documented: BTreeSet<_> = OPERATIONS
+        .iter()
+        .map(|operation| {
+            (
+                operation.method.as_str().to_ascii_lowercase(),
+                operation.path.clone(),
+            )
+        })
+        .collect();
+    let routed = routed(&app).await;
+
+    let undocumented: Vec<_> = routed.difference(&documented).collect();
+    let unrouted: Vec<_> = documented.difference(&routed).collect();
+    assert!(
+        undocumented.is_empty() && unrouted.is_empty(),
+        "undocumented routes: {undocumented:?}\ndocumented but not routed: {unrouted:?}"
+    );
+}
+
+#[test]
+fn request_bodies_are_documented() {
+    for operation in OPERATIONS.iter() {
+        let method = operation.method.as_str().to_ascii_lowercase();
+        if ["post", "put", "patch"].contains(&method.as_str())
+            && !WITHOUT_BODY.contains(&(method.as_str(), operation.path.as_str()))
+        {
+            assert!(
+                !operation.request_content_types.is_empty(),
+                "{} {} declares no request body",
+                operation.method,
+                operation.path
+            );
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use super::TestApp;
+use crate::schedules;
+use axum::http::StatusCode;
+use serde_json::{Value, json};
+use time::{Duration, OffsetDateTime};
+
+#[tokio::test]
+async fn due_dates_are_matched_to_donations() {
+    let app = TestApp::signed_in().await;
+    let today = OffsetDateTime::now_utc().date();
+    let days = |n: i64| (today + Duration::days(n)).to_string();
+
+    let supporter: Value = app
+        .post("/supporters")
+        .json(&json!({ "name": "Ada" }))
+        .send()
+        .await
+        .json();
+    let schedule = json!({
+        "supporter_id": supporter["id"],
+        "co_op": "S4L",
+        "amount": "5.00",
+        "interval_unit": "week",
+        "starts_on": days(-15),
+        "grace_days": 1,
+    });
+    let created: Value = app
+        .post("/schedules")
+        .json(&schedule)
+        .send()
+        .await
+        .assert_status(StatusCode::CREATED)
+        .json();
+    let uri = format!("/schedules/{}", created["id"]);
+    let stored: Value = app.get(&uri).send().await.json();
+    assert_eq!(stored["supporter_name"], "Ada");
+    assert_eq!(stored["interval_count"], 1);
+
+    let upcoming: Vec<Value> = app
+        .get("/schedules/upcoming?days=14")
+        .send()
+        .await
+        .assert_status(StatusCode::OK)
+        .json();
+    let due: Vec<_> = upcoming.iter().map(|d| d["due_on"].clone()).collect();
+    assert_eq!(due, [days(6), days(13)]);
+
+    app.post("/donations/import")
+        .body(
+            "text/csv",
+            format!(
+                "coins,amount,co_op,donated_at,name\n500,5.00,S4L,{},Ada\n",
+                days(-8)
+            ),
+        )
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+    let detail = schedules::flag_missed_donations(app.state.schedules.clone())
+        .await
+        .unwrap();
+    assert_eq!(detail, "Checked scheduled donations: 1 received, 1 missed");
+    let detail = schedules::flag_missed_donations(app.state.schedules.clone())
+        .await
+        .unwrap();
+    assert_eq!(detail, "Checked scheduled donations: 0 received, 0 missed");
+
+    let overdue: Vec<Value> = app
+        .get("/schedules/overdue")
+        .send()
+        .await
+        .assert_status(StatusCode::OK)
+        .json();
+    let due: Vec<_> = overdue.iter().map(|d| d["due_on"].clone()).collect();
+    assert_eq!(due, [days(-15)]);
+
+    let mut unknown_supporter = schedule.clone();
+    unknown_supporter["supporter_id"] = json!(0);
+    app.put(&uri)
+        .json(&unknown_supporter)
+        .send()
+        .await
+        .assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    app.delete(&uri)
+        .send()
+        .await
+        .assert_status(StatusCode::NO_CONTENT);
+    app.get(&uri)
+        .send()
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+}
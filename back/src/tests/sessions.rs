@@ -0,0 +1,61 @@
+use super::{EMAIL, PASSWORD, TestApp};
+use crate::users::auth;
+use axum::http::{StatusCode, header};
+use std::time::Duration;
+
+#[tokio::test]
+async fn requests_without_a_session_are_rejected() {
+    let app = TestApp::new().await;
+
+    let res = app.get("/donations").send().await;
+    res.assert_status(StatusCode::UNAUTHORIZED);
+    assert_eq!(res.code(), "missing_cookies");
+
+    let res = app
+        .get("/donations")
+        .header(header::COOKIE, "theme=dark")
+        .send()
+        .await;
+    res.assert_status(StatusCode::UNAUTHORIZED);
+    assert_eq!(res.code(), "missing_session_token");
+
+    let res = app
+        .get("/donations")
+        .header(header::COOKIE, "theme=dark; session_token=forged")
+        .send()
+        .await;
+    res.assert_status(StatusCode::UNAUTHORIZED);
+    assert_eq!(res.code(), "invalid_session_token");
+}
+
+#[tokio::test]
+async fn sessions_expire_after_their_max_age() {
+    let app = TestApp::with_config(|config| config.session.max_age = Duration::from_secs(1)).await;
+    app.signup(EMAIL, PASSWORD)
+        .await
+        .assert_status(StatusCode::CREATED);
+    let res = app.signin(EMAIL, PASSWORD).await;
+    assert!(
+        res.header(header::SET_COOKIE)
+            .unwrap()
+            .contains("Max-Age=1")
+    );
+    app.get("/users/auth/validate")
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+    assert_eq!(app.state.sessions.count_active().await.unwrap(), 1);
+
+    // expiry is stored with second precision
+    tokio::time::sleep(Duration::from_millis(2100)).await;
+
+    let res = app.get("/users/auth/validate").send().await;
+    res.assert_status(StatusCode::UNAUTHORIZED);
+    assert_eq!(res.code(), "invalid_session_token");
+    assert_eq!(app.state.sessions.count_active().await.unwrap(), 0);
+
+    let removed = auth::cleanup_expired_sessions(app.state.sessions.clone())
+        .await
+        .unwrap();
+    assert_eq!(removed, "Deleted 1 expired sessions");
+}
@@ -0,0 +1,317 @@
+use super::TestApp;
+use axum::http::{StatusCode, header};
+use serde_json::{Value, json};
+
+#[tokio::test]
+async fn supporters_can_be_created_read_updated_and_deleted() {
+    let app = TestApp::signed_in().await;
+
+    let res: Value = app
+        .post("/supporters")
+        .json(&json!({ "name": "Ada", "email": "ada@studio-matic.org", "public": true }))
+        .send()
+        .await
+        .assert_status(StatusCode::CREATED)
+        .json();
+    let id = res["id"].as_u64().unwrap();
+
+    let res = app.get(&format!("/supporters/{id}")).send().await;
+    res.assert_status(StatusCode::OK);
+    assert_eq!(res.etag(), "\"1\"");
+    let supporter: Value = res.json();
+    assert_eq!(supporter["name"], "Ada");
+    assert_eq!(supporter["email"], "ada@studio-matic.org");
+    assert_eq!(supporter["phone"], Value::Null);
+    assert_eq!(supporter["public"], true);
+
+    let res = app
+        .put(&format!("/supporters/{id}"))
+        .header(header::IF_MATCH, "\"1\"")
+        .json(&json!({ "name": "Ada L.", "phone": "+49 30 1234567" }))
+        .send()
+        .await;
+    res.assert_status(StatusCode::OK);
+    assert_eq!(res.etag(), "\"2\"");
+
+    app.patch(&format!("/supporters/{id}"))
+        .header(header::IF_MATCH, "\"2\"")
+        .body(
+            "application/merge-patch+json",
+            r#"{"notes": "Met at the fair", "phone": null}"#,
+        )
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+
+    let supporter: Value = app
+        .get(&format!("/supporters/{id}"))
+        .send()
+        .await
+        .assert_status(StatusCode::OK)
+        .json();
+    assert_eq!(supporter["name"], "Ada L.");
+    // PUT replaces the whole supporter
+    assert_eq!(supporter["email"], Value::Null);
+    assert_eq!(supporter["public"], false);
+    assert_eq!(supporter["phone"], Value::Null);
+    assert_eq!(supporter["notes"], "Met at the fair");
+
+    let all: Vec<Value> = app.get("/supporters").send().await.json();
+    assert_eq!(all.len(), 1);
+
+    app.delete(&format!("/supporters/{id}"))
+        .header(header::IF_MATCH, "\"2\"")
+        .send()
+        .await
+        .assert_status(StatusCode::PRECONDITION_FAILED);
+    app.delete(&format!("/supporters/{id}"))
+        .header(header::IF_MATCH, "\"3\"")
+        .send()
+        .await
+        .assert_status(StatusCode::NO_CONTENT);
+    app.get(&format!("/supporters/{id}"))
+        .send()
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn supporters_list_their_donations_and_totals() {
+    let app = TestApp::signed_in().await;
+    let id = app
+        .post("/supporters")
+        .json(&json!({ "name": "Ada" }))
+        .send()
+        .await
+        .json::<Value>()["id"]
+        .as_u64()
+        .unwrap();
+
+    let totals: Value = app
+        .get(&format!("/supporters/{id}/totals"))
+        .send()
+        .await
+        .assert_status(StatusCode::OK)
+        .json();
+    assert_eq!(totals["donations"], 0);
+    assert_eq!(totals["income_eur"], "0.00");
+    assert_eq!(totals["first_donated_at"], Value::Null);
+
+    for (coins, amount, co_op) in [(100, "1.00", "S4L"), (250, "2.50", "STUDIO-MATIC")] {
+        app.post("/donations")
+            .json(&json!({ "coins": coins, "amount": amount, "co_op": co_op, "supporter_id": id }))
+            .send()
+            .await
+            .assert_status(StatusCode::CREATED);
+    }
+    app.post("/donations")
+        .json(&json!({ "coins": 1, "amount": "9.00", "co_op": "S4L" }))
+        .send()
+        .await
+        .assert_status(StatusCode::CREATED);
+
+    let donations: Vec<Value> = app
+        .get(&format!("/supporters/{id}/donations"))
+        .send()
+        .await
+        .assert_status(StatusCode::OK)
+        .json();
+    assert_eq!(donations.len(), 2);
+    let s4l: Vec<Value> = app
+        .get(&format!("/supporters/{id}/donations?co_op=S4L"))
+        .send()
+        .await
+        .json();
+    assert_eq!(s4l.len(), 1);
+
+    let totals: Value = app
+        .get(&format!("/supporters/{id}/totals"))
+        .send()
+        .await
+        .json();
+    assert_eq!(totals["donations"], 2);
+    assert_eq!(totals["coins"], 350);
+    assert_eq!(totals["income_eur"], "3.50");
+    assert!(totals["first_donated_at"].is_string());
+    assert!(totals["last_donated_at"].is_string());
+}
+
+#[tokio::test]
+async fn deleting_a_supporter_keeps_their_donations() {
+    let app = TestApp::signed_in().await;
+    let id = app
+        .post("/supporters")
+        .json(&json!({ "name": "Ada" }))
+        .send()
+        .await
+        .json::<Value>()["id"]
+        .as_u64()
+        .unwrap();
+    let donation = app
+        .post("/donations")
+        .json(&json!({ "coins": 1, "amount": "1.00", "co_op": "S4L", "supporter_id": id }))
+        .send()
+        .await
+        .json::<Value>()["id"]
+        .as_u64()
+        .unwrap();
+
+    app.delete(&format!("/supporters/{id}"))
+        .send()
+        .await
+        .assert_status(StatusCode::NO_CONTENT);
+
+    let donation: Value = app
+        .get(&format!("/donations/{donation}"))
+        .send()
+        .await
+        .assert_status(StatusCode::OK)
+        .json();
+    assert_eq!(donation["supporter_id"], Value::Null);
+    app.get(&format!("/supporters/{id}/donations"))
+        .send()
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn the_public_wall_lists_consenting_donors() {
+    let app = TestApp::signed_in().await;
+    for (name, public, amount) in [
+        ("Grace", true, Some("60.00")),
+        ("Ada", true, Some("5.00")),
+        ("Alan", false, Some("100.00")),
+        ("Barbara", true, None),
+    ] {
+        let supporter: Value = app
+            .post("/supporters")
+            .json(&json!({ "name": name, "public": public }))
+            .send()
+            .await
+            .json();
+        if let Some(amount) = amount {
+            app.post("/donations")
+                .json(&json!({
+                    "coins": 1,
+                    "amount": amount,
+                    "co_op": "S4L",
+                    "supporter_id": supporter["id"],
+                }))
+                .send()
+                .await
+                .assert_status(StatusCode::CREATED);
+        }
+    }
+
+    let res = app
+        .get("/public/supporters?tiers=true")
+        .anonymous()
+        .send()
+        .await;
+    res.assert_status(StatusCode::OK);
+    let wall: Value = res.json();
+    assert_eq!(
+        wall,
+        json!([{ "name": "Ada" }, { "name": "Grace", "tier_eur": "50.00" }])
+    );
+
+    let res = app
+        .get("/public/supporters?tiers=true")
+        .anonymous()
+        .header(header::IF_NONE_MATCH, res.etag())
+        .send()
+        .await;
+    res.assert_status(StatusCode::NOT_MODIFIED);
+}
+
+#[tokio::test]
+async fn duplicates_reject_scores_outside_zero_to_one() {
+    let app = TestApp::signed_in().await;
+    for min_score in ["NaN", "-0.1", "1.5"] {
+        let res = app
+            .get(&format!("/supporters/duplicates?min_score={min_score}"))
+            .send()
+            .await;
+        res.assert_status(StatusCode::BAD_REQUEST);
+        assert_eq!(res.code(), "invalid_min_score");
+    }
+    app.get("/supporters/duplicates?min_score=1")
+        .send()
+        .await
+        .assert_status(StatusCode::OK);
+}
+
+#[tokio::test]
+async fn duplicates_are_merged_into_the_canonical_supporter() {
+    let app = TestApp::signed_in().await;
+    let mut ids = Vec::new();
+    for supporter in [
+        json!({ "name": "Bob", "notes": "Regular" }),
+        json!({ "name": "Bob S.", "email": "bob@example.org", "notes": "Pays cash" }),
+    ] {
+        let res: Value = app.post("/supporters").json(&supporter).send().await.json();
+        ids.push(res["id"].as_u64().unwrap());
+    }
+    let (canonical, duplicate) = (ids[0], ids[1]);
+    let donation: Value = app
+        .post("/donations")
+        .json(&json!({
+            "coins": 1,
+            "amount": "1.00",
+            "co_op": "S4L",
+            "supporter_id": duplicate,
+        }))
+        .send()
+        .await
+        .json();
+
+    let duplicates: Value = app
+        .get("/supporters/duplicates")
+        .send()
+        .await
+        .assert_status(StatusCode::OK)
+        .json();
+    assert_eq!(duplicates[0]["reasons"], json!(["name_prefix"]));
+
+    let merged: Value = app
+        .post("/supporters/merge")
+        .json(&json!({ "canonical_id": canonical, "duplicate_ids": [duplicate] }))
+        .send()
+        .await
+        .assert_status(StatusCode::OK)
+        .json();
+    assert_eq!(merged["donations_moved"], 1);
+
+    let supporter: Value = app
+        .get(&format!("/supporters/{canonical}"))
+        .send()
+        .await
+        .json();
+    assert_eq!(supporter["email"], "bob@example.org");
+    assert_eq!(supporter["notes"], "Regular\n\nPays cash");
+    let moved: Value = app
+        .get(&format!("/donations/{}", donation["id"]))
+        .send()
+        .await
+        .json();
+    assert_eq!(moved["supporter_id"], canonical);
+    app.get(&format!("/supporters/{duplicate}"))
+        .send()
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+
+    let log: Value = app
+        .get("/supporters/merges")
+        .send()
+        .await
+        .assert_status(StatusCode::OK)
+        .json();
+    assert_eq!(log[0]["merged_name"], "Bob S.");
+    assert_eq!(log[0]["donation_ids"], json!([donation["id"]]));
+
+    app.post("/supporters/merge")
+        .json(&json!({ "canonical_id": canonical, "duplicate_ids": [duplicate] }))
+        .send()
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+}
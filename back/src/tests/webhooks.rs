@@ -0,0 +1,70 @@
+use super::{EMAIL, PASSWORD, TestApp, WEBHOOK_SECRET, webhook_signature};
+use crate::webhooks::providers::Provider;
+use axum::http::StatusCode;
+use serde_json::{Value, json};
+
+const KOFI: &[u8] = include_bytes!("../../fixtures/webhooks/kofi_donation.json");
+
+/// An app accepting Ko-fi webhooks that records their donations for `co_op`,
+/// with a USD exchange rate to convert them.
+async fn kofi_app(co_op: &str) -> TestApp {
+    let app = TestApp::with_config(|config| {
+        config
+            .webhooks
+            .secrets
+            .insert(Provider::Kofi, WEBHOOK_SECRET.to_owned());
+        config
+            .webhooks
+            .co_ops
+            .insert(Provider::Kofi, co_op.to_owned());
+    })
+    .await;
+    app.signup(EMAIL, PASSWORD)
+        .await
+        .assert_status(StatusCode::CREATED);
+    app.signin(EMAIL, PASSWORD)
+        .await
+        .assert_status(StatusCode::OK);
+    app.post("/exchange-rates")
+        .json(&json!({ "currency": "USD", "valid_from": "2020-01-01", "rate": "1.25" }))
+        .send()
+        .await
+        .assert_status(StatusCode::CREATED);
+    app
+}
+
+async fn deliver(app: &TestApp, body: Vec<u8>) -> Value {
+    app.post("/webhooks/kofi")
+        .anonymous()
+        .header("x-webhook-signature", webhook_signature(&body))
+        .body("application/json", body)
+        .send()
+        .await
+        .assert_status(StatusCode::OK)
+        .json()
+}
+
+#[tokio::test]
+async fn failed_payments_create_no_supporter() {
+    let app = kofi_app("NOT-A-CO-OP").await;
+
+    let event = deliver(&app, KOFI.to_vec()).await;
+    assert_eq!(event["status"], "failed");
+    assert_eq!(event["error"], "payment could not be stored");
+
+    let supporters: Value = app.get("/supporters").send().await.json();
+    assert_eq!(supporters, json!([]));
+}
+
+#[tokio::test]
+async fn payer_names_are_trimmed_and_shortened() {
+    let app = kofi_app("S4L").await;
+    let mut payload: Value = serde_json::from_slice(KOFI).unwrap();
+    payload["from_name"] = json!(format!("  {}  ", "é".repeat(300)));
+
+    let event = deliver(&app, serde_json::to_vec(&payload).unwrap()).await;
+    assert_eq!(event["status"], "processed");
+
+    let supporters: Value = app.get("/supporters").send().await.json();
+    assert_eq!(supporters[0]["name"], "é".repeat(255));
+}
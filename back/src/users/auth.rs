@@ -2,23 +2,37 @@ pub mod signin;
 pub mod signout;
 pub mod signup;
 pub mod validate;
+use crate::{config::EmailConfig, jobs::JobResult, repo::Sessions};
+use emval::{EmailValidator, ValidationError};
 use rand::Rng;
 use serde::Deserialize;
 pub use signin::signin;
 pub use signout::signout;
 pub use signup::signup;
-use sqlx::MySqlPool;
-use std::time::Duration;
+use tokio::task;
 pub use validate::validate;
 
-const SESSION_TOKEN_MAX_AGE: Duration = Duration::from_hours(1);
-
 #[derive(Deserialize, utoipa::ToSchema)]
 pub struct SignRequest {
     email: String,
     password: String,
 }
 
+/// Normalized form of `email`, or why it is invalid.
+async fn normalize_email(email: String, config: &EmailConfig) -> Result<String, String> {
+    let validator = EmailValidator {
+        deliverable_address: config.check_deliverability,
+        ..EmailValidator::default()
+    };
+    task::spawn_blocking(move || validator.validate_email(&email))
+        .await
+        .expect("Unable to join email validation thread")
+        .map(|email| email.normalized)
+        .map_err(|e| match e {
+            ValidationError::SyntaxError(e) | ValidationError::ValueError(e) => e,
+        })
+}
+
 fn generate_session_token() -> String {
     rand::rng()
         .sample_iter(&rand::distr::Alphanumeric)
@@ -27,17 +41,8 @@ fn generate_session_token() -> String {
         .collect()
 }
 
-pub async fn cleanup_expired_sessions(pool: MySqlPool) {
-    let mut interval = tokio::time::interval(Duration::from_mins(5));
-    loop {
-        interval.tick().await;
-
-        match sqlx::query("DELETE FROM sessions WHERE expires_at < NOW()")
-            .execute(&pool)
-            .await
-        {
-            Ok(res) => println!("Deleted {} expired sessions", res.rows_affected()),
-            Err(e) => eprintln!("Failed to cleanup expired sessions: {e}"),
-        }
-    }
+pub async fn cleanup_expired_sessions(sessions: Sessions) -> JobResult {
+    let deleted = sessions.delete_expired().await?;
+    metrics::counter!(crate::metrics::SESSIONS_EXPIRED_REMOVED).increment(deleted);
+    Ok(format!("Deleted {deleted} expired sessions"))
 }
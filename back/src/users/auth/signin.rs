@@ -1,16 +1,20 @@
-use crate::ApiResult;
+use crate::{
+    ApiResult,
+    config::Config,
+    error::{FieldError, JsonBody, Problem, ProblemDetails},
+    metrics,
+    repo::{Accounts, Sessions},
+};
 
-use super::{SESSION_TOKEN_MAX_AGE, SignRequest, generate_session_token};
+use super::{SignRequest, generate_session_token, normalize_email};
 use argon2::{Argon2, PasswordHash, PasswordVerifier};
 use axum::{
     Json,
     extract::State,
     http::{StatusCode, header},
-    response::{AppendHeaders, IntoResponse, Response},
+    response::{AppendHeaders, IntoResponse},
 };
-use emval::ValidationError as EmailValidationError;
-use sqlx::MySqlPool;
-use tokio::task;
+use std::sync::Arc;
 
 #[derive(utoipa::OpenApi)]
 #[openapi(paths(signin))]
@@ -36,107 +40,115 @@ pub enum SigninError {
     DatabaseError(#[from] sqlx::Error),
 }
 
-impl IntoResponse for SigninError {
-    fn into_response(self) -> Response {
-        let status = match self {
+impl ProblemDetails for SigninError {
+    fn status(&self) -> StatusCode {
+        match self {
             Self::InvalidEmail(_) => StatusCode::BAD_REQUEST,
             Self::IncorrectPassword => StatusCode::UNAUTHORIZED,
             Self::AccountNotFound => StatusCode::NOT_FOUND,
             Self::SessionError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             Self::PasswordHashError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             Self::DatabaseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
-        };
+        }
+    }
 
-        let msg = self.to_string();
+    fn code(&self) -> &'static str {
+        match self {
+            Self::InvalidEmail(_) => "invalid_email",
+            Self::IncorrectPassword => "incorrect_password",
+            Self::AccountNotFound => "account_not_found",
+            Self::SessionError(_) => "session_error",
+            Self::PasswordHashError(_) => "password_hash_error",
+            Self::DatabaseError(_) => "database_error",
+        }
+    }
 
-        (status, Json(msg)).into_response()
+    fn errors(&self) -> Vec<FieldError> {
+        match self {
+            Self::InvalidEmail(e) => vec![FieldError {
+                field: "email".to_owned(),
+                message: e.clone(),
+                line: None,
+            }],
+            _ => Vec::new(),
+        }
     }
 }
 
 #[utoipa::path(
     post,
     path = "/users/auth/signin",
+    request_body = SignRequest,
     responses(
         (
             status = StatusCode::OK,
-            description = "Successful signin"
+            description = "Successful signin",
+            body = String,
+            content_type = "application/json",
+            headers(
+                ("Set-Cookie" = String, description = "The session cookie"),
+            ),
         ),
         (
             status = StatusCode::BAD_REQUEST,
             description = "Invalid email",
+            body = Problem,
+            content_type = "application/problem+json",
         ),
         (
             status = StatusCode::UNAUTHORIZED,
             description = "Password incorrect",
+            body = Problem,
+            content_type = "application/problem+json",
         ),
         (
             status = StatusCode::NOT_FOUND,
             description = "Account not found",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = Problem,
+            content_type = "application/problem+json",
         ),
-        (status = StatusCode::INTERNAL_SERVER_ERROR),
     ),
 )]
 pub async fn signin(
-    State(pool): State<MySqlPool>,
-    Json(req): Json<SignRequest>,
+    State(accounts): State<Accounts>,
+    State(sessions): State<Sessions>,
+    State(config): State<Arc<Config>>,
+    JsonBody(req): JsonBody<SignRequest>,
 ) -> ApiResult<impl IntoResponse> {
-    let email = task::spawn_blocking(|| emval::validate_email(req.email))
+    let email = normalize_email(req.email, &config.email)
         .await
-        .expect("Unable to join email validation thread")
-        .map_err(|e| {
-            SigninError::InvalidEmail(match e {
-                EmailValidationError::SyntaxError(e) | EmailValidationError::ValueError(e) => e,
-            })
-        })?
-        .normalized;
+        .map_err(SigninError::InvalidEmail)?;
 
-    let (id, hashed_password): (u64, String) =
-        sqlx::query_as("SELECT id, password FROM accounts WHERE email = ? LIMIT 1")
-            .bind(&email)
-            .fetch_optional(&pool)
-            .await
-            .map_err(SigninError::DatabaseError)?
-            .ok_or(SigninError::AccountNotFound)?;
+    let account = accounts
+        .find_by_email(&email)
+        .await
+        .map_err(SigninError::DatabaseError)?
+        .ok_or(SigninError::AccountNotFound)?;
 
-    if Argon2::default()
-        .verify_password(
-            req.password.as_bytes(),
-            &PasswordHash::new(&hashed_password)
-                .map_err(|e| SigninError::PasswordHashError(e.to_string()))?,
-        )
-        .is_ok()
+    let hashed_password = PasswordHash::new(&account.password)
+        .map_err(|e| SigninError::PasswordHashError(e.to_string()))?;
+    if metrics::time_argon2("verify", || {
+        Argon2::default().verify_password(req.password.as_bytes(), &hashed_password)
+    })
+    .is_ok()
     {
         let token = generate_session_token();
 
-        let _ = sqlx::query(
-            "INSERT INTO sessions (token, account_id, expires_at)
-                VALUES (
-                    ?,
-                    ?,
-                    NOW() + INTERVAL ? SECOND
-                )",
-        )
-        .bind(&token)
-        .bind(id)
-        .bind(SESSION_TOKEN_MAX_AGE.as_secs())
-        .execute(&pool)
-        .await
-        .map_err(|e| SigninError::SessionError(e.to_string()))?;
+        sessions
+            .create(&token, account.id, config.session.max_age)
+            .await
+            .map_err(|e| SigninError::SessionError(e.to_string()))?;
 
         Ok((
             StatusCode::OK,
             AppendHeaders([(
                 header::SET_COOKIE,
-                #[cfg(debug_assertions)]
-                format!(
-                    "session_token={token}; Max-Age={}; Path=/; HttpOnly",
-                    SESSION_TOKEN_MAX_AGE.as_secs()
-                ),
-                #[cfg(not(debug_assertions))]
-                format!(
-                    "session_token={token}; Max-Age={}; Path=/; HttpOnly; Secure; SameSite=None",
-                    SESSION_TOKEN_MAX_AGE.as_secs()
-                ),
+                config.cookie.session_cookie(&token, config.session.max_age),
             )]),
             Json("Successful signin"),
         )
@@ -1,10 +1,10 @@
-use crate::ApiResult;
+use crate::{ApiResult, config::Config, error::Problem, repo::Sessions};
 use axum::{
     extract::State,
     http::{HeaderMap, StatusCode, header},
     response::{AppendHeaders, IntoResponse},
 };
-use sqlx::MySqlPool;
+use std::{sync::Arc, time::Duration};
 
 use super::validate::{ValidationError, extract_session_token};
 
@@ -27,26 +27,29 @@ pub fn openapi() -> utoipa::openapi::OpenApi {
         (
             status = StatusCode::UNAUTHORIZED,
             description = "Not logged in",
+            body = Problem,
+            content_type = "application/problem+json",
         ),
-        (status = StatusCode::INTERNAL_SERVER_ERROR)
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = Problem,
+            content_type = "application/problem+json",
+        )
     ),
 )]
 pub async fn signout(
-    State(pool): State<MySqlPool>,
+    State(sessions): State<Sessions>,
+    State(config): State<Arc<Config>>,
     headers: HeaderMap,
 ) -> ApiResult<impl IntoResponse> {
     let token = extract_session_token(headers)?;
 
-    let _ = sqlx::query("DELETE FROM sessions WHERE token = ?")
-        .bind(&token)
-        .execute(&pool)
+    sessions
+        .delete(&token)
         .await
         .map_err(ValidationError::DatabaseError)?;
 
-    #[cfg(debug_assertions)]
-    let remove_cookie = "session_token=; Max-Age=0; Path=/; HttpOnly";
-    #[cfg(not(debug_assertions))]
-    let remove_cookie = "session_token=; Max-Age=0; Path=/; HttpOnly; Secure; SameSite=None";
+    let remove_cookie = config.cookie.session_cookie("", Duration::ZERO);
 
     Ok((
         StatusCode::OK,
@@ -1,18 +1,17 @@
-use super::SignRequest;
-use crate::ApiResult;
+use super::{SignRequest, normalize_email};
+use crate::{
+    ApiResult,
+    config::Config,
+    error::{FieldError, JsonBody, Problem, ProblemDetails},
+    metrics,
+    repo::Accounts,
+};
 use argon2::{
     Argon2,
     password_hash::{PasswordHasher, SaltString, rand_core::OsRng},
 };
-use axum::{
-    Json,
-    extract::State,
-    http::StatusCode,
-    response::{IntoResponse, Response},
-};
-use emval::ValidationError as EmailValidationError;
-use sqlx::MySqlPool;
-use tokio::task;
+use axum::{extract::State, http::StatusCode, response::IntoResponse};
+use std::sync::Arc;
 
 #[derive(utoipa::OpenApi)]
 #[openapi(paths(signup))]
@@ -34,24 +33,41 @@ pub enum SignupError {
     DatabaseError(#[from] sqlx::Error),
 }
 
-impl IntoResponse for SignupError {
-    fn into_response(self) -> Response {
-        let status = match self {
+impl ProblemDetails for SignupError {
+    fn status(&self) -> StatusCode {
+        match self {
             Self::InvalidEmail(_) => StatusCode::BAD_REQUEST,
             Self::Conflict => StatusCode::CONFLICT,
             Self::PasswordHashError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             Self::DatabaseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
-        };
+        }
+    }
 
-        let msg = self.to_string();
+    fn code(&self) -> &'static str {
+        match self {
+            Self::InvalidEmail(_) => "invalid_email",
+            Self::Conflict => "account_exists",
+            Self::PasswordHashError(_) => "password_hash_error",
+            Self::DatabaseError(_) => "database_error",
+        }
+    }
 
-        (status, Json(msg)).into_response()
+    fn errors(&self) -> Vec<FieldError> {
+        match self {
+            Self::InvalidEmail(e) => vec![FieldError {
+                field: "email".to_owned(),
+                message: e.clone(),
+                line: None,
+            }],
+            _ => Vec::new(),
+        }
     }
 }
 
 #[utoipa::path(
     post,
     path = "/users/auth/signup",
+    request_body = SignRequest,
     responses(
         (
             status = StatusCode::CREATED,
@@ -60,45 +76,43 @@ impl IntoResponse for SignupError {
         (
             status = StatusCode::BAD_REQUEST,
             description = "Invalid email",
+            body = Problem,
+            content_type = "application/problem+json",
         ),
         (
             status = StatusCode::CONFLICT,
             description = "Account already exists",
+            body = Problem,
+            content_type = "application/problem+json",
         ),
         (
             status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = Problem,
+            content_type = "application/problem+json",
         ),
     ),
 )]
 pub async fn signup(
-    State(pool): State<MySqlPool>,
-    Json(req): Json<SignRequest>,
+    State(accounts): State<Accounts>,
+    State(config): State<Arc<Config>>,
+    JsonBody(req): JsonBody<SignRequest>,
 ) -> ApiResult<impl IntoResponse> {
-    let email = task::spawn_blocking(|| emval::validate_email(req.email))
+    let email = normalize_email(req.email, &config.email)
         .await
-        .expect("Unable to join email validation thread")
-        .map_err(|e| {
-            SignupError::InvalidEmail(match e {
-                EmailValidationError::SyntaxError(e) | EmailValidationError::ValueError(e) => e,
-            })
-        })?
-        .normalized;
+        .map_err(SignupError::InvalidEmail)?;
 
-    let hashed_password = Argon2::default()
-        .hash_password(req.password.as_bytes(), &SaltString::generate(&mut OsRng))
-        .map_err(|e| SignupError::PasswordHashError(e.to_string()))?
-        .to_string();
+    let hashed_password = metrics::time_argon2("hash", || {
+        Argon2::default()
+            .hash_password(req.password.as_bytes(), &SaltString::generate(&mut OsRng))
+            .map(|hash| hash.to_string())
+    })
+    .map_err(|e| SignupError::PasswordHashError(e.to_string()))?;
 
-    match sqlx::query("INSERT INTO accounts (email, password) VALUES (?, ?)")
-        .bind(&email)
-        .bind(&hashed_password)
-        .execute(&pool)
+    accounts
+        .create(&email, &hashed_password)
         .await
-    {
-        Ok(_) => Ok(StatusCode::CREATED.into_response()),
-        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
-            Err(SignupError::Conflict.into())
-        }
-        Err(e) => Err(SignupError::DatabaseError(e).into()),
-    }
+        .map_err(SignupError::DatabaseError)?
+        .ok_or(SignupError::Conflict)?;
+
+    Ok(StatusCode::CREATED.into_response())
 }
@@ -1,11 +1,13 @@
-use crate::ApiResult;
+use crate::{
+    ApiResult,
+    error::{Problem, ProblemDetails},
+    repo::{SessionRepo, Sessions},
+};
 use axum::{
-    Json,
     extract::State,
     http::{HeaderMap, StatusCode, header},
-    response::{IntoResponse, Response},
+    response::IntoResponse,
 };
-use sqlx::MySqlPool;
 use thiserror::Error;
 
 #[derive(utoipa::OpenApi)]
@@ -28,16 +30,21 @@ pub enum ValidationError {
     DatabaseError(#[from] sqlx::Error),
 }
 
-impl IntoResponse for ValidationError {
-    fn into_response(self) -> Response {
-        let status = match self {
+impl ProblemDetails for ValidationError {
+    fn status(&self) -> StatusCode {
+        match self {
             Self::DatabaseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             _ => StatusCode::UNAUTHORIZED,
-        };
-
-        let msg = self.to_string();
+        }
+    }
 
-        (status, Json(msg)).into_response()
+    fn code(&self) -> &'static str {
+        match self {
+            Self::NoCookies => "missing_cookies",
+            Self::NoSessionToken => "missing_session_token",
+            Self::InvalidToken => "invalid_session_token",
+            Self::DatabaseError(_) => "database_error",
+        }
     }
 }
 
@@ -52,32 +59,22 @@ impl IntoResponse for ValidationError {
         (
             status = StatusCode::UNAUTHORIZED,
             description = "Unsuccessful login",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = Problem,
+            content_type = "application/problem+json",
         ),
-        (status = StatusCode::INTERNAL_SERVER_ERROR),
     ),
 )]
 pub async fn validate(
-    State(pool): State<MySqlPool>,
+    State(sessions): State<Sessions>,
     headers: HeaderMap,
 ) -> ApiResult<impl IntoResponse> {
-    let session_token = extract_session_token(headers)?;
-
-    if sqlx::query(
-        "SELECT 1
-            FROM sessions
-            WHERE token = ?
-            LIMIT 1",
-    )
-    .bind(session_token)
-    .fetch_optional(&pool)
-    .await
-    .map_err(ValidationError::DatabaseError)?
-    .is_some()
-    {
-        Ok(StatusCode::OK.into_response())
-    } else {
-        Err(ValidationError::InvalidToken.into())
-    }
+    session_account(&*sessions, headers).await?;
+    Ok(StatusCode::OK.into_response())
 }
 
 pub fn extract_session_token(headers: HeaderMap) -> ApiResult<String> {
@@ -91,3 +88,14 @@ pub fn extract_session_token(headers: HeaderMap) -> ApiResult<String> {
         .ok_or(ValidationError::NoSessionToken)?;
     Ok(session_token.to_owned())
 }
+
+/// Id of the account owning the request's session.
+pub async fn session_account(sessions: &dyn SessionRepo, headers: HeaderMap) -> ApiResult<u64> {
+    let session_token = extract_session_token(headers)?;
+
+    sessions
+        .account_id(&session_token)
+        .await
+        .map_err(ValidationError::DatabaseError)?
+        .ok_or(ValidationError::InvalidToken.into())
+}
@@ -5,10 +5,11 @@ use axum::{
     response::IntoResponse,
 };
 use serde::Serialize;
-use sqlx::MySqlPool;
 
 use crate::{
     ApiResult,
+    error::Problem,
+    repo::{Accounts, Sessions},
     users::auth::validate::{self, ValidationError},
 };
 
@@ -20,7 +21,7 @@ pub fn openapi() -> utoipa::openapi::OpenApi {
     ApiDoc::openapi()
 }
 
-#[derive(Serialize, sqlx::FromRow, utoipa::ToSchema)]
+#[derive(Serialize, utoipa::ToSchema)]
 struct UserDataResponse {
     email: String,
     id: u64,
@@ -37,26 +38,34 @@ struct UserDataResponse {
         (
             status = StatusCode::UNAUTHORIZED,
             description = "Not logged in",
+            body = Problem,
+            content_type = "application/problem+json",
         ),
-        (status = StatusCode::INTERNAL_SERVER_ERROR)
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = Problem,
+            content_type = "application/problem+json",
+        )
     ),
 )]
-pub async fn me(State(pool): State<MySqlPool>, headers: HeaderMap) -> ApiResult<impl IntoResponse> {
-    let session_token = validate::extract_session_token(headers)?;
+pub async fn me(
+    State(sessions): State<Sessions>,
+    State(accounts): State<Accounts>,
+    headers: HeaderMap,
+) -> ApiResult<impl IntoResponse> {
+    let account_id = validate::session_account(&*sessions, headers).await?;
+
+    let account = accounts
+        .get(account_id)
+        .await
+        .map_err(ValidationError::DatabaseError)?
+        .ok_or(ValidationError::InvalidToken)?;
 
-    match sqlx::query_as::<_, UserDataResponse>(
-        "SELECT accounts.id, accounts.email
-            FROM sessions
-            JOIN accounts ON accounts.id = sessions.account_id
-            WHERE sessions.token = ?
-            LIMIT 1",
-    )
-    .bind(session_token)
-    .fetch_optional(&pool)
-    .await
-    .map_err(ValidationError::DatabaseError)?
-    {
-        Some(v) => Ok((StatusCode::OK, Json(v))),
-        None => Err(ValidationError::InvalidToken.into()),
-    }
+    Ok((
+        StatusCode::OK,
+        Json(UserDataResponse {
+            email: account.email,
+            id: account.id,
+        }),
+    ))
 }
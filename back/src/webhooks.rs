@@ -0,0 +1,582 @@
+pub mod providers;
+
+use crate::{
+    ApiResult,
+    config::Config,
+    error::{PathParams, Problem, ProblemDetails, QueryParams},
+    exchange_rates::ExchangeRateError,
+    idempotency::IdempotencyKeyHeader,
+    repo::{
+        NewSupporter, NewWebhookEvent, PaidDonation, PaymentTx, Payments, Sessions, WebhookEvent,
+    },
+    users::auth::validate,
+};
+use axum::{
+    Json,
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+};
+use providers::{Payment, Provider};
+use serde::{Deserialize, Serialize};
+use sqlx::{Database, Decode, Type, error::BoxDynError};
+use std::{str::FromStr, sync::Arc};
+use thiserror::Error;
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+
+#[derive(utoipa::OpenApi)]
+#[openapi(paths(receive_webhook, get_webhook_events, replay_webhook_event))]
+struct ApiDoc;
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    use utoipa::OpenApi;
+    ApiDoc::openapi()
+}
+
+#[derive(Error, Debug)]
+pub enum WebhookError {
+    #[error("Unknown webhook provider")]
+    UnknownProvider,
+    #[error("Webhooks of this provider are not configured")]
+    NotConfigured,
+    #[error("Invalid webhook signature")]
+    InvalidSignature,
+    #[error("Invalid webhook payload: {0}")]
+    InvalidPayload(String),
+    #[error("Webhook event not found")]
+    EventNotFound,
+    #[error("Webhook event was already processed")]
+    AlreadyProcessed,
+    #[error("Could not format")]
+    FormatError(#[from] time::error::Format),
+    #[error("Could not query database")]
+    DatabaseError(#[from] sqlx::Error),
+}
+
+impl ProblemDetails for WebhookError {
+    fn status(&self) -> StatusCode {
+        match self {
+            Self::UnknownProvider => StatusCode::NOT_FOUND,
+            Self::NotConfigured => StatusCode::SERVICE_UNAVAILABLE,
+            Self::InvalidSignature => StatusCode::UNAUTHORIZED,
+            Self::InvalidPayload(_) => StatusCode::BAD_REQUEST,
+            Self::EventNotFound => StatusCode::NOT_FOUND,
+            Self::AlreadyProcessed => StatusCode::CONFLICT,
+            Self::FormatError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::DatabaseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn code(&self) -> &'static str {
+        match self {
+            Self::UnknownProvider => "unknown_webhook_provider",
+            Self::NotConfigured => "webhook_not_configured",
+            Self::InvalidSignature => "invalid_webhook_signature",
+            Self::InvalidPayload(_) => "invalid_webhook_payload",
+            Self::EventNotFound => "webhook_event_not_found",
+            Self::AlreadyProcessed => "webhook_event_already_processed",
+            Self::FormatError(_) => "format_error",
+            Self::DatabaseError(_) => "database_error",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum EventStatus {
+    /// A donation was recorded
+    Processed,
+    /// The event does not record a donation, or its payment was already recorded
+    Ignored,
+    /// The event could not be recorded and can be replayed once the cause is fixed
+    Failed,
+}
+
+impl EventStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Processed => "processed",
+            Self::Ignored => "ignored",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+impl FromStr for EventStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "processed" => Ok(Self::Processed),
+            "ignored" => Ok(Self::Ignored),
+            "failed" => Ok(Self::Failed),
+            s => Err(format!("unknown webhook event status {s:?}")),
+        }
+    }
+}
+
+impl<DB: Database> Type<DB> for EventStatus
+where
+    str: Type<DB>,
+{
+    fn type_info() -> DB::TypeInfo {
+        <str as Type<DB>>::type_info()
+    }
+
+    fn compatible(ty: &DB::TypeInfo) -> bool {
+        <str as Type<DB>>::compatible(ty)
+    }
+}
+
+impl<'r, DB: Database> Decode<'r, DB> for EventStatus
+where
+    &'r str: Decode<'r, DB>,
+{
+    fn decode(value: DB::ValueRef<'r>) -> Result<Self, BoxDynError> {
+        Ok(<&str as Decode<DB>>::decode(value)?.parse()?)
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+struct WebhookEventResponse {
+    id: u64,
+    provider: String,
+    /// Identifier of the event at the provider
+    event_id: String,
+    event_type: String,
+    status: EventStatus,
+    /// Why the event was ignored or failed
+    error: Option<String>,
+    donation_id: Option<u64>,
+    received_at: String,
+    processed_at: Option<String>,
+    /// Raw request body as received
+    payload: String,
+}
+
+impl TryFrom<WebhookEvent> for WebhookEventResponse {
+    type Error = WebhookError;
+
+    fn try_from(event: WebhookEvent) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: event.id,
+            provider: event.provider,
+            event_id: event.event_id,
+            event_type: event.event_type,
+            status: event.status,
+            error: event.error,
+            donation_id: event.donation_id,
+            received_at: event.received_at.to_utc().format(&Rfc3339)?,
+            processed_at: event
+                .processed_at
+                .map(|t| t.to_utc().format(&Rfc3339))
+                .transpose()?,
+            payload: String::from_utf8_lossy(&event.payload).into_owned(),
+        })
+    }
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+struct WebhookReceipt {
+    /// Identifier of the stored event, for replays
+    id: u64,
+    status: EventStatus,
+    /// The event was delivered before, nothing was recorded this time
+    duplicate: bool,
+    donation_id: Option<u64>,
+    error: Option<String>,
+}
+
+#[derive(Deserialize, utoipa::IntoParams)]
+pub struct WebhookEventQuery {
+    provider: Option<String>,
+    status: Option<EventStatus>,
+    /// Maximum number of events, newest first (default 50, at most 500)
+    limit: Option<u32>,
+}
+
+enum Outcome {
+    Processed(u64),
+    Ignored(String),
+    Failed(String),
+}
+
+impl Outcome {
+    fn status(&self) -> EventStatus {
+        match self {
+            Self::Processed(_) => EventStatus::Processed,
+            Self::Ignored(_) => EventStatus::Ignored,
+            Self::Failed(_) => EventStatus::Failed,
+        }
+    }
+
+    fn donation_id(&self) -> Option<u64> {
+        match self {
+            Self::Processed(id) => Some(*id),
+            _ => None,
+        }
+    }
+
+    fn error(&self) -> Option<&str> {
+        match self {
+            Self::Processed(_) => None,
+            Self::Ignored(reason) | Self::Failed(reason) => Some(reason),
+        }
+    }
+}
+
+/// Longest payer name stored, the length of `supporters.name`.
+const MAX_NAME_CHARS: usize = 255;
+
+async fn find_or_create_supporter(
+    tx: &mut dyn PaymentTx,
+    payment: &Payment,
+) -> Result<Option<u64>, sqlx::Error> {
+    let name = payment.name.as_deref().map(|name| {
+        let name = name.trim();
+        name.char_indices()
+            .nth(MAX_NAME_CHARS)
+            .map_or(name, |(end, _)| name[..end].trim_end())
+    });
+    let existing = match (&payment.email, name) {
+        (Some(email), _) => tx.supporter_by_email(email).await?,
+        (None, Some(name)) => tx.supporter_by_name(name).await?,
+        (None, None) => return Ok(None),
+    };
+    if existing.is_some() {
+        return Ok(existing);
+    }
+
+    let id = tx
+        .create_supporter(NewSupporter {
+            name: name.unwrap_or("Anonymous").to_owned(),
+            email: payment.email.clone(),
+            phone: None,
+            notes: None,
+            public: payment.public,
+        })
+        .await?;
+    Ok(Some(id))
+}
+
+async fn record_payment(
+    tx: &mut dyn PaymentTx,
+    provider: Provider,
+    payment: Option<Payment>,
+    config: &Config,
+) -> Result<Outcome, sqlx::Error> {
+    let Some(payment) = payment else {
+        return Ok(Outcome::Ignored(
+            "event type does not record a donation".into(),
+        ));
+    };
+    let default_co_op = config.webhooks.co_ops.get(&provider);
+    let Some(co_op) = payment.co_op.clone().or_else(|| default_co_op.cloned()) else {
+        return Ok(Outcome::Failed(format!(
+            "no co_op in the payload and WEBHOOK_CO_OP_{} is not set",
+            provider.to_string().to_ascii_uppercase()
+        )));
+    };
+
+    let external_ref = format!("{provider}:{}", payment.reference);
+    if tx.external_ref_exists(&external_ref).await? {
+        return Ok(Outcome::Ignored("payment was already recorded".into()));
+    }
+
+    let donated_at = payment.donated_at.unwrap_or_else(OffsetDateTime::now_utc);
+    let rate = match tx.rate_at(&payment.currency, donated_at).await {
+        Ok(rate) => rate,
+        Err(ExchangeRateError::DatabaseError(e)) => return Err(e),
+        Err(e) => return Ok(Outcome::Failed(e.to_string())),
+    };
+
+    let income_eur = match rate.to_eur(payment.amount) {
+        Ok(income_eur) => income_eur,
+        Err(e) => return Ok(Outcome::Failed(e.to_string())),
+    };
+
+    tx.savepoint().await?;
+    let res = async {
+        let supporter_id = find_or_create_supporter(tx, &payment).await?;
+        tx.create_donation(PaidDonation {
+            coins: payment.coins,
+            donated_at,
+            income_eur,
+            currency: payment.currency,
+            amount: payment.amount,
+            co_op,
+            external_ref: Some(external_ref.clone()),
+            supporter_id,
+        })
+        .await
+    }
+    .await;
+
+    match res {
+        Ok(id) => Ok(Outcome::Processed(id)),
+        // constraint violations such as an unknown `co_op` only fail the event,
+        // without keeping the supporter created for it
+        Err(sqlx::Error::Database(e)) => {
+            tracing::warn!(error = %e, external_ref, "Could not record payment");
+            tx.rollback_to_savepoint().await?;
+            Ok(Outcome::Failed("payment could not be stored".into()))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+async fn fetch_event(payments: &Payments, id: u64) -> Result<WebhookEvent, WebhookError> {
+    payments.event(id).await?.ok_or(WebhookError::EventNotFound)
+}
+
+#[utoipa::path(
+    post,
+    path = "/webhooks/{provider}",
+    params(
+        ("provider" = String, Path, description = "One of `stripe`, `kofi` and `paypal`"),
+    ),
+    request_body(
+        content = String,
+        description = "Event payload exactly as sent by the provider, signed with \
+            `Stripe-Signature` for Stripe and `X-Webhook-Signature: sha256=<hex>` otherwise",
+        content_type = "application/json",
+    ),
+    responses(
+        (
+            status = StatusCode::OK,
+            body = WebhookReceipt,
+            description = "Event stored; failed events can be replayed",
+        ),
+        (
+            status = StatusCode::BAD_REQUEST,
+            description = "Payload is not a valid event of the provider",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Missing or invalid signature",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            description = "Unknown provider",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::SERVICE_UNAVAILABLE,
+            description = "No signing secret configured for the provider",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = Problem,
+            content_type = "application/problem+json",
+        )
+    )
+)]
+pub async fn receive_webhook(
+    State(payments): State<Payments>,
+    State(config): State<Arc<Config>>,
+    headers: HeaderMap,
+    PathParams(provider): PathParams<String>,
+    body: Bytes,
+) -> ApiResult<impl IntoResponse> {
+    let provider: Provider = provider
+        .parse()
+        .map_err(|_| WebhookError::UnknownProvider)?;
+    let secret = config
+        .webhooks
+        .secrets
+        .get(&provider)
+        .ok_or(WebhookError::NotConfigured)?;
+    if !provider.verify(&headers, &body, secret, OffsetDateTime::now_utc()) {
+        return Err(WebhookError::InvalidSignature.into());
+    }
+    let event = provider
+        .parse(&body)
+        .map_err(WebhookError::InvalidPayload)?;
+
+    let mut tx = payments
+        .begin()
+        .await
+        .map_err(WebhookError::DatabaseError)?;
+    let outcome = record_payment(&mut *tx, provider, event.payment, &config)
+        .await
+        .map_err(WebhookError::DatabaseError)?;
+
+    let provider = provider.to_string();
+    let res = tx
+        .create_event(NewWebhookEvent {
+            provider: &provider,
+            event_id: &event.id,
+            event_type: &event.kind,
+            payload: &body,
+            status: outcome.status(),
+            error: outcome.error(),
+            donation_id: outcome.donation_id(),
+        })
+        .await;
+
+    let receipt = match res {
+        Ok(id) => {
+            tx.commit().await.map_err(WebhookError::DatabaseError)?;
+            WebhookReceipt {
+                id,
+                status: outcome.status(),
+                duplicate: false,
+                donation_id: outcome.donation_id(),
+                error: outcome.error().map(str::to_owned),
+            }
+        }
+        // the provider retried an event that was already stored, undo this attempt
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+            tx.rollback().await.map_err(WebhookError::DatabaseError)?;
+            let stored = payments
+                .event_by_provider_id(&provider, &event.id)
+                .await
+                .map_err(WebhookError::DatabaseError)?
+                .ok_or(WebhookError::EventNotFound)?;
+            WebhookReceipt {
+                id: stored.id,
+                status: stored.status,
+                duplicate: true,
+                donation_id: stored.donation_id,
+                error: stored.error,
+            }
+        }
+        Err(e) => return Err(WebhookError::DatabaseError(e).into()),
+    };
+
+    Ok((StatusCode::OK, Json(receipt)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/webhooks/events",
+    params(WebhookEventQuery),
+    responses(
+        (
+            status = StatusCode::OK,
+            body = Vec<WebhookEventResponse>,
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = Problem,
+            content_type = "application/problem+json",
+        )
+    ),
+)]
+pub async fn get_webhook_events(
+    sessions: State<Sessions>,
+    State(payments): State<Payments>,
+    headers: HeaderMap,
+    QueryParams(query): QueryParams<WebhookEventQuery>,
+) -> ApiResult<impl IntoResponse> {
+    let _ = validate(sessions, headers).await?;
+
+    let events = payments
+        .events(
+            query.provider.as_deref(),
+            query.status,
+            query.limit.unwrap_or(50).min(500),
+        )
+        .await
+        .map_err(WebhookError::DatabaseError)?;
+
+    let events = events
+        .into_iter()
+        .map(WebhookEventResponse::try_from)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok((StatusCode::OK, Json(events)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/webhooks/events/{id}/replay",
+    params(("id" = u64, Path), IdempotencyKeyHeader),
+    responses(
+        (
+            status = StatusCode::OK,
+            body = WebhookEventResponse,
+            description = "The stored payload was processed again",
+        ),
+        (
+            status = StatusCode::NOT_FOUND,
+            description = "Event not found",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::UNPROCESSABLE_ENTITY,
+            description = "The `Idempotency-Key` was already used for a different request",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::CONFLICT,
+            description = "The event already recorded a donation, or a request with the same \
+                `Idempotency-Key` is still being processed",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::UNAUTHORIZED,
+            description = "Not logged in",
+            body = Problem,
+            content_type = "application/problem+json",
+        ),
+        (
+            status = StatusCode::INTERNAL_SERVER_ERROR,
+            body = Problem,
+            content_type = "application/problem+json",
+        )
+    )
+)]
+pub async fn replay_webhook_event(
+    sessions: State<Sessions>,
+    State(payments): State<Payments>,
+    State(config): State<Arc<Config>>,
+    headers: HeaderMap,
+    PathParams(id): PathParams<u64>,
+) -> ApiResult<impl IntoResponse> {
+    let _ = validate(sessions, headers).await?;
+
+    let stored = fetch_event(&payments, id).await?;
+    if stored.status == EventStatus::Processed {
+        return Err(WebhookError::AlreadyProcessed.into());
+    }
+    let provider: Provider = stored
+        .provider
+        .parse()
+        .map_err(|_| WebhookError::UnknownProvider)?;
+    // the signature was checked on receipt, the stored payload is trusted
+    let event = provider
+        .parse(&stored.payload)
+        .map_err(WebhookError::InvalidPayload)?;
+
+    let mut tx = payments
+        .begin()
+        .await
+        .map_err(WebhookError::DatabaseError)?;
+    let outcome = record_payment(&mut *tx, provider, event.payment, &config)
+        .await
+        .map_err(WebhookError::DatabaseError)?;
+    tx.update_event(id, outcome.status(), outcome.error(), outcome.donation_id())
+        .await
+        .map_err(WebhookError::DatabaseError)?;
+    tx.commit().await.map_err(WebhookError::DatabaseError)?;
+
+    let event = fetch_event(&payments, id).await?;
+    Ok((StatusCode::OK, Json(WebhookEventResponse::try_from(event)?)))
+}
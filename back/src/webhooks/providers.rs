@@ -0,0 +1,501 @@
+//! Signature schemes and payload mappings of the supported payment providers.
+//!
+//! Stripe events are verified with Stripe's own `Stripe-Signature` header.
+//! Ko-fi and PayPal do not sign their deliveries with a shared secret, so their
+//! payloads are expected through a relay that adds
+//! `X-Webhook-Signature: sha256=<hex HMAC-SHA256 of the body>`.
+
+use crate::{
+    exchange_rates,
+    money::{Currency, Money},
+};
+use axum::http::HeaderMap;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, de};
+use sha2::Sha256;
+use std::{collections::HashMap, fmt, str::FromStr};
+use time::{Duration, OffsetDateTime};
+
+type HmacSha256 = Hmac<Sha256>;
+
+const STRIPE_SIGNATURE: &str = "stripe-signature";
+const WEBHOOK_SIGNATURE: &str = "x-webhook-signature";
+/// Maximum age of a signed Stripe delivery, to limit replays of captured requests.
+const STRIPE_TOLERANCE: Duration = Duration::minutes(5);
+/// Currencies Stripe counts in whole units instead of hundredths.
+const STRIPE_ZERO_DECIMAL: &[&str] = &[
+    "BIF", "CLP", "DJF", "GNF", "JPY", "KMF", "KRW", "MGA", "PYG", "RWF", "UGX", "VND", "VUV",
+    "XAF", "XOF", "XPF",
+];
+/// Currencies Stripe counts in thousandths, always ending in a zero.
+const STRIPE_THREE_DECIMAL: &[&str] = &["BHD", "JOD", "KWD", "OMR", "TND"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Provider {
+    Stripe,
+    Kofi,
+    Paypal,
+}
+
+impl FromStr for Provider {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "stripe" => Ok(Self::Stripe),
+            "kofi" => Ok(Self::Kofi),
+            "paypal" => Ok(Self::Paypal),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for Provider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Stripe => "stripe",
+            Self::Kofi => "kofi",
+            Self::Paypal => "paypal",
+        })
+    }
+}
+
+/// A payment that should be recorded as a donation.
+pub struct Payment {
+    /// Identifier of the payment at the provider, shared by all events about it
+    pub reference: String,
+    pub amount: Money,
+    pub currency: Currency,
+    pub donated_at: Option<OffsetDateTime>,
+    pub name: Option<String>,
+    pub email: Option<String>,
+    /// Whether the supporter agreed to be listed publicly
+    pub public: bool,
+    pub co_op: Option<String>,
+    pub coins: u64,
+}
+
+pub struct Event {
+    pub id: String,
+    pub kind: String,
+    /// `None` for event types that do not record a donation
+    pub payment: Option<Payment>,
+}
+
+impl Provider {
+    pub const ALL: [Self; 3] = [Self::Stripe, Self::Kofi, Self::Paypal];
+
+    pub fn verify(
+        self,
+        headers: &HeaderMap,
+        body: &[u8],
+        secret: &str,
+        now: OffsetDateTime,
+    ) -> bool {
+        let header = |name| headers.get(name).and_then(|v| v.to_str().ok());
+        match self {
+            Self::Stripe => header(STRIPE_SIGNATURE)
+                .is_some_and(|signature| verify_stripe(signature, body, secret, now)),
+            Self::Kofi | Self::Paypal => header(WEBHOOK_SIGNATURE)
+                .and_then(|signature| signature.strip_prefix("sha256="))
+                .and_then(decode_hex)
+                .is_some_and(|signature| mac(secret, &[body]).verify_slice(&signature).is_ok()),
+        }
+    }
+
+    pub fn parse(self, body: &[u8]) -> Result<Event, String> {
+        match self {
+            Self::Stripe => parse_stripe(body),
+            Self::Kofi => parse_kofi(body),
+            Self::Paypal => parse_paypal(body),
+        }
+        .map_err(|e| e.to_string())
+    }
+}
+
+fn mac(secret: &str, parts: &[&[u8]]) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("any key length");
+    for part in parts {
+        mac.update(part);
+    }
+    mac
+}
+
+fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Checks a `t=<unix time>,v1=<hex>` header against the HMAC of `<t>.<body>`.
+fn verify_stripe(signature: &str, body: &[u8], secret: &str, now: OffsetDateTime) -> bool {
+    let mut timestamp = None;
+    let mut signatures = Vec::new();
+    for (key, value) in signature.split(',').filter_map(|part| part.split_once('=')) {
+        match key.trim() {
+            "t" => timestamp = value.parse::<i64>().ok(),
+            "v1" => signatures.extend(decode_hex(value)),
+            _ => {}
+        }
+    }
+
+    let Some(timestamp) = timestamp else {
+        return false;
+    };
+    let Ok(signed_at) = OffsetDateTime::from_unix_timestamp(timestamp) else {
+        return false;
+    };
+    if (now - signed_at).abs() > STRIPE_TOLERANCE {
+        return false;
+    }
+
+    let mac = mac(secret, &[timestamp.to_string().as_bytes(), b".", body]);
+    signatures
+        .iter()
+        .any(|signature| mac.clone().verify_slice(signature).is_ok())
+}
+
+fn non_empty(s: Option<String>) -> Option<String> {
+    s.map(|s| s.trim().to_owned()).filter(|s| !s.is_empty())
+}
+
+#[derive(Deserialize)]
+struct StripeEvent {
+    id: String,
+    #[serde(rename = "type")]
+    kind: String,
+    data: StripeData,
+}
+
+#[derive(Deserialize)]
+struct StripeData {
+    object: serde_json::Value,
+}
+
+/// The parts of a Checkout Session needed to record a donation.
+#[derive(Deserialize)]
+struct StripeCheckoutSession {
+    id: String,
+    payment_intent: Option<String>,
+    payment_status: String,
+    /// Amount in the currency's minor unit
+    amount_total: i64,
+    currency: Currency,
+    created: i64,
+    customer_details: Option<StripeCustomer>,
+    #[serde(default)]
+    metadata: HashMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct StripeCustomer {
+    name: Option<String>,
+    email: Option<String>,
+}
+
+/// Converts an amount in the currency's minor unit as Stripe counts it to [`Money`].
+fn stripe_amount(amount: i64, currency: &Currency) -> Option<Money> {
+    let code = currency.to_string();
+    let cents = if STRIPE_ZERO_DECIMAL.contains(&code.as_str()) {
+        amount.checked_mul(100)?
+    } else if STRIPE_THREE_DECIMAL.contains(&code.as_str()) {
+        (amount % 10 == 0).then_some(amount / 10)?
+    } else {
+        amount
+    };
+    Some(Money::from_cents(cents))
+}
+
+/// Only completed and paid Checkout Sessions are recorded, so that a payment is
+/// not counted again through its payment intent or charge events.
+fn parse_stripe(body: &[u8]) -> serde_json::Result<Event> {
+    let event: StripeEvent = serde_json::from_slice(body)?;
+    let payment = match event.kind.as_str() {
+        "checkout.session.completed" | "checkout.session.async_payment_succeeded" => {
+            let session: StripeCheckoutSession = serde_json::from_value(event.data.object)?;
+            let amount =
+                stripe_amount(session.amount_total, &session.currency).ok_or_else(|| {
+                    de::Error::custom(format!(
+                        "invalid amount_total {} {}",
+                        session.amount_total, session.currency
+                    ))
+                })?;
+            (session.payment_status == "paid").then(|| {
+                let customer = session.customer_details;
+                let (name, email) = customer.map_or((None, None), |c| (c.name, c.email));
+                Payment {
+                    reference: session.payment_intent.unwrap_or(session.id),
+                    amount,
+                    currency: session.currency,
+                    donated_at: OffsetDateTime::from_unix_timestamp(session.created).ok(),
+                    name: non_empty(name),
+                    email: non_empty(email),
+                    public: session.metadata.get("public").is_some_and(|v| v == "true"),
+                    co_op: non_empty(session.metadata.get("co_op").cloned()),
+                    coins: session
+                        .metadata
+                        .get("coins")
+                        .and_then(|v| v.parse().ok())
+                        .unwrap_or_default(),
+                }
+            })
+        }
+        _ => None,
+    };
+
+    Ok(Event {
+        id: event.id,
+        kind: event.kind,
+        payment,
+    })
+}
+
+#[derive(Deserialize)]
+struct KofiEvent {
+    message_id: String,
+    #[serde(rename = "type")]
+    kind: String,
+    timestamp: Option<String>,
+    kofi_transaction_id: String,
+    from_name: Option<String>,
+    email: Option<String>,
+    amount: Money,
+    currency: Currency,
+    #[serde(default)]
+    is_public: bool,
+}
+
+fn parse_kofi(body: &[u8]) -> serde_json::Result<Event> {
+    let event: KofiEvent = serde_json::from_slice(body)?;
+    let payment = matches!(event.kind.as_str(), "Donation" | "Subscription").then(|| Payment {
+        reference: event.kofi_transaction_id,
+        amount: event.amount,
+        currency: event.currency,
+        donated_at: event
+            .timestamp
+            .as_deref()
+            .and_then(exchange_rates::parse_timestamp),
+        name: non_empty(event.from_name),
+        email: non_empty(event.email),
+        public: event.is_public,
+        co_op: None,
+        coins: 0,
+    });
+
+    Ok(Event {
+        id: event.message_id,
+        kind: event.kind,
+        payment,
+    })
+}
+
+#[derive(Deserialize)]
+struct PaypalEvent {
+    id: String,
+    event_type: String,
+    resource: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct PaypalCapture {
+    id: String,
+    amount: PaypalAmount,
+    create_time: Option<String>,
+    /// Set by the checkout to the co-op the donation is for
+    custom_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct PaypalAmount {
+    currency_code: Currency,
+    value: Money,
+}
+
+/// PayPal captures carry no payer details, so they are recorded without a supporter.
+fn parse_paypal(body: &[u8]) -> serde_json::Result<Event> {
+    let event: PaypalEvent = serde_json::from_slice(body)?;
+    let payment = match event.event_type.as_str() {
+        "PAYMENT.CAPTURE.COMPLETED" => {
+            let capture: PaypalCapture = serde_json::from_value(event.resource)?;
+            Some(Payment {
+                reference: capture.id,
+                amount: capture.amount.value,
+                currency: capture.amount.currency_code,
+                donated_at: capture
+                    .create_time
+                    .as_deref()
+                    .and_then(exchange_rates::parse_timestamp),
+                name: None,
+                email: None,
+                public: false,
+                co_op: non_empty(capture.custom_id),
+                coins: 0,
+            })
+        }
+        _ => None,
+    };
+
+    Ok(Event {
+        id: event.id,
+        kind: event.event_type,
+        payment,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    const SECRET: &str = "whsec_test";
+    const STRIPE: &[u8] = include_bytes!("../../fixtures/webhooks/stripe_checkout_completed.json");
+    const KOFI: &[u8] = include_bytes!("../../fixtures/webhooks/kofi_donation.json");
+    const PAYPAL: &[u8] = include_bytes!("../../fixtures/webhooks/paypal_capture_completed.json");
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{b:02x}")).collect()
+    }
+
+    /// Headers signing `body` like the provider, or its relay, at `signed_at`.
+    fn signed(provider: Provider, body: &[u8], signed_at: OffsetDateTime) -> HeaderMap {
+        let (name, value) = match provider {
+            Provider::Stripe => {
+                let t = signed_at.unix_timestamp().to_string();
+                let signature = mac(SECRET, &[t.as_bytes(), b".", body]).finalize();
+                (
+                    STRIPE_SIGNATURE,
+                    format!("t={t},v1={}", hex(&signature.into_bytes())),
+                )
+            }
+            Provider::Kofi | Provider::Paypal => {
+                let signature = mac(SECRET, &[body]).finalize();
+                (
+                    WEBHOOK_SIGNATURE,
+                    format!("sha256={}", hex(&signature.into_bytes())),
+                )
+            }
+        };
+        let mut headers = HeaderMap::new();
+        headers.insert(name, HeaderValue::from_str(&value).unwrap());
+        headers
+    }
+
+    fn stripe_session(currency: &str, amount_total: i64) -> Vec<u8> {
+        let mut event: serde_json::Value = serde_json::from_slice(STRIPE).unwrap();
+        event["data"]["object"]["currency"] = currency.into();
+        event["data"]["object"]["amount_total"] = amount_total.into();
+        serde_json::to_vec(&event).unwrap()
+    }
+
+    #[test]
+    fn fixtures_verify_and_parse() {
+        let now = OffsetDateTime::now_utc();
+        for (provider, body, amount, currency) in [
+            (Provider::Stripe, STRIPE, "25.00", "EUR"),
+            (Provider::Kofi, KOFI, "5.00", "USD"),
+            (Provider::Paypal, PAYPAL, "10.00", "EUR"),
+        ] {
+            let headers = signed(provider, body, now);
+            assert!(provider.verify(&headers, body, SECRET, now), "{provider}");
+            assert!(
+                !provider.verify(&headers, body, "other secret", now),
+                "{provider}"
+            );
+            assert!(
+                !provider.verify(&HeaderMap::new(), body, SECRET, now),
+                "{provider}"
+            );
+
+            let payment = provider.parse(body).unwrap().payment.unwrap();
+            assert_eq!(payment.amount.to_string(), amount, "{provider}");
+            assert_eq!(payment.currency.to_string(), currency, "{provider}");
+        }
+
+        let payment = Provider::Stripe.parse(STRIPE).unwrap().payment.unwrap();
+        assert_eq!(payment.reference, "pi_3PdX4rLkdIwHu7ix1YbZ9QmT");
+        assert_eq!(payment.name.as_deref(), Some("Ada Lovelace"));
+        assert_eq!(payment.co_op.as_deref(), Some("S4L"));
+        assert_eq!(payment.coins, 250);
+        assert!(payment.public);
+    }
+
+    #[test]
+    fn tampered_bodies_are_rejected() {
+        let now = OffsetDateTime::now_utc();
+        for (provider, body) in [
+            (Provider::Stripe, STRIPE),
+            (Provider::Kofi, KOFI),
+            (Provider::Paypal, PAYPAL),
+        ] {
+            let headers = signed(provider, body, now);
+            let mut tampered = body.to_vec();
+            let digit = tampered.iter().position(u8::is_ascii_digit).unwrap();
+            tampered[digit] = if tampered[digit] == b'9' { b'8' } else { b'9' };
+            assert!(
+                !provider.verify(&headers, &tampered, SECRET, now),
+                "{provider}"
+            );
+        }
+    }
+
+    #[test]
+    fn stale_stripe_signatures_are_rejected() {
+        let now = OffsetDateTime::now_utc();
+        let verify = |signed_at| {
+            let headers = signed(Provider::Stripe, STRIPE, signed_at);
+            Provider::Stripe.verify(&headers, STRIPE, SECRET, now)
+        };
+        assert!(verify(now - Duration::minutes(4)));
+        assert!(!verify(now - Duration::minutes(6)));
+        assert!(!verify(now + Duration::minutes(6)));
+    }
+
+    #[test]
+    fn duplicate_events_share_their_ids() {
+        for (provider, body) in [
+            (Provider::Stripe, STRIPE),
+            (Provider::Kofi, KOFI),
+            (Provider::Paypal, PAYPAL),
+        ] {
+            let (first, again) = (provider.parse(body).unwrap(), provider.parse(body).unwrap());
+            assert_eq!(first.id, again.id, "{provider}");
+            assert_eq!(
+                first.payment.unwrap().reference,
+                again.payment.unwrap().reference,
+                "{provider}"
+            );
+        }
+
+        // a later event about the same payment is a new event for the same donation
+        let mut event: serde_json::Value = serde_json::from_slice(STRIPE).unwrap();
+        event["id"] = "evt_async".into();
+        event["type"] = "checkout.session.async_payment_succeeded".into();
+        let later = Provider::Stripe
+            .parse(&serde_json::to_vec(&event).unwrap())
+            .unwrap();
+        assert_eq!(later.id, "evt_async");
+        assert_eq!(
+            later.payment.unwrap().reference,
+            "pi_3PdX4rLkdIwHu7ix1YbZ9QmT"
+        );
+    }
+
+    #[test]
+    fn stripe_amounts_are_scaled_by_the_currency_minor_unit() {
+        let amount = |currency, amount_total| {
+            Provider::Stripe
+                .parse(&stripe_session(currency, amount_total))
+                .map(|event| event.payment.unwrap().amount.to_string())
+        };
+        assert_eq!(amount("eur", 2500).unwrap(), "25.00");
+        assert_eq!(amount("jpy", 500).unwrap(), "500.00");
+        assert_eq!(amount("krw", 10000).unwrap(), "10000.00");
+        assert_eq!(amount("kwd", 1230).unwrap(), "1.23");
+        assert!(amount("kwd", 1235).is_err());
+        assert!(amount("jpy", i64::MAX).is_err());
+    }
+}
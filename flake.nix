@@ -80,6 +80,10 @@
           };
           PORT = 3000;
           DATABASE_URL = "mysql://root@localhost/db?socket=${socket}";
+          CORS_ALLOW_ANY_ORIGIN = "true";
+          COOKIE_SECURE = "false";
+          COOKIE_SAME_SITE = "lax";
+          LOG_FORMAT = "pretty";
           shellHook = ''
             export DOCKER_HOST="unix://$XDG_RUNTIME_DIR/docker.sock"
           '';
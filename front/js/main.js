@@ -23,7 +23,7 @@ async function checkBackend() {
     const controller = new AbortController();
     const timeoutId = setTimeout(() => controller.abort(), 3000);
     try {
-        const r = await fetch(`${baseUrl}/health`, { method: "GET", signal: controller.signal });
+        const r = await fetch(`${baseUrl}/health/ready`, { method: "GET", signal: controller.signal });
         clearTimeout(timeoutId);
         el.innerText = r.ok ? "backend online ✅" : "backend offline ❌";
     } catch (_) {
@@ -34,6 +34,52 @@ async function checkBackend() {
 checkBackend()
 setInterval(checkBackend, 10000)
 
+async function errorText(res) {
+    const text = await res.text();
+    try {
+        const problem = JSON.parse(text);
+        return problem.detail ?? text;
+    } catch (_) {
+        return text;
+    }
+}
+
+// Creates a resource, retrying network failures with the same Idempotency-Key so
+// that a request which reached the backend is not applied twice.
+async function postIdempotent(path, body, attempts = 3) {
+    const key = crypto.randomUUID();
+    for (let attempt = 1; ; attempt++) {
+        try {
+            return await fetch(`${baseUrl}${path}`, {
+                method: "POST",
+                headers: { "Content-Type": "application/json", "Idempotency-Key": key },
+                credentials: "include",
+                body: JSON.stringify(body)
+            });
+        } catch (err) {
+            if (attempt >= attempts) {
+                throw err;
+            }
+            await new Promise(resolve => setTimeout(resolve, 500 * attempt));
+        }
+    }
+}
+
+// Escapes text for interpolation into HTML, as values such as supporter names
+// and emails come from payers.
+function escapeHtml(value) {
+    return String(value)
+        .replaceAll("&", "&amp;")
+        .replaceAll("<", "&lt;")
+        .replaceAll(">", "&gt;")
+        .replaceAll('"', "&quot;")
+        .replaceAll("'", "&#39;");
+}
+
+function ifMatch(version) {
+    return version ? { "If-Match": `"${version}"` } : {};
+}
+
 async function welc() {
     const el = document.getElementById("welc");
     const res = await fetch(`${baseUrl}/users/me`, {
@@ -47,6 +93,25 @@ async function welc() {
     }
 }
 
+async function loadSupportersWall() {
+    const el = document.getElementById("supporters-wall");
+    try {
+        const res = await fetch(`${baseUrl}/public/supporters?tiers=true`, { method: "GET" });
+        if (!res.ok) {
+            return;
+        }
+        const data = await res.json();
+        el.innerHTML = "";
+        data.forEach(({ name, tier_eur }) => {
+            const li = document.createElement("li");
+            li.innerText = tier_eur ? `${name} (€${parseInt(tier_eur, 10)}+)` : name;
+            el.appendChild(li);
+        });
+    } catch (err) {
+        console.error(err);
+    }
+}
+
 async function signup() {
     const email = document.getElementById("email").value;
     const password = document.getElementById("password").value;
@@ -59,7 +124,7 @@ async function signup() {
     if (res.ok) {
         signin();
     } else {
-        alert(await res.text());
+        alert(await errorText(res));
     }
 }
 
@@ -77,7 +142,7 @@ async function signin() {
         const nextPage = params.get('next') || `${hostingPrefix}/`;
         window.location.href = nextPage;
     } else {
-        alert(await res.text());
+        alert(await errorText(res));
     }
 }
 
@@ -88,7 +153,7 @@ async function signout() {
         credentials: "include"
     });
     updateAuthUI();
-    alert(await res.text());
+    alert(await errorText(res));
 }
 
 async function cookiesignin() {
@@ -97,7 +162,7 @@ async function cookiesignin() {
         headers: { "Content-Type": "application/json" },
         credentials: "include"
     });
-    alert(await res.text());
+    alert(await errorText(res));
 }
 
 async function updateAuthUI() {
@@ -149,7 +214,8 @@ function prettyDate(isoString) {
 
 async function loadTable({ url, selector, emptyText, columns }) {
     const tbody = document.querySelector(selector);
-    tbody.innerHTML = `<tr><td colspan="5">Loading…</td></tr>`;
+    const colspan = tbody.closest("table").querySelectorAll("thead th").length;
+    tbody.innerHTML = `<tr><td colspan="${colspan}">Loading…</td></tr>`;
 
     try {
         const res = await fetch(url, {
@@ -159,7 +225,7 @@ async function loadTable({ url, selector, emptyText, columns }) {
         });
 
         if (!res.ok) {
-            tbody.innerHTML = `<tr><td colspan="5">Failed to load data ❌</td></tr>`;
+            tbody.innerHTML = `<tr><td colspan="${colspan}">Failed to load data ❌</td></tr>`;
             return;
         }
 
@@ -167,7 +233,7 @@ async function loadTable({ url, selector, emptyText, columns }) {
         tbody.innerHTML = "";
 
         if (!data.length) {
-            tbody.innerHTML = `<tr><td colspan="5">${emptyText}</td></tr>`;
+            tbody.innerHTML = `<tr><td colspan="${colspan}">${emptyText}</td></tr>`;
             return;
         }
 
@@ -179,7 +245,7 @@ async function loadTable({ url, selector, emptyText, columns }) {
 
     } catch (err) {
         console.error(err);
-        tbody.innerHTML = `<tr><td colspan="5">Error connecting to backend ❌</td></tr>`;
+        tbody.innerHTML = `<tr><td colspan="${colspan}">Error connecting to backend ❌</td></tr>`;
     }
 }
 
@@ -188,14 +254,14 @@ async function loadDbData() {
         url: `${baseUrl}/donations`,
         selector: "#donations tbody",
         emptyText: "No donations yet",
-        columns: ({ id, coins, donated_at, income_eur, co_op }) => `
-            <td>${coins}</td>
-            <td>${prettyDate(donated_at)}</td>
-            <td>${income_eur.toFixed(2)}</td>
-            <td>${co_op}</td>
+        columns: ({ id, coins, donated_at, income_eur, co_op, supporter_id, campaign_id, version }) => `
+            <td>${escapeHtml(coins)}</td>
+            <td>${escapeHtml(prettyDate(donated_at))}</td>
+            <td>${escapeHtml(income_eur)}</td>
+            <td>${escapeHtml(co_op)}</td>
             <td>
-                <button class="edit-donation" data-id="${id}">Edit</button>
-                <button class="delete-donation" data-id="${id}">Delete</button>
+                <button class="edit-donation" data-id="${escapeHtml(id)}" data-supporter-id="${escapeHtml(supporter_id ?? "")}" data-campaign-id="${escapeHtml(campaign_id ?? "")}" data-version="${escapeHtml(version)}">Edit</button>
+                <button class="delete-donation" data-id="${escapeHtml(id)}" data-version="${escapeHtml(version)}">Delete</button>
             </td>
         `
     });
@@ -206,22 +272,29 @@ async function loadDbData() {
         credentials: "include"
     });
     const donationsData = donationsRes.ok ? await donationsRes.json() : [];
-    const donationMap = new Map(donationsData.map(d => [d.id, d]));
+    const supporterDonations = new Map();
+    donationsData.forEach(d => {
+        if (d.supporter_id !== null) {
+            supporterDonations.set(d.supporter_id, [...(supporterDonations.get(d.supporter_id) ?? []), d]);
+        }
+    });
 
     await loadTable({
         url: `${baseUrl}/supporters`,
         selector: "#supporters tbody",
         emptyText: "No supporters yet",
-        columns: ({ id, name, donation_id }) => {
-            const donation = donationMap.get(donation_id);
+        columns: ({ id, name, email, public: isPublic, version }) => {
+            const donations = supporterDonations.get(id) ?? [];
+            const cents = donations.reduce((sum, d) => sum + Math.round(parseFloat(d.income_eur) * 100), 0);
             return `
-                <td>${name}</td>
-                <td>${prettyDate(donation.donated_at)}</td>
-                <td>${donation.income_eur.toFixed(2)}</td>
-                <td>${donation.co_op}</td>
+                <td>${escapeHtml(name)}</td>
+                <td data-public="${escapeHtml(isPublic)}">${isPublic ? "yes" : "no"}</td>
+                <td>${escapeHtml(email ?? "")}</td>
+                <td>${escapeHtml(donations.length)}</td>
+                <td>${escapeHtml((cents / 100).toFixed(2))}</td>
                 <td>
-                    <button class="edit-supporter" data-id="${id}">Edit</button>
-                    <button class="delete-supporter" data-id="${id}">Delete</button>
+                    <button class="edit-supporter" data-id="${escapeHtml(id)}" data-version="${escapeHtml(version)}">Edit</button>
+                    <button class="delete-supporter" data-id="${escapeHtml(id)}" data-version="${escapeHtml(version)}">Delete</button>
                 </td>
             `;
         }
@@ -232,6 +305,9 @@ function resetDonationForm() {
     const form = document.getElementById("add-donation-form");
     form.reset();
     document.getElementById("donation-id").value = "";
+    document.getElementById("donation-supporter-id").value = "";
+    document.getElementById("donation-campaign-id").value = "";
+    document.getElementById("donation-version").value = "";
     document.getElementById("donation-heading").innerText = "Add a new donation";
     document.getElementById("donation-submit").innerText = "Add Donation";
     document.getElementById("donation-cancel").style.display = "none";
@@ -242,6 +318,7 @@ function resetSupporterForm() {
     const form = document.getElementById("add-supporter-form");
     form.reset();
     document.getElementById("supporter-id").value = "";
+    document.getElementById("supporter-version").value = "";
     document.getElementById("supporter-heading").innerText = "Add a new supporter";
     document.getElementById("supporter-submit").innerText = "Add Supporter";
     document.getElementById("supporter-cancel").style.display = "none";
@@ -258,27 +335,25 @@ async function enableForms() {
         e.preventDefault();
 
         const id = document.getElementById("donation-id").value;
+        const supporter_id = parseInt(document.getElementById("donation-supporter-id").value, 10) || null;
+        const campaign_id = parseInt(document.getElementById("donation-campaign-id").value, 10) || null;
         const coins = parseInt(document.getElementById("donation-coins").value, 10);
-        const income_eur = parseFloat(document.getElementById("donation-income").value);
+        const income_eur = document.getElementById("donation-income").value;
         const co_op = "STUDIO-MATIC";
         const statusEl = document.getElementById("add-donation-status");
 
         try {
             let res;
             if (id) {
+                const version = document.getElementById("donation-version").value;
                 res = await fetch(`${baseUrl}/donations/${id}`, {
                     method: "PUT",
-                    headers: { "Content-Type": "application/json" },
+                    headers: { "Content-Type": "application/json", ...ifMatch(version) },
                     credentials: "include",
-                    body: JSON.stringify({ coins, income_eur, co_op })
+                    body: JSON.stringify({ coins, income_eur, co_op, supporter_id, campaign_id })
                 });
             } else {
-                res = await fetch(`${baseUrl}/donations`, {
-                    method: "POST",
-                    headers: { "Content-Type": "application/json" },
-                    credentials: "include",
-                    body: JSON.stringify({ coins, income_eur, co_op })
-                });
+                res = await postIdempotent("/donations", { coins, income_eur, co_op });
             }
 
             if (res.ok) {
@@ -286,7 +361,7 @@ async function enableForms() {
                 resetDonationForm();
                 loadDbData();
             } else {
-                const text = await res.text();
+                const text = await errorText(res);
                 statusEl.innerText = `Failed ❌: ${text}`;
             }
         } catch (err) {
@@ -303,13 +378,14 @@ async function enableForms() {
             if (confirm("Are you sure you want to delete this donation?")) {
                 const res = await fetch(`${baseUrl}/donations/${id}`, {
                     method: "DELETE",
+                    headers: ifMatch(e.target.dataset.version),
                     credentials: "include"
                 });
                 if (res.ok) {
                     alert("Donation deleted ✅");
                     loadDbData();
                 } else {
-                    alert(await res.text());
+                    alert(await errorText(res));
                 }
             }
         }
@@ -320,6 +396,9 @@ async function enableForms() {
             const cells = tr.children;
 
             document.getElementById("donation-id").value = id;
+            document.getElementById("donation-supporter-id").value = e.target.dataset.supporterId;
+            document.getElementById("donation-campaign-id").value = e.target.dataset.campaignId;
+            document.getElementById("donation-version").value = e.target.dataset.version;
             document.getElementById("donation-coins").value = cells[0].innerText;
             document.getElementById("donation-income").value = cells[2].innerText;
 
@@ -336,43 +415,32 @@ async function enableForms() {
 
         const supporterId = document.getElementById("supporter-id").value;
         const name = document.getElementById("supporter-name").value;
-        const income_eur = parseFloat(document.getElementById("supporter-income").value);
+        const isPublic = document.getElementById("supporter-public").checked;
+        const email = document.getElementById("supporter-email").value || null;
+        const income_eur = document.getElementById("supporter-income").value;
 
         const statusEl = document.getElementById("add-supporter-status");
 
         try {
             if (!supporterId) {
-                const donationRes = await fetch(`${baseUrl}/donations`, {
-                    method: "POST",
-                    headers: { "Content-Type": "application/json" },
-                    credentials: "include",
-                    body: JSON.stringify({
-                        coins: 0,
-                        income_eur,
-                        co_op: "STUDIO-MATIC"
-                    })
-                });
+                const supporterRes = await postIdempotent("/supporters", { name, email, public: isPublic });
 
-                if (!donationRes.ok) {
-                    statusEl.innerText = "Failed to create donation ❌";
+                if (!supporterRes.ok) {
+                    statusEl.innerText = "Failed to create supporter ❌";
                     return;
                 }
 
-                const donationData = await donationRes.json();
-                const donationId = donationData.id;
+                const supporterData = await supporterRes.json();
 
-                const supporterRes = await fetch(`${baseUrl}/supporters`, {
-                    method: "POST",
-                    headers: { "Content-Type": "application/json" },
-                    credentials: "include",
-                    body: JSON.stringify({
-                        name,
-                        donation_id: donationId
-                    })
+                const donationRes = await postIdempotent("/donations", {
+                    coins: 0,
+                    income_eur,
+                    co_op: "STUDIO-MATIC",
+                    supporter_id: supporterData.id
                 });
 
-                if (!supporterRes.ok) {
-                    statusEl.innerText = "Failed to create supporter ❌";
+                if (!donationRes.ok) {
+                    statusEl.innerText = "Failed to create donation ❌";
                     return;
                 }
 
@@ -390,16 +458,20 @@ async function enableForms() {
                     return;
                 }
 
-                const supporterData = await getRes.json();
-                const donationId = supporterData.donation_id;
+                const { phone, notes } = await getRes.json();
 
+                const version = document.getElementById("supporter-version").value;
                 const supporterUpdate = await fetch(`${baseUrl}/supporters/${supporterId}`, {
                     method: "PUT",
-                    headers: { "Content-Type": "application/json" },
+                    headers: { "Content-Type": "application/json", ...ifMatch(version) },
                     credentials: "include",
-                    body: JSON.stringify({ name, donation_id: donationId })
+                    body: JSON.stringify({ name, email, phone, notes, public: isPublic })
                 });
 
+                if (supporterUpdate.status === 412) {
+                    statusEl.innerText = "Supporter was changed by someone else, reload and try again ❌";
+                    return;
+                }
                 if (!supporterUpdate.ok) {
                     statusEl.innerText = "Failed to update supporter ❌";
                     return;
@@ -428,12 +500,13 @@ async function enableForms() {
             if (confirm("Delete supporter?")) {
                 const res = await fetch(`${baseUrl}/supporters/${id}`, {
                     method: "DELETE",
+                    headers: ifMatch(e.target.dataset.version),
                     credentials: "include"
                 });
                 if (res.ok) {
                     loadDbData();
                 } else {
-                    alert(await res.text());
+                    alert(await errorText(res));
                 }
             }
         }
@@ -444,7 +517,10 @@ async function enableForms() {
 
             const supporterId = e.target.dataset.id;
             document.getElementById("supporter-id").value = supporterId;
+            document.getElementById("supporter-version").value = e.target.dataset.version;
             document.getElementById("supporter-name").value = cells[0].innerText;
+            document.getElementById("supporter-public").checked = cells[1].dataset.public === "true";
+            document.getElementById("supporter-email").value = cells[2].innerText;
 
             document.getElementById("supporter-income").style.display = "none";
             document.getElementById("supporter-income").required = false;